@@ -0,0 +1,19 @@
+#[derive(Debug, thiserror::Error)]
+pub enum ArchiveError {
+    #[error("database error: {0}")]
+    Db(#[from] localdb::DBError),
+
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Request error for URL <{url}>: {source}")]
+    Reqwest { url: String, source: reqwest::Error },
+
+    #[error("<{url}> is larger than {limit} bytes")]
+    TooLarge { url: String, limit: usize },
+
+    #[error("invalid url: {0}")]
+    InvalidUrl(#[from] url::ParseError),
+}
+
+pub type ArchiveResult<T> = Result<T, ArchiveError>;
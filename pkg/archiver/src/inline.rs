@@ -0,0 +1,439 @@
+use scraper::{node::Element, ElementRef, Html, Node};
+use std::collections::HashMap;
+use url::Url;
+
+const VOID: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "param", "source",
+    "track", "wbr",
+];
+
+/// Elements whose content is written as is
+const RAW_TEXT: &[&str] = &["style", "xmp", "plaintext"];
+
+/// Elements removed from the snapshot, the snapshot is a static document.
+const DROPPED: &[&str] = &[
+    "script", "noscript", "iframe", "frame", "object", "embed", "base",
+];
+
+/// Attributes holding urls, made absolute so they keep working offline
+const URL_ATTRIBUTES: &[&str] = &["href", "src", "action", "poster", "cite"];
+
+/// Resources referenced by a page, all urls are absolute.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct References {
+    pub stylesheets: Vec<String>,
+    pub images: Vec<String>,
+}
+
+/// Returns the base url for relative references in the document.
+pub fn base_url(document: &Html, url: &Url) -> Url {
+    let selector = scraper::Selector::parse("base[href]").expect("valid selector");
+    document
+        .select(&selector)
+        .next()
+        .and_then(|base| base.attr("href"))
+        .and_then(|href| url.join(href).ok())
+        .unwrap_or_else(|| url.clone())
+}
+
+/// Collects stylesheets and images of a page, including the images used in
+/// inline styles.
+pub fn references(document: &Html, base: &Url) -> References {
+    let mut references = References::default();
+    for node in document.tree.root().descendants() {
+        let Some(element) = ElementRef::wrap(node) else {
+            continue;
+        };
+        let value = element.value();
+        match value.name() {
+            "link" if is_stylesheet(value) => {
+                push(&mut references.stylesheets, base, value.attr("href"));
+            }
+            "link" if is_icon(value) => push(&mut references.images, base, value.attr("href")),
+            "img" => push(&mut references.images, base, image_src(value)),
+            "style" => {
+                let css: String = element.text().collect();
+                for url in css_urls(&css) {
+                    push(&mut references.images, base, Some(&url));
+                }
+            }
+            _ => {}
+        }
+        if let Some(style) = value.attr("style") {
+            for url in css_urls(style) {
+                push(&mut references.images, base, Some(&url));
+            }
+        }
+    }
+    references
+}
+
+fn push(urls: &mut Vec<String>, base: &Url, href: Option<&str>) {
+    let Some(url) = href.and_then(|href| resolve(base, href)) else {
+        return;
+    };
+    if !urls.contains(&url) {
+        urls.push(url);
+    }
+}
+
+fn resolve(base: &Url, href: &str) -> Option<String> {
+    let url = base.join(href.trim()).ok()?;
+    matches!(url.scheme(), "http" | "https").then(|| url.to_string())
+}
+
+fn is_stylesheet(link: &Element) -> bool {
+    rel(link).any(|rel| rel == "stylesheet")
+}
+
+fn is_icon(link: &Element) -> bool {
+    rel(link).any(|rel| rel == "icon")
+}
+
+fn rel<'a>(link: &'a Element) -> impl Iterator<Item = String> + 'a {
+    link.attr("rel")
+        .unwrap_or_default()
+        .split_ascii_whitespace()
+        .map(|rel| rel.to_ascii_lowercase())
+}
+
+/// Lazy loaded images keep the real url in `data-src`
+fn image_src(img: &Element) -> Option<&str> {
+    img.attr("src")
+        .filter(|src| !src.is_empty() && !src.starts_with("data:"))
+        .or_else(|| img.attr("data-src"))
+}
+
+/// Returns the urls referenced with `url(...)` in a stylesheet, excluding
+/// data urls.
+pub fn css_urls(css: &str) -> Vec<String> {
+    let mut urls = vec![];
+    rewrite_css_urls(css, |url| {
+        urls.push(url.to_string());
+        None
+    });
+    urls
+}
+
+/// Calls `replace` for every `url(...)` in the stylesheet and substitutes the
+/// url with the returned value.
+pub fn rewrite_css_urls(css: &str, mut replace: impl FnMut(&str) -> Option<String>) -> String {
+    let mut out = String::with_capacity(css.len());
+    let mut rest = css;
+    while let Some(start) = find_ascii_case_insensitive(rest, "url(") {
+        let (before, after) = rest.split_at(start + 4);
+        out.push_str(before);
+        let Some(end) = after.find(')') else {
+            rest = after;
+            break;
+        };
+        let url = after[..end]
+            .trim()
+            .trim_matches(|c| c == '"' || c == '\'')
+            .trim();
+        let replacement = if url.is_empty() || url.starts_with("data:") {
+            None
+        } else {
+            replace(url)
+        };
+        match replacement {
+            Some(replacement) => out.push_str(&format!("\"{}\"", replacement)),
+            None => out.push_str(&after[..end]),
+        }
+        out.push(')');
+        rest = &after[end + 1..];
+    }
+    out.push_str(rest);
+    out
+}
+
+fn find_ascii_case_insensitive(haystack: &str, needle: &str) -> Option<usize> {
+    haystack
+        .as_bytes()
+        .windows(needle.len())
+        .position(|window| window.eq_ignore_ascii_case(needle.as_bytes()))
+}
+
+/// Inlines the given stylesheets and data urls into the document.
+pub struct Inliner<'a> {
+    pub base: &'a Url,
+    /// Absolute stylesheet url to the stylesheet, with its urls already
+    /// replaced with data urls
+    pub stylesheets: &'a HashMap<String, String>,
+    /// Absolute url to its data url
+    pub data_urls: &'a HashMap<String, String>,
+    /// Comment placed at the top of the document
+    pub comment: &'a str,
+}
+
+impl Inliner<'_> {
+    pub fn write(&self, document: &Html) -> String {
+        let mut out = String::new();
+        for child in document.tree.root().children() {
+            match child.value() {
+                Node::Doctype(_) => out.push_str("<!DOCTYPE html>\n"),
+                Node::Element(_) => {
+                    out.push_str(&format!("<!-- {} -->\n", self.comment.replace("--", "")));
+                    self.write_element(ElementRef::wrap(child).expect("is element"), &mut out);
+                }
+                _ => {}
+            }
+        }
+        out
+    }
+
+    fn write_element(&self, element: ElementRef, out: &mut String) {
+        let value = element.value();
+        let name = value.name();
+        if DROPPED.contains(&name) {
+            return;
+        }
+
+        match name {
+            "link" if is_stylesheet(value) => {
+                let href = value.attr("href").and_then(|href| resolve(self.base, href));
+                if let Some(css) = href.and_then(|href| self.stylesheets.get(&href)) {
+                    out.push_str("<style>");
+                    out.push_str(&css.replace("</style", "<\\/style"));
+                    out.push_str("</style>");
+                    return;
+                }
+            }
+            "meta" => {
+                let http_equiv = value.attr("http-equiv").unwrap_or_default();
+                // the document is always written as utf-8 and without scripts
+                if value.attr("charset").is_some()
+                    || http_equiv.eq_ignore_ascii_case("content-type")
+                    || http_equiv.eq_ignore_ascii_case("content-security-policy")
+                    || http_equiv.eq_ignore_ascii_case("refresh")
+                {
+                    return;
+                }
+            }
+            _ => {}
+        }
+
+        out.push('<');
+        out.push_str(name);
+        self.write_attributes(value, out);
+        out.push('>');
+        if name == "head" {
+            out.push_str("<meta charset=\"utf-8\">");
+        }
+        if VOID.contains(&name) {
+            return;
+        }
+
+        for child in element.children() {
+            match child.value() {
+                Node::Text(text) if name == "style" => {
+                    out.push_str(&self.rewrite_css(text));
+                }
+                Node::Text(text) if RAW_TEXT.contains(&name) => out.push_str(text),
+                Node::Text(text) => out.push_str(&escape(text, false)),
+                Node::Element(_) => {
+                    self.write_element(ElementRef::wrap(child).expect("is element"), out)
+                }
+                _ => {}
+            }
+        }
+
+        out.push_str("</");
+        out.push_str(name);
+        out.push('>');
+    }
+
+    fn write_attributes(&self, element: &Element, out: &mut String) {
+        let is_img = element.name() == "img";
+        let is_icon = element.name() == "link" && is_icon(element);
+        for (name, value) in element.attrs() {
+            if name.starts_with("on") || name == "integrity" || name == "srcset" {
+                continue;
+            }
+            if is_img && (name == "data-src" || name == "loading") {
+                continue;
+            }
+
+            let value = match name {
+                "src" if is_img => image_src(element)
+                    .and_then(|src| self.data_url(src))
+                    .unwrap_or_else(|| self.absolute(value)),
+                "href" if is_icon => self.data_url(value).unwrap_or_else(|| self.absolute(value)),
+                "style" => self.rewrite_css(value),
+                name if URL_ATTRIBUTES.contains(&name) => self.absolute(value),
+                _ => value.to_string(),
+            };
+            out.push_str(&format!(" {}=\"{}\"", name, escape(&value, true)));
+        }
+
+        // lazy loaded image without src
+        if is_img && element.attr("src").is_none() {
+            if let Some(src) = image_src(element).and_then(|src| self.data_url(src)) {
+                out.push_str(&format!(" src=\"{}\"", escape(&src, true)));
+            }
+        }
+    }
+
+    fn data_url(&self, href: &str) -> Option<String> {
+        resolve(self.base, href).and_then(|url| self.data_urls.get(&url).cloned())
+    }
+
+    fn absolute(&self, href: &str) -> String {
+        if href.starts_with('#') {
+            return href.to_string();
+        }
+        self.base
+            .join(href.trim())
+            .map(|url| url.to_string())
+            .unwrap_or_else(|_| href.to_string())
+    }
+
+    fn rewrite_css(&self, css: &str) -> String {
+        rewrite_css_urls(css, |url| self.data_url(url))
+    }
+}
+
+fn escape(text: &str, attribute: bool) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '"' if attribute => escaped.push_str("&quot;"),
+            '<' if !attribute => escaped.push_str("&lt;"),
+            '>' if !attribute => escaped.push_str("&gt;"),
+            '\u{a0}' => escaped.push_str("&nbsp;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Decodes a page or a stylesheet with the charset of its Content-Type, else
+/// the one it declares itself near its start, else as utf-8. A byte order
+/// mark wins over both.
+pub fn decode(body: &[u8], content_type: Option<&str>) -> String {
+    let declared = |text: &str| {
+        let lower = text.to_ascii_lowercase();
+        let start = lower.find("charset")? + "charset".len();
+        let label = lower[start..]
+            .trim_start_matches(|c: char| c == '=' || c == '"' || c == '\'' || c.is_whitespace());
+        let end = label
+            .find(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | ':' | '.')))
+            .unwrap_or(label.len());
+        encoding_rs::Encoding::for_label(&label.as_bytes()[..end])
+    };
+    let head = String::from_utf8_lossy(&body[..body.len().min(1024)]);
+    let encoding = content_type
+        .and_then(declared)
+        .or_else(|| declared(&head))
+        .unwrap_or(encoding_rs::UTF_8);
+    encoding.decode(body).0.into_owned()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const PAGE: &str = r#"<!doctype html>
+<html><head>
+<meta charset="iso-8859-1">
+<link rel="stylesheet" href="/style.css">
+<link rel="icon" href="favicon.png">
+<style>body { background: url('bg.png') }</style>
+<script>alert(1)</script>
+</head><body onload="x()">
+<img src="/a.png" srcset="/a-2x.png 2x"><img data-src="lazy.png">
+<a href="/about">About &amp; more</a><p>Café</p>
+<div style="background-image: url(&quot;/div.png&quot;)"></div>
+</body></html>"#;
+
+    #[test]
+    fn test_references() {
+        let document = Html::parse_document(PAGE);
+        let base = Url::parse("https://example.com/posts/1").unwrap();
+        let references = references(&document, &base);
+        assert_eq!(
+            references.stylesheets,
+            vec!["https://example.com/style.css"]
+        );
+        assert_eq!(
+            references.images,
+            vec![
+                "https://example.com/posts/favicon.png",
+                "https://example.com/posts/bg.png",
+                "https://example.com/a.png",
+                "https://example.com/posts/lazy.png",
+                "https://example.com/div.png",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_base_url() {
+        let document = Html::parse_document(r#"<head><base href="/docs/"></head>"#);
+        let url = Url::parse("https://example.com/posts/1").unwrap();
+        assert_eq!(
+            base_url(&document, &url).as_str(),
+            "https://example.com/docs/"
+        );
+    }
+
+    #[test]
+    fn test_rewrite_css_urls() {
+        let css =
+            r#"a { background: URL("x.png") } b { src: url(data:abc) } c { x: url( 'y.png' ) }"#;
+        assert_eq!(css_urls(css), vec!["x.png", "y.png"]);
+        let rewritten = rewrite_css_urls(css, |url| Some(format!("data:{url}")));
+        assert_eq!(
+            rewritten,
+            r#"a { background: URL("data:x.png") } b { src: url(data:abc) } c { x: url("data:y.png") }"#
+        );
+    }
+
+    #[test]
+    fn test_inline() {
+        let (latin1, _, _) = encoding_rs::WINDOWS_1252.encode(PAGE);
+        assert!(std::str::from_utf8(&latin1).is_err());
+        let document = Html::parse_document(&decode(&latin1, Some("text/html")));
+        let base = Url::parse("https://example.com/posts/1").unwrap();
+        let stylesheets = HashMap::from([(
+            "https://example.com/style.css".to_string(),
+            "p { color: red }".to_string(),
+        )]);
+        let data_urls = HashMap::from([
+            (
+                "https://example.com/a.png".to_string(),
+                "data:image/png;base64,QQ==".to_string(),
+            ),
+            (
+                "https://example.com/posts/lazy.png".to_string(),
+                "data:image/png;base64,TA==".to_string(),
+            ),
+            (
+                "https://example.com/posts/bg.png".to_string(),
+                "data:image/png;base64,Qg==".to_string(),
+            ),
+        ]);
+        let html = Inliner {
+            base: &base,
+            stylesheets: &stylesheets,
+            data_urls: &data_urls,
+            comment: "saved by readlater",
+        }
+        .write(&document);
+
+        assert!(html.starts_with("<!DOCTYPE html>\n<!-- saved by readlater -->\n<html>"));
+        assert!(html.contains(r#"<head><meta charset="utf-8">"#));
+        assert!(html.contains("<p>Café</p>"));
+        assert!(!html.contains("iso-8859-1"));
+        assert!(html.contains("<style>p { color: red }</style>"));
+        assert!(html.contains(r#"url("data:image/png;base64,Qg==")"#));
+        assert!(!html.contains("<script"));
+        assert!(!html.contains("onload"));
+        assert!(html.contains(r#"<img src="data:image/png;base64,QQ==">"#));
+        assert!(html.contains(r#"<img src="data:image/png;base64,TA==">"#));
+        assert!(html.contains(r#"<link href="https://example.com/posts/favicon.png" rel="icon">"#));
+        assert!(html.contains(r#"<a href="https://example.com/about">About &amp; more</a>"#));
+        assert!(html.contains(r#"style="background-image: url(&quot;/div.png&quot;)""#));
+    }
+}
@@ -0,0 +1,361 @@
+mod error;
+pub mod inline;
+mod store;
+pub mod warc;
+
+use base64::{prelude::BASE64_STANDARD, Engine};
+use chrono::{DateTime, Utc};
+pub use error::{ArchiveError, ArchiveResult};
+use inline::Inliner;
+use reqwest::{header::CONTENT_TYPE, Client};
+use scraper::Html;
+use std::collections::HashMap;
+use std::time::Duration;
+pub use store::SnapshotStore;
+use url::Url;
+
+/// Pages and resources larger than this are not captured, resources are
+/// left as remote references
+const MAX_RESOURCE_SIZE: usize = 10 * 1024 * 1024;
+
+/// A page captured as a single self contained html document
+pub struct Capture {
+    /// Url of the page after following redirects
+    pub url: String,
+    pub status: u16,
+    pub time_fetched: DateTime<Utc>,
+    pub html: String,
+    /// WARC file with the original responses of the page and its resources
+    pub warc: Option<Vec<u8>>,
+}
+
+struct Fetched {
+    url: String,
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl Fetched {
+    fn mime(&self) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(CONTENT_TYPE.as_str()))
+            .and_then(|(_, value)| value.split(';').next())
+            .map(str::trim)
+    }
+
+    fn data_url(&self) -> String {
+        format!(
+            "data:{};base64,{}",
+            self.mime().unwrap_or("application/octet-stream"),
+            BASE64_STANDARD.encode(&self.body)
+        )
+    }
+
+    fn text(&self) -> String {
+        let content_type = self
+            .headers
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(CONTENT_TYPE.as_str()))
+            .map(|(_, value)| value.as_str());
+        inline::decode(&self.body, content_type)
+    }
+}
+
+/// Captures web pages with their stylesheets and images inlined, so that the
+/// snapshot renders without network access.
+pub struct Archiver {
+    client: Client,
+    warc: bool,
+}
+
+impl Archiver {
+    /// Archiver giving up on each request after `timeout`
+    pub fn new(timeout: Duration, user_agent: &str) -> Archiver {
+        let client = Client::builder()
+            .timeout(timeout)
+            .user_agent(user_agent)
+            .build()
+            .expect("valid http client");
+        Archiver {
+            client,
+            warc: false,
+        }
+    }
+
+    /// Also record the original responses in a WARC file
+    pub fn warc(mut self, warc: bool) -> Archiver {
+        self.warc = warc;
+        self
+    }
+
+    /// Captures the page at `url`. Pages returning an error status are still
+    /// captured, the status is part of the capture.
+    pub async fn capture(&self, url: &str) -> ArchiveResult<Capture> {
+        let time_fetched = Utc::now();
+        let page = self.get(url).await?;
+        let page_url = Url::parse(&page.url)?;
+
+        let text = page.text();
+        // the document is not Send, so it is parsed again to write the capture
+        // rather than held across the downloads
+        let (base, references) = {
+            let document = Html::parse_document(&text);
+            let base = inline::base_url(&document, &page_url);
+            let references = inline::references(&document, &base);
+            (base, references)
+        };
+
+        let mut responses = vec![];
+        let mut data_urls: HashMap<String, String> = HashMap::new();
+        let mut stylesheets: HashMap<String, String> = HashMap::new();
+
+        for href in references.stylesheets {
+            let Some(stylesheet) = self.fetch(&href).await else {
+                continue;
+            };
+            let css = stylesheet.text();
+            // urls in a stylesheet are relative to the stylesheet
+            let css_base = Url::parse(&stylesheet.url)?;
+            for css_url in inline::css_urls(&css) {
+                let Ok(css_url) = css_base.join(&css_url) else {
+                    continue;
+                };
+                self.fetch_data_url(css_url.as_str(), &mut data_urls, &mut responses)
+                    .await;
+            }
+            let css = inline::rewrite_css_urls(&css, |css_url| {
+                let css_url = css_base.join(css_url).ok()?;
+                data_urls.get(css_url.as_str()).cloned()
+            });
+            stylesheets.insert(href, css);
+            responses.push(stylesheet);
+        }
+
+        for src in references.images {
+            self.fetch_data_url(&src, &mut data_urls, &mut responses)
+                .await;
+        }
+
+        let comment = format!(
+            "Saved by readlater from {} on {}",
+            page.url,
+            time_fetched.to_rfc3339()
+        );
+        let html = Inliner {
+            base: &base,
+            stylesheets: &stylesheets,
+            data_urls: &data_urls,
+            comment: &comment,
+        }
+        .write(&Html::parse_document(&text));
+
+        let warc = self.warc.then(|| {
+            let filename = format!("{}.warc", time_fetched.timestamp());
+            let mut warc = warc::warcinfo(time_fetched, &filename);
+            for response in std::iter::once(&page).chain(responses.iter()) {
+                warc.extend(warc::response(
+                    time_fetched,
+                    &warc::Response {
+                        url: &response.url,
+                        status: response.status,
+                        headers: &response.headers,
+                        body: &response.body,
+                    },
+                ));
+            }
+            warc
+        });
+
+        Ok(Capture {
+            url: page.url,
+            status: page.status,
+            time_fetched,
+            html,
+            warc,
+        })
+    }
+
+    async fn get(&self, url: &str) -> ArchiveResult<Fetched> {
+        let reqwest_error = |source| ArchiveError::Reqwest {
+            url: url.to_string(),
+            source,
+        };
+        let too_large = || ArchiveError::TooLarge {
+            url: url.to_string(),
+            limit: MAX_RESOURCE_SIZE,
+        };
+        let mut response = self.client.get(url).send().await.map_err(reqwest_error)?;
+        if response
+            .content_length()
+            .is_some_and(|size| size > MAX_RESOURCE_SIZE as u64)
+        {
+            return Err(too_large());
+        }
+        let url = response.url().to_string();
+        let status = response.status().as_u16();
+        let headers = response
+            .headers()
+            .iter()
+            .map(|(name, value)| {
+                (
+                    name.to_string(),
+                    String::from_utf8_lossy(value.as_bytes()).into_owned(),
+                )
+            })
+            .collect();
+        let mut body = vec![];
+        while let Some(chunk) = response.chunk().await.map_err(reqwest_error)? {
+            body.extend_from_slice(&chunk);
+            if body.len() > MAX_RESOURCE_SIZE {
+                return Err(too_large());
+            }
+        }
+        Ok(Fetched {
+            url,
+            status,
+            headers,
+            body,
+        })
+    }
+
+    /// Fetches a resource of the page. Failing resources are skipped, a
+    /// snapshot with a missing image is better than no snapshot.
+    async fn fetch(&self, url: &str) -> Option<Fetched> {
+        let fetched = self.get(url).await.ok()?;
+        (200..300).contains(&fetched.status).then_some(fetched)
+    }
+
+    async fn fetch_data_url(
+        &self,
+        url: &str,
+        data_urls: &mut HashMap<String, String>,
+        responses: &mut Vec<Fetched>,
+    ) {
+        if data_urls.contains_key(url) {
+            return;
+        }
+        if let Some(fetched) = self.fetch(url).await {
+            data_urls.insert(url.to_string(), fetched.data_url());
+            responses.push(fetched);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use wiremock::{
+        matchers::{method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    async fn mount(server: &MockServer, route: &str, mime: &str, body: &[u8]) {
+        Mock::given(method("GET"))
+            .and(path(route))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("content-type", mime)
+                    .set_body_bytes(body),
+            )
+            .mount(server)
+            .await;
+    }
+
+    fn archiver() -> Archiver {
+        Archiver::new(Duration::from_secs(10), "readlater-test")
+    }
+
+    async fn server() -> MockServer {
+        let server = MockServer::start().await;
+        mount(
+            &server,
+            "/posts/1",
+            "text/html; charset=utf-8",
+            br#"<html><head><title>Post</title><link rel="stylesheet" href="/css/site.css"></head>
+<body><h1>Post</h1><img src="a.png" alt="a"><img src="/missing.png"></body></html>"#,
+        )
+        .await;
+        mount(
+            &server,
+            "/css/site.css",
+            "text/css",
+            b"body { background: url(../bg.png) }",
+        )
+        .await;
+        mount(&server, "/posts/a.png", "image/png", b"A").await;
+        mount(&server, "/bg.png", "image/png", b"B").await;
+        server
+    }
+
+    #[tokio::test]
+    async fn test_capture() {
+        let server = server().await;
+        let capture = archiver()
+            .capture(&format!("{}/posts/1", server.uri()))
+            .await
+            .unwrap();
+
+        assert_eq!(capture.status, 200);
+        assert!(capture.warc.is_none());
+        let html = &capture.html;
+        assert!(html
+            .contains(r#"<style>body { background: url("data:image/png;base64,Qg==") }</style>"#));
+        assert!(html.contains(r#"<img alt="a" src="data:image/png;base64,QQ==">"#));
+        assert!(html.contains(&format!(r#"<img src="{}/missing.png">"#, server.uri())));
+    }
+
+    #[tokio::test]
+    async fn test_capture_warc() {
+        let server = server().await;
+        let capture = archiver()
+            .warc(true)
+            .capture(&format!("{}/posts/1", server.uri()))
+            .await
+            .unwrap();
+
+        let warc = String::from_utf8(capture.warc.unwrap()).unwrap();
+        assert!(warc.starts_with("WARC/1.1\r\n"));
+        assert_eq!(warc.matches("WARC-Type: warcinfo").count(), 1);
+        // page, stylesheet and two images
+        assert_eq!(warc.matches("WARC-Type: response").count(), 4);
+        assert!(warc.contains(&format!("WARC-Target-URI: {}/posts/1\r\n", server.uri())));
+    }
+
+    #[tokio::test]
+    async fn test_capture_records_status() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(404).set_body_string("gone"))
+            .mount(&server)
+            .await;
+
+        let capture = archiver().capture(&server.uri()).await.unwrap();
+        assert_eq!(capture.status, 404);
+    }
+
+    #[tokio::test]
+    async fn test_large_resources_stay_remote() {
+        let server = MockServer::start().await;
+        mount(
+            &server,
+            "/",
+            "text/html",
+            br#"<html><body><img src="/big.png"></body></html>"#,
+        )
+        .await;
+        mount(
+            &server,
+            "/big.png",
+            "image/png",
+            &vec![0; MAX_RESOURCE_SIZE + 1],
+        )
+        .await;
+
+        let capture = archiver().capture(&server.uri()).await.unwrap();
+        assert!(capture
+            .html
+            .contains(&format!(r#"<img src="{}/big.png">"#, server.uri())));
+    }
+}
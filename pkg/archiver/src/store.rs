@@ -0,0 +1,96 @@
+use crate::{ArchiveResult, Capture};
+use localdb::{LocalDb, Snapshot};
+use std::path::{Path, PathBuf};
+
+/// Stores captures on disk as `<item id>/<unix time>.html` along with the
+/// optional `.warc` file, and records them in the database.
+pub struct SnapshotStore {
+    root: PathBuf,
+}
+
+impl SnapshotStore {
+    pub fn new(root: impl Into<PathBuf>) -> SnapshotStore {
+        SnapshotStore { root: root.into() }
+    }
+
+    pub fn path(&self, relative: &str) -> PathBuf {
+        self.root.join(relative)
+    }
+
+    pub async fn save(
+        &self,
+        db: &mut LocalDb,
+        item_id: i64,
+        capture: &Capture,
+    ) -> ArchiveResult<Snapshot> {
+        let name = format!("{}/{}", item_id, capture.time_fetched.timestamp());
+        tokio::fs::create_dir_all(self.root.join(item_id.to_string())).await?;
+
+        let html_path = format!("{name}.html");
+        write(&self.path(&html_path), capture.html.as_bytes()).await?;
+        let mut size = capture.html.len();
+
+        let warc_path = match &capture.warc {
+            Some(warc) => {
+                let warc_path = format!("{name}.warc");
+                write(&self.path(&warc_path), warc).await?;
+                size += warc.len();
+                Some(warc_path)
+            }
+            None => None,
+        };
+
+        let mut snapshot = Snapshot {
+            id: 0,
+            item_id,
+            url: capture.url.clone(),
+            status: capture.status as i32,
+            html_path,
+            warc_path,
+            size: size as i64,
+            time_fetched: capture.time_fetched.timestamp() as i32,
+        };
+        snapshot.id = db.add_snapshot(&snapshot).await?;
+        Ok(snapshot)
+    }
+}
+
+async fn write(path: &Path, data: &[u8]) -> ArchiveResult<()> {
+    let tmp = path.with_extension("tmp");
+    tokio::fs::write(&tmp, data).await?;
+    tokio::fs::rename(&tmp, path).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::DateTime;
+    use localdb::open_database;
+
+    #[tokio::test]
+    async fn test_save() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SnapshotStore::new(dir.path());
+        let mut db = LocalDb::new(open_database(":memory:").await.unwrap());
+        let item_id = db.add(&Default::default()).await.unwrap() as i64;
+
+        let capture = Capture {
+            url: "https://example.com/".to_string(),
+            status: 200,
+            time_fetched: DateTime::from_timestamp(1741600000, 0).unwrap(),
+            html: "<html></html>".to_string(),
+            warc: Some(b"WARC/1.1".to_vec()),
+        };
+        let snapshot = store.save(&mut db, item_id, &capture).await.unwrap();
+
+        assert_eq!(snapshot.html_path, format!("{item_id}/1741600000.html"));
+        assert_eq!(
+            std::fs::read_to_string(store.path(&snapshot.html_path)).unwrap(),
+            "<html></html>"
+        );
+        let warc_path = snapshot.warc_path.as_ref().unwrap();
+        assert_eq!(std::fs::read(store.path(warc_path)).unwrap(), b"WARC/1.1");
+        assert_eq!(db.get_snapshots(item_id).await.unwrap(), vec![snapshot]);
+    }
+}
@@ -0,0 +1,110 @@
+use chrono::{DateTime, Utc};
+
+/// A captured http response
+pub struct Response<'a> {
+    pub url: &'a str,
+    pub status: u16,
+    pub headers: &'a [(String, String)],
+    pub body: &'a [u8],
+}
+
+/// Headers that no longer describe the body once it has been decoded by the
+/// http client
+const DROPPED_HEADERS: &[&str] = &["content-encoding", "content-length", "transfer-encoding"];
+
+/// Writes a `warcinfo` record describing the file
+pub fn warcinfo(date: DateTime<Utc>, filename: &str) -> Vec<u8> {
+    let block = format!(
+        "software: readlater/{}\r\nformat: WARC File Format 1.1\r\n",
+        env!("CARGO_PKG_VERSION")
+    );
+    record(
+        &[
+            ("WARC-Type", "warcinfo"),
+            ("WARC-Date", &format_date(date)),
+            ("WARC-Filename", filename),
+            ("Content-Type", "application/warc-fields"),
+        ],
+        block.as_bytes(),
+    )
+}
+
+/// Writes a WARC/1.1 `response` record for the given http response
+pub fn response(date: DateTime<Utc>, response: &Response) -> Vec<u8> {
+    let reason = reqwest::StatusCode::from_u16(response.status)
+        .ok()
+        .and_then(|status| status.canonical_reason())
+        .unwrap_or_default();
+    let mut block = format!("HTTP/1.1 {} {}\r\n", response.status, reason).into_bytes();
+    for (name, value) in response.headers {
+        if DROPPED_HEADERS.contains(&name.to_ascii_lowercase().as_str()) {
+            continue;
+        }
+        block.extend_from_slice(format!("{}: {}\r\n", name, value).as_bytes());
+    }
+    block.extend_from_slice(format!("Content-Length: {}\r\n\r\n", response.body.len()).as_bytes());
+    block.extend_from_slice(response.body);
+
+    record(
+        &[
+            ("WARC-Type", "response"),
+            ("WARC-Date", &format_date(date)),
+            ("WARC-Target-URI", response.url),
+            ("Content-Type", "application/http;msgtype=response"),
+        ],
+        &block,
+    )
+}
+
+fn record(fields: &[(&str, &str)], block: &[u8]) -> Vec<u8> {
+    let mut record = b"WARC/1.1\r\n".to_vec();
+    record.extend_from_slice(
+        format!("WARC-Record-ID: <urn:uuid:{}>\r\n", uuid::Uuid::new_v4()).as_bytes(),
+    );
+    for (name, value) in fields {
+        record.extend_from_slice(format!("{}: {}\r\n", name, value).as_bytes());
+    }
+    record.extend_from_slice(format!("Content-Length: {}\r\n\r\n", block.len()).as_bytes());
+    record.extend_from_slice(block);
+    record.extend_from_slice(b"\r\n\r\n");
+    record
+}
+
+fn format_date(date: DateTime<Utc>) -> String {
+    date.format("%Y-%m-%dT%H:%M:%SZ").to_string()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_response_record() {
+        let date = DateTime::from_timestamp(1741600000, 0).unwrap();
+        let headers = vec![
+            ("content-type".to_string(), "text/html".to_string()),
+            ("content-encoding".to_string(), "gzip".to_string()),
+        ];
+        let record = response(
+            date,
+            &Response {
+                url: "https://example.com/",
+                status: 200,
+                headers: &headers,
+                body: b"<p>hi</p>",
+            },
+        );
+        let record = String::from_utf8(record).unwrap();
+        let (head, block) = record.split_once("\r\n\r\n").unwrap();
+
+        assert!(head.starts_with("WARC/1.1\r\nWARC-Record-ID: <urn:uuid:"));
+        assert!(head.contains("WARC-Type: response\r\n"));
+        assert!(head.contains("WARC-Date: 2025-03-10T09:46:40Z\r\n"));
+        assert!(head.contains("WARC-Target-URI: https://example.com/\r\n"));
+        assert_eq!(
+            block,
+            "HTTP/1.1 200 OK\r\ncontent-type: text/html\r\nContent-Length: 9\r\n\r\n<p>hi</p>\r\n\r\n"
+        );
+        assert!(head.ends_with(&format!("Content-Length: {}", block.len() - 4)));
+    }
+}
@@ -0,0 +1,19 @@
+#[derive(Debug, thiserror::Error)]
+pub enum AssetError {
+    #[error("database error: {0}")]
+    Db(#[from] localdb::DBError),
+
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Request error for URL <{url}>: {source}")]
+    Reqwest { url: String, source: reqwest::Error },
+
+    #[error("asset <{url}> of {size} bytes exceeds the cache budget")]
+    TooLarge { url: String, size: u64 },
+
+    #[error("invalid url: {0}")]
+    InvalidUrl(String),
+}
+
+pub type AssetResult<T> = Result<T, AssetError>;
@@ -0,0 +1,448 @@
+mod error;
+
+pub use error::{AssetError, AssetResult};
+use localdb::{Asset, Item, LocalDb};
+use reqwest::{header::CONTENT_TYPE, Client};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    io::ErrorKind,
+    path::PathBuf,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use url::Url;
+
+/// Content addressed store for images and other files referenced by items.
+///
+/// Files live under `root/<first two hex digits>/<sha256>` and are shared by
+/// every url serving the same bytes. Once the store grows beyond `budget`
+/// bytes, the least recently used files are evicted. A download larger than
+/// the whole budget is abandoned, as is one taking longer than `timeout`.
+pub struct AssetStore {
+    root: PathBuf,
+    budget: u64,
+    client: Client,
+}
+
+impl AssetStore {
+    pub fn new(root: impl Into<PathBuf>, budget: u64, timeout: Duration) -> AssetStore {
+        let client = Client::builder()
+            .timeout(timeout)
+            .build()
+            .expect("failed to build reqwest client");
+        AssetStore {
+            root: root.into(),
+            budget,
+            client,
+        }
+    }
+
+    pub fn path(&self, asset: &Asset) -> PathBuf {
+        self.root.join(&asset.path)
+    }
+
+    /// Returns a cached asset and its content without touching the network.
+    pub async fn get(&self, db: &mut LocalDb, url: &str) -> AssetResult<Option<(Asset, Vec<u8>)>> {
+        let Some(asset) = db.get_asset(url).await? else {
+            return Ok(None);
+        };
+        match tokio::fs::read(self.path(&asset)).await {
+            Ok(data) => {
+                db.touch_asset(url, now()).await?;
+                Ok(Some((asset, data)))
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Returns the cached asset for the url without reading it or touching
+    /// the network.
+    pub async fn find(&self, db: &LocalDb, url: &str) -> AssetResult<Option<Asset>> {
+        let asset = db.get_asset(url).await?;
+        Ok(asset.filter(|asset| self.path(asset).exists()))
+    }
+
+    /// Returns the cached asset for the url, downloading it if needed.
+    pub async fn fetch(&self, db: &mut LocalDb, url: &str) -> AssetResult<Asset> {
+        if let Some(asset) = db.get_asset(url).await? {
+            if self.path(&asset).exists() {
+                db.touch_asset(url, now()).await?;
+                return Ok(asset);
+            }
+        }
+
+        let reqwest_error = |source| AssetError::Reqwest {
+            url: url.to_string(),
+            source,
+        };
+        let too_large = |size| AssetError::TooLarge {
+            url: url.to_string(),
+            size,
+        };
+        let mut response = self
+            .client
+            .get(url)
+            .send()
+            .await
+            .and_then(|res| res.error_for_status())
+            .map_err(reqwest_error)?;
+        let mime = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|mime| mime.to_str().ok())
+            .and_then(|mime| mime.split(';').next())
+            .map(|mime| mime.trim().to_lowercase())
+            .filter(|mime| !mime.is_empty() && mime != "application/octet-stream");
+        if let Some(size) = response.content_length().filter(|size| *size > self.budget) {
+            return Err(too_large(size));
+        }
+        // the length may be missing or wrong, so the budget is also checked
+        // while streaming
+        let mut data = vec![];
+        while let Some(chunk) = response.chunk().await.map_err(reqwest_error)? {
+            data.extend_from_slice(&chunk);
+            if data.len() as u64 > self.budget {
+                return Err(too_large(data.len() as u64));
+            }
+        }
+
+        let size = data.len() as u64;
+
+        let hash = hex(&Sha256::digest(&data));
+        let path = format!("{}/{}", &hash[..2], hash);
+        let file = self.root.join(&path);
+        if !file.exists() {
+            tokio::fs::create_dir_all(file.parent().expect("asset has a parent dir")).await?;
+            // write to a temporary file first so that a crash never leaves a
+            // truncated file behind under a valid hash
+            let tmp = file.with_extension("tmp");
+            tokio::fs::write(&tmp, &data).await?;
+            tokio::fs::rename(&tmp, &file).await?;
+        }
+
+        let now = now();
+        let asset = Asset {
+            id: 0,
+            url: url.to_string(),
+            mime: mime.unwrap_or_else(|| sniff_mime(&data).to_string()),
+            hash,
+            path,
+            size: size as i64,
+            time_fetched: now,
+            time_accessed: now,
+        };
+        db.add_asset(&asset).await?;
+        self.evict(db).await?;
+        Ok(asset)
+    }
+
+    /// Downloads the images of an item, including the ones referenced by its
+    /// extracted content. Returns the result for every url.
+    pub async fn cache_item(
+        &self,
+        db: &mut LocalDb,
+        item: &Item,
+    ) -> AssetResult<Vec<(String, AssetResult<Asset>)>> {
+        let content = db.get_content(item.id).await?;
+        let urls = asset_urls(item, content.as_ref().map(|c| c.html.as_str()));
+
+        let mut results = vec![];
+        for url in urls {
+            let result = self.fetch(db, &url).await;
+            results.push((url, result));
+        }
+        Ok(results)
+    }
+
+    /// Removes the least recently used files until the store fits within its
+    /// budget. Returns the number of bytes freed.
+    pub async fn evict(&self, db: &mut LocalDb) -> AssetResult<u64> {
+        let mut files = files(db.get_assets().await?);
+        let mut total: u64 = files.iter().map(|file| file.size).sum();
+        files.sort_by_key(|file| file.time_accessed);
+
+        let mut freed = 0;
+        for file in files {
+            if total <= self.budget {
+                break;
+            }
+            match tokio::fs::remove_file(self.root.join(&file.path)).await {
+                Err(e) if e.kind() != ErrorKind::NotFound => return Err(e.into()),
+                _ => {}
+            }
+            db.delete_assets(&file.hash).await?;
+            total -= file.size;
+            freed += file.size;
+        }
+        Ok(freed)
+    }
+
+    /// Number of files in the store and their total size in bytes
+    pub async fn usage(&self, db: &LocalDb) -> AssetResult<(usize, u64)> {
+        let files = files(db.get_assets().await?);
+        Ok((files.len(), files.iter().map(|file| file.size).sum()))
+    }
+}
+
+struct File {
+    hash: String,
+    path: String,
+    size: u64,
+    time_accessed: i32,
+}
+
+/// Groups assets by the file they point to
+fn files(assets: Vec<Asset>) -> Vec<File> {
+    let mut files: HashMap<String, File> = HashMap::new();
+    for asset in assets {
+        let file = files.entry(asset.hash.clone()).or_insert(File {
+            hash: asset.hash,
+            path: asset.path,
+            size: asset.size as u64,
+            time_accessed: asset.time_accessed,
+        });
+        file.time_accessed = file.time_accessed.max(asset.time_accessed);
+    }
+    files.into_values().collect()
+}
+
+/// Resolves `src` relative to the page it was found on. Only http(s) urls are
+/// returned.
+pub fn resolve(base: &str, src: &str) -> Option<String> {
+    let url = match Url::parse(base) {
+        Ok(base) => base.join(src).ok()?,
+        Err(_) => Url::parse(src).ok()?,
+    };
+    matches!(url.scheme(), "http" | "https").then(|| url.to_string())
+}
+
+/// Urls of the images of an item and the ones in its html content
+pub fn asset_urls(item: &Item, html: Option<&str>) -> Vec<String> {
+    let mut urls: Vec<String> = vec![];
+    let mut push = |url: Option<String>| {
+        if let Some(url) = url {
+            if !urls.contains(&url) {
+                urls.push(url);
+            }
+        }
+    };
+
+    push(
+        item.top_image_url
+            .as_deref()
+            .and_then(|src| resolve(&item.url, src)),
+    );
+    for image in &item.images {
+        push(resolve(&item.url, &image.src));
+    }
+    if let Some(html) = html {
+        let document = scraper::Html::parse_fragment(html);
+        let selector = scraper::Selector::parse("img[src]").expect("valid selector");
+        for img in document.select(&selector) {
+            push(img.attr("src").and_then(|src| resolve(&item.url, src)));
+        }
+    }
+    urls
+}
+
+fn sniff_mime(data: &[u8]) -> &'static str {
+    match data {
+        [0x89, b'P', b'N', b'G', ..] => "image/png",
+        [0xff, 0xd8, 0xff, ..] => "image/jpeg",
+        [b'G', b'I', b'F', b'8', ..] => "image/gif",
+        [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'E', b'B', b'P', ..] => "image/webp",
+        _ if data.starts_with(b"<svg") || data.starts_with(b"<?xml") => "image/svg+xml",
+        _ => "application/octet-stream",
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn now() -> i32 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i32)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use localdb::{open_database, Content, Image};
+    use std::collections::HashSet;
+    use wiremock::{
+        matchers::{method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    const PNG: &[u8] = &[0x89, b'P', b'N', b'G', 1, 2, 3, 4];
+    const TIMEOUT: Duration = Duration::from_secs(10);
+
+    async fn get_db() -> LocalDb {
+        LocalDb::new(open_database(":memory:").await.unwrap())
+    }
+
+    async fn server() -> MockServer {
+        let server = MockServer::start().await;
+        for route in ["/a.png", "/copy-of-a.png"] {
+            Mock::given(method("GET"))
+                .and(path(route))
+                .respond_with(ResponseTemplate::new(200).set_body_bytes(PNG))
+                .mount(&server)
+                .await;
+        }
+        Mock::given(method("GET"))
+            .and(path("/b.jpg"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("content-type", "image/jpeg")
+                    .set_body_bytes(vec![0xff, 0xd8, 0xff, 0, 0, 0]),
+            )
+            .mount(&server)
+            .await;
+        server
+    }
+
+    #[tokio::test]
+    async fn test_fetch_deduplicates_content() {
+        let server = server().await;
+        let dir = tempfile::tempdir().unwrap();
+        let store = AssetStore::new(dir.path(), 1024, TIMEOUT);
+        let mut db = get_db().await;
+
+        let a = store
+            .fetch(&mut db, &format!("{}/a.png", server.uri()))
+            .await
+            .unwrap();
+        let copy = store
+            .fetch(&mut db, &format!("{}/copy-of-a.png", server.uri()))
+            .await
+            .unwrap();
+        assert_eq!(a.hash, copy.hash);
+        assert_eq!(a.mime, "image/png");
+        assert_eq!(std::fs::read(store.path(&a)).unwrap(), PNG);
+        assert_eq!(store.usage(&db).await.unwrap(), (1, PNG.len() as u64));
+
+        let b = store
+            .fetch(&mut db, &format!("{}/b.jpg", server.uri()))
+            .await
+            .unwrap();
+        assert_eq!(b.mime, "image/jpeg");
+        assert_eq!(store.usage(&db).await.unwrap().0, 2);
+
+        let (cached, data) = store.get(&mut db, &a.url).await.unwrap().unwrap();
+        assert_eq!(cached.hash, a.hash);
+        assert_eq!(data, PNG);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_over_budget() {
+        let server = server().await;
+        Mock::given(method("GET"))
+            .and(path("/slow.png"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_bytes(PNG)
+                    .set_delay(Duration::from_secs(5)),
+            )
+            .mount(&server)
+            .await;
+        let dir = tempfile::tempdir().unwrap();
+        let store = AssetStore::new(dir.path(), 4, Duration::from_millis(200));
+        let mut db = get_db().await;
+
+        let result = store
+            .fetch(&mut db, &format!("{}/a.png", server.uri()))
+            .await;
+        assert!(matches!(result, Err(AssetError::TooLarge { .. })));
+        let result = store
+            .fetch(&mut db, &format!("{}/slow.png", server.uri()))
+            .await;
+        assert!(matches!(result, Err(AssetError::Reqwest { .. })));
+        assert!(db.get_assets().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_fetch_missing() {
+        let server = server().await;
+        let dir = tempfile::tempdir().unwrap();
+        let store = AssetStore::new(dir.path(), 1024, TIMEOUT);
+        let mut db = get_db().await;
+
+        let result = store
+            .fetch(&mut db, &format!("{}/missing.png", server.uri()))
+            .await;
+        assert!(matches!(result, Err(AssetError::Reqwest { .. })));
+        assert!(db.get_assets().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_evicts_least_recently_used() {
+        let server = server().await;
+        let dir = tempfile::tempdir().unwrap();
+        // fits only one of the two files
+        let store = AssetStore::new(dir.path(), 10, TIMEOUT);
+        let mut db = get_db().await;
+
+        let a = store
+            .fetch(&mut db, &format!("{}/a.png", server.uri()))
+            .await
+            .unwrap();
+        db.touch_asset(&a.url, 0).await.unwrap();
+        let b = store
+            .fetch(&mut db, &format!("{}/b.jpg", server.uri()))
+            .await
+            .unwrap();
+
+        assert!(db.get_asset(&a.url).await.unwrap().is_none());
+        assert!(!store.path(&a).exists());
+        assert!(store.path(&b).exists());
+    }
+
+    #[tokio::test]
+    async fn test_cache_item() {
+        let server = server().await;
+        let dir = tempfile::tempdir().unwrap();
+        let store = AssetStore::new(dir.path(), 1024, TIMEOUT);
+        let mut db = get_db().await;
+
+        let item = Item {
+            url: format!("{}/articles/1", server.uri()),
+            images: HashSet::from([Image {
+                src: format!("{}/a.png", server.uri()),
+                ..Default::default()
+            }]),
+            ..Default::default()
+        };
+        let id = db.add(&item).await.unwrap() as i64;
+        db.set_content(&Content {
+            item_id: id,
+            html: r#"<p><img src="/b.jpg"><img src="/a.png"></p>"#.to_string(),
+            time_fetched: None,
+        })
+        .await
+        .unwrap();
+
+        let item = Item { id, ..item };
+        let results = store.cache_item(&mut db, &item).await.unwrap();
+        assert_eq!(results.len(), 2);
+        assert!(results.iter().all(|(_, result)| result.is_ok()));
+    }
+
+    #[test]
+    fn test_resolve() {
+        let base = "https://example.com/posts/1";
+        assert_eq!(
+            resolve(base, "/img/a.png").as_deref(),
+            Some("https://example.com/img/a.png")
+        );
+        assert_eq!(
+            resolve(base, "b.png").as_deref(),
+            Some("https://example.com/posts/b.png")
+        );
+        assert_eq!(resolve(base, "data:image/png;base64,AAAA"), None);
+    }
+}
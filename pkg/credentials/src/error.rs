@@ -0,0 +1,16 @@
+#[derive(Debug, thiserror::Error)]
+pub enum CredentialError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("invalid credentials file: {0}")]
+    Format(String),
+
+    #[error("wrong passphrase, or the credentials file was tampered with")]
+    WrongPassphrase,
+
+    #[error("secret service error: {0}")]
+    SecretService(#[from] secret_service::Error),
+}
+
+pub type CredentialResult<T> = Result<T, CredentialError>;
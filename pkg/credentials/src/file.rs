@@ -0,0 +1,272 @@
+use crate::{CredentialError, CredentialResult, CredentialStore};
+use argon2::Argon2;
+use async_trait::async_trait;
+use base64::{prelude::BASE64_STANDARD, Engine};
+use chacha20poly1305::{
+    aead::{rand_core::RngCore, Aead, AeadCore, KeyInit, OsRng},
+    Key, XChaCha20Poly1305, XNonce,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+const VERSION: u32 = 1;
+const SALT_LEN: usize = 16;
+
+/// On disk layout, every field but the version is base64
+#[derive(Serialize, Deserialize)]
+struct Envelope {
+    version: u32,
+    salt: String,
+    nonce: String,
+    data: String,
+}
+
+/// Secrets kept in a single file, encrypted with XChaCha20-Poly1305 under a
+/// key derived from a passphrase with Argon2id. The whole file is rewritten
+/// on every change.
+///
+/// Other processes share the file, so it is read again on every access and
+/// changed under a lock on a `.lock` file next to it.
+pub struct EncryptedFile {
+    path: PathBuf,
+    passphrase: String,
+    /// Key derived for the salt the file was last read or written with
+    key: Mutex<Option<([u8; SALT_LEN], Key)>>,
+}
+
+impl EncryptedFile {
+    /// Opens the file at `path`, or starts an empty one if it does not exist
+    pub fn open(path: impl Into<PathBuf>, passphrase: &str) -> CredentialResult<EncryptedFile> {
+        let store = EncryptedFile {
+            path: path.into(),
+            passphrase: passphrase.to_string(),
+            key: Mutex::default(),
+        };
+        // fail early on a wrong passphrase
+        let _lock = store.lock(false)?;
+        store.read()?;
+        Ok(store)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// `path` with `suffix` appended to its file name
+    fn sibling(&self, suffix: &str) -> PathBuf {
+        let mut name = self.path.as_os_str().to_owned();
+        name.push(suffix);
+        PathBuf::from(name)
+    }
+
+    /// Locks the file against the other processes until the returned file
+    /// is dropped, `exclusive` for changes
+    fn lock(&self, exclusive: bool) -> CredentialResult<std::fs::File> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let file = std::fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(self.sibling(".lock"))?;
+        if exclusive {
+            file.lock()?;
+        } else {
+            file.lock_shared()?;
+        }
+        Ok(file)
+    }
+
+    /// Key for `salt`, derived again only when the salt changed
+    fn key(&self, salt: &[u8; SALT_LEN]) -> CredentialResult<Key> {
+        let mut cached = self.key.lock().unwrap();
+        match &*cached {
+            Some((cached_salt, key)) if cached_salt == salt => Ok(*key),
+            _ => {
+                let key = derive_key(&self.passphrase, salt)?;
+                *cached = Some((*salt, key));
+                Ok(key)
+            }
+        }
+    }
+
+    /// Decrypts the secrets currently in the file, none when it is missing
+    fn read(&self) -> CredentialResult<BTreeMap<String, String>> {
+        let text = match std::fs::read_to_string(&self.path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(BTreeMap::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let envelope: Envelope =
+            serde_json::from_str(&text).map_err(|e| CredentialError::Format(e.to_string()))?;
+        if envelope.version != VERSION {
+            return Err(CredentialError::Format(format!(
+                "unsupported version {}",
+                envelope.version
+            )));
+        }
+        let salt: [u8; SALT_LEN] = decode(&envelope.salt)?
+            .try_into()
+            .map_err(|_| CredentialError::Format("bad salt".to_string()))?;
+        let nonce = decode(&envelope.nonce)?;
+        if nonce.len() != 24 {
+            return Err(CredentialError::Format("bad nonce".to_string()));
+        }
+        let plain = XChaCha20Poly1305::new(&self.key(&salt)?)
+            .decrypt(
+                XNonce::from_slice(&nonce),
+                decode(&envelope.data)?.as_slice(),
+            )
+            .map_err(|_| CredentialError::WrongPassphrase)?;
+        serde_json::from_slice(&plain).map_err(|e| CredentialError::Format(e.to_string()))
+    }
+
+    /// Writes the secrets under a fresh nonce, replacing the file atomically
+    fn save(&self, secrets: &BTreeMap<String, String>) -> CredentialResult<()> {
+        let cached = *self.key.lock().unwrap();
+        let (salt, key) = match cached {
+            Some(cached) => cached,
+            None => {
+                let mut salt = [0; SALT_LEN];
+                OsRng.fill_bytes(&mut salt);
+                (salt, self.key(&salt)?)
+            }
+        };
+        let plain = serde_json::to_vec(secrets).expect("secrets serialize");
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let data = XChaCha20Poly1305::new(&key)
+            .encrypt(&nonce, plain.as_slice())
+            .map_err(|_| CredentialError::Format("encryption failed".to_string()))?;
+        let envelope = Envelope {
+            version: VERSION,
+            salt: BASE64_STANDARD.encode(salt),
+            nonce: BASE64_STANDARD.encode(nonce),
+            data: BASE64_STANDARD.encode(data),
+        };
+
+        let tmp = self.sibling(&format!(
+            ".{}-{:x}.tmp",
+            std::process::id(),
+            OsRng.next_u64()
+        ));
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let mut file = options.open(&tmp)?;
+        let written = file
+            .write_all(serde_json::to_string(&envelope).unwrap().as_bytes())
+            .and_then(|_| file.sync_all())
+            .and_then(|_| std::fs::rename(&tmp, &self.path));
+        if written.is_err() {
+            let _ = std::fs::remove_file(&tmp);
+        }
+        Ok(written?)
+    }
+}
+
+fn derive_key(passphrase: &str, salt: &[u8]) -> CredentialResult<Key> {
+    let mut key = Key::default();
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| CredentialError::Format(e.to_string()))?;
+    Ok(key)
+}
+
+fn decode(text: &str) -> CredentialResult<Vec<u8>> {
+    BASE64_STANDARD
+        .decode(text)
+        .map_err(|e| CredentialError::Format(e.to_string()))
+}
+
+#[async_trait]
+impl CredentialStore for EncryptedFile {
+    fn backend(&self) -> &'static str {
+        "file"
+    }
+
+    async fn get(&self, key: &str) -> CredentialResult<Option<String>> {
+        let _lock = self.lock(false)?;
+        Ok(self.read()?.remove(key))
+    }
+
+    async fn set(&self, key: &str, secret: &str) -> CredentialResult<()> {
+        let _lock = self.lock(true)?;
+        let mut secrets = self.read()?;
+        secrets.insert(key.to_string(), secret.to_string());
+        self.save(&secrets)
+    }
+
+    async fn delete(&self, key: &str) -> CredentialResult<bool> {
+        let _lock = self.lock(true)?;
+        let mut secrets = self.read()?;
+        let removed = secrets.remove(key).is_some();
+        if removed {
+            self.save(&secrets)?;
+        }
+        Ok(removed)
+    }
+
+    async fn keys(&self) -> CredentialResult<Vec<String>> {
+        let _lock = self.lock(false)?;
+        Ok(self.read()?.into_keys().collect())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("credentials");
+
+        let store = EncryptedFile::open(&path, "hunter2").unwrap();
+        store.set("pocket", "token").await.unwrap();
+        store.set("wallabag", "other").await.unwrap();
+        assert!(store.delete("wallabag").await.unwrap());
+        assert!(!store.delete("wallabag").await.unwrap());
+
+        let text = std::fs::read_to_string(&path).unwrap();
+        assert!(!text.contains("token"));
+
+        let store = EncryptedFile::open(&path, "hunter2").unwrap();
+        assert_eq!(store.get("pocket").await.unwrap().as_deref(), Some("token"));
+        assert_eq!(store.keys().await.unwrap(), vec!["pocket"]);
+
+        assert!(matches!(
+            EncryptedFile::open(&path, "wrong"),
+            Err(CredentialError::WrongPassphrase)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_shared_between_processes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("credentials.json");
+
+        // opened before the file exists, the other one creates it
+        let daemon = EncryptedFile::open(&path, "hunter2").unwrap();
+        let cli = EncryptedFile::open(&path, "hunter2").unwrap();
+        cli.set("wallabag", "first").await.unwrap();
+        daemon.set("pocket", "token").await.unwrap();
+        cli.set("wallabag", "rotated").await.unwrap();
+
+        assert_eq!(
+            daemon.get("wallabag").await.unwrap().as_deref(),
+            Some("rotated")
+        );
+        assert_eq!(cli.keys().await.unwrap(), vec!["pocket", "wallabag"]);
+        let files: Vec<_> = std::fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(files.len(), 2, "{files:?}");
+    }
+}
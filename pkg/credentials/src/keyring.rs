@@ -0,0 +1,81 @@
+use crate::{CredentialResult, CredentialStore};
+use async_trait::async_trait;
+use secret_service::{EncryptionType, SecretService};
+use std::collections::HashMap;
+
+/// Secrets kept by the freedesktop Secret Service, such as GNOME Keyring or
+/// KWallet, tagged with the name of the application
+pub struct Keyring {
+    service: SecretService<'static>,
+    application: String,
+}
+
+impl Keyring {
+    pub async fn connect(application: &str) -> CredentialResult<Keyring> {
+        Ok(Keyring {
+            service: SecretService::connect(EncryptionType::Dh).await?,
+            application: application.to_string(),
+        })
+    }
+
+    fn attributes<'a>(&'a self, key: &'a str) -> HashMap<&'a str, &'a str> {
+        HashMap::from([("application", self.application.as_str()), ("key", key)])
+    }
+}
+
+#[async_trait]
+impl CredentialStore for Keyring {
+    fn backend(&self) -> &'static str {
+        "secret-service"
+    }
+
+    async fn get(&self, key: &str) -> CredentialResult<Option<String>> {
+        let found = self.service.search_items(self.attributes(key)).await?;
+        let Some(item) = found.unlocked.first().or(found.locked.first()) else {
+            return Ok(None);
+        };
+        item.ensure_unlocked().await?;
+        let secret = item.get_secret().await?;
+        Ok(Some(String::from_utf8_lossy(&secret).into_owned()))
+    }
+
+    async fn set(&self, key: &str, secret: &str) -> CredentialResult<()> {
+        let collection = self.service.get_default_collection().await?;
+        collection.ensure_unlocked().await?;
+        let label = format!("{} {}", self.application, key);
+        collection
+            .create_item(
+                &label,
+                self.attributes(key),
+                secret.as_bytes(),
+                true,
+                "text/plain",
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn delete(&self, key: &str) -> CredentialResult<bool> {
+        let found = self.service.search_items(self.attributes(key)).await?;
+        let mut deleted = false;
+        for item in found.unlocked.iter().chain(found.locked.iter()) {
+            item.delete().await?;
+            deleted = true;
+        }
+        Ok(deleted)
+    }
+
+    async fn keys(&self) -> CredentialResult<Vec<String>> {
+        let attributes = HashMap::from([("application", self.application.as_str())]);
+        let found = self.service.search_items(attributes).await?;
+        let mut keys = vec![];
+        for item in found.unlocked.iter().chain(found.locked.iter()) {
+            if let Some(key) = item.get_attributes().await?.remove("key") {
+                keys.push(key);
+            }
+        }
+        keys.sort();
+        keys.dedup();
+        Ok(keys)
+    }
+}
@@ -0,0 +1,38 @@
+//! Keeps secrets such as access tokens out of the database, either in a file
+//! encrypted with a passphrase or in the freedesktop Secret Service.
+
+mod error;
+mod file;
+mod keyring;
+
+pub use error::{CredentialError, CredentialResult};
+pub use file::EncryptedFile;
+pub use keyring::Keyring;
+
+use async_trait::async_trait;
+use base64::{prelude::BASE64_STANDARD, Engine};
+use chacha20poly1305::aead::{rand_core::RngCore, OsRng};
+
+/// Random secret of 256 bits, as base64
+pub fn random_secret() -> String {
+    let mut bytes = [0; 32];
+    OsRng.fill_bytes(&mut bytes);
+    BASE64_STANDARD.encode(bytes)
+}
+
+/// Secrets stored under a key, such as the name of a remote
+#[async_trait]
+pub trait CredentialStore: Send + Sync {
+    /// Short name of the backend, for display
+    fn backend(&self) -> &'static str;
+
+    async fn get(&self, key: &str) -> CredentialResult<Option<String>>;
+
+    async fn set(&self, key: &str, secret: &str) -> CredentialResult<()>;
+
+    /// Removes the secret of `key`, returns whether there was one
+    async fn delete(&self, key: &str) -> CredentialResult<bool>;
+
+    /// Keys having a secret, sorted
+    async fn keys(&self) -> CredentialResult<Vec<String>>;
+}
@@ -0,0 +1,10 @@
+#[derive(Debug, thiserror::Error)]
+pub enum EpubError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("zip error: {0}")]
+    Zip(#[from] zip::result::ZipError),
+}
+
+pub type EpubResult<T> = Result<T, EpubError>;
@@ -0,0 +1,367 @@
+mod error;
+pub mod xhtml;
+
+pub use error::{EpubError, EpubResult};
+use std::io::{Cursor, Seek, Write};
+use xhtml::escape;
+use zip::{write::SimpleFileOptions, CompressionMethod, ZipWriter};
+
+const CONTAINER_XML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles>
+    <rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/>
+  </rootfiles>
+</container>
+"#;
+
+const STYLE_CSS: &str = r#"body { font-family: serif; line-height: 1.5; }
+h1 { font-size: 1.4em; margin-bottom: 0.2em; }
+p.source { font-size: 0.8em; color: #555; margin-top: 0; }
+span.alt { font-style: italic; }
+img { max-width: 100%; }
+pre { white-space: pre-wrap; }
+"#;
+
+pub struct Chapter {
+    pub title: String,
+    /// xhtml fragment placed inside `<body>`, see [`xhtml::from_html`]
+    pub body: String,
+}
+
+pub struct Resource {
+    pub href: String,
+    pub media_type: String,
+    pub data: Vec<u8>,
+}
+
+/// Builds an EPUB 3 book with a navigation document and a legacy NCX table of
+/// contents for older readers.
+pub struct EpubBuilder {
+    identifier: String,
+    title: String,
+    language: String,
+    modified: String,
+    creators: Vec<String>,
+    chapters: Vec<Chapter>,
+    resources: Vec<Resource>,
+}
+
+impl EpubBuilder {
+    pub fn new(identifier: &str, title: &str) -> EpubBuilder {
+        EpubBuilder {
+            identifier: identifier.to_string(),
+            title: title.to_string(),
+            language: "en".to_string(),
+            modified: "1970-01-01T00:00:00Z".to_string(),
+            creators: vec![],
+            chapters: vec![],
+            resources: vec![],
+        }
+    }
+
+    pub fn language(mut self, language: &str) -> EpubBuilder {
+        if !language.is_empty() {
+            self.language = language.to_string();
+        }
+        self
+    }
+
+    /// Last modification time formatted as `CCYY-MM-DDThh:mm:ssZ`
+    pub fn modified(mut self, modified: &str) -> EpubBuilder {
+        self.modified = modified.to_string();
+        self
+    }
+
+    pub fn creator(mut self, creator: &str) -> EpubBuilder {
+        self.creators.push(creator.to_string());
+        self
+    }
+
+    pub fn add_chapter(&mut self, chapter: Chapter) {
+        self.chapters.push(chapter);
+    }
+
+    /// Adds a file such as an image to the book and returns the path to
+    /// reference it with from a chapter.
+    pub fn add_resource(&mut self, media_type: &str, data: Vec<u8>) -> String {
+        let extension = media_type
+            .rsplit('/')
+            .next()
+            .map(|ext| ext.split('+').next().unwrap_or(ext))
+            .unwrap_or("bin");
+        let href = format!("images/{}.{}", self.resources.len() + 1, extension);
+        self.resources.push(Resource {
+            href: href.clone(),
+            media_type: media_type.to_string(),
+            data,
+        });
+        href
+    }
+
+    pub fn chapters(&self) -> &[Chapter] {
+        &self.chapters
+    }
+
+    pub fn write<W: Write + Seek>(&self, writer: W) -> EpubResult<W> {
+        let mut zip = ZipWriter::new(writer);
+        let stored = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+        let deflated = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+
+        // the mimetype must be the first entry and must not be compressed
+        zip.start_file("mimetype", stored)?;
+        zip.write_all(b"application/epub+zip")?;
+
+        zip.start_file("META-INF/container.xml", deflated)?;
+        zip.write_all(CONTAINER_XML.as_bytes())?;
+
+        zip.start_file("OEBPS/content.opf", deflated)?;
+        zip.write_all(self.package_document().as_bytes())?;
+
+        zip.start_file("OEBPS/nav.xhtml", deflated)?;
+        zip.write_all(self.navigation_document().as_bytes())?;
+
+        zip.start_file("OEBPS/toc.ncx", deflated)?;
+        zip.write_all(self.ncx().as_bytes())?;
+
+        zip.start_file("OEBPS/style.css", deflated)?;
+        zip.write_all(STYLE_CSS.as_bytes())?;
+
+        for (index, chapter) in self.chapters.iter().enumerate() {
+            zip.start_file(format!("OEBPS/{}", chapter_href(index)), deflated)?;
+            zip.write_all(self.chapter_document(chapter).as_bytes())?;
+        }
+
+        for resource in &self.resources {
+            // images are already compressed
+            zip.start_file(format!("OEBPS/{}", resource.href), stored)?;
+            zip.write_all(&resource.data)?;
+        }
+
+        Ok(zip.finish()?)
+    }
+
+    pub fn to_bytes(&self) -> EpubResult<Vec<u8>> {
+        Ok(self.write(Cursor::new(vec![]))?.into_inner())
+    }
+
+    fn package_document(&self) -> String {
+        let creators: String = self
+            .creators
+            .iter()
+            .map(|creator| format!("    <dc:creator>{}</dc:creator>\n", escape(creator)))
+            .collect();
+
+        let mut manifest = String::new();
+        let mut spine = String::new();
+        for index in 0..self.chapters.len() {
+            manifest.push_str(&format!(
+                "    <item id=\"chapter-{}\" href=\"{}\" media-type=\"application/xhtml+xml\"/>\n",
+                index + 1,
+                chapter_href(index)
+            ));
+            spine.push_str(&format!("    <itemref idref=\"chapter-{}\"/>\n", index + 1));
+        }
+        for (index, resource) in self.resources.iter().enumerate() {
+            manifest.push_str(&format!(
+                "    <item id=\"resource-{}\" href=\"{}\" media-type=\"{}\"/>\n",
+                index + 1,
+                escape(&resource.href),
+                escape(&resource.media_type)
+            ));
+        }
+
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="book-id" xml:lang="{language}">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+    <dc:identifier id="book-id">{identifier}</dc:identifier>
+    <dc:title>{title}</dc:title>
+    <dc:language>{language}</dc:language>
+{creators}    <meta property="dcterms:modified">{modified}</meta>
+  </metadata>
+  <manifest>
+    <item id="nav" href="nav.xhtml" media-type="application/xhtml+xml" properties="nav"/>
+    <item id="ncx" href="toc.ncx" media-type="application/x-dtbncx+xml"/>
+    <item id="css" href="style.css" media-type="text/css"/>
+{manifest}  </manifest>
+  <spine toc="ncx">
+{spine}  </spine>
+</package>
+"#,
+            language = escape(&self.language),
+            identifier = escape(&self.identifier),
+            title = escape(&self.title),
+            modified = escape(&self.modified),
+        )
+    }
+
+    fn navigation_document(&self) -> String {
+        let entries: String = self
+            .chapters
+            .iter()
+            .enumerate()
+            .map(|(index, chapter)| {
+                format!(
+                    "      <li><a href=\"{}\">{}</a></li>\n",
+                    chapter_href(index),
+                    escape(&chapter.title)
+                )
+            })
+            .collect();
+
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE html>
+<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops" xml:lang="{language}" lang="{language}">
+<head>
+  <meta charset="UTF-8"/>
+  <title>{title}</title>
+</head>
+<body>
+  <nav epub:type="toc" id="toc">
+    <h1>{title}</h1>
+    <ol>
+{entries}    </ol>
+  </nav>
+</body>
+</html>
+"#,
+            language = escape(&self.language),
+            title = escape(&self.title),
+        )
+    }
+
+    fn ncx(&self) -> String {
+        let points: String = self
+            .chapters
+            .iter()
+            .enumerate()
+            .map(|(index, chapter)| {
+                format!(
+                    r#"    <navPoint id="point-{n}" playOrder="{n}">
+      <navLabel><text>{title}</text></navLabel>
+      <content src="{href}"/>
+    </navPoint>
+"#,
+                    n = index + 1,
+                    title = escape(&chapter.title),
+                    href = chapter_href(index)
+                )
+            })
+            .collect();
+
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<ncx xmlns="http://www.daisy.org/z3986/2005/ncx/" version="2005-1">
+  <head>
+    <meta name="dtb:uid" content="{identifier}"/>
+  </head>
+  <docTitle><text>{title}</text></docTitle>
+  <navMap>
+{points}  </navMap>
+</ncx>
+"#,
+            identifier = escape(&self.identifier),
+            title = escape(&self.title),
+        )
+    }
+
+    fn chapter_document(&self, chapter: &Chapter) -> String {
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE html>
+<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops" xml:lang="{language}" lang="{language}">
+<head>
+  <meta charset="UTF-8"/>
+  <title>{title}</title>
+  <link rel="stylesheet" type="text/css" href="style.css"/>
+</head>
+<body>
+<h1>{title}</h1>
+{body}
+</body>
+</html>
+"#,
+            language = escape(&self.language),
+            title = escape(&chapter.title),
+            body = chapter.body,
+        )
+    }
+}
+
+fn chapter_href(index: usize) -> String {
+    format!("chapter-{:03}.xhtml", index + 1)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Read;
+    use zip::ZipArchive;
+
+    fn book() -> EpubBuilder {
+        let mut book = EpubBuilder::new("urn:readlater:test", "Reading <list>")
+            .creator("readlater")
+            .modified("2025-03-10T12:00:00Z");
+        book.add_chapter(Chapter {
+            title: "First & foremost".to_string(),
+            body: "<p>one</p>".to_string(),
+        });
+        let href = book.add_resource("image/png", vec![1, 2, 3]);
+        book.add_chapter(Chapter {
+            title: "Second".to_string(),
+            body: format!(r#"<img src="{href}" alt=""/>"#),
+        });
+        book
+    }
+
+    fn read(archive: &mut ZipArchive<Cursor<Vec<u8>>>, name: &str) -> String {
+        let mut content = String::new();
+        archive
+            .by_name(name)
+            .unwrap()
+            .read_to_string(&mut content)
+            .unwrap();
+        content
+    }
+
+    #[test]
+    fn test_mimetype_is_first_and_stored() {
+        let bytes = book().to_bytes().unwrap();
+        let mut archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+        let mimetype = archive.by_index(0).unwrap();
+        assert_eq!(mimetype.name(), "mimetype");
+        assert_eq!(mimetype.compression(), CompressionMethod::Stored);
+    }
+
+    #[test]
+    fn test_contents() {
+        let bytes = book().to_bytes().unwrap();
+        let mut archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+        let names: Vec<_> = archive.file_names().map(str::to_string).collect();
+        for name in [
+            "META-INF/container.xml",
+            "OEBPS/content.opf",
+            "OEBPS/nav.xhtml",
+            "OEBPS/toc.ncx",
+            "OEBPS/chapter-001.xhtml",
+            "OEBPS/chapter-002.xhtml",
+            "OEBPS/images/1.png",
+        ] {
+            assert!(names.contains(&name.to_string()), "{name} is missing");
+        }
+
+        let nav = read(&mut archive, "OEBPS/nav.xhtml");
+        assert!(nav.contains(r#"<a href="chapter-001.xhtml">First &amp; foremost</a>"#));
+
+        let opf = read(&mut archive, "OEBPS/content.opf");
+        assert!(opf.contains("<dc:title>Reading &lt;list&gt;</dc:title>"));
+        assert!(opf.contains(r#"href="images/1.png" media-type="image/png""#));
+        assert!(opf.contains(r#"<itemref idref="chapter-2"/>"#));
+    }
+
+    #[test]
+    fn test_output_is_reproducible() {
+        assert_eq!(book().to_bytes().unwrap(), book().to_bytes().unwrap());
+    }
+}
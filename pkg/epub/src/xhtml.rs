@@ -0,0 +1,235 @@
+use scraper::{node::Element, ElementRef, Html, Node};
+
+/// Elements that are dropped along with everything inside them.
+const DROPPED: &[&str] = &[
+    "script", "style", "iframe", "object", "embed", "form", "noscript", "button", "input",
+    "select", "textarea", "nav", "svg", "canvas", "template", "head", "title", "meta", "link",
+];
+
+/// Elements that are kept as is. Anything else is unwrapped and only its
+/// children are written.
+const ALLOWED: &[&str] = &[
+    "a",
+    "abbr",
+    "b",
+    "blockquote",
+    "br",
+    "caption",
+    "cite",
+    "code",
+    "dd",
+    "del",
+    "dfn",
+    "div",
+    "dl",
+    "dt",
+    "em",
+    "figcaption",
+    "figure",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "hr",
+    "i",
+    "img",
+    "ins",
+    "kbd",
+    "li",
+    "mark",
+    "ol",
+    "p",
+    "pre",
+    "q",
+    "s",
+    "samp",
+    "small",
+    "span",
+    "strong",
+    "sub",
+    "sup",
+    "table",
+    "tbody",
+    "td",
+    "tfoot",
+    "th",
+    "thead",
+    "time",
+    "tr",
+    "u",
+    "ul",
+    "var",
+];
+
+const VOID: &[&str] = &["br", "hr", "img"];
+
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Returns the `src` of every image in the given html.
+pub fn image_sources(html: &str) -> Vec<String> {
+    let document = Html::parse_fragment(html);
+    let selector = scraper::Selector::parse("img[src]").expect("valid selector");
+    document
+        .select(&selector)
+        .filter_map(|img| img.attr("src"))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Converts arbitrary, possibly malformed html into a well formed xhtml
+/// fragment that is safe to embed in an EPUB chapter.
+///
+/// `resolve_image` maps the `src` of an image to its path inside the book.
+/// Images that can't be resolved are replaced by their alt text, as remote
+/// images are not available on an offline reader anyway.
+pub fn from_html(html: &str, mut resolve_image: impl FnMut(&str) -> Option<String>) -> String {
+    let document = Html::parse_fragment(html);
+    let mut out = String::new();
+    write_children(document.root_element(), &mut out, &mut resolve_image);
+    out
+}
+
+fn write_children(
+    element: ElementRef,
+    out: &mut String,
+    resolve_image: &mut impl FnMut(&str) -> Option<String>,
+) {
+    for child in element.children() {
+        match child.value() {
+            Node::Text(text) => out.push_str(&escape(text)),
+            Node::Element(_) => {
+                let child = ElementRef::wrap(child).expect("node is an element");
+                write_element(child, out, resolve_image);
+            }
+            _ => {}
+        }
+    }
+}
+
+fn write_element(
+    element: ElementRef,
+    out: &mut String,
+    resolve_image: &mut impl FnMut(&str) -> Option<String>,
+) {
+    let value = element.value();
+    let name = value.name();
+    if DROPPED.contains(&name) {
+        return;
+    }
+    if !ALLOWED.contains(&name) {
+        write_children(element, out, resolve_image);
+        return;
+    }
+
+    if name == "img" {
+        let src = value.attr("src").and_then(&mut *resolve_image);
+        let alt = value.attr("alt").unwrap_or_default();
+        match src {
+            Some(src) => out.push_str(&format!(
+                r#"<img src="{}" alt="{}"/>"#,
+                escape(&src),
+                escape(alt)
+            )),
+            None if !alt.is_empty() => {
+                out.push_str(&format!(r#"<span class="alt">{}</span>"#, escape(alt)))
+            }
+            None => {}
+        }
+        return;
+    }
+
+    out.push('<');
+    out.push_str(name);
+    write_attributes(value, out);
+    if VOID.contains(&name) {
+        out.push_str("/>");
+        return;
+    }
+    out.push('>');
+    write_children(element, out, resolve_image);
+    out.push_str("</");
+    out.push_str(name);
+    out.push('>');
+}
+
+fn write_attributes(element: &Element, out: &mut String) {
+    for (name, value) in element.attrs() {
+        let keep = match name {
+            "href" => {
+                element.name() == "a"
+                    && ["http://", "https://", "mailto:"]
+                        .iter()
+                        .any(|scheme| value.starts_with(scheme))
+            }
+            "title" | "colspan" | "rowspan" | "datetime" => true,
+            _ => false,
+        };
+        if keep {
+            out.push_str(&format!(r#" {}="{}""#, name, escape(value)));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_void_elements_are_closed() {
+        assert_eq!(from_html("a<br>b<hr>", |_| None), "a<br/>b<hr/>");
+    }
+
+    #[test]
+    fn test_unsafe_elements_are_dropped() {
+        let html = r#"<p onclick="x()">hi<script>alert(1)</script></p><form><input></form>"#;
+        assert_eq!(from_html(html, |_| None), "<p>hi</p>");
+    }
+
+    #[test]
+    fn test_unknown_elements_are_unwrapped() {
+        let html = "<article><custom-tag><p>text &amp; more</p></custom-tag></article>";
+        assert_eq!(from_html(html, |_| None), "<p>text &amp; more</p>");
+    }
+
+    #[test]
+    fn test_links() {
+        let html = r#"<a href="https://example.com">ok</a><a href="javascript:x()">bad</a>"#;
+        assert_eq!(
+            from_html(html, |_| None),
+            r#"<a href="https://example.com">ok</a><a>bad</a>"#
+        );
+    }
+
+    #[test]
+    fn test_images() {
+        let html = r#"<img src="https://example.com/a.png" alt="a"><img src="/b.png" alt="b">"#;
+        let xhtml = from_html(html, |src| {
+            (src == "https://example.com/a.png").then(|| "images/1.png".to_string())
+        });
+        assert_eq!(
+            xhtml,
+            r#"<img src="images/1.png" alt="a"/><span class="alt">b</span>"#
+        );
+    }
+
+    #[test]
+    fn test_image_sources() {
+        let html = r#"<p><img src="a.png"><img alt="no src"><img src="b.png"></p>"#;
+        assert_eq!(image_sources(html), vec!["a.png", "b.png"]);
+    }
+}
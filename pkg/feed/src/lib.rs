@@ -0,0 +1,241 @@
+pub mod opds;
+
+use chrono::{DateTime, Utc};
+
+pub struct Entry {
+    /// Permanent identifier, such as the url of the page
+    pub id: String,
+    pub title: String,
+    pub link: String,
+    pub summary: Option<String>,
+    /// Full html of the article
+    pub content: Option<String>,
+    pub authors: Vec<String>,
+    pub categories: Vec<String>,
+    pub published: DateTime<Utc>,
+    pub updated: DateTime<Utc>,
+}
+
+/// Builds Atom 1.0 and RSS 2.0 documents from the same entries.
+pub struct FeedBuilder {
+    id: String,
+    title: String,
+    link: String,
+    self_link: Option<String>,
+    updated: DateTime<Utc>,
+    entries: Vec<Entry>,
+}
+
+impl FeedBuilder {
+    pub fn new(id: &str, title: &str, link: &str) -> FeedBuilder {
+        FeedBuilder {
+            id: id.to_string(),
+            title: title.to_string(),
+            link: link.to_string(),
+            self_link: None,
+            updated: DateTime::UNIX_EPOCH,
+            entries: vec![],
+        }
+    }
+
+    /// Address the feed itself is published at
+    pub fn self_link(mut self, link: &str) -> FeedBuilder {
+        self.self_link = Some(link.to_string());
+        self
+    }
+
+    pub fn add_entry(&mut self, entry: Entry) {
+        self.updated = self.updated.max(entry.updated);
+        self.entries.push(entry);
+    }
+
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    pub fn atom(&self) -> String {
+        let self_link = self
+            .self_link
+            .as_ref()
+            .map(|link| format!("  <link rel=\"self\" href=\"{}\"/>\n", escape(link)))
+            .unwrap_or_default();
+        let entries: String = self.entries.iter().map(atom_entry).collect();
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<feed xmlns="http://www.w3.org/2005/Atom">
+  <id>{id}</id>
+  <title>{title}</title>
+  <updated>{updated}</updated>
+  <link rel="alternate" href="{link}"/>
+{self_link}  <generator>readlater</generator>
+{entries}</feed>
+"#,
+            id = escape(&self.id),
+            title = escape(&self.title),
+            updated = self.updated.to_rfc3339(),
+            link = escape(&self.link),
+        )
+    }
+
+    pub fn rss(&self) -> String {
+        let self_link = self
+            .self_link
+            .as_ref()
+            .map(|link| {
+                format!(
+                    "    <atom:link rel=\"self\" type=\"application/rss+xml\" href=\"{}\"/>\n",
+                    escape(link)
+                )
+            })
+            .unwrap_or_default();
+        let items: String = self.entries.iter().map(rss_item).collect();
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<rss version="2.0" xmlns:atom="http://www.w3.org/2005/Atom" xmlns:content="http://purl.org/rss/1.0/modules/content/" xmlns:dc="http://purl.org/dc/elements/1.1/">
+  <channel>
+    <title>{title}</title>
+    <link>{link}</link>
+    <description>{title}</description>
+    <lastBuildDate>{updated}</lastBuildDate>
+{self_link}    <generator>readlater</generator>
+{items}  </channel>
+</rss>
+"#,
+            title = escape(&self.title),
+            link = escape(&self.link),
+            updated = self.updated.to_rfc2822(),
+        )
+    }
+}
+
+fn atom_entry(entry: &Entry) -> String {
+    let mut out = format!(
+        "  <entry>\n    <id>{}</id>\n    <title>{}</title>\n    <link rel=\"alternate\" href=\"{}\"/>\n    <published>{}</published>\n    <updated>{}</updated>\n",
+        escape(&entry.id),
+        escape(&entry.title),
+        escape(&entry.link),
+        entry.published.to_rfc3339(),
+        entry.updated.to_rfc3339(),
+    );
+    for author in &entry.authors {
+        out.push_str(&format!(
+            "    <author><name>{}</name></author>\n",
+            escape(author)
+        ));
+    }
+    for category in &entry.categories {
+        out.push_str(&format!("    <category term=\"{}\"/>\n", escape(category)));
+    }
+    if let Some(summary) = &entry.summary {
+        out.push_str(&format!("    <summary>{}</summary>\n", escape(summary)));
+    }
+    if let Some(content) = &entry.content {
+        out.push_str(&format!(
+            "    <content type=\"html\">{}</content>\n",
+            escape(content)
+        ));
+    }
+    out.push_str("  </entry>\n");
+    out
+}
+
+fn rss_item(entry: &Entry) -> String {
+    let mut out = format!(
+        "    <item>\n      <guid isPermaLink=\"false\">{}</guid>\n      <title>{}</title>\n      <link>{}</link>\n      <pubDate>{}</pubDate>\n",
+        escape(&entry.id),
+        escape(&entry.title),
+        escape(&entry.link),
+        entry.published.to_rfc2822(),
+    );
+    for author in &entry.authors {
+        out.push_str(&format!(
+            "      <dc:creator>{}</dc:creator>\n",
+            escape(author)
+        ));
+    }
+    for category in &entry.categories {
+        out.push_str(&format!(
+            "      <category>{}</category>\n",
+            escape(category)
+        ));
+    }
+    if let Some(summary) = &entry.summary {
+        out.push_str(&format!(
+            "      <description>{}</description>\n",
+            escape(summary)
+        ));
+    }
+    if let Some(content) = &entry.content {
+        out.push_str(&format!(
+            "      <content:encoded>{}</content:encoded>\n",
+            escape(content)
+        ));
+    }
+    out.push_str("    </item>\n");
+    out
+}
+
+pub(crate) fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn feed() -> FeedBuilder {
+        let mut feed = FeedBuilder::new("urn:readlater:unread", "Unread", "http://localhost/")
+            .self_link("http://localhost/feeds/unread.atom");
+        feed.add_entry(Entry {
+            id: "https://example.com/a".to_string(),
+            title: "Fish & chips".to_string(),
+            link: "https://example.com/a?x=1&y=2".to_string(),
+            summary: Some("A summary".to_string()),
+            content: Some("<p>Body</p>".to_string()),
+            authors: vec!["Jane Doe".to_string()],
+            categories: vec!["food".to_string()],
+            published: DateTime::from_timestamp(1_700_000_000, 0).unwrap(),
+            updated: DateTime::from_timestamp(1_700_000_100, 0).unwrap(),
+        });
+        feed
+    }
+
+    #[test]
+    fn test_atom() {
+        let atom = feed().atom();
+        assert!(atom.contains("<updated>2023-11-14T22:15:00+00:00</updated>"));
+        assert!(atom.contains("<title>Fish &amp; chips</title>"));
+        assert!(atom.contains(r#"href="https://example.com/a?x=1&amp;y=2""#));
+        assert!(atom.contains(r#"<content type="html">&lt;p&gt;Body&lt;/p&gt;</content>"#));
+        assert!(atom.contains(r#"<category term="food"/>"#));
+        assert!(atom.contains(r#"<link rel="self" href="http://localhost/feeds/unread.atom"/>"#));
+    }
+
+    #[test]
+    fn test_rss() {
+        let rss = feed().rss();
+        assert!(rss.contains("<pubDate>Tue, 14 Nov 2023 22:13:20 +0000</pubDate>"));
+        assert!(rss.contains("<content:encoded>&lt;p&gt;Body&lt;/p&gt;</content:encoded>"));
+        assert!(rss.contains("<dc:creator>Jane Doe</dc:creator>"));
+    }
+
+    #[test]
+    fn test_empty_feed() {
+        let feed = FeedBuilder::new("urn:readlater:empty", "Empty", "http://localhost/");
+        assert!(feed
+            .atom()
+            .contains("<updated>1970-01-01T00:00:00+00:00</updated>"));
+        assert!(!feed.rss().contains("<item>"));
+    }
+}
@@ -0,0 +1,206 @@
+//! OPDS 1.2 catalogs, the Atom feeds e-reader apps browse
+
+use crate::escape;
+use chrono::{DateTime, Utc};
+
+pub const NAVIGATION_TYPE: &str = "application/atom+xml;profile=opds-catalog;kind=navigation";
+pub const ACQUISITION_TYPE: &str = "application/atom+xml;profile=opds-catalog;kind=acquisition";
+pub const ACQUISITION_REL: &str = "http://opds-spec.org/acquisition";
+pub const EPUB_TYPE: &str = "application/epub+zip";
+
+pub struct Link {
+    pub rel: String,
+    pub href: String,
+    pub media_type: String,
+    pub title: Option<String>,
+}
+
+impl Link {
+    pub fn new(rel: &str, href: &str, media_type: &str) -> Link {
+        Link {
+            rel: rel.to_string(),
+            href: href.to_string(),
+            media_type: media_type.to_string(),
+            title: None,
+        }
+    }
+
+    pub fn title(mut self, title: &str) -> Link {
+        self.title = Some(title.to_string());
+        self
+    }
+
+    fn xml(&self) -> String {
+        let title = self
+            .title
+            .as_ref()
+            .map(|title| format!(" title=\"{}\"", escape(title)))
+            .unwrap_or_default();
+        format!(
+            "<link rel=\"{}\" href=\"{}\" type=\"{}\"{}/>",
+            escape(&self.rel),
+            escape(&self.href),
+            escape(&self.media_type),
+            title
+        )
+    }
+}
+
+pub struct CatalogEntry {
+    pub id: String,
+    pub title: String,
+    pub updated: DateTime<Utc>,
+    /// Plain text shown under the title
+    pub content: Option<String>,
+    pub authors: Vec<String>,
+    pub categories: Vec<String>,
+    pub language: Option<String>,
+    pub links: Vec<Link>,
+}
+
+impl CatalogEntry {
+    pub fn new(id: &str, title: &str, updated: DateTime<Utc>) -> CatalogEntry {
+        CatalogEntry {
+            id: id.to_string(),
+            title: title.to_string(),
+            updated,
+            content: None,
+            authors: vec![],
+            categories: vec![],
+            language: None,
+            links: vec![],
+        }
+    }
+
+    /// Entry of a navigation feed pointing to another catalog
+    pub fn navigation(id: &str, title: &str, href: &str, media_type: &str) -> CatalogEntry {
+        let mut entry = CatalogEntry::new(id, title, DateTime::UNIX_EPOCH);
+        entry.links.push(Link::new("subsection", href, media_type));
+        entry
+    }
+
+    fn xml(&self) -> String {
+        let mut out = format!(
+            "  <entry>\n    <id>{}</id>\n    <title>{}</title>\n    <updated>{}</updated>\n",
+            escape(&self.id),
+            escape(&self.title),
+            self.updated.to_rfc3339(),
+        );
+        for author in &self.authors {
+            out.push_str(&format!(
+                "    <author><name>{}</name></author>\n",
+                escape(author)
+            ));
+        }
+        if let Some(language) = &self.language {
+            out.push_str(&format!(
+                "    <dc:language>{}</dc:language>\n",
+                escape(language)
+            ));
+        }
+        for category in &self.categories {
+            out.push_str(&format!(
+                "    <category term=\"{}\" label=\"{}\"/>\n",
+                escape(category),
+                escape(category)
+            ));
+        }
+        if let Some(content) = &self.content {
+            out.push_str(&format!(
+                "    <content type=\"text\">{}</content>\n",
+                escape(content)
+            ));
+        }
+        for link in &self.links {
+            out.push_str(&format!("    {}\n", link.xml()));
+        }
+        out.push_str("  </entry>\n");
+        out
+    }
+}
+
+/// A navigation or acquisition feed of a catalog
+pub struct Catalog {
+    id: String,
+    title: String,
+    updated: DateTime<Utc>,
+    links: Vec<Link>,
+    entries: Vec<CatalogEntry>,
+}
+
+impl Catalog {
+    pub fn new(id: &str, title: &str) -> Catalog {
+        Catalog {
+            id: id.to_string(),
+            title: title.to_string(),
+            updated: DateTime::UNIX_EPOCH,
+            links: vec![],
+            entries: vec![],
+        }
+    }
+
+    pub fn link(mut self, link: Link) -> Catalog {
+        self.links.push(link);
+        self
+    }
+
+    pub fn add_entry(&mut self, entry: CatalogEntry) {
+        self.updated = self.updated.max(entry.updated);
+        self.entries.push(entry);
+    }
+
+    pub fn entries(&self) -> &[CatalogEntry] {
+        &self.entries
+    }
+
+    pub fn to_xml(&self) -> String {
+        let links: String = self
+            .links
+            .iter()
+            .map(|link| format!("  {}\n", link.xml()))
+            .collect();
+        let entries: String = self.entries.iter().map(CatalogEntry::xml).collect();
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<feed xmlns="http://www.w3.org/2005/Atom" xmlns:dc="http://purl.org/dc/terms/" xmlns:opds="http://opds-spec.org/2010/catalog">
+  <id>{id}</id>
+  <title>{title}</title>
+  <updated>{updated}</updated>
+  <author><name>readlater</name></author>
+{links}{entries}</feed>
+"#,
+            id = escape(&self.id),
+            title = escape(&self.title),
+            updated = self.updated.to_rfc3339(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_acquisition_feed() {
+        let mut catalog = Catalog::new("urn:readlater:opds:unread", "Unread")
+            .link(Link::new("start", "/opds", NAVIGATION_TYPE))
+            .link(Link::new("next", "/opds/unread?page=2", ACQUISITION_TYPE));
+        let mut entry = CatalogEntry::new(
+            "urn:readlater:item:1",
+            "Rust & you",
+            DateTime::from_timestamp(1_700_000_000, 0).unwrap(),
+        );
+        entry.language = Some("en".to_string());
+        entry
+            .links
+            .push(Link::new(ACQUISITION_REL, "/opds/items/1.epub", EPUB_TYPE));
+        catalog.add_entry(entry);
+
+        let xml = catalog.to_xml();
+        assert!(xml.contains("<updated>2023-11-14T22:13:20+00:00</updated>"));
+        assert!(xml.contains("<title>Rust &amp; you</title>"));
+        assert!(xml.contains(r#"<link rel="next" href="/opds/unread?page=2" type="application/atom+xml;profile=opds-catalog;kind=acquisition"/>"#));
+        assert!(xml.contains(r#"<link rel="http://opds-spec.org/acquisition" href="/opds/items/1.epub" type="application/epub+zip"/>"#));
+        assert!(xml.contains("<dc:language>en</dc:language>"));
+    }
+}
@@ -0,0 +1,232 @@
+mod wayback;
+
+use futures::{Stream, StreamExt};
+pub use localdb::LinkStatus;
+use reqwest::{Client, Response, StatusCode};
+use std::time::Duration;
+use url::Url;
+pub use wayback::{Wayback, WaybackError, WAYBACK_AVAILABLE_URL};
+
+/// Defaults of the checkers and archive clients built without options
+const TIMEOUT: Duration = Duration::from_secs(20);
+const USER_AGENT: &str = concat!("readlater/", env!("CARGO_PKG_VERSION"));
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LinkCheck {
+    pub status: LinkStatus,
+    /// HTTP status of the last response, if the server answered at all
+    pub http_status: Option<u16>,
+    /// Url after following redirects
+    pub final_url: Option<String>,
+}
+
+/// Probes urls to find pages that moved or disappeared.
+pub struct LinkChecker {
+    client: Client,
+    concurrency: usize,
+}
+
+impl LinkChecker {
+    pub fn new(concurrency: usize) -> LinkChecker {
+        LinkChecker::with_options(concurrency, TIMEOUT, USER_AGENT)
+    }
+
+    /// Checker giving up on links after `timeout`
+    pub fn with_options(concurrency: usize, timeout: Duration, user_agent: &str) -> LinkChecker {
+        LinkChecker::with_client(
+            Client::builder().timeout(timeout).user_agent(user_agent),
+            concurrency,
+        )
+    }
+
+    fn with_client(builder: reqwest::ClientBuilder, concurrency: usize) -> LinkChecker {
+        let client = builder.build().expect("valid http client");
+        LinkChecker {
+            client,
+            concurrency: concurrency.max(1),
+        }
+    }
+
+    pub async fn check(&self, url: &str) -> LinkCheck {
+        // plenty of servers refuse HEAD requests, retry with GET before
+        // declaring the link dead
+        let response = match self.client.head(url).send().await {
+            Ok(res) if res.status().is_client_error() || res.status().is_server_error() => {
+                self.client.get(url).send().await
+            }
+            response => response,
+        };
+
+        match response {
+            Ok(response) => classify(url, &response),
+            Err(e) => LinkCheck {
+                status: if is_dns_error(&e) {
+                    LinkStatus::DnsFailure
+                } else {
+                    LinkStatus::Error
+                },
+                http_status: None,
+                final_url: None,
+            },
+        }
+    }
+
+    /// Checks every url, running at most `concurrency` requests at a time.
+    /// Results are yielded as soon as they are available.
+    pub fn check_all<'a, K: 'a>(
+        &'a self,
+        urls: impl IntoIterator<Item = (K, String)> + 'a,
+    ) -> impl Stream<Item = (K, LinkCheck)> + 'a {
+        futures::stream::iter(urls)
+            .map(move |(key, url)| async move { (key, self.check(&url).await) })
+            .buffer_unordered(self.concurrency)
+    }
+}
+
+fn classify(url: &str, response: &Response) -> LinkCheck {
+    let final_url = response.url().to_string();
+    let status = match response.status() {
+        status if status.is_success() && !same_page(url, &final_url) => LinkStatus::Redirected,
+        status if status.is_success() => LinkStatus::Ok,
+        StatusCode::NOT_FOUND => LinkStatus::NotFound,
+        StatusCode::GONE => LinkStatus::Gone,
+        _ => LinkStatus::Error,
+    };
+    LinkCheck {
+        status,
+        http_status: Some(response.status().as_u16()),
+        final_url: Some(final_url),
+    }
+}
+
+fn same_page(a: &str, b: &str) -> bool {
+    let normalize = |url: &str| {
+        Url::parse(url).ok().map(|mut url| {
+            url.set_fragment(None);
+            url.to_string().trim_end_matches('/').to_string()
+        })
+    };
+    normalize(a) == normalize(b)
+}
+
+fn is_dns_error(error: &reqwest::Error) -> bool {
+    let mut source: Option<&dyn std::error::Error> = Some(error);
+    while let Some(error) = source {
+        let message = error.to_string();
+        if message.contains("dns error") || message.contains("failed to lookup address") {
+            return true;
+        }
+        source = error.source();
+    }
+    false
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use wiremock::{
+        matchers::{method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    async fn server() -> MockServer {
+        let server = MockServer::start().await;
+        for (route, status) in [
+            ("/ok", 200),
+            ("/missing", 404),
+            ("/gone", 410),
+            ("/error", 500),
+        ] {
+            Mock::given(path(route))
+                .respond_with(ResponseTemplate::new(status))
+                .mount(&server)
+                .await;
+        }
+        Mock::given(path("/moved"))
+            .respond_with(ResponseTemplate::new(301).insert_header("location", "/ok"))
+            .mount(&server)
+            .await;
+        Mock::given(method("HEAD"))
+            .and(path("/no-head"))
+            .respond_with(ResponseTemplate::new(405))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/no-head"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&server)
+            .await;
+        server
+    }
+
+    #[tokio::test]
+    async fn test_check() {
+        let server = server().await;
+        let checker = LinkChecker::new(4);
+        for (route, expected) in [
+            ("/ok", LinkStatus::Ok),
+            ("/ok#section", LinkStatus::Ok),
+            ("/moved", LinkStatus::Redirected),
+            ("/missing", LinkStatus::NotFound),
+            ("/gone", LinkStatus::Gone),
+            ("/error", LinkStatus::Error),
+            ("/no-head", LinkStatus::Ok),
+        ] {
+            let check = checker.check(&format!("{}{}", server.uri(), route)).await;
+            assert_eq!(check.status, expected, "{route}");
+        }
+
+        let check = checker.check(&format!("{}/moved", server.uri())).await;
+        assert_eq!(check.final_url, Some(format!("{}/ok", server.uri())));
+        assert_eq!(check.http_status, Some(200));
+    }
+
+    /// Resolver failing every lookup, so that the test does not depend on
+    /// the network
+    struct NoDns;
+
+    impl reqwest::dns::Resolve for NoDns {
+        fn resolve(&self, _: reqwest::dns::Name) -> reqwest::dns::Resolving {
+            Box::pin(async {
+                let error = std::io::Error::new(std::io::ErrorKind::NotFound, "no such host");
+                Err(error.into())
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_dns_failure() {
+        let builder = Client::builder().dns_resolver(std::sync::Arc::new(NoDns));
+        let check = LinkChecker::with_client(builder, 1)
+            .check("http://readlater.invalid/")
+            .await;
+        assert_eq!(check.status, LinkStatus::DnsFailure);
+    }
+
+    #[tokio::test]
+    async fn test_unreachable() {
+        // nothing listens on a port once its listener is dropped
+        let addr = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let check = LinkChecker::new(1)
+            .check(&format!("http://{}/", addr))
+            .await;
+        assert_eq!(check.status, LinkStatus::Error);
+        assert_eq!(check.http_status, None);
+    }
+
+    #[tokio::test]
+    async fn test_check_all() {
+        let server = server().await;
+        let checker = LinkChecker::new(2);
+        let urls = (0..5).map(|i| (i, format!("{}/ok", server.uri())));
+        let mut results: Vec<_> = checker.check_all(urls).collect().await;
+        results.sort_by_key(|(key, _)| *key);
+        assert_eq!(results.len(), 5);
+        assert!(results
+            .iter()
+            .all(|(_, check)| check.status == LinkStatus::Ok));
+    }
+}
@@ -0,0 +1,120 @@
+use reqwest::Client;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::time::Duration;
+
+pub const WAYBACK_AVAILABLE_URL: &str = "https://archive.org/wayback/available";
+
+#[derive(Debug, thiserror::Error)]
+pub enum WaybackError {
+    #[error("Request error for URL <{url}>: {source}")]
+    Reqwest { url: String, source: reqwest::Error },
+}
+
+#[derive(Deserialize)]
+struct AvailableResponse {
+    archived_snapshots: HashMap<String, ArchivedSnapshot>,
+}
+
+#[derive(Deserialize)]
+struct ArchivedSnapshot {
+    available: bool,
+    url: String,
+}
+
+/// Client for the Wayback Machine availability API
+pub struct Wayback {
+    client: Client,
+    endpoint: String,
+}
+
+impl Default for Wayback {
+    fn default() -> Self {
+        Wayback::new(WAYBACK_AVAILABLE_URL)
+    }
+}
+
+impl Wayback {
+    pub fn new(endpoint: &str) -> Wayback {
+        Wayback::with_options(endpoint, crate::TIMEOUT, crate::USER_AGENT)
+    }
+
+    /// Client giving up on the archive after `timeout`
+    pub fn with_options(endpoint: &str, timeout: Duration, user_agent: &str) -> Wayback {
+        let client = Client::builder()
+            .timeout(timeout)
+            .user_agent(user_agent)
+            .build()
+            .expect("valid http client");
+        Wayback {
+            client,
+            endpoint: endpoint.to_string(),
+        }
+    }
+
+    /// Returns the url of the snapshot closest to now, if the page was ever
+    /// archived.
+    pub async fn closest(&self, url: &str) -> Result<Option<String>, WaybackError> {
+        let reqwest_error = |source| WaybackError::Reqwest {
+            url: self.endpoint.clone(),
+            source,
+        };
+        let response: AvailableResponse = self
+            .client
+            .get(&self.endpoint)
+            .query(&[("url", url)])
+            .send()
+            .await
+            .and_then(|res| res.error_for_status())
+            .map_err(reqwest_error)?
+            .json()
+            .await
+            .map_err(reqwest_error)?;
+
+        Ok(response
+            .archived_snapshots
+            .get("closest")
+            .filter(|snapshot| snapshot.available)
+            .map(|snapshot| snapshot.url.clone()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use wiremock::{
+        matchers::{path, query_param},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    #[tokio::test]
+    async fn test_closest() {
+        let server = MockServer::start().await;
+        Mock::given(path("/wayback/available"))
+            .and(query_param("url", "https://example.com/old"))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(
+                r#"{"url": "https://example.com/old", "archived_snapshots": {"closest": {"status": "200", "available": true, "url": "http://web.archive.org/web/20200101000000/https://example.com/old", "timestamp": "20200101000000"}}}"#,
+                "application/json",
+            ))
+            .mount(&server)
+            .await;
+        Mock::given(path("/wayback/available"))
+            .and(query_param("url", "https://example.com/never"))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(
+                r#"{"url": "https://example.com/never", "archived_snapshots": {}}"#,
+                "application/json",
+            ))
+            .mount(&server)
+            .await;
+
+        let wayback = Wayback::new(&format!("{}/wayback/available", server.uri()));
+        assert_eq!(
+            wayback.closest("https://example.com/old").await.unwrap(),
+            Some("http://web.archive.org/web/20200101000000/https://example.com/old".to_string())
+        );
+        assert_eq!(
+            wayback.closest("https://example.com/never").await.unwrap(),
+            None
+        );
+    }
+}
@@ -0,0 +1,5 @@
+CREATE TABLE [item_contents] (
+   [item_id] INTEGER PRIMARY KEY REFERENCES items(id),
+   [html] TEXT NOT NULL,
+   [time_fetched] INTEGER
+);
@@ -0,0 +1,12 @@
+CREATE TABLE [assets] (
+   [id] INTEGER PRIMARY KEY AUTOINCREMENT,
+   [url] TEXT NOT NULL UNIQUE,
+   [hash] TEXT NOT NULL,
+   [path] TEXT NOT NULL,
+   [mime] TEXT NOT NULL,
+   [size] INTEGER NOT NULL DEFAULT 0,
+   [time_fetched] INTEGER NOT NULL,
+   [time_accessed] INTEGER NOT NULL
+);
+
+CREATE INDEX [assets_hash] ON [assets] ([hash]);
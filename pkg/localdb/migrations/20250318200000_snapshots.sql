@@ -0,0 +1,12 @@
+CREATE TABLE [snapshots] (
+   [id] INTEGER PRIMARY KEY AUTOINCREMENT,
+   [item_id] INTEGER NOT NULL REFERENCES items(id),
+   [url] TEXT NOT NULL,
+   [status] INTEGER NOT NULL,
+   [html_path] TEXT NOT NULL,
+   [warc_path] TEXT,
+   [size] INTEGER NOT NULL DEFAULT 0,
+   [time_fetched] INTEGER NOT NULL
+);
+
+CREATE INDEX [snapshots_item_id] ON [snapshots] ([item_id]);
@@ -0,0 +1,4 @@
+ALTER TABLE [items] ADD COLUMN [link_status] INTEGER;
+ALTER TABLE [items] ADD COLUMN [link_final_url] TEXT;
+ALTER TABLE [items] ADD COLUMN [time_link_checked] INTEGER;
+ALTER TABLE [items] ADD COLUMN [wayback_url] TEXT;
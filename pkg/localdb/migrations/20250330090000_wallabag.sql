@@ -0,0 +1,2 @@
+ALTER TABLE [items] ADD COLUMN [wallabag_id] INTEGER DEFAULT NULL;
+CREATE UNIQUE INDEX [items_wallabag_id] ON [items] ([wallabag_id]);
@@ -0,0 +1,59 @@
+CREATE TABLE [remotes] (
+   [id] INTEGER PRIMARY KEY AUTOINCREMENT,
+   [name] TEXT NOT NULL UNIQUE,
+   [backend] TEXT NOT NULL,
+   [settings] TEXT NOT NULL DEFAULT '{}',
+   [cursor] TEXT,
+   [time_added] INTEGER NOT NULL DEFAULT (unixepoch()),
+   [time_synced] INTEGER
+);
+
+CREATE TABLE [remote_items] (
+   [remote_id] INTEGER NOT NULL REFERENCES remotes(id),
+   [remote_item_id] TEXT NOT NULL,
+   [item_id] INTEGER NOT NULL REFERENCES items(id),
+   PRIMARY KEY (remote_id, remote_item_id)
+);
+
+CREATE INDEX [remote_items_item_id] ON [remote_items] ([item_id]);
+
+CREATE TABLE [outbox] (
+   [id] INTEGER PRIMARY KEY AUTOINCREMENT,
+   [remote_id] INTEGER NOT NULL REFERENCES remotes(id),
+   [item_id] INTEGER NOT NULL REFERENCES items(id),
+   [mutation] TEXT NOT NULL,
+   [attempts] INTEGER NOT NULL DEFAULT 0,
+   [last_error] TEXT,
+   [time_added] INTEGER NOT NULL DEFAULT (unixepoch())
+);
+
+-- credentials and cursors used to live in kv, one set per service
+INSERT INTO remotes (name, backend, settings, cursor)
+SELECT 'pocket', 'pocket', json_object('access_token', value),
+   (SELECT json_object('since', CAST(value AS INTEGER), 'offset', 0) FROM kv WHERE key = 'pocket_since')
+FROM kv WHERE key = 'pocket_access_token';
+
+INSERT INTO remotes (name, backend, settings, cursor)
+SELECT 'wallabag', 'wallabag', json_object(
+      'url', (SELECT value FROM kv WHERE key = 'wallabag_url'),
+      'client_id', (SELECT value FROM kv WHERE key = 'wallabag_client_id'),
+      'client_secret', (SELECT value FROM kv WHERE key = 'wallabag_client_secret'),
+      'refresh_token', value),
+   (SELECT json_object('since', CAST(value AS INTEGER), 'page', 1) FROM kv WHERE key = 'wallabag_since')
+FROM kv WHERE key = 'wallabag_refresh_token';
+
+INSERT INTO remote_items (remote_id, remote_item_id, item_id)
+SELECT remotes.id, CAST(items.pocket_id AS TEXT), items.id
+FROM items JOIN remotes ON remotes.name = 'pocket'
+WHERE items.pocket_id IS NOT NULL;
+
+INSERT INTO remote_items (remote_id, remote_item_id, item_id)
+SELECT remotes.id, CAST(items.wallabag_id AS TEXT), items.id
+FROM items JOIN remotes ON remotes.name = 'wallabag'
+WHERE items.wallabag_id IS NOT NULL;
+
+DELETE FROM kv WHERE key IN (
+   'pocket_access_token', 'pocket_since', 'pocket_offset',
+   'wallabag_url', 'wallabag_client_id', 'wallabag_client_secret',
+   'wallabag_refresh_token', 'wallabag_since'
+);
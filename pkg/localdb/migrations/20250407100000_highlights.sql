@@ -0,0 +1,12 @@
+CREATE TABLE [highlights] (
+   [id] INTEGER PRIMARY KEY AUTOINCREMENT,
+   [item_id] INTEGER NOT NULL REFERENCES items(id),
+   [text] TEXT NOT NULL,
+   [note] TEXT,
+   [color] TEXT,
+   [position] TEXT,
+   [time_added] INTEGER NOT NULL DEFAULT (unixepoch()),
+   [time_updated] INTEGER
+);
+
+CREATE INDEX [highlights_item_id] ON [highlights] ([item_id]);
@@ -0,0 +1,17 @@
+-- KOReader identifies a book by a hash of its file or of its file name
+CREATE TABLE [documents] (
+   [hash] TEXT PRIMARY KEY,
+   [item_id] INTEGER NOT NULL REFERENCES items(id),
+   [time_added] INTEGER NOT NULL DEFAULT (unixepoch())
+);
+
+CREATE INDEX [documents_item_id] ON [documents] ([item_id]);
+
+CREATE TABLE [reading_progress] (
+   [document] TEXT PRIMARY KEY,
+   [progress] TEXT NOT NULL,
+   [percentage] REAL NOT NULL,
+   [device] TEXT NOT NULL,
+   [device_id] TEXT NOT NULL,
+   [timestamp] INTEGER NOT NULL
+);
@@ -0,0 +1,13 @@
+CREATE TABLE [jobs] (
+   [id] INTEGER PRIMARY KEY AUTOINCREMENT,
+   [kind] INTEGER NOT NULL,
+   [item_id] INTEGER NOT NULL REFERENCES items(id),
+   [state] INTEGER NOT NULL DEFAULT 0,
+   [attempts] INTEGER NOT NULL DEFAULT 0,
+   [next_run] INTEGER NOT NULL DEFAULT (unixepoch()),
+   [last_error] TEXT,
+   [time_added] INTEGER NOT NULL DEFAULT (unixepoch())
+);
+
+CREATE INDEX [jobs_next_run] ON [jobs] ([state], [next_run]);
+CREATE INDEX [jobs_item_id] ON [jobs] ([item_id]);
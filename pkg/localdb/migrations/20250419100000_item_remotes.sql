@@ -0,0 +1,15 @@
+-- accounts an item belongs to, known before the remote assigns it an id
+CREATE TABLE [item_remotes] (
+   [item_id] INTEGER NOT NULL REFERENCES items(id),
+   [remote_id] INTEGER NOT NULL REFERENCES remotes(id),
+   [time_added] INTEGER NOT NULL DEFAULT (unixepoch()),
+   PRIMARY KEY (item_id, remote_id)
+);
+
+CREATE INDEX [item_remotes_remote_id] ON [item_remotes] ([remote_id]);
+
+INSERT OR IGNORE INTO item_remotes (item_id, remote_id)
+SELECT item_id, remote_id FROM remote_items;
+
+INSERT OR IGNORE INTO item_remotes (item_id, remote_id)
+SELECT item_id, remote_id FROM outbox;
@@ -0,0 +1,7 @@
+ALTER TABLE [items] ADD COLUMN [favorite] INTEGER NOT NULL DEFAULT 0;
+
+-- pocket sends 0 for items that were never favorited
+UPDATE items SET time_favorited = NULL WHERE time_favorited = 0;
+UPDATE items SET favorite = 1 WHERE time_favorited IS NOT NULL;
+
+CREATE INDEX [items_favorite] ON [items] ([favorite]) WHERE favorite;
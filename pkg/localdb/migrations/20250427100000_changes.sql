@@ -0,0 +1,22 @@
+-- history of the changes to the items, only ever appended to
+CREATE TABLE [operations] (
+   [id] INTEGER PRIMARY KEY AUTOINCREMENT,
+   [source] INTEGER NOT NULL,
+   [time] INTEGER NOT NULL DEFAULT (unixepoch()),
+   -- operation reverted by this one
+   [undo_of] INTEGER REFERENCES operations(id)
+);
+
+CREATE INDEX [operations_undo_of] ON [operations] ([undo_of]);
+
+CREATE TABLE [changes] (
+   [id] INTEGER PRIMARY KEY AUTOINCREMENT,
+   [operation_id] INTEGER NOT NULL REFERENCES operations(id),
+   -- not a reference, the history outlives purged items
+   [item_id] INTEGER NOT NULL,
+   [field] INTEGER NOT NULL,
+   [before] TEXT,
+   [after] TEXT
+);
+
+CREATE INDEX [changes_operation_id] ON [changes] ([operation_id]);
@@ -0,0 +1,3 @@
+-- remote ids are only unique on one account, remote_items keeps them per
+-- remote. items.pocket_id and items.wallabag_id are no longer written.
+DROP INDEX [items_wallabag_id];
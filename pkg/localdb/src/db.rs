@@ -1,15 +1,174 @@
-use crate::{Author, DBError, HasImage, HasVideo, Image, Item, ItemStatus, Tag};
+use crate::{
+    Asset, Author, Change, Content, DBError, Field, HasImage, HasVideo, Highlight, Image, Item,
+    ItemQuery, ItemStatus, Job, JobKind, JobState, LinkStatus, Operation, OutboxEntry, Progress,
+    Remote, Snapshot, Source, Tag,
+};
 use itertools::Itertools;
-use sqlx::SqlitePool;
-
+use sqlx::migrate::Migrator;
+use sqlx::pool::PoolConnection;
+use sqlx::{Row, Sqlite, SqliteConnection, SqlitePool, Transaction};
+use std::ops::{Deref, DerefMut};
+use tokio::sync::{Mutex, MutexGuard};
+
+pub(crate) static MIGRATOR: Migrator = sqlx::migrate!();
+
+/// Tables whose rows belong to an item, removed along with it
+const ITEM_TABLES: &[&str] = &[
+    "items_tags",
+    "items_authors",
+    "items_images",
+    "items_videos",
+    "item_contents",
+    "snapshots",
+    "remote_items",
+    "item_remotes",
+    "outbox",
+    "highlights",
+    "documents",
+    "jobs",
+];
+
+/// Opens the database at `path`, creating it on first use
 pub async fn open_database(path: &str) -> crate::Result<SqlitePool> {
-    let pool = SqlitePool::connect(path).await?;
-    sqlx::migrate!().run(&pool).await?;
+    open_database_with_key(path, None).await
+}
+
+/// Opens the database at `path` without migrating it, to inspect it
+pub async fn connect_database(path: &str, key: Option<&str>) -> crate::Result<SqlitePool> {
+    let options = crate::encryption::options(path, key)?;
+    if key.is_some() {
+        crate::encryption::check_key(&options).await?;
+    }
+    Ok(SqlitePool::connect_with(options).await?)
+}
+
+/// Opens the database at `path`, decrypting it with `key` when given
+pub async fn open_database_with_key(path: &str, key: Option<&str>) -> crate::Result<SqlitePool> {
+    let pool = connect_database(path, key).await?;
+    crate::maintenance::backup_before_migrating(&pool, path, key, &MIGRATOR).await?;
+    MIGRATOR.run(&pool).await?;
     Ok(pool)
 }
 
+/// Columns of an item whose changes are recorded, with the fields they are
+/// recorded as. Bookkeeping such as `time_updated` is left out.
+const ITEM_FIELDS: &[(Field, &str)] = &[
+    (Field::Status, "status"),
+    (Field::Favorite, "favorite"),
+    (Field::Title, "title"),
+    (Field::Excerpt, "excerpt"),
+    (Field::CanonicalUrl, "canonical_url"),
+    (Field::IsArticle, "is_article"),
+    (Field::IsIndex, "is_index"),
+    (Field::HasVideo, "has_video"),
+    (Field::HasImage, "has_image"),
+    (Field::WordCount, "word_count"),
+    (Field::Lang, "lang"),
+    (Field::TimeToRead, "time_to_read"),
+    (Field::TopImageUrl, "top_image_url"),
+    (Field::TimeRead, "time_read"),
+    (Field::TimeFavorited, "time_favorited"),
+    (Field::LinkStatus, "link_status"),
+    (Field::LinkFinalUrl, "link_final_url"),
+    (Field::WaybackUrl, "wayback_url"),
+];
+
+/// Values of the `ITEM_FIELDS` of an item, as text
+type ItemState = Vec<Option<String>>;
+
 pub struct LocalDb {
     pool: SqlitePool,
+    /// Transaction all queries run in, see `transaction`
+    tx: Option<Mutex<Transaction<'static, Sqlite>>>,
+    /// Recorded with the changes made through this handle
+    source: Source,
+    /// Operation the next changes are recorded under
+    operation: std::sync::Mutex<Option<i64>>,
+    /// Whether changes share one operation, see `begin_operation`
+    grouped: bool,
+}
+
+/// Connection a query runs on: one of the pool, a transaction of its own,
+/// or the transaction of the handle
+enum Conn<'a> {
+    Pool(PoolConnection<Sqlite>),
+    Begun(Transaction<'static, Sqlite>),
+    Bound(MutexGuard<'a, Transaction<'static, Sqlite>>),
+}
+
+impl Conn<'_> {
+    /// Commits a transaction begun by `LocalDb::begin`. The transaction of
+    /// the handle is committed by `LocalDb::commit`.
+    async fn commit(self) -> crate::Result<()> {
+        if let Conn::Begun(tx) = self {
+            tx.commit().await?;
+        }
+        Ok(())
+    }
+}
+
+impl Deref for Conn<'_> {
+    type Target = SqliteConnection;
+
+    fn deref(&self) -> &SqliteConnection {
+        match self {
+            Conn::Pool(conn) => conn,
+            Conn::Begun(tx) => tx,
+            Conn::Bound(tx) => tx,
+        }
+    }
+}
+
+impl DerefMut for Conn<'_> {
+    fn deref_mut(&mut self) -> &mut SqliteConnection {
+        match self {
+            Conn::Pool(conn) => conn,
+            Conn::Begun(tx) => tx,
+            Conn::Bound(tx) => tx,
+        }
+    }
+}
+
+async fn item_state(conn: &mut SqliteConnection, item: i64) -> crate::Result<Option<ItemState>> {
+    let columns = ITEM_FIELDS
+        .iter()
+        .map(|(field, column)| match field {
+            Field::Status => {
+                "CASE status WHEN 0 THEN 'unread' WHEN 1 THEN 'archived' ELSE 'deleted' END"
+                    .to_string()
+            }
+            Field::Favorite => "CASE WHEN favorite THEN 'true' ELSE 'false' END".to_string(),
+            _ => format!("CAST({column} AS TEXT)"),
+        })
+        .join(", ");
+    let row = sqlx::query(&format!("SELECT {columns} FROM items WHERE id = ?"))
+        .bind(item)
+        .fetch_optional(conn)
+        .await?;
+    Ok(row.map(|row| (0..ITEM_FIELDS.len()).map(|i| row.get(i)).collect()))
+}
+
+/// Links a tag without recording it, for the tags that come with an item.
+/// Returns whether the link is new.
+async fn insert_tag_link(conn: &mut SqliteConnection, tag: i32, item: i32) -> crate::Result<bool> {
+    let inserted = sqlx::query(
+        "INSERT INTO items_tags (item_id, tag_id) VALUES (?, ?) ON CONFLICT(item_id, tag_id) DO NOTHING",
+    )
+    .bind(item)
+    .bind(tag)
+    .execute(conn)
+    .await?
+    .rows_affected();
+    Ok(inserted > 0)
+}
+
+/// A highlight as recorded in the history
+async fn highlight_json(conn: &mut SqliteConnection, id: i64) -> crate::Result<Option<String>> {
+    let highlight: Option<Highlight> = sqlx::query_as("SELECT * FROM highlights WHERE id = ?")
+        .bind(id)
+        .fetch_optional(conn)
+        .await?;
+    Ok(highlight.map(|h| serde_json::to_string(&h).expect("highlights serialize")))
 }
 
 #[derive(Debug, sqlx::FromRow)]
@@ -18,6 +177,7 @@ struct ItemRow {
     pub title: String,
     pub url: String,
     pub excerpt: Option<String>,
+    pub canonical_url: Option<String>,
 
     /// Extended metadata about the url
     pub is_article: Option<bool>,
@@ -32,11 +192,17 @@ struct ItemRow {
 
     // fields related to the status of the url with respect to the user
     pub status: ItemStatus,
+    pub favorite: bool,
     pub time_added: i32,
     pub time_updated: Option<i32>,
     pub time_read: Option<i32>,
     pub time_favorited: Option<i32>,
 
+    pub link_status: Option<LinkStatus>,
+    pub link_final_url: Option<String>,
+    pub time_link_checked: Option<i32>,
+    pub wayback_url: Option<String>,
+
     // tag infos
     pub tag: Option<String>,
     pub tag_id: Option<i32>,
@@ -64,13 +230,222 @@ struct ItemRow {
 }
 
 #[derive(sqlx::FromRow, Debug)]
+struct OperationRow {
+    id: i64,
+    source: Source,
+    time: i64,
+    undo_of: Option<i64>,
+    undone: bool,
+}
+
+#[derive(sqlx::FromRow)]
 struct RowId {
     pub id: i32,
 }
 
 impl LocalDb {
     pub fn new(pool: SqlitePool) -> Self {
-        Self { pool }
+        Self {
+            pool,
+            tx: None,
+            source: Source::default(),
+            operation: Default::default(),
+            grouped: false,
+        }
+    }
+
+    /// Handle on the same database recording its changes as made by `source`
+    pub fn with_source(&self, source: Source) -> Self {
+        Self {
+            source,
+            ..Self::new(self.pool.clone())
+        }
+    }
+
+    /// Handle running all its queries in one transaction, until `commit`.
+    /// Dropped before, its changes are rolled back.
+    pub async fn transaction(&self) -> crate::Result<Self> {
+        Ok(Self {
+            tx: Some(Mutex::new(self.pool.begin().await?)),
+            ..self.with_source(self.source)
+        })
+    }
+
+    pub async fn commit(self) -> crate::Result<()> {
+        if let Some(tx) = self.tx {
+            tx.into_inner().commit().await?;
+        }
+        Ok(())
+    }
+
+    async fn conn(&self) -> crate::Result<Conn<'_>> {
+        Ok(match &self.tx {
+            Some(tx) => Conn::Bound(tx.lock().await),
+            None => Conn::Pool(self.pool.acquire().await?),
+        })
+    }
+
+    /// Connection for a mutation and the changes it records
+    async fn begin(&self) -> crate::Result<Conn<'_>> {
+        Ok(match &self.tx {
+            Some(tx) => Conn::Bound(tx.lock().await),
+            None => Conn::Begun(self.pool.begin().await?),
+        })
+    }
+
+    /// Groups the following changes into one operation, undone together.
+    /// Otherwise each change is an operation of its own.
+    pub fn begin_operation(&mut self) {
+        self.grouped = true;
+        *self.operation.get_mut().unwrap() = None;
+    }
+
+    /// Groups the following changes into an operation reverting `operation`,
+    /// which counts as undone even if nothing had to change
+    pub async fn begin_undo(&mut self, operation: i64) -> crate::Result<()> {
+        let id = self
+            .insert_operation(&mut *self.conn().await?, Some(operation))
+            .await?;
+        self.grouped = true;
+        *self.operation.get_mut().unwrap() = Some(id);
+        Ok(())
+    }
+
+    async fn insert_operation(
+        &self,
+        conn: &mut SqliteConnection,
+        undo_of: Option<i64>,
+    ) -> crate::Result<i64> {
+        Ok(sqlx::query_scalar(
+            "INSERT INTO operations (source, undo_of) VALUES (?, ?) RETURNING id",
+        )
+        .bind(self.source)
+        .bind(undo_of)
+        .fetch_one(conn)
+        .await?)
+    }
+
+    /// Records a change on the connection of its mutation, so both are
+    /// written or neither
+    async fn record(
+        &self,
+        conn: &mut SqliteConnection,
+        item: i64,
+        field: Field,
+        before: Option<&str>,
+        after: Option<&str>,
+    ) -> crate::Result<()> {
+        let current = *self.operation.lock().unwrap();
+        let operation = match current {
+            Some(operation) => operation,
+            None => {
+                let operation = self.insert_operation(conn, None).await?;
+                if self.grouped {
+                    *self.operation.lock().unwrap() = Some(operation);
+                }
+                operation
+            }
+        };
+        sqlx::query(
+            "INSERT INTO changes (operation_id, item_id, field, before, after) VALUES (?, ?, ?, ?, ?)",
+        )
+        .bind(operation)
+        .bind(item)
+        .bind(field)
+        .bind(before)
+        .bind(after)
+        .execute(conn)
+        .await?;
+        Ok(())
+    }
+
+    /// Records the `ITEM_FIELDS` of `item` that changed since `before`
+    async fn record_state(
+        &self,
+        conn: &mut SqliteConnection,
+        item: i64,
+        before: Option<ItemState>,
+    ) -> crate::Result<()> {
+        let (Some(before), Some(after)) = (before, item_state(conn, item).await?) else {
+            return Ok(());
+        };
+        for ((field, _), (old, new)) in ITEM_FIELDS.iter().zip(before.iter().zip(&after)) {
+            if old != new {
+                self.record(conn, item, *field, old.as_deref(), new.as_deref())
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Puts back the value a change replaced, recording it as a change of
+    /// its own. Purged items stay purged.
+    pub async fn revert(&mut self, change: &Change) -> crate::Result<()> {
+        let item = change.item_id;
+        let before = change.before.as_deref();
+        match change.field {
+            Field::Added => self.set_status(item, ItemStatus::Deleted).await,
+            Field::Status => {
+                let status = before.ok_or(DBError::ParseError)?.parse()?;
+                self.set_status(item, status).await
+            }
+            Field::Favorite => self.set_favorite(item, before == Some("true")).await,
+            Field::Tag => match (before, &change.after) {
+                (_, Some(added)) => self.unlink_tag(added, item).await,
+                (Some(removed), None) => {
+                    let tag = self
+                        .add_tag(&Tag {
+                            id: 0,
+                            tag: removed.to_string(),
+                            name: None,
+                        })
+                        .await?;
+                    self.link_tag(tag, item as i32).await
+                }
+                (None, None) => Ok(()),
+            },
+            Field::Content => match before {
+                Some(html) => {
+                    let content = Content {
+                        item_id: item,
+                        html: html.to_string(),
+                        time_fetched: None,
+                    };
+                    self.set_content(&content).await
+                }
+                None => self.delete_content(item).await,
+            },
+            Field::Highlight => {
+                let parse = |json: &Option<String>| -> crate::Result<Option<Highlight>> {
+                    json.as_deref()
+                        .map(serde_json::from_str)
+                        .transpose()
+                        .map_err(|_| DBError::ParseError)
+                };
+                match (parse(&change.before)?, parse(&change.after)?) {
+                    (Some(highlight), Some(_)) => self.update_highlight(&highlight).await,
+                    (Some(highlight), None) => self.restore_highlight(&highlight).await,
+                    (None, Some(highlight)) => self.delete_highlight(highlight.id).await,
+                    (None, None) => Ok(()),
+                }
+            }
+            Field::Purged => Ok(()),
+            field => {
+                let Some((_, column)) = ITEM_FIELDS.iter().find(|(f, _)| *f == field) else {
+                    return Ok(());
+                };
+                let mut conn = self.begin().await?;
+                let state = item_state(&mut conn, item).await?;
+                // the column affinity turns the text back into numbers
+                sqlx::query(&format!("UPDATE items SET {column} = ? WHERE id = ?"))
+                    .bind(before)
+                    .bind(item)
+                    .execute(&mut *conn)
+                    .await?;
+                self.record_state(&mut conn, item, state).await?;
+                conn.commit().await
+            }
+        }
     }
 
     pub async fn add_image(&mut self, img: &Image) -> crate::Result<i32> {
@@ -82,7 +457,7 @@ impl LocalDb {
         .bind(img.height)
         .bind(&img.caption)
         .bind(&img.credit)
-        .execute(&self.pool)
+        .execute(&mut *self.conn().await?)
         .await?;
 
         if result.rows_affected() != 0 && result.last_insert_rowid() != 0 {
@@ -91,7 +466,7 @@ impl LocalDb {
 
         let result: RowId = sqlx::query_as("SELECT id FROM images where src = ?")
             .bind(&img.src)
-            .fetch_one(&self.pool)
+            .fetch_one(&mut *self.conn().await?)
             .await
             .map_err(DBError::SqlxError)?;
         Ok(result.id)
@@ -100,7 +475,7 @@ impl LocalDb {
     pub async fn get_images(&self) -> crate::Result<Vec<Image>> {
         let res: Vec<Image> =
             sqlx::query_as("SELECT id, src, width, height, caption, credit FROM images")
-                .fetch_all(&self.pool)
+                .fetch_all(&mut *self.conn().await?)
                 .await?;
         Ok(res)
     }
@@ -111,7 +486,7 @@ impl LocalDb {
         )
         .bind(item)
         .bind(image)
-        .execute(&self.pool)
+        .execute(&mut *self.conn().await?)
         .await?;
         Ok(())
     }
@@ -125,7 +500,7 @@ impl LocalDb {
         .bind(video.width)
         .bind(video.height)
         .bind(&video.kind)
-        .execute(&self.pool)
+        .execute(&mut *self.conn().await?)
         .await?;
 
         if result.rows_affected() != 0 && result.last_insert_rowid() != 0 {
@@ -134,7 +509,7 @@ impl LocalDb {
 
         let result: RowId = sqlx::query_as("SELECT id FROM videos where src = ?")
             .bind(&video.src)
-            .fetch_one(&self.pool)
+            .fetch_one(&mut *self.conn().await?)
             .await
             .map_err(DBError::SqlxError)?;
         Ok(result.id)
@@ -143,7 +518,7 @@ impl LocalDb {
     pub async fn get_videos(&self) -> crate::Result<Vec<crate::Video>> {
         let res: Vec<crate::Video> =
             sqlx::query_as("SELECT id, src, width, height, kind FROM videos")
-                .fetch_all(&self.pool)
+                .fetch_all(&mut *self.conn().await?)
                 .await?;
         Ok(res)
     }
@@ -154,7 +529,7 @@ impl LocalDb {
         )
         .bind(item)
         .bind(video)
-        .execute(&self.pool)
+        .execute(&mut *self.conn().await?)
         .await?;
         Ok(())
     }
@@ -165,7 +540,7 @@ impl LocalDb {
         )
         .bind(&author.name)
         .bind(&author.url)
-        .execute(&self.pool)
+        .execute(&mut *self.conn().await?)
         .await?;
 
         if result.rows_affected() != 0 && result.last_insert_rowid() != 0 {
@@ -174,7 +549,7 @@ impl LocalDb {
 
         let result: RowId = sqlx::query_as("SELECT id FROM authors where url = ?")
             .bind(&author.url)
-            .fetch_one(&self.pool)
+            .fetch_one(&mut *self.conn().await?)
             .await
             .map_err(DBError::SqlxError)?;
 
@@ -183,7 +558,7 @@ impl LocalDb {
 
     pub async fn get_authors(&mut self) -> crate::Result<Vec<Author>> {
         let res: Vec<Author> = sqlx::query_as("SELECT id, name, url FROM authors")
-            .fetch_all(&self.pool)
+            .fetch_all(&mut *self.conn().await?)
             .await?;
         Ok(res)
     }
@@ -194,7 +569,7 @@ impl LocalDb {
         )
         .bind(item)
         .bind(author)
-        .execute(&self.pool)
+        .execute(&mut *self.conn().await?)
         .await?;
         Ok(())
     }
@@ -205,7 +580,7 @@ impl LocalDb {
         )
         .bind(&tag.name)
         .bind(tag.tag.to_lowercase())
-        .execute(&self.pool)
+        .execute(&mut *self.conn().await?)
         .await?;
         if result.rows_affected() != 0 && result.last_insert_rowid() != 0 {
             return Ok(result.last_insert_rowid() as i32);
@@ -218,33 +593,62 @@ impl LocalDb {
     pub async fn get_tag(&mut self, tag: &str) -> crate::Result<Tag> {
         let res: Tag = sqlx::query_as("SELECT id, tag, name FROM tags where tag = ?")
             .bind(tag)
-            .fetch_one(&self.pool)
+            .fetch_one(&mut *self.conn().await?)
             .await?;
         Ok(res)
     }
 
     pub async fn get_tags(&mut self) -> crate::Result<Vec<Tag>> {
         let res: Vec<Tag> = sqlx::query_as("SELECT id, tag, name FROM tags")
-            .fetch_all(&self.pool)
+            .fetch_all(&mut *self.conn().await?)
             .await?;
         Ok(res)
     }
 
-    pub async fn link_tag(&mut self, tag: i32, item: i32) -> crate::Result<()> {
-        sqlx::query(
-            "INSERT INTO items_tags (item_id, tag_id) VALUES (?, ?) ON CONFLICT(item_id, tag_id) DO NOTHING",
+    pub async fn unlink_tag(&mut self, tag: &str, item: i64) -> crate::Result<()> {
+        let tag = tag.to_lowercase();
+        let mut conn = self.begin().await?;
+        let removed = sqlx::query(
+            "DELETE FROM items_tags WHERE item_id = ? AND tag_id IN (SELECT id FROM tags WHERE tag = ?)",
         )
         .bind(item)
-        .bind(tag)
-        .execute(&self.pool)
-        .await?;
-        Ok(())
+        .bind(&tag)
+        .execute(&mut *conn)
+        .await?
+        .rows_affected();
+        if removed > 0 {
+            self.record(&mut conn, item, Field::Tag, Some(&tag), None)
+                .await?;
+        }
+        conn.commit().await
+    }
+
+    pub async fn link_tag(&mut self, tag: i32, item: i32) -> crate::Result<()> {
+        let mut conn = self.begin().await?;
+        if insert_tag_link(&mut conn, tag, item).await? {
+            let name: String = sqlx::query_scalar("SELECT tag FROM tags WHERE id = ?")
+                .bind(tag)
+                .fetch_one(&mut *conn)
+                .await?;
+            self.record(&mut conn, item as i64, Field::Tag, None, Some(&name))
+                .await?;
+        }
+        conn.commit().await
     }
 
     pub async fn add(&mut self, item: &Item) -> crate::Result<i32> {
-        let result = sqlx::query(
+        let mut conn = self.begin().await?;
+        let existing: Option<i64> = sqlx::query_scalar("SELECT id FROM items WHERE url = ?")
+            .bind(&item.url)
+            .fetch_optional(&mut *conn)
+            .await?;
+        let before = match existing {
+            Some(id) => item_state(&mut conn, id).await?,
+            None => None,
+        };
+
+        let result: RowId = sqlx::query_as(
             "INSERT INTO items (
-            pocket_id, 
             title, 
             url, 
             excerpt, 
@@ -257,6 +661,7 @@ impl LocalDb {
             time_to_read,
             top_image_url,
             status,
+            favorite,
             time_added,            
             time_updated,
             time_read,
@@ -265,15 +670,12 @@ impl LocalDb {
             ?, ?, ?, ?, ?, ?,
             ?, ?, ?, ?, ?, ?,
             ?, ?, ?, ?, ?
-        ) ON CONFLICT(pocket_id) DO UPDATE SET
-            status = excluded.status,
-            time_added = excluded.time_added,
-            time_updated = excluded.time_updated,
-            time_read = excluded.time_read,
-            time_favorited = excluded.time_favorited
+        ) ON CONFLICT(url) DO UPDATE SET
+            title = CASE WHEN excluded.title = '' THEN items.title ELSE excluded.title END,
+            time_updated = excluded.time_updated
+        RETURNING id
          ",
         )
-        .bind(item.pocket_id)
         .bind(&item.title)
         .bind(&item.url)
         .bind(&item.excerpt)
@@ -286,28 +688,82 @@ impl LocalDb {
         .bind(item.time_to_read)
         .bind(&item.top_image_url)
         .bind(item.status)
+        .bind(item.favorite)
         .bind(item.time_added)
         .bind(item.time_updated)
         .bind(item.time_read)
         .bind(item.time_favorited)
-        .execute(&self.pool)
+        .fetch_one(&mut *conn)
         .await
         .map_err(DBError::SqlxError)?;
 
-        let item_id = if result.last_insert_rowid() != 0 && result.rows_affected() != 0 {
-            result.last_insert_rowid() as i32
-        } else {
-            let result: RowId = sqlx::query_as("SELECT id FROM items where url = ?")
-                .bind(&item.url)
-                .fetch_one(&self.pool)
-                .await
-                .map_err(DBError::SqlxError)?;
-            result.id
-        };
+        let item_id = result.id;
+        match existing {
+            Some(_) => self.record_state(&mut conn, item_id as i64, before).await?,
+            None => {
+                self.record(
+                    &mut conn,
+                    item_id as i64,
+                    Field::Added,
+                    None,
+                    Some(&item.url),
+                )
+                .await?
+            }
+        }
+        conn.commit().await?;
+
+        self.link_all(item, item_id).await?;
+        Ok(item_id)
+    }
+
+    /// Updates the descriptive fields of an existing item. Tags, authors,
+    /// images and videos of the item are linked in addition to the existing
+    /// ones.
+    pub async fn update_item(&mut self, item: &Item) -> crate::Result<()> {
+        let mut conn = self.begin().await?;
+        let before = item_state(&mut conn, item.id).await?;
+        sqlx::query(
+            "UPDATE items SET
+                title = ?,
+                excerpt = ?,
+                canonical_url = ?,
+                is_article = ?,
+                is_index = ?,
+                has_video = ?,
+                has_image = ?,
+                word_count = ?,
+                lang = ?,
+                time_to_read = ?,
+                top_image_url = ?,
+                time_updated = unixepoch()
+            WHERE id = ?",
+        )
+        .bind(&item.title)
+        .bind(&item.excerpt)
+        .bind(&item.canonical_url)
+        .bind(item.is_article)
+        .bind(item.is_index)
+        .bind(item.has_video)
+        .bind(item.has_image)
+        .bind(item.word_count)
+        .bind(&item.lang)
+        .bind(item.time_to_read)
+        .bind(&item.top_image_url)
+        .bind(item.id)
+        .execute(&mut *conn)
+        .await?;
+
+        self.record_state(&mut conn, item.id, before).await?;
+        conn.commit().await?;
+
+        self.link_all(item, item.id as i32).await
+    }
 
+    async fn link_all(&mut self, item: &Item, item_id: i32) -> crate::Result<()> {
         for tag in item.tags.iter() {
             let tag_id = self.add_tag(tag).await?;
-            self.link_tag(tag_id, item_id).await?;
+            insert_tag_link(&mut *self.conn().await?, tag_id, item_id).await?;
         }
 
         for author in item.authors.iter() {
@@ -324,11 +780,16 @@ impl LocalDb {
             let video_id = self.add_video(video).await?;
             self.link_video(video_id, item_id).await?;
         }
-
-        Ok(item_id)
+        Ok(())
     }
 
     pub async fn get_items(&self) -> crate::Result<Vec<Item>> {
+        self.select_items(None).await
+    }
+
+    /// Items with their tags, authors, images and videos, only the one with
+    /// id `id` when given
+    async fn select_items(&self, id: Option<i64>) -> crate::Result<Vec<Item>> {
         let rows: Vec<ItemRow> = sqlx::query_as(
             r#"
             select items.*,
@@ -366,9 +827,11 @@ impl LocalDb {
                 on items.id = items_videos.item_id
             left join videos
                 on items_videos.video_id = videos.id
+            where ?1 is null or items.id = ?1
             "#,
         )
-        .fetch_all(&self.pool)
+        .bind(id)
+        .fetch_all(&mut *self.conn().await?)
         .await?;
 
         let rows = rows
@@ -379,6 +842,7 @@ impl LocalDb {
                 item.url = row.url;
                 item.id = row.id;
                 item.excerpt = row.excerpt;
+                item.canonical_url = row.canonical_url;
                 item.is_article = row.is_article;
                 item.is_index = row.is_index;
                 item.has_video = row.has_video;
@@ -393,6 +857,11 @@ impl LocalDb {
                 item.time_read = row.time_read;
                 item.time_favorited = row.time_favorited;
                 item.status = row.status;
+                item.favorite = row.favorite;
+                item.link_status = row.link_status;
+                item.link_final_url = row.link_final_url;
+                item.time_link_checked = row.time_link_checked;
+                item.wayback_url = row.wayback_url;
 
                 if let Some(tag_id) = row.tag_id {
                     item.tags.insert(Tag {
@@ -437,114 +906,945 @@ impl LocalDb {
 
         Ok(rows)
     }
-}
-
-#[cfg(test)]
-mod test {
-    use super::*;
-    use crate::{Image, Item, ItemStatus, Tag, Video};
-    use std::collections::HashSet;
 
-    async fn get_db() -> LocalDb {
-        let pool = open_database(":memory:").await.unwrap();
-        LocalDb::new(pool)
+    pub async fn get_item(&self, id: i64) -> crate::Result<Option<Item>> {
+        Ok(self.select_items(Some(id)).await?.pop())
     }
 
-    #[tokio::test]
-    async fn test_open_in_memory() {
-        let con = open_database(":memory:").await;
-        assert!(con.is_ok());
+    pub async fn query_items(&self, query: &ItemQuery) -> crate::Result<Vec<Item>> {
+        Ok(query.apply(self.get_items().await?))
     }
 
-    #[tokio::test]
-    async fn test_get_items() {
-        let db = get_db().await;
-        let items = db.get_items().await.unwrap();
-        assert!(items.is_empty());
+    pub async fn set_favorite(&mut self, item: i64, favorite: bool) -> crate::Result<()> {
+        let mut conn = self.begin().await?;
+        let before = item_state(&mut conn, item).await?;
+        sqlx::query(
+            "UPDATE items SET
+                favorite = ?,
+                time_updated = unixepoch(),
+                time_favorited = CASE WHEN ? THEN coalesce(time_favorited, unixepoch()) END
+            WHERE id = ?",
+        )
+        .bind(favorite)
+        .bind(favorite)
+        .bind(item)
+        .execute(&mut *conn)
+        .await?;
+        self.record_state(&mut conn, item, before).await?;
+        conn.commit().await
     }
 
-    #[tokio::test]
-    async fn test_add_item() {
-        let mut db = get_db().await;
+    pub async fn set_title(&mut self, item: i64, title: &str) -> crate::Result<()> {
+        let mut conn = self.begin().await?;
+        let before = item_state(&mut conn, item).await?;
+        sqlx::query("UPDATE items SET title = ?, time_updated = unixepoch() WHERE id = ?")
+            .bind(title)
+            .bind(item)
+            .execute(&mut *conn)
+            .await?;
+        self.record_state(&mut conn, item, before).await?;
+        conn.commit().await
+    }
 
-        let result = db
-            .add(&Item {
-                tags: HashSet::from([Tag::default()]),
-                authors: HashSet::from([Default::default()]),
-                images: HashSet::from([Default::default()]),
-                videos: HashSet::from([Default::default()]),
-                ..Default::default()
-            })
-            .await;
-        assert!(result.is_ok());
-        assert_eq!(result.unwrap(), 1);
+    /// Removes an item and everything attached to it. Returns whether it
+    /// existed.
+    pub async fn delete_item(&mut self, item: i64) -> crate::Result<bool> {
+        let mut tx = self.begin().await?;
+        for table in ITEM_TABLES {
+            sqlx::query(&format!("DELETE FROM {table} WHERE item_id = ?"))
+                .bind(item)
+                .execute(&mut *tx)
+                .await?;
+        }
+        let url: Option<String> =
+            sqlx::query_scalar("DELETE FROM items WHERE id = ? RETURNING url")
+                .bind(item)
+                .fetch_optional(&mut *tx)
+                .await?;
+        if let Some(url) = &url {
+            self.record(&mut tx, item, Field::Purged, Some(url), None)
+                .await?;
+        }
+        tx.commit().await?;
+        Ok(url.is_some())
+    }
 
-        let items = db.get_items().await.unwrap();
-        assert_eq!(items.len(), 1);
+    /// The latest `limit` operations, newest first
+    pub async fn get_operations(&self, limit: i64) -> crate::Result<Vec<Operation>> {
+        self.select_operations(false, limit).await
+    }
 
-        let item = &items[0];
-        assert_eq!(item.id, 1);
-        assert_eq!(item.title, Item::default().title);
-        assert_eq!(item.url, Item::default().url);
-        assert_eq!(item.status, ItemStatus::Unread);
+    /// The latest `limit` operations that can still be undone, newest first.
+    /// Undo operations are left out, so undoing again goes further back.
+    pub async fn get_undoable(&self, limit: i64) -> crate::Result<Vec<Operation>> {
+        self.select_operations(true, limit).await
+    }
 
-        assert_eq!(item.tags.len(), 1);
-        assert!(item.tags.iter().any(|t| t.tag == Tag::default().tag));
+    async fn select_operations(&self, undoable: bool, limit: i64) -> crate::Result<Vec<Operation>> {
+        let rows: Vec<OperationRow> = sqlx::query_as(
+            "SELECT operations.id, operations.source, operations.time, operations.undo_of,
+                EXISTS(SELECT 1 FROM operations AS undo WHERE undo.undo_of = operations.id) AS undone
+            FROM operations
+            WHERE NOT ?1 OR (operations.undo_of IS NULL AND NOT undone)
+            ORDER BY operations.id DESC
+            LIMIT ?2",
+        )
+        .bind(undoable)
+        .bind(limit)
+        .fetch_all(&mut *self.conn().await?)
+        .await?;
 
-        assert_eq!(item.authors.len(), 1);
-        assert!(item
-            .authors
-            .iter()
-            .any(|a| a.name == Author::default().name));
+        let mut operations = Vec::with_capacity(rows.len());
+        for row in rows {
+            let changes =
+                sqlx::query_as("SELECT * FROM changes WHERE operation_id = ? ORDER BY id")
+                    .bind(row.id)
+                    .fetch_all(&mut *self.conn().await?)
+                    .await?;
+            operations.push(Operation {
+                id: row.id,
+                source: row.source,
+                time: row.time,
+                undo_of: row.undo_of,
+                undone: row.undone,
+                changes,
+            });
+        }
+        Ok(operations)
+    }
 
-        assert_eq!(item.images.len(), 1);
-        assert!(item.images.iter().any(|i| i.src == Image::default().src));
+    pub async fn set_status(&mut self, item: i64, status: ItemStatus) -> crate::Result<()> {
+        let mut conn = self.begin().await?;
+        let before = item_state(&mut conn, item).await?;
+        sqlx::query(
+            "UPDATE items SET
+                status = ?,
+                time_updated = unixepoch(),
+                time_read = CASE WHEN ? = 1 THEN unixepoch() ELSE time_read END
+            WHERE id = ?",
+        )
+        .bind(status)
+        .bind(status)
+        .bind(item)
+        .execute(&mut *conn)
+        .await?;
+        self.record_state(&mut conn, item, before).await?;
+        conn.commit().await
+    }
 
-        assert_eq!(item.videos.len(), 1);
-        assert!(item.videos.iter().any(|i| i.src == Video::default().src));
+    pub async fn set_link_status(
+        &mut self,
+        item: i64,
+        status: LinkStatus,
+        final_url: Option<&str>,
+        time: i32,
+    ) -> crate::Result<()> {
+        let mut conn = self.begin().await?;
+        let before = item_state(&mut conn, item).await?;
+        sqlx::query(
+            "UPDATE items SET link_status = ?, link_final_url = ?, time_link_checked = ? WHERE id = ?",
+        )
+        .bind(status)
+        .bind(final_url)
+        .bind(time)
+        .bind(item)
+        .execute(&mut *conn)
+        .await?;
+        self.record_state(&mut conn, item, before).await?;
+        conn.commit().await
     }
 
-    #[tokio::test]
-    async fn test_add_image() {
-        let mut db = get_db().await;
-        let img = Image::default();
-        let id = db.add_image(&img).await.unwrap();
-        assert_eq!(id, 1);
-        let id = db.add_image(&img).await.unwrap();
-        assert_eq!(id, 1);
+    pub async fn set_wayback_url(&mut self, item: i64, url: &str) -> crate::Result<()> {
+        let mut conn = self.begin().await?;
+        let before = item_state(&mut conn, item).await?;
+        sqlx::query("UPDATE items SET wayback_url = ? WHERE id = ?")
+            .bind(url)
+            .bind(item)
+            .execute(&mut *conn)
+            .await?;
+        self.record_state(&mut conn, item, before).await?;
+        conn.commit().await
     }
 
-    #[tokio::test]
-    async fn test_get_images() {
-        let mut db = get_db().await;
-        let id = db.add_image(&Default::default()).await.unwrap();
-        assert_eq!(id, 1);
+    pub async fn set_content(&mut self, content: &Content) -> crate::Result<()> {
+        let mut conn = self.begin().await?;
+        let before: Option<String> =
+            sqlx::query_scalar("SELECT html FROM item_contents WHERE item_id = ?")
+                .bind(content.item_id)
+                .fetch_optional(&mut *conn)
+                .await?;
+        sqlx::query(
+            "INSERT INTO item_contents (item_id, html, time_fetched) VALUES (?, ?, ?)
+            ON CONFLICT(item_id) DO UPDATE SET
+                html = excluded.html,
+                time_fetched = excluded.time_fetched",
+        )
+        .bind(content.item_id)
+        .bind(&content.html)
+        .bind(content.time_fetched)
+        .execute(&mut *conn)
+        .await?;
+        if before.as_ref() != Some(&content.html) {
+            let html = Some(content.html.as_str());
+            self.record(
+                &mut conn,
+                content.item_id,
+                Field::Content,
+                before.as_deref(),
+                html,
+            )
+            .await?;
+        }
+        conn.commit().await
+    }
 
-        let img = Image {
-            src: "https://example.com/image2.jpg".to_string(),
-            ..Default::default()
-        };
-        let id = db.add_image(&img).await.unwrap();
-        assert_eq!(id, 2);
+    /// Removes the extracted content of an item
+    pub async fn delete_content(&mut self, item: i64) -> crate::Result<()> {
+        let mut conn = self.begin().await?;
+        let before: Option<String> =
+            sqlx::query_scalar("DELETE FROM item_contents WHERE item_id = ? RETURNING html")
+                .bind(item)
+                .fetch_optional(&mut *conn)
+                .await?;
+        if let Some(html) = &before {
+            self.record(&mut conn, item, Field::Content, Some(html), None)
+                .await?;
+        }
+        conn.commit().await
+    }
 
-        let images = db.get_images().await;
-        assert!(images.is_ok());
-        let images = images.unwrap();
-        assert_eq!(images.len(), 2);
+    pub async fn get_content(&self, item: i64) -> crate::Result<Option<Content>> {
+        let res: Option<Content> = sqlx::query_as(
+            "SELECT item_id, html, time_fetched FROM item_contents WHERE item_id = ?",
+        )
+        .bind(item)
+        .fetch_optional(&mut *self.conn().await?)
+        .await?;
+        Ok(res)
     }
 
-    #[tokio::test]
-    async fn test_link_image() {
-        let mut db = get_db().await;
-        let img_id = db.add_image(&Default::default()).await.unwrap();
-        let item_id = db.add(&Default::default()).await.unwrap();
-        let res = db.link_image(img_id, item_id).await;
-        assert!(res.is_ok());
+    pub async fn add_asset(&mut self, asset: &Asset) -> crate::Result<()> {
+        sqlx::query(
+            "INSERT INTO assets (url, hash, path, mime, size, time_fetched, time_accessed)
+            VALUES (?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT(url) DO UPDATE SET
+                hash = excluded.hash,
+                path = excluded.path,
+                mime = excluded.mime,
+                size = excluded.size,
+                time_fetched = excluded.time_fetched,
+                time_accessed = excluded.time_accessed",
+        )
+        .bind(&asset.url)
+        .bind(&asset.hash)
+        .bind(&asset.path)
+        .bind(&asset.mime)
+        .bind(asset.size)
+        .bind(asset.time_fetched)
+        .bind(asset.time_accessed)
+        .execute(&mut *self.conn().await?)
+        .await?;
+        Ok(())
+    }
 
-        let items = db.get_items().await.unwrap();
-        assert_eq!(items.len(), 1);
+    pub async fn get_asset(&self, url: &str) -> crate::Result<Option<Asset>> {
+        let res: Option<Asset> = sqlx::query_as("SELECT * FROM assets WHERE url = ?")
+            .bind(url)
+            .fetch_optional(&mut *self.conn().await?)
+            .await?;
+        Ok(res)
+    }
 
-        let item = &items[0];
+    /// One of the urls pointing to the file with the given hash
+    pub async fn get_asset_by_hash(&self, hash: &str) -> crate::Result<Option<Asset>> {
+        let res: Option<Asset> = sqlx::query_as("SELECT * FROM assets WHERE hash = ? LIMIT 1")
+            .bind(hash)
+            .fetch_optional(&mut *self.conn().await?)
+            .await?;
+        Ok(res)
+    }
+
+    pub async fn get_assets(&self) -> crate::Result<Vec<Asset>> {
+        let res: Vec<Asset> = sqlx::query_as("SELECT * FROM assets")
+            .fetch_all(&mut *self.conn().await?)
+            .await?;
+        Ok(res)
+    }
+
+    /// Marks the asset as recently used so that it is evicted last
+    pub async fn touch_asset(&mut self, url: &str, time: i32) -> crate::Result<()> {
+        sqlx::query("UPDATE assets SET time_accessed = ? WHERE url = ?")
+            .bind(time)
+            .bind(url)
+            .execute(&mut *self.conn().await?)
+            .await?;
+        Ok(())
+    }
+
+    /// Removes every url pointing to the file with the given hash
+    pub async fn delete_assets(&mut self, hash: &str) -> crate::Result<u64> {
+        let result = sqlx::query("DELETE FROM assets WHERE hash = ?")
+            .bind(hash)
+            .execute(&mut *self.conn().await?)
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    pub async fn add_snapshot(&mut self, snapshot: &Snapshot) -> crate::Result<i32> {
+        let result = sqlx::query(
+            "INSERT INTO snapshots (item_id, url, status, html_path, warc_path, size, time_fetched)
+            VALUES (?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(snapshot.item_id)
+        .bind(&snapshot.url)
+        .bind(snapshot.status)
+        .bind(&snapshot.html_path)
+        .bind(&snapshot.warc_path)
+        .bind(snapshot.size)
+        .bind(snapshot.time_fetched)
+        .execute(&mut *self.conn().await?)
+        .await?;
+        Ok(result.last_insert_rowid() as i32)
+    }
+
+    /// Snapshots of an item, newest first
+    pub async fn get_snapshots(&self, item: i64) -> crate::Result<Vec<Snapshot>> {
+        let res: Vec<Snapshot> = sqlx::query_as(
+            "SELECT * FROM snapshots WHERE item_id = ? ORDER BY time_fetched DESC, id DESC",
+        )
+        .bind(item)
+        .fetch_all(&mut *self.conn().await?)
+        .await?;
+        Ok(res)
+    }
+
+    pub async fn get_item_id_by_url(&self, url: &str) -> crate::Result<Option<i64>> {
+        let res: Option<(i64,)> = sqlx::query_as("SELECT id FROM items WHERE url = ?")
+            .bind(url)
+            .fetch_optional(&mut *self.conn().await?)
+            .await?;
+        Ok(res.map(|(id,)| id))
+    }
+
+    /// Overwrites the status and the read and favorite times of an item with
+    /// the ones reported by a remote.
+    pub async fn set_remote_state(&mut self, item: &Item) -> crate::Result<()> {
+        let mut conn = self.begin().await?;
+        let before = item_state(&mut conn, item.id).await?;
+        sqlx::query(
+            "UPDATE items SET
+                status = ?,
+                time_updated = ?,
+                time_read = ?,
+                favorite = ?,
+                time_favorited = ?
+            WHERE id = ?",
+        )
+        .bind(item.status)
+        .bind(item.time_updated)
+        .bind(item.time_read)
+        .bind(item.favorite)
+        .bind(item.time_favorited)
+        .bind(item.id)
+        .execute(&mut *conn)
+        .await?;
+        self.record_state(&mut conn, item.id, before).await?;
+        conn.commit().await
+    }
+
+    pub async fn add_remote(
+        &mut self,
+        name: &str,
+        backend: &str,
+        settings: &str,
+    ) -> crate::Result<i64> {
+        let result = sqlx::query("INSERT INTO remotes (name, backend, settings) VALUES (?, ?, ?)")
+            .bind(name)
+            .bind(backend)
+            .bind(settings)
+            .execute(&mut *self.conn().await?)
+            .await?;
+        Ok(result.last_insert_rowid())
+    }
+
+    pub async fn get_remotes(&self) -> crate::Result<Vec<Remote>> {
+        let res: Vec<Remote> = sqlx::query_as("SELECT * FROM remotes ORDER BY id")
+            .fetch_all(&mut *self.conn().await?)
+            .await?;
+        Ok(res)
+    }
+
+    pub async fn get_remote(&self, name: &str) -> crate::Result<Option<Remote>> {
+        let res: Option<Remote> = sqlx::query_as("SELECT * FROM remotes WHERE name = ?")
+            .bind(name)
+            .fetch_optional(&mut *self.conn().await?)
+            .await?;
+        Ok(res)
+    }
+
+    /// Removes a remote along with its pending changes. Items synced from it
+    /// stay in the library.
+    pub async fn remove_remote(&mut self, remote: i64) -> crate::Result<()> {
+        let mut tx = self.begin().await?;
+        for table in ["outbox", "remote_items", "item_remotes"] {
+            sqlx::query(&format!("DELETE FROM {table} WHERE remote_id = ?"))
+                .bind(remote)
+                .execute(&mut *tx)
+                .await?;
+        }
+        sqlx::query("DELETE FROM remotes WHERE id = ?")
+            .bind(remote)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    pub async fn set_remote_settings(&mut self, remote: i64, settings: &str) -> crate::Result<()> {
+        sqlx::query("UPDATE remotes SET settings = ? WHERE id = ?")
+            .bind(settings)
+            .bind(remote)
+            .execute(&mut *self.conn().await?)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn set_remote_cursor(&mut self, remote: i64, cursor: &str) -> crate::Result<()> {
+        sqlx::query("UPDATE remotes SET cursor = ? WHERE id = ?")
+            .bind(cursor)
+            .bind(remote)
+            .execute(&mut *self.conn().await?)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn set_remote_synced(&mut self, remote: i64, time: i64) -> crate::Result<()> {
+        sqlx::query("UPDATE remotes SET time_synced = ? WHERE id = ?")
+            .bind(time)
+            .bind(remote)
+            .execute(&mut *self.conn().await?)
+            .await?;
+        Ok(())
+    }
+
+    /// Records that `item` is known to the remote as `remote_item`
+    pub async fn link_remote_item(
+        &mut self,
+        remote: i64,
+        remote_item: &str,
+        item: i64,
+    ) -> crate::Result<()> {
+        sqlx::query(
+            "INSERT INTO remote_items (remote_id, remote_item_id, item_id) VALUES (?, ?, ?)
+            ON CONFLICT(remote_id, remote_item_id) DO UPDATE SET item_id = excluded.item_id",
+        )
+        .bind(remote)
+        .bind(remote_item)
+        .bind(item)
+        .execute(&mut *self.conn().await?)
+        .await?;
+        self.add_item_remote(item, remote).await
+    }
+
+    /// Records that `item` belongs to the account of `remote`
+    pub async fn add_item_remote(&mut self, item: i64, remote: i64) -> crate::Result<()> {
+        sqlx::query("INSERT OR IGNORE INTO item_remotes (item_id, remote_id) VALUES (?, ?)")
+            .bind(item)
+            .bind(remote)
+            .execute(&mut *self.conn().await?)
+            .await?;
+        Ok(())
+    }
+
+    /// Remotes whose account holds `item`
+    pub async fn get_item_remotes(&self, item: i64) -> crate::Result<Vec<Remote>> {
+        let res: Vec<Remote> = sqlx::query_as(
+            "SELECT remotes.* FROM remotes
+            JOIN item_remotes ON item_remotes.remote_id = remotes.id
+            WHERE item_remotes.item_id = ?
+            ORDER BY remotes.id",
+        )
+        .bind(item)
+        .fetch_all(&mut *self.conn().await?)
+        .await?;
+        Ok(res)
+    }
+
+    /// Number of items in the account of `remote`
+    pub async fn count_remote_items(&self, remote: i64) -> crate::Result<i64> {
+        let (count,): (i64,) =
+            sqlx::query_as("SELECT count(*) FROM item_remotes WHERE remote_id = ?")
+                .bind(remote)
+                .fetch_one(&mut *self.conn().await?)
+                .await?;
+        Ok(count)
+    }
+
+    /// Local item the remote knows as `remote_item`
+    pub async fn find_remote_item(
+        &self,
+        remote: i64,
+        remote_item: &str,
+    ) -> crate::Result<Option<i64>> {
+        let res: Option<(i64,)> = sqlx::query_as(
+            "SELECT item_id FROM remote_items WHERE remote_id = ? AND remote_item_id = ?",
+        )
+        .bind(remote)
+        .bind(remote_item)
+        .fetch_optional(&mut *self.conn().await?)
+        .await?;
+        Ok(res.map(|(id,)| id))
+    }
+
+    /// Id of a local item on the remote, if it was ever synced there
+    pub async fn get_remote_item_id(
+        &self,
+        remote: i64,
+        item: i64,
+    ) -> crate::Result<Option<String>> {
+        let res: Option<(String,)> = sqlx::query_as(
+            "SELECT remote_item_id FROM remote_items WHERE remote_id = ? AND item_id = ?",
+        )
+        .bind(remote)
+        .bind(item)
+        .fetch_optional(&mut *self.conn().await?)
+        .await?;
+        Ok(res.map(|(id,)| id))
+    }
+
+    pub async fn enqueue(&mut self, remote: i64, item: i64, mutation: &str) -> crate::Result<i64> {
+        let result =
+            sqlx::query("INSERT INTO outbox (remote_id, item_id, mutation) VALUES (?, ?, ?)")
+                .bind(remote)
+                .bind(item)
+                .bind(mutation)
+                .execute(&mut *self.conn().await?)
+                .await?;
+        Ok(result.last_insert_rowid())
+    }
+
+    /// Pending changes of a remote, oldest first
+    pub async fn get_outbox(&self, remote: i64) -> crate::Result<Vec<OutboxEntry>> {
+        let res: Vec<OutboxEntry> =
+            sqlx::query_as("SELECT * FROM outbox WHERE remote_id = ? ORDER BY id")
+                .bind(remote)
+                .fetch_all(&mut *self.conn().await?)
+                .await?;
+        Ok(res)
+    }
+
+    pub async fn remove_outbox(&mut self, entry: i64) -> crate::Result<()> {
+        sqlx::query("DELETE FROM outbox WHERE id = ?")
+            .bind(entry)
+            .execute(&mut *self.conn().await?)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn fail_outbox(&mut self, entry: i64, error: &str) -> crate::Result<()> {
+        sqlx::query("UPDATE outbox SET attempts = attempts + 1, last_error = ? WHERE id = ?")
+            .bind(error)
+            .bind(entry)
+            .execute(&mut *self.conn().await?)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn add_highlight(&mut self, highlight: &Highlight) -> crate::Result<i64> {
+        let mut conn = self.begin().await?;
+        let result = sqlx::query(
+            "INSERT INTO highlights (item_id, text, note, color, position) VALUES (?, ?, ?, ?, ?)",
+        )
+        .bind(highlight.item_id)
+        .bind(&highlight.text)
+        .bind(&highlight.note)
+        .bind(&highlight.color)
+        .bind(&highlight.position)
+        .execute(&mut *conn)
+        .await?;
+        let id = result.last_insert_rowid();
+        self.record_new_highlight(&mut conn, highlight.item_id, id)
+            .await?;
+        conn.commit().await?;
+        Ok(id)
+    }
+
+    pub async fn get_highlight(&self, id: i64) -> crate::Result<Option<Highlight>> {
+        let res: Option<Highlight> = sqlx::query_as("SELECT * FROM highlights WHERE id = ?")
+            .bind(id)
+            .fetch_optional(&mut *self.conn().await?)
+            .await?;
+        Ok(res)
+    }
+
+    /// Highlights of an item, or of every item, in the order they were made
+    pub async fn get_highlights(&self, item: Option<i64>) -> crate::Result<Vec<Highlight>> {
+        let res: Vec<Highlight> = sqlx::query_as(
+            "SELECT * FROM highlights WHERE ? IS NULL OR item_id = ? ORDER BY time_added, id",
+        )
+        .bind(item)
+        .bind(item)
+        .fetch_all(&mut *self.conn().await?)
+        .await?;
+        Ok(res)
+    }
+
+    pub async fn update_highlight(&mut self, highlight: &Highlight) -> crate::Result<()> {
+        let mut conn = self.begin().await?;
+        let before = highlight_json(&mut conn, highlight.id).await?;
+        sqlx::query(
+            "UPDATE highlights SET
+                text = ?,
+                note = ?,
+                color = ?,
+                position = ?,
+                time_updated = unixepoch()
+            WHERE id = ?",
+        )
+        .bind(&highlight.text)
+        .bind(&highlight.note)
+        .bind(&highlight.color)
+        .bind(&highlight.position)
+        .bind(highlight.id)
+        .execute(&mut *conn)
+        .await?;
+        let after = highlight_json(&mut conn, highlight.id).await?;
+        if before != after {
+            self.record(
+                &mut conn,
+                highlight.item_id,
+                Field::Highlight,
+                before.as_deref(),
+                after.as_deref(),
+            )
+            .await?;
+        }
+        conn.commit().await
+    }
+
+    pub async fn delete_highlight(&mut self, id: i64) -> crate::Result<()> {
+        let mut conn = self.begin().await?;
+        let before = highlight_json(&mut conn, id).await?;
+        let item: Option<i64> =
+            sqlx::query_scalar("DELETE FROM highlights WHERE id = ? RETURNING item_id")
+                .bind(id)
+                .fetch_optional(&mut *conn)
+                .await?;
+        if let Some(item) = item {
+            self.record(&mut conn, item, Field::Highlight, before.as_deref(), None)
+                .await?;
+        }
+        conn.commit().await
+    }
+
+    /// Inserts a highlight removed by a change again, under its old id
+    async fn restore_highlight(&mut self, highlight: &Highlight) -> crate::Result<()> {
+        let mut conn = self.begin().await?;
+        sqlx::query(
+            "INSERT INTO highlights (id, item_id, text, note, color, position, time_added, time_updated)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(highlight.id)
+        .bind(highlight.item_id)
+        .bind(&highlight.text)
+        .bind(&highlight.note)
+        .bind(&highlight.color)
+        .bind(&highlight.position)
+        .bind(highlight.time_added)
+        .bind(highlight.time_updated)
+        .execute(&mut *conn)
+        .await?;
+        self.record_new_highlight(&mut conn, highlight.item_id, highlight.id)
+            .await?;
+        conn.commit().await
+    }
+
+    async fn record_new_highlight(
+        &self,
+        conn: &mut SqliteConnection,
+        item: i64,
+        id: i64,
+    ) -> crate::Result<()> {
+        let after = highlight_json(conn, id).await?;
+        self.record(conn, item, Field::Highlight, None, after.as_deref())
+            .await
+    }
+
+    /// Remembers that a file handed out for an item has the given hash
+    pub async fn add_document(&mut self, hash: &str, item: i64) -> crate::Result<()> {
+        sqlx::query(
+            "INSERT INTO documents (hash, item_id) VALUES (?, ?)
+            ON CONFLICT(hash) DO UPDATE SET item_id = excluded.item_id",
+        )
+        .bind(hash)
+        .bind(item)
+        .execute(&mut *self.conn().await?)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn find_document(&self, hash: &str) -> crate::Result<Option<i64>> {
+        let res: Option<(i64,)> = sqlx::query_as("SELECT item_id FROM documents WHERE hash = ?")
+            .bind(hash)
+            .fetch_optional(&mut *self.conn().await?)
+            .await?;
+        Ok(res.map(|(id,)| id))
+    }
+
+    pub async fn set_progress(&mut self, progress: &Progress) -> crate::Result<()> {
+        sqlx::query(
+            "INSERT INTO reading_progress (document, progress, percentage, device, device_id, timestamp)
+            VALUES (?, ?, ?, ?, ?, ?)
+            ON CONFLICT(document) DO UPDATE SET
+                progress = excluded.progress,
+                percentage = excluded.percentage,
+                device = excluded.device,
+                device_id = excluded.device_id,
+                timestamp = excluded.timestamp",
+        )
+        .bind(&progress.document)
+        .bind(&progress.progress)
+        .bind(progress.percentage)
+        .bind(&progress.device)
+        .bind(&progress.device_id)
+        .bind(progress.timestamp)
+        .execute(&mut *self.conn().await?)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn get_progress(&self, document: &str) -> crate::Result<Option<Progress>> {
+        let res: Option<Progress> =
+            sqlx::query_as("SELECT * FROM reading_progress WHERE document = ?")
+                .bind(document)
+                .fetch_optional(&mut *self.conn().await?)
+                .await?;
+        Ok(res)
+    }
+
+    /// Queues a job unless the same work on the item is already waiting
+    pub async fn add_job(&mut self, kind: JobKind, item: i64) -> crate::Result<()> {
+        sqlx::query(
+            "INSERT INTO jobs (kind, item_id) SELECT ?, ?
+            WHERE NOT EXISTS (SELECT 1 FROM jobs WHERE kind = ? AND item_id = ? AND state != ?)",
+        )
+        .bind(kind)
+        .bind(item)
+        .bind(kind)
+        .bind(item)
+        .bind(JobState::Failed)
+        .execute(&mut *self.conn().await?)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn get_jobs(&self) -> crate::Result<Vec<Job>> {
+        let res: Vec<Job> = sqlx::query_as("SELECT * FROM jobs ORDER BY next_run, id")
+            .fetch_all(&mut *self.conn().await?)
+            .await?;
+        Ok(res)
+    }
+
+    /// Marks the next pending job due at `now` as running and returns it
+    pub async fn claim_job(&mut self, now: i64) -> crate::Result<Option<Job>> {
+        let res: Option<Job> = sqlx::query_as(
+            "UPDATE jobs SET state = ? WHERE id = (
+                SELECT id FROM jobs WHERE state = ? AND next_run <= ? ORDER BY next_run, id LIMIT 1
+            ) RETURNING *",
+        )
+        .bind(JobState::Running)
+        .bind(JobState::Pending)
+        .bind(now)
+        .fetch_optional(&mut *self.conn().await?)
+        .await?;
+        Ok(res)
+    }
+
+    /// Time the next pending job is due
+    pub async fn next_job_time(&self) -> crate::Result<Option<i64>> {
+        let res: (Option<i64>,) = sqlx::query_as("SELECT min(next_run) FROM jobs WHERE state = ?")
+            .bind(JobState::Pending)
+            .fetch_one(&mut *self.conn().await?)
+            .await?;
+        Ok(res.0)
+    }
+
+    pub async fn complete_job(&mut self, id: i64) -> crate::Result<()> {
+        sqlx::query("DELETE FROM jobs WHERE id = ?")
+            .bind(id)
+            .execute(&mut *self.conn().await?)
+            .await?;
+        Ok(())
+    }
+
+    /// Records a failed attempt. The job runs again at `retry_at`, or is
+    /// given up without it.
+    pub async fn fail_job(
+        &mut self,
+        id: i64,
+        error: &str,
+        retry_at: Option<i64>,
+    ) -> crate::Result<()> {
+        let state = match retry_at {
+            Some(_) => JobState::Pending,
+            None => JobState::Failed,
+        };
+        sqlx::query(
+            "UPDATE jobs SET state = ?, attempts = attempts + 1, last_error = ?,
+            next_run = coalesce(?, next_run) WHERE id = ?",
+        )
+        .bind(state)
+        .bind(error)
+        .bind(retry_at)
+        .bind(id)
+        .execute(&mut *self.conn().await?)
+        .await?;
+        Ok(())
+    }
+
+    /// Runs a job again as soon as possible, with fresh attempts
+    pub async fn retry_job(&mut self, id: i64) -> crate::Result<()> {
+        let res = sqlx::query(
+            "UPDATE jobs SET state = ?, attempts = 0, next_run = unixepoch() WHERE id = ?",
+        )
+        .bind(JobState::Pending)
+        .bind(id)
+        .execute(&mut *self.conn().await?)
+        .await?;
+        if res.rows_affected() == 0 {
+            return Err(DBError::NotFound);
+        }
+        Ok(())
+    }
+
+    pub async fn cancel_job(&mut self, id: i64) -> crate::Result<()> {
+        let res = sqlx::query("DELETE FROM jobs WHERE id = ?")
+            .bind(id)
+            .execute(&mut *self.conn().await?)
+            .await?;
+        if res.rows_affected() == 0 {
+            return Err(DBError::NotFound);
+        }
+        Ok(())
+    }
+
+    /// Puts back the jobs of a worker that stopped while running them
+    pub async fn reset_running_jobs(&mut self) -> crate::Result<()> {
+        sqlx::query("UPDATE jobs SET state = ? WHERE state = ?")
+            .bind(JobState::Pending)
+            .bind(JobState::Running)
+            .execute(&mut *self.conn().await?)
+            .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Image, Item, ItemStatus, Tag, Video};
+    use std::collections::HashSet;
+
+    async fn get_db() -> LocalDb {
+        let pool = open_database(":memory:").await.unwrap();
+        LocalDb::new(pool)
+    }
+
+    #[tokio::test]
+    async fn test_open_in_memory() {
+        let con = open_database(":memory:").await;
+        assert!(con.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_get_items() {
+        let db = get_db().await;
+        let items = db.get_items().await.unwrap();
+        assert!(items.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_add_item() {
+        let mut db = get_db().await;
+
+        let result = db
+            .add(&Item {
+                tags: HashSet::from([Tag::default()]),
+                authors: HashSet::from([Default::default()]),
+                images: HashSet::from([Default::default()]),
+                videos: HashSet::from([Default::default()]),
+                ..Default::default()
+            })
+            .await;
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap(), 1);
+
+        let items = db.get_items().await.unwrap();
+        assert_eq!(items.len(), 1);
+
+        let item = &items[0];
+        assert_eq!(item.id, 1);
+        assert_eq!(item.title, Item::default().title);
+        assert_eq!(item.url, Item::default().url);
+        assert_eq!(item.status, ItemStatus::Unread);
+
+        assert_eq!(item.tags.len(), 1);
+        assert!(item.tags.iter().any(|t| t.tag == Tag::default().tag));
+
+        assert_eq!(item.authors.len(), 1);
+        assert!(item
+            .authors
+            .iter()
+            .any(|a| a.name == Author::default().name));
+
+        assert_eq!(item.images.len(), 1);
+        assert!(item.images.iter().any(|i| i.src == Image::default().src));
+
+        assert_eq!(item.videos.len(), 1);
+        assert!(item.videos.iter().any(|i| i.src == Video::default().src));
+    }
+
+    #[tokio::test]
+    async fn test_add_image() {
+        let mut db = get_db().await;
+        let img = Image::default();
+        let id = db.add_image(&img).await.unwrap();
+        assert_eq!(id, 1);
+        let id = db.add_image(&img).await.unwrap();
+        assert_eq!(id, 1);
+    }
+
+    #[tokio::test]
+    async fn test_get_images() {
+        let mut db = get_db().await;
+        let id = db.add_image(&Default::default()).await.unwrap();
+        assert_eq!(id, 1);
+
+        let img = Image {
+            src: "https://example.com/image2.jpg".to_string(),
+            ..Default::default()
+        };
+        let id = db.add_image(&img).await.unwrap();
+        assert_eq!(id, 2);
+
+        let images = db.get_images().await;
+        assert!(images.is_ok());
+        let images = images.unwrap();
+        assert_eq!(images.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_link_image() {
+        let mut db = get_db().await;
+        let img_id = db.add_image(&Default::default()).await.unwrap();
+        let item_id = db.add(&Default::default()).await.unwrap();
+        let res = db.link_image(img_id, item_id).await;
+        assert!(res.is_ok());
+
+        let items = db.get_items().await.unwrap();
+        assert_eq!(items.len(), 1);
+
+        let item = &items[0];
         assert_eq!(item.images.len(), 1);
 
         let image = item.images.iter().next().unwrap();
@@ -684,4 +1984,588 @@ mod test {
         assert_eq!(tag.id, 1);
         assert_eq!(tag.tag, "example");
     }
+
+    #[tokio::test]
+    async fn test_set_status() {
+        let mut db = get_db().await;
+        let item_id = db.add(&Default::default()).await.unwrap();
+        db.set_status(item_id as i64, ItemStatus::Archived)
+            .await
+            .unwrap();
+
+        let items = db.get_items().await.unwrap();
+        assert_eq!(items[0].status, ItemStatus::Archived);
+        assert!(items[0].time_read.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_set_content() {
+        let mut db = get_db().await;
+        let item_id = db.add(&Default::default()).await.unwrap() as i64;
+        assert!(db.get_content(item_id).await.unwrap().is_none());
+
+        let mut content = Content {
+            item_id,
+            html: "<p>first</p>".to_string(),
+            time_fetched: Some(1),
+        };
+        db.set_content(&content).await.unwrap();
+        content.html = "<p>second</p>".to_string();
+        db.set_content(&content).await.unwrap();
+
+        assert_eq!(db.get_content(item_id).await.unwrap(), Some(content));
+    }
+
+    #[tokio::test]
+    async fn test_assets() {
+        let mut db = get_db().await;
+        let asset = Asset {
+            id: 0,
+            url: "https://example.com/a.png".to_string(),
+            hash: "abc".to_string(),
+            path: "ab/abc".to_string(),
+            mime: "image/png".to_string(),
+            size: 3,
+            time_fetched: 1,
+            time_accessed: 1,
+        };
+        db.add_asset(&asset).await.unwrap();
+        db.add_asset(&Asset {
+            url: "https://example.com/b.png".to_string(),
+            ..asset.clone()
+        })
+        .await
+        .unwrap();
+        db.touch_asset(&asset.url, 5).await.unwrap();
+
+        let stored = db.get_asset(&asset.url).await.unwrap().unwrap();
+        assert_eq!(stored.time_accessed, 5);
+        assert_eq!(db.get_assets().await.unwrap().len(), 2);
+        assert!(db.get_asset_by_hash("abc").await.unwrap().is_some());
+
+        assert_eq!(db.delete_assets("abc").await.unwrap(), 2);
+        assert!(db.get_asset(&asset.url).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_snapshots() {
+        let mut db = get_db().await;
+        let item_id = db.add(&Default::default()).await.unwrap() as i64;
+        let snapshot = Snapshot {
+            id: 0,
+            item_id,
+            url: Item::default().url,
+            status: 200,
+            html_path: "1/1.html".to_string(),
+            warc_path: None,
+            size: 10,
+            time_fetched: 1,
+        };
+        db.add_snapshot(&snapshot).await.unwrap();
+        let id = db
+            .add_snapshot(&Snapshot {
+                time_fetched: 2,
+                ..snapshot.clone()
+            })
+            .await
+            .unwrap();
+
+        let snapshots = db.get_snapshots(item_id).await.unwrap();
+        assert_eq!(snapshots.len(), 2);
+        assert_eq!(snapshots[0].id, id);
+    }
+
+    #[tokio::test]
+    async fn test_set_link_status() {
+        let mut db = get_db().await;
+        let item_id = db.add(&Default::default()).await.unwrap() as i64;
+        db.set_link_status(
+            item_id,
+            LinkStatus::Redirected,
+            Some("https://example.org/"),
+            10,
+        )
+        .await
+        .unwrap();
+        db.set_wayback_url(item_id, "https://web.archive.org/web/2020/")
+            .await
+            .unwrap();
+
+        let item = db.get_items().await.unwrap().remove(0);
+        assert_eq!(item.link_status, Some(LinkStatus::Redirected));
+        assert_eq!(item.link_final_url.as_deref(), Some("https://example.org/"));
+        assert_eq!(item.time_link_checked, Some(10));
+        assert_eq!(
+            item.wayback_url.as_deref(),
+            Some("https://web.archive.org/web/2020/")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_update_item() {
+        let mut db = get_db().await;
+        let id = db.add(&Default::default()).await.unwrap() as i64;
+
+        db.update_item(&Item {
+            id,
+            title: "Updated".to_string(),
+            excerpt: Some("excerpt".to_string()),
+            canonical_url: Some("https://example.com/canonical".to_string()),
+            authors: HashSet::from([Author::default()]),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        let item = db.get_items().await.unwrap().remove(0);
+        assert_eq!(item.title, "Updated");
+        assert_eq!(item.excerpt.as_deref(), Some("excerpt"));
+        assert_eq!(
+            item.canonical_url.as_deref(),
+            Some("https://example.com/canonical")
+        );
+        assert_eq!(item.authors.len(), 1);
+        assert!(item.time_updated.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_remotes() {
+        let mut db = get_db().await;
+        let id = db.add_remote("work", "wallabag", "{}").await.unwrap();
+        assert!(db.add_remote("work", "pocket", "{}").await.is_err());
+
+        db.set_remote_settings(id, r#"{"url":"https://example.com"}"#)
+            .await
+            .unwrap();
+        db.set_remote_cursor(id, "42").await.unwrap();
+        db.set_remote_synced(id, 100).await.unwrap();
+
+        let remote = db.get_remote("work").await.unwrap().unwrap();
+        assert_eq!(remote.backend, "wallabag");
+        assert_eq!(remote.settings, r#"{"url":"https://example.com"}"#);
+        assert_eq!(remote.cursor.as_deref(), Some("42"));
+        assert_eq!(remote.time_synced, Some(100));
+        assert_eq!(db.get_remotes().await.unwrap().len(), 1);
+        assert!(db.get_remote("home").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_remote_items_and_outbox() {
+        let mut db = get_db().await;
+        let remote = db.add_remote("work", "wallabag", "{}").await.unwrap();
+        let item = db.add(&Item::default()).await.unwrap() as i64;
+
+        db.link_remote_item(remote, "abc", item).await.unwrap();
+        let other = db.add_remote("home", "pocket", "{}").await.unwrap();
+        db.add_item_remote(item, other).await.unwrap();
+        db.add_item_remote(item, other).await.unwrap();
+        let accounts = db.get_item_remotes(item).await.unwrap();
+        assert_eq!(accounts.len(), 2);
+        assert_eq!(accounts[0].name, "work");
+        assert_eq!(db.count_remote_items(other).await.unwrap(), 1);
+        assert_eq!(
+            db.find_remote_item(remote, "abc").await.unwrap(),
+            Some(item)
+        );
+        assert_eq!(
+            db.get_remote_item_id(remote, item)
+                .await
+                .unwrap()
+                .as_deref(),
+            Some("abc")
+        );
+        assert_eq!(db.find_remote_item(remote, "def").await.unwrap(), None);
+
+        let first = db.enqueue(remote, item, "archive").await.unwrap();
+        db.enqueue(remote, item, "favorite").await.unwrap();
+        db.fail_outbox(first, "offline").await.unwrap();
+        let outbox = db.get_outbox(remote).await.unwrap();
+        assert_eq!(outbox.len(), 2);
+        assert_eq!(outbox[0].mutation, "archive");
+        assert_eq!(outbox[0].attempts, 1);
+        assert_eq!(outbox[0].last_error.as_deref(), Some("offline"));
+        db.remove_outbox(first).await.unwrap();
+        assert_eq!(db.get_outbox(remote).await.unwrap().len(), 1);
+
+        db.remove_remote(remote).await.unwrap();
+        assert_eq!(db.get_remotes().await.unwrap().len(), 1);
+        assert_eq!(db.get_item_remotes(item).await.unwrap().len(), 1);
+        assert!(db.get_outbox(remote).await.unwrap().is_empty());
+        assert_eq!(db.find_remote_item(remote, "abc").await.unwrap(), None);
+        assert_eq!(db.get_items().await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_set_remote_state() {
+        let mut db = get_db().await;
+        let id = db.add(&Item::default()).await.unwrap() as i64;
+        assert_eq!(
+            db.get_item_id_by_url(&Item::default().url).await.unwrap(),
+            Some(id)
+        );
+
+        db.set_remote_state(&Item {
+            id,
+            status: ItemStatus::Archived,
+            time_updated: Some(5),
+            time_read: Some(5),
+            time_favorited: Some(3),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        let item = &db.get_items().await.unwrap()[0];
+        assert_eq!(item.status, ItemStatus::Archived);
+        assert_eq!(item.time_read, Some(5));
+        assert_eq!(item.time_favorited, Some(3));
+    }
+
+    #[tokio::test]
+    async fn test_unlink_tag() {
+        let mut db = get_db().await;
+        let id = db
+            .add(&Item {
+                tags: HashSet::from([Tag::default()]),
+                ..Default::default()
+            })
+            .await
+            .unwrap() as i64;
+        db.unlink_tag("Example", id).await.unwrap();
+        let item = db.get_item(id).await.unwrap().unwrap();
+        assert!(item.tags.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_set_favorite() {
+        let mut db = get_db().await;
+        let id = db.add(&Item::default()).await.unwrap() as i64;
+        db.set_favorite(id, true).await.unwrap();
+        let item = db.get_item(id).await.unwrap().unwrap();
+        assert!(item.favorite);
+        assert!(item.time_favorited.is_some());
+        db.set_favorite(id, false).await.unwrap();
+        let item = db.get_item(id).await.unwrap().unwrap();
+        assert!(!item.favorite);
+        assert!(item.time_favorited.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_add_upserts_on_url() {
+        let mut db = get_db().await;
+        let item = Item {
+            title: "Saved".to_string(),
+            url: "https://example.com/a".to_string(),
+            ..Default::default()
+        };
+        let id = db.add(&item).await.unwrap();
+
+        let imported = Item {
+            title: String::new(),
+            status: ItemStatus::Archived,
+            ..item
+        };
+        assert_eq!(db.add(&imported).await.unwrap(), id);
+        let item = db.get_item(id as i64).await.unwrap().unwrap();
+        assert_eq!(item.title, "Saved");
+        // saving again does not bring an archived item back either
+        assert_eq!(item.status, ItemStatus::Unread);
+        db.set_status(id as i64, ItemStatus::Archived)
+            .await
+            .unwrap();
+        db.add(&Item {
+            status: ItemStatus::Unread,
+            ..imported
+        })
+        .await
+        .unwrap();
+        let item = db.get_item(id as i64).await.unwrap().unwrap();
+        assert_eq!(item.status, ItemStatus::Archived);
+    }
+
+    #[tokio::test]
+    async fn test_set_title() {
+        let mut db = get_db().await;
+        let id = db.add(&Item::default()).await.unwrap() as i64;
+        db.set_title(id, "Renamed").await.unwrap();
+        let item = db.get_item(id).await.unwrap().unwrap();
+        assert_eq!(item.title, "Renamed");
+        assert!(item.time_updated.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_delete_item() {
+        let mut db = get_db().await;
+        let item = Item {
+            tags: HashSet::from([Tag::default()]),
+            ..Default::default()
+        };
+        let id = db.add(&item).await.unwrap() as i64;
+        let other = db
+            .add(&Item {
+                url: "https://example.com/other".to_string(),
+                ..item
+            })
+            .await
+            .unwrap() as i64;
+        db.set_content(&Content {
+            item_id: id,
+            html: "<p>gone</p>".to_string(),
+            time_fetched: None,
+        })
+        .await
+        .unwrap();
+        db.add_job(JobKind::Extract, id).await.unwrap();
+
+        assert!(db.delete_item(id).await.unwrap());
+        assert!(!db.delete_item(id).await.unwrap());
+        assert!(db.get_item(id).await.unwrap().is_none());
+        assert!(db.get_content(id).await.unwrap().is_none());
+        assert!(db.get_jobs().await.unwrap().is_empty());
+        // the tag stays with the other item
+        let other = db.get_item(other).await.unwrap().unwrap();
+        assert_eq!(other.tags.len(), 1);
+        assert!(crate::check(&db.pool).await.unwrap().is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_highlights() {
+        let mut db = get_db().await;
+        let item = db.add(&Item::default()).await.unwrap() as i64;
+        let id = db
+            .add_highlight(&Highlight {
+                item_id: item,
+                text: "a future is a value".to_string(),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        let mut highlight = db.get_highlight(id).await.unwrap().unwrap();
+        assert!(highlight.time_added > 0);
+        highlight.note = Some("read again".to_string());
+        db.update_highlight(&highlight).await.unwrap();
+        assert_eq!(
+            db.get_highlights(Some(item)).await.unwrap()[0].note,
+            highlight.note
+        );
+        assert!(db.get_highlights(Some(item + 1)).await.unwrap().is_empty());
+
+        db.delete_highlight(id).await.unwrap();
+        assert!(db.get_highlights(None).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_documents_and_progress() {
+        let mut db = get_db().await;
+        let item = db.add(&Item::default()).await.unwrap() as i64;
+        db.add_document("abc", item).await.unwrap();
+        assert_eq!(db.find_document("abc").await.unwrap(), Some(item));
+        assert_eq!(db.find_document("def").await.unwrap(), None);
+
+        let mut progress = Progress {
+            document: "abc".to_string(),
+            progress: "/body/DocFragment[3]".to_string(),
+            percentage: 0.25,
+            device: "Kobo".to_string(),
+            device_id: "1".to_string(),
+            timestamp: 10,
+        };
+        db.set_progress(&progress).await.unwrap();
+        progress.percentage = 0.5;
+        db.set_progress(&progress).await.unwrap();
+        assert_eq!(db.get_progress("abc").await.unwrap(), Some(progress));
+    }
+
+    #[tokio::test]
+    async fn test_jobs() {
+        let mut db = get_db().await;
+        let item = db.add(&Item::default()).await.unwrap() as i64;
+        db.add_job(JobKind::Extract, item).await.unwrap();
+        db.add_job(JobKind::Extract, item).await.unwrap();
+        db.add_job(JobKind::Assets, item).await.unwrap();
+        assert_eq!(db.get_jobs().await.unwrap().len(), 2);
+
+        // later than the default next run of new jobs
+        let now = i64::from(i32::MAX);
+        let job = db.claim_job(now).await.unwrap().unwrap();
+        assert_eq!(job.kind, JobKind::Extract);
+        assert_eq!(job.state, JobState::Running);
+        db.fail_job(job.id, "timeout", Some(now + 60))
+            .await
+            .unwrap();
+
+        // the failed extraction waits for its retry
+        let next = db.claim_job(now).await.unwrap().unwrap();
+        assert_eq!(next.kind, JobKind::Assets);
+        db.complete_job(next.id).await.unwrap();
+        assert!(db.claim_job(now).await.unwrap().is_none());
+        assert_eq!(db.next_job_time().await.unwrap(), Some(now + 60));
+
+        let job = db.claim_job(now + 60).await.unwrap().unwrap();
+        db.fail_job(job.id, "timeout", None).await.unwrap();
+        let failed = &db.get_jobs().await.unwrap()[0];
+        assert_eq!(failed.state, JobState::Failed);
+        assert_eq!(failed.attempts, 2);
+        assert_eq!(failed.last_error.as_deref(), Some("timeout"));
+
+        db.retry_job(job.id).await.unwrap();
+        assert!(db.claim_job(now + 60).await.unwrap().is_some());
+        db.cancel_job(job.id).await.unwrap();
+        assert!(matches!(
+            db.cancel_job(job.id).await,
+            Err(DBError::NotFound)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_history() {
+        let mut db = get_db().await;
+        let item = Item {
+            url: "https://example.com".to_string(),
+            title: "Example".to_string(),
+            ..Default::default()
+        };
+        let id = db.add(&item).await.unwrap() as i64;
+        // saving again without changes records nothing
+        db.add(&item).await.unwrap();
+
+        let mut grouped = db.with_source(Source::NativeHost);
+        grouped.begin_operation();
+        grouped.set_status(id, ItemStatus::Archived).await.unwrap();
+        grouped.set_favorite(id, true).await.unwrap();
+        let tag = grouped
+            .add_tag(&Tag {
+                tag: "rust".to_string(),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        grouped.link_tag(tag, id as i32).await.unwrap();
+        grouped.link_tag(tag, id as i32).await.unwrap();
+
+        let operations = db.get_operations(10).await.unwrap();
+        assert_eq!(operations.len(), 2);
+        let latest = &operations[0];
+        assert_eq!(latest.source, Source::NativeHost);
+        let changes: Vec<_> = latest
+            .changes
+            .iter()
+            // the times depend on the clock
+            .filter(|c| !matches!(c.field, Field::TimeRead | Field::TimeFavorited))
+            .map(|c| (c.field, c.before.as_deref(), c.after.as_deref()))
+            .collect();
+        assert_eq!(
+            changes,
+            vec![
+                (Field::Status, Some("unread"), Some("archived")),
+                (Field::Favorite, Some("false"), Some("true")),
+                (Field::Tag, None, Some("rust")),
+            ]
+        );
+        assert_eq!(latest.changes.len(), 5);
+        assert_eq!(operations[1].source, Source::Cli);
+        assert_eq!(operations[1].changes[0].field, Field::Added);
+
+        let mut undo = db.with_source(Source::Undo);
+        undo.begin_undo(latest.id).await.unwrap();
+        undo.set_status(id, ItemStatus::Unread).await.unwrap();
+
+        let undoable = db.get_undoable(10).await.unwrap();
+        assert_eq!(undoable.len(), 1);
+        assert_eq!(undoable[0].id, operations[1].id);
+        let operations = db.get_operations(10).await.unwrap();
+        assert_eq!(operations[0].undo_of, Some(latest.id));
+        assert!(operations[1].undone);
+
+        // the history outlives the item
+        db.delete_item(id).await.unwrap();
+        let purged = &db.get_operations(1).await.unwrap()[0];
+        assert_eq!(purged.changes[0].field, Field::Purged);
+        assert_eq!(
+            purged.changes[0].before.as_deref(),
+            Some("https://example.com")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_revert() {
+        let mut db = get_db().await;
+        let id = db.add(&Item::default()).await.unwrap() as i64;
+        db.begin_operation();
+        db.update_item(&Item {
+            id,
+            excerpt: Some("Summary".to_string()),
+            word_count: Some(120),
+            is_article: Some(true),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        db.set_content(&Content {
+            item_id: id,
+            html: "<p>text</p>".to_string(),
+            time_fetched: None,
+        })
+        .await
+        .unwrap();
+        let highlight = db
+            .add_highlight(&Highlight {
+                item_id: id,
+                text: "text".to_string(),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        db.delete_highlight(highlight).await.unwrap();
+
+        let operation = db.get_undoable(1).await.unwrap().remove(0);
+        let fields: Vec<_> = operation.changes.iter().map(|c| c.field).collect();
+        assert_eq!(
+            fields,
+            vec![
+                Field::Excerpt,
+                Field::IsArticle,
+                Field::WordCount,
+                Field::Content,
+                Field::Highlight,
+                Field::Highlight
+            ]
+        );
+
+        let mut undo = db.with_source(Source::Undo);
+        undo.begin_undo(operation.id).await.unwrap();
+        for change in operation.changes.iter().rev() {
+            undo.revert(change).await.unwrap();
+        }
+        let item = db.get_item(id).await.unwrap().unwrap();
+        assert_eq!(item.excerpt, None);
+        assert_eq!(item.word_count, None);
+        assert_eq!(item.is_article, None);
+        assert!(db.get_content(id).await.unwrap().is_none());
+        assert!(db.get_highlights(Some(id)).await.unwrap().is_empty());
+
+        // the highlight came back in between, under its id
+        let undo = &db.get_operations(1).await.unwrap()[0];
+        let restored: Highlight =
+            serde_json::from_str(undo.changes[0].after.as_deref().unwrap()).unwrap();
+        assert_eq!(restored.id, highlight);
+    }
+
+    #[tokio::test]
+    async fn test_transaction_rolls_back() {
+        let db = get_db().await;
+        let mut tx = db.transaction().await.unwrap();
+        let id = tx.add(&Item::default()).await.unwrap() as i64;
+        tx.set_status(id, ItemStatus::Archived).await.unwrap();
+        assert!(tx.get_item(id).await.unwrap().is_some());
+        drop(tx);
+        assert!(db.get_items().await.unwrap().is_empty());
+        assert!(db.get_operations(10).await.unwrap().is_empty());
+
+        let mut tx = db.transaction().await.unwrap();
+        tx.add(&Item::default()).await.unwrap();
+        tx.commit().await.unwrap();
+        assert_eq!(db.get_items().await.unwrap().len(), 1);
+    }
 }
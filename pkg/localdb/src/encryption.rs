@@ -0,0 +1,147 @@
+//! Encryption at rest with SQLCipher. Plain databases open as before, the
+//! key pragma is only set for encrypted ones.
+
+use crate::DBError;
+use sqlx::sqlite::SqliteConnectOptions;
+use sqlx::{ConnectOptions, Connection};
+use std::io::Read;
+use std::path::Path;
+use std::str::FromStr;
+
+/// First bytes of every plain SQLite file
+const HEADER: &[u8; 16] = b"SQLite format 3\0";
+
+/// Whether the database at `path` is encrypted. Missing and empty files are
+/// not, they become plain databases when opened.
+pub fn is_encrypted(path: &Path) -> crate::Result<bool> {
+    let mut header = [0; 16];
+    let mut file = match std::fs::File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+        Err(e) => return Err(e.into()),
+    };
+    let read = file.read(&mut header)?;
+    Ok(read > 0 && &header != HEADER)
+}
+
+/// Options opening `path`, with `key` when the database is encrypted
+pub(crate) fn options(path: &str, key: Option<&str>) -> crate::Result<SqliteConnectOptions> {
+    let mut options = SqliteConnectOptions::from_str(path)?.create_if_missing(true);
+    if let Some(key) = key {
+        options = options.pragma("key", quote(key));
+    }
+    Ok(options)
+}
+
+pub(crate) fn quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "''"))
+}
+
+/// Checks that the key opened the database, a wrong one makes every page
+/// unreadable
+pub(crate) async fn check_key(options: &SqliteConnectOptions) -> crate::Result<()> {
+    let mut conn = options.connect().await?;
+    let result = sqlx::query("SELECT count(*) FROM sqlite_master")
+        .execute(&mut conn)
+        .await;
+    conn.close().await?;
+    match result {
+        Ok(_) => Ok(()),
+        Err(sqlx::Error::Database(e)) if e.message().contains("not a database") => {
+            Err(DBError::WrongKey)
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Copies the database at `path`, opened with `key`, to `dest` encrypted with
+/// `new_key`, or in plain text without one. Encrypting, decrypting and
+/// changing the key all go through this copy, the original stays untouched
+/// until the caller replaces it.
+pub async fn export_database(
+    path: &Path,
+    key: Option<&str>,
+    dest: &Path,
+    new_key: Option<&str>,
+) -> crate::Result<()> {
+    let path = path.to_str().ok_or(DBError::ParseError)?;
+    let dest = dest.to_str().ok_or(DBError::ParseError)?;
+    match std::fs::remove_file(dest) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+        _ => {}
+    }
+
+    if !Path::new(path).exists() {
+        return Err(DBError::NotFound);
+    }
+    // attached databases are opened with the flags of the main one
+    let options = options(path, key)?;
+    check_key(&options).await?;
+    let mut conn = options.connect().await?;
+    let attach = format!(
+        "ATTACH DATABASE {} AS export KEY {}",
+        quote(dest),
+        quote(new_key.unwrap_or_default())
+    );
+    sqlx::query(&attach).execute(&mut conn).await?;
+    sqlx::query("SELECT sqlcipher_export('export')")
+        .execute(&mut conn)
+        .await?;
+    sqlx::query("DETACH DATABASE export")
+        .execute(&mut conn)
+        .await?;
+    conn.close().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{open_database, open_database_with_key, Item, LocalDb};
+
+    #[tokio::test]
+    async fn test_encrypt_and_decrypt() {
+        let dir = tempfile::tempdir().unwrap();
+        let plain = dir.path().join("plain.sqlite");
+        let encrypted = dir.path().join("encrypted.sqlite");
+        let path = plain.to_str().unwrap();
+
+        let pool = open_database(path).await.unwrap();
+        LocalDb::new(pool.clone())
+            .add(&Item {
+                title: "secret reading".to_string(),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        pool.close().await;
+        assert!(!is_encrypted(&plain).unwrap());
+
+        export_database(&plain, None, &encrypted, Some("k'ey"))
+            .await
+            .unwrap();
+        assert!(is_encrypted(&encrypted).unwrap());
+        let bytes = std::fs::read(&encrypted).unwrap();
+        assert!(!bytes.windows(14).any(|w| w == b"secret reading"));
+
+        let encrypted = encrypted.to_str().unwrap();
+        assert!(matches!(
+            open_database_with_key(encrypted, Some("wrong")).await,
+            Err(DBError::WrongKey)
+        ));
+        let pool = open_database_with_key(encrypted, Some("k'ey"))
+            .await
+            .unwrap();
+        let items = LocalDb::new(pool.clone()).get_items().await.unwrap();
+        assert_eq!(items[0].title, "secret reading");
+        pool.close().await;
+
+        let decrypted = dir.path().join("decrypted.sqlite");
+        export_database(Path::new(encrypted), Some("k'ey"), &decrypted, None)
+            .await
+            .unwrap();
+        assert!(!is_encrypted(&decrypted).unwrap());
+        let pool = open_database(decrypted.to_str().unwrap()).await.unwrap();
+        assert_eq!(LocalDb::new(pool).get_items().await.unwrap().len(), 1);
+    }
+}
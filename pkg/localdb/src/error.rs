@@ -11,6 +11,15 @@ pub enum DBError {
 
     #[error("parse error")]
     ParseError,
+
+    #[error("not found")]
+    NotFound,
+
+    #[error("wrong key for the encrypted database")]
+    WrongKey,
+
+    #[error("backup error: {0}")]
+    Backup(String),
 }
 
 pub type Result<T> = std::result::Result<T, DBError>;
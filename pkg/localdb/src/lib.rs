@@ -1,13 +1,23 @@
 mod db;
+mod encryption;
 mod error;
 mod kv;
 mod kv_config;
+mod maintenance;
 mod model;
+mod query;
 
-pub use db::open_database;
 pub use db::LocalDb;
+pub use db::{connect_database, open_database, open_database_with_key};
+pub use encryption::{export_database, is_encrypted};
 pub use error::{DBError, Result};
 pub use kv::KeyValue;
 pub use kv::KvDB;
 pub use kv_config::KvConfig;
+pub use maintenance::{
+    backup, backup_dir, backup_path, check, migration_status, remove_orphans, restore, stats,
+    vacuum, CheckReport, DbStats, MigrationState, MigrationStatus, Orphans, TableStats,
+    KEEP_BACKUPS,
+};
 pub use model::*;
+pub use query::{ItemQuery, SortBy};
@@ -0,0 +1,601 @@
+//! Upkeep of the library file: online backups, integrity checks, table sizes
+//! and the state of the migrations
+
+use crate::encryption::quote;
+use crate::DBError;
+use libsqlite3_sys as ffi;
+use sqlx::migrate::Migrator;
+use sqlx::{Row, SqlitePool};
+use std::collections::HashMap;
+use std::ffi::{c_int, CStr, CString};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Automatic backups taken before migrating that are kept, the older ones
+/// are removed
+pub const KEEP_BACKUPS: usize = 3;
+/// Pages copied at a time, writers can get in between
+const PAGES_PER_STEP: c_int = 1024;
+
+/// Link tables and the tables their columns point to
+const LINKS: &[(&str, &str, &str)] = &[
+    ("items_tags", "item_id", "items"),
+    ("items_tags", "tag_id", "tags"),
+    ("items_authors", "item_id", "items"),
+    ("items_authors", "author_id", "authors"),
+    ("items_images", "item_id", "items"),
+    ("items_images", "image_id", "images"),
+    ("items_videos", "item_id", "items"),
+    ("items_videos", "video_id", "videos"),
+];
+
+/// Connection of the C API, for the backup API that sqlx does not expose
+struct Handle(*mut ffi::sqlite3);
+
+impl Handle {
+    fn open(path: &Path, flags: c_int, key: Option<&str>) -> crate::Result<Handle> {
+        let path = path.to_str().ok_or(DBError::ParseError)?;
+        let path = CString::new(path).map_err(|_| DBError::ParseError)?;
+        let mut db = std::ptr::null_mut();
+        let rc = unsafe { ffi::sqlite3_open_v2(path.as_ptr(), &mut db, flags, std::ptr::null()) };
+        // sqlite allocates the connection even when opening fails
+        let handle = Handle(db);
+        handle.check(rc)?;
+        if let Some(key) = key {
+            handle.exec(&format!("PRAGMA key = {}", quote(key)))?;
+        }
+        match handle.exec("SELECT count(*) FROM sqlite_master") {
+            Err(_) if key.is_some() => Err(DBError::WrongKey),
+            result => result.map(|_| handle),
+        }
+    }
+
+    fn exec(&self, sql: &str) -> crate::Result<()> {
+        let sql = CString::new(sql).map_err(|_| DBError::ParseError)?;
+        let rc = unsafe {
+            ffi::sqlite3_exec(
+                self.0,
+                sql.as_ptr(),
+                None,
+                std::ptr::null_mut(),
+                std::ptr::null_mut(),
+            )
+        };
+        self.check(rc)
+    }
+
+    fn check(&self, rc: c_int) -> crate::Result<()> {
+        if rc == ffi::SQLITE_OK {
+            return Ok(());
+        }
+        let message = unsafe { CStr::from_ptr(ffi::sqlite3_errmsg(self.0)) };
+        Err(DBError::Backup(message.to_string_lossy().into_owned()))
+    }
+
+    /// Copies the main database of `source` over the one of `self`
+    fn copy_from(&self, source: &Handle) -> crate::Result<()> {
+        let main = c"main";
+        let backup =
+            unsafe { ffi::sqlite3_backup_init(self.0, main.as_ptr(), source.0, main.as_ptr()) };
+        if backup.is_null() {
+            return self.check(unsafe { ffi::sqlite3_errcode(self.0) });
+        }
+        loop {
+            match unsafe { ffi::sqlite3_backup_step(backup, PAGES_PER_STEP) } {
+                ffi::SQLITE_DONE => break,
+                ffi::SQLITE_OK => {}
+                ffi::SQLITE_BUSY | ffi::SQLITE_LOCKED => {
+                    std::thread::sleep(Duration::from_millis(50))
+                }
+                // finish reports the error
+                _ => break,
+            }
+        }
+        self.check(unsafe { ffi::sqlite3_backup_finish(backup) })
+    }
+}
+
+impl Drop for Handle {
+    fn drop(&mut self) {
+        unsafe { ffi::sqlite3_close(self.0) };
+    }
+}
+
+/// Copies the database at `path`, opened with `key`, to `dest` while it stays
+/// in use. The copy is encrypted with the same key.
+pub async fn backup(path: &Path, key: Option<&str>, dest: &Path) -> crate::Result<()> {
+    if !path.exists() {
+        return Err(DBError::NotFound);
+    }
+    let (path, key, dest) = (path.to_owned(), key.map(str::to_owned), dest.to_owned());
+    tokio::task::spawn_blocking(move || {
+        let source = Handle::open(&path, ffi::SQLITE_OPEN_READONLY, key.as_deref())?;
+        let flags = ffi::SQLITE_OPEN_READWRITE | ffi::SQLITE_OPEN_CREATE;
+        Handle::open(&dest, flags, key.as_deref())?.copy_from(&source)
+    })
+    .await
+    .map_err(|e| DBError::Backup(e.to_string()))?
+}
+
+/// Replaces the database at `path` with the backup at `backup`, after
+/// checking it. Open connections see the restored content.
+pub async fn restore(path: &Path, key: Option<&str>, backup: &Path) -> crate::Result<()> {
+    if !backup.exists() {
+        return Err(DBError::NotFound);
+    }
+    let (path, key, backup) = (path.to_owned(), key.map(str::to_owned), backup.to_owned());
+    tokio::task::spawn_blocking(move || {
+        let source = Handle::open(&backup, ffi::SQLITE_OPEN_READONLY, key.as_deref())?;
+        source.exec("PRAGMA quick_check")?;
+        let flags = ffi::SQLITE_OPEN_READWRITE | ffi::SQLITE_OPEN_CREATE;
+        Handle::open(&path, flags, key.as_deref())?.copy_from(&source)
+    })
+    .await
+    .map_err(|e| DBError::Backup(e.to_string()))?
+}
+
+/// Directory of the backups of the database at `path`
+pub fn backup_dir(path: &Path) -> PathBuf {
+    path.with_file_name("backups")
+}
+
+/// Path of a new backup of the database at `path`, named after the time and
+/// `label`
+pub fn backup_path(path: &Path, label: Option<&str>) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let name = match label {
+        Some(label) => format!("{}-{}-{}.sqlite", stem, now, label),
+        None => format!("{}-{}.sqlite", stem, now),
+    };
+    backup_dir(path).join(name)
+}
+
+/// Backs up the database at `path` before `migrator` changes it, and removes
+/// the oldest of these backups. Returns the new backup, if one was needed.
+pub(crate) async fn backup_before_migrating(
+    pool: &SqlitePool,
+    path: &str,
+    key: Option<&str>,
+    migrator: &Migrator,
+) -> crate::Result<Option<PathBuf>> {
+    let Some(version) = first_pending(pool, migrator).await? else {
+        return Ok(None);
+    };
+    let path = Path::new(path);
+    if !path.is_file() {
+        return Ok(None);
+    }
+    let label = format!("pre-{}", version);
+    let dest = backup_path(path, Some(&label));
+    std::fs::create_dir_all(backup_dir(path))?;
+    backup(path, key, &dest).await?;
+    rotate(path)?;
+    Ok(Some(dest))
+}
+
+/// Removes all but the last `KEEP_BACKUPS` automatic backups
+fn rotate(path: &Path) -> crate::Result<()> {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let mut backups = vec![];
+    for entry in std::fs::read_dir(backup_dir(path))? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if name.starts_with(&format!("{}-", stem)) && name.contains("-pre-") {
+            backups.push((entry.metadata()?.modified()?, entry.path()));
+        }
+    }
+    backups.sort();
+    let old = backups.len().saturating_sub(KEEP_BACKUPS);
+    for (_, backup) in &backups[..old] {
+        std::fs::remove_file(backup)?;
+    }
+    Ok(())
+}
+
+/// Version of the first migration not applied yet, none for a new database
+async fn first_pending(pool: &SqlitePool, migrator: &Migrator) -> crate::Result<Option<i64>> {
+    if !migrated(pool).await? {
+        return Ok(None);
+    }
+    let applied: Vec<i64> =
+        sqlx::query_scalar("SELECT version FROM _sqlx_migrations WHERE success")
+            .fetch_all(pool)
+            .await?;
+    Ok(migrator
+        .iter()
+        .map(|m| m.version)
+        .find(|version| !applied.contains(version)))
+}
+
+/// Whether migrations were ever applied to the database
+async fn migrated(pool: &SqlitePool) -> crate::Result<bool> {
+    Ok(sqlx::query_scalar(
+        "SELECT count(*) > 0 FROM sqlite_master WHERE type = 'table' AND name = '_sqlx_migrations'",
+    )
+    .fetch_one(pool)
+    .await?)
+}
+
+/// Rows pointing to a row that does not exist
+#[derive(Debug, PartialEq)]
+pub struct Orphans {
+    pub table: String,
+    /// Table the rows point to
+    pub parent: String,
+    pub count: i64,
+}
+
+#[derive(Debug, Default)]
+pub struct CheckReport {
+    /// Problems found by `PRAGMA integrity_check`
+    pub problems: Vec<String>,
+    pub orphans: Vec<Orphans>,
+}
+
+impl CheckReport {
+    pub fn is_ok(&self) -> bool {
+        self.problems.is_empty() && self.orphans.is_empty()
+    }
+}
+
+/// Checks the structure of the database and looks for rows pointing to
+/// deleted ones
+pub async fn check(pool: &SqlitePool) -> crate::Result<CheckReport> {
+    let mut report = CheckReport::default();
+    let results: Vec<String> = sqlx::query_scalar("PRAGMA integrity_check")
+        .fetch_all(pool)
+        .await?;
+    report.problems = results.into_iter().filter(|r| r != "ok").collect();
+
+    // the columns of the link tables accept nulls, which the foreign key
+    // check skips
+    for (table, column, parent) in LINKS {
+        let count: i64 = sqlx::query_scalar(&format!(
+            "SELECT count(*) FROM {table} WHERE {column} IS NULL OR {column} NOT IN (SELECT id FROM {parent})"
+        ))
+        .fetch_one(pool)
+        .await?;
+        if count > 0 {
+            report.orphans.push(Orphans {
+                table: table.to_string(),
+                parent: parent.to_string(),
+                count,
+            });
+        }
+    }
+    let rows = sqlx::query(
+        r#"SELECT "table", parent, count(*) FROM pragma_foreign_key_check GROUP BY 1, 2"#,
+    )
+    .fetch_all(pool)
+    .await?;
+    for row in rows {
+        let table: String = row.get(0);
+        if LINKS.iter().any(|(link, _, _)| *link == table) {
+            continue;
+        }
+        report.orphans.push(Orphans {
+            table,
+            parent: row.get(1),
+            count: row.get(2),
+        });
+    }
+    Ok(report)
+}
+
+/// Deletes the rows of the link tables pointing to deleted ones, returns how
+/// many there were
+pub async fn remove_orphans(pool: &SqlitePool) -> crate::Result<u64> {
+    let mut tx = pool.begin().await?;
+    let mut removed = 0;
+    for (table, column, parent) in LINKS {
+        removed += sqlx::query(&format!(
+            "DELETE FROM {table} WHERE {column} IS NULL OR {column} NOT IN (SELECT id FROM {parent})"
+        ))
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+    }
+    tx.commit().await?;
+    Ok(removed)
+}
+
+/// Size of a table, its indexes included
+#[derive(Debug)]
+pub struct TableStats {
+    pub name: String,
+    pub rows: i64,
+    pub bytes: i64,
+}
+
+#[derive(Debug)]
+pub struct DbStats {
+    pub page_size: i64,
+    pub pages: i64,
+    /// Pages left empty by deletes, `vacuum` gives them back
+    pub free_pages: i64,
+    /// Largest first
+    pub tables: Vec<TableStats>,
+}
+
+impl DbStats {
+    pub fn bytes(&self) -> i64 {
+        self.page_size * self.pages
+    }
+}
+
+pub async fn stats(pool: &SqlitePool) -> crate::Result<DbStats> {
+    let page_size = pragma(pool, "page_size").await?;
+    let pages = pragma(pool, "page_count").await?;
+    let free_pages = pragma(pool, "freelist_count").await?;
+
+    let sizes: HashMap<String, i64> = sqlx::query_as(
+        "SELECT m.tbl_name, sum(s.pgsize) FROM dbstat s JOIN sqlite_master m ON m.name = s.name GROUP BY m.tbl_name",
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .collect();
+    let names: Vec<String> = sqlx::query_scalar(
+        "SELECT name FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%'",
+    )
+    .fetch_all(pool)
+    .await?;
+    let mut tables = vec![];
+    for name in names {
+        let rows = sqlx::query_scalar(&format!("SELECT count(*) FROM [{}]", name))
+            .fetch_one(pool)
+            .await?;
+        let bytes = sizes.get(&name).copied().unwrap_or_default();
+        tables.push(TableStats { name, rows, bytes });
+    }
+    tables.sort_by(|a, b| b.bytes.cmp(&a.bytes).then(a.name.cmp(&b.name)));
+
+    Ok(DbStats {
+        page_size,
+        pages,
+        free_pages,
+        tables,
+    })
+}
+
+/// Value of a pragma returning a number. SQLCipher answers some of them
+/// with text.
+async fn pragma(pool: &SqlitePool, name: &str) -> crate::Result<i64> {
+    let row = sqlx::query(&format!("PRAGMA {}", name))
+        .fetch_one(pool)
+        .await?;
+    match row.try_get(0) {
+        Ok(value) => Ok(value),
+        Err(_) => row
+            .get::<String, _>(0)
+            .parse()
+            .map_err(|_| DBError::ParseError),
+    }
+}
+
+/// Rebuilds the database to give back the space of deleted rows. Returns the
+/// size before and after, in bytes.
+pub async fn vacuum(pool: &SqlitePool) -> crate::Result<(i64, i64)> {
+    let before = stats(pool).await?.bytes();
+    sqlx::query("VACUUM").execute(pool).await?;
+    sqlx::query("PRAGMA optimize").execute(pool).await?;
+    let after = stats(pool).await?.bytes();
+    Ok((before, after))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MigrationState {
+    Applied,
+    Pending,
+    /// Applied, but the file changed since
+    Modified,
+    /// Applied by a newer version of readlater
+    Unknown,
+}
+
+#[derive(Debug)]
+pub struct MigrationStatus {
+    pub version: i64,
+    pub description: String,
+    pub state: MigrationState,
+    pub installed_on: Option<String>,
+}
+
+/// State of each migration, known or found in the database
+pub async fn migration_status(pool: &SqlitePool) -> crate::Result<Vec<MigrationStatus>> {
+    let rows = match migrated(pool).await? {
+        false => vec![],
+        true => sqlx::query(
+        "SELECT version, description, checksum, installed_on FROM _sqlx_migrations WHERE success ORDER BY version",
+    )
+        .fetch_all(pool)
+        .await?,
+    };
+    let mut applied: HashMap<i64, (String, Vec<u8>, String)> = rows
+        .into_iter()
+        .map(|row| (row.get(0), (row.get(1), row.get(2), row.get(3))))
+        .collect();
+
+    let mut status = vec![];
+    for migration in crate::db::MIGRATOR.iter() {
+        let (state, installed_on) = match applied.remove(&migration.version) {
+            Some((_, checksum, installed_on)) if checksum == *migration.checksum => {
+                (MigrationState::Applied, Some(installed_on))
+            }
+            Some((_, _, installed_on)) => (MigrationState::Modified, Some(installed_on)),
+            None => (MigrationState::Pending, None),
+        };
+        status.push(MigrationStatus {
+            version: migration.version,
+            description: migration.description.to_string(),
+            state,
+            installed_on,
+        });
+    }
+    for (version, (description, _, installed_on)) in applied {
+        status.push(MigrationStatus {
+            version,
+            description,
+            state: MigrationState::Unknown,
+            installed_on: Some(installed_on),
+        });
+    }
+    status.sort_by_key(|s| s.version);
+    Ok(status)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{open_database, open_database_with_key, Item, LocalDb, Tag};
+
+    async fn library(path: &Path) -> SqlitePool {
+        let pool = open_database(path.to_str().unwrap()).await.unwrap();
+        let mut db = LocalDb::new(pool.clone());
+        db.add(&Item {
+            title: "kept".to_string(),
+            url: "https://example.com/kept".to_string(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        pool
+    }
+
+    #[tokio::test]
+    async fn test_backup_and_restore() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("readlater.sqlite");
+        let pool = library(&path).await;
+
+        // while the library is open
+        let dest = backup_path(&path, None);
+        std::fs::create_dir_all(backup_dir(&path)).unwrap();
+        backup(&path, None, &dest).await.unwrap();
+        let mut db = LocalDb::new(pool.clone());
+        db.add(&Item {
+            title: "lost".to_string(),
+            url: "https://example.com/lost".to_string(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        restore(&path, None, &dest).await.unwrap();
+        let items = db.get_items().await.unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].title, "kept");
+
+        let missing = dir.path().join("missing.sqlite");
+        assert!(matches!(
+            restore(&path, None, &missing).await,
+            Err(DBError::NotFound)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_encrypted_backup() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("readlater.sqlite");
+        let pool = library(&path).await;
+        pool.close().await;
+        let encrypted = dir.path().join("encrypted.sqlite");
+        crate::export_database(&path, None, &encrypted, Some("key"))
+            .await
+            .unwrap();
+
+        let dest = dir.path().join("backup.sqlite");
+        assert!(matches!(
+            backup(&encrypted, Some("wrong"), &dest).await,
+            Err(DBError::WrongKey)
+        ));
+        backup(&encrypted, Some("key"), &dest).await.unwrap();
+        assert!(crate::is_encrypted(&dest).unwrap());
+        let pool = open_database_with_key(dest.to_str().unwrap(), Some("key"))
+            .await
+            .unwrap();
+        assert!(stats(&pool).await.unwrap().bytes() > 0);
+        assert_eq!(LocalDb::new(pool).get_items().await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_backups_before_migrating() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("readlater.sqlite");
+        let pool = library(&path).await;
+        assert!(!backup_dir(&path).exists());
+        let status = migration_status(&pool).await.unwrap();
+        assert!(status.iter().all(|s| s.state == MigrationState::Applied));
+        let migrator = &crate::db::MIGRATOR;
+        let path = path.to_str().unwrap();
+        assert!(backup_before_migrating(&pool, path, None, migrator)
+            .await
+            .unwrap()
+            .is_none());
+
+        for _ in 0..KEEP_BACKUPS + 1 {
+            // as if the last migration was new
+            sqlx::query(
+                "DELETE FROM _sqlx_migrations WHERE version = (SELECT max(version) FROM _sqlx_migrations)",
+            )
+            .execute(&pool)
+            .await
+            .unwrap();
+            let backup = backup_before_migrating(&pool, path, None, migrator)
+                .await
+                .unwrap();
+            assert!(backup.unwrap().exists());
+        }
+        let backups = std::fs::read_dir(backup_dir(Path::new(path))).unwrap();
+        assert_eq!(backups.count(), KEEP_BACKUPS);
+        let status = migration_status(&pool).await.unwrap();
+        let pending = status.iter().filter(|s| s.state == MigrationState::Pending);
+        assert_eq!(pending.count(), KEEP_BACKUPS + 1);
+    }
+
+    #[tokio::test]
+    async fn test_check_and_stats() {
+        let pool = open_database(":memory:").await.unwrap();
+        let mut db = LocalDb::new(pool.clone());
+        db.add(&Item {
+            title: "tagged".to_string(),
+            tags: [Tag::default()].into(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        assert!(check(&pool).await.unwrap().is_ok());
+
+        // left behind by an older version
+        let mut conn = pool.acquire().await.unwrap();
+        sqlx::query("PRAGMA foreign_keys = OFF")
+            .execute(&mut *conn)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO items_tags (item_id, tag_id) VALUES (42, 1)")
+            .execute(&mut *conn)
+            .await
+            .unwrap();
+        drop(conn);
+        let report = check(&pool).await.unwrap();
+        assert_eq!(
+            report.orphans,
+            vec![Orphans {
+                table: "items_tags".to_string(),
+                parent: "items".to_string(),
+                count: 1
+            }]
+        );
+        assert_eq!(remove_orphans(&pool).await.unwrap(), 1);
+        assert!(check(&pool).await.unwrap().is_ok());
+
+        let stats = stats(&pool).await.unwrap();
+        let items = stats.tables.iter().find(|t| t.name == "items").unwrap();
+        assert_eq!(items.rows, 1);
+        assert!(items.bytes > 0);
+    }
+}
@@ -0,0 +1,17 @@
+use serde::{Deserialize, Serialize};
+
+/// A remote file, such as an image, downloaded into the local asset store.
+/// Assets are content addressed, several urls may share the same file.
+#[derive(Deserialize, Serialize, Debug, sqlx::FromRow, Clone, PartialEq, Eq)]
+pub struct Asset {
+    pub id: i32,
+    pub url: String,
+    /// sha256 of the content
+    pub hash: String,
+    /// Path relative to the asset store
+    pub path: String,
+    pub mime: String,
+    pub size: i64,
+    pub time_fetched: i32,
+    pub time_accessed: i32,
+}
@@ -7,16 +7,6 @@ pub struct Author {
     pub url: Option<String>,
 }
 
-impl From<pocket::item::Authors> for Author {
-    fn from(author: pocket::item::Authors) -> Self {
-        Self {
-            id: author.author_id,
-            name: author.name,
-            url: author.url,
-        }
-    }
-}
-
 impl Default for Author {
     fn default() -> Self {
         Author {
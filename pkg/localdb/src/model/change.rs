@@ -0,0 +1,126 @@
+use serde::{Deserialize, Serialize};
+
+/// Where a change to the library came from. Rules have no source of their
+/// own, readlater has no rules engine to apply them yet.
+#[derive(Deserialize, Serialize, Debug, Clone, sqlx::Type, Copy, PartialEq, Eq, Default)]
+#[repr(i32)]
+pub enum Source {
+    #[default]
+    Cli = 0,
+    NativeHost = 1,
+    /// The API and the web reader
+    Server = 2,
+    Tui = 3,
+    /// Pulled from a remote
+    Sync = 4,
+    /// Background jobs, such as filling in metadata
+    Job = 5,
+    /// Reverting an earlier operation
+    Undo = 6,
+}
+
+impl Source {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Source::Cli => "cli",
+            Source::NativeHost => "native host",
+            Source::Server => "server",
+            Source::Tui => "tui",
+            Source::Sync => "sync",
+            Source::Job => "job",
+            Source::Undo => "undo",
+        }
+    }
+}
+
+/// What a change did to an item
+#[derive(Deserialize, Serialize, Debug, Clone, sqlx::Type, Copy, PartialEq, Eq)]
+#[repr(i32)]
+pub enum Field {
+    /// The item was saved, `after` is its url
+    Added = 0,
+    /// `before` and `after` are the names of the statuses
+    Status = 1,
+    /// `before` and `after` are "true" or "false"
+    Favorite = 2,
+    Title = 3,
+    /// A tag was added when `after` is set, removed when `before` is
+    Tag = 4,
+    /// The item was removed from the library, `before` is its url
+    Purged = 5,
+    Excerpt = 6,
+    CanonicalUrl = 7,
+    IsArticle = 8,
+    IsIndex = 9,
+    HasVideo = 10,
+    HasImage = 11,
+    WordCount = 12,
+    Lang = 13,
+    TimeToRead = 14,
+    TopImageUrl = 15,
+    TimeRead = 16,
+    TimeFavorited = 17,
+    LinkStatus = 18,
+    LinkFinalUrl = 19,
+    WaybackUrl = 20,
+    /// `before` and `after` are the extracted html
+    Content = 21,
+    /// `before` and `after` are the highlight as json, one of them missing
+    /// when it was added or removed
+    Highlight = 22,
+}
+
+impl Field {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Field::Added => "added",
+            Field::Status => "status",
+            Field::Favorite => "favorite",
+            Field::Title => "title",
+            Field::Tag => "tag",
+            Field::Purged => "purged",
+            Field::Excerpt => "excerpt",
+            Field::CanonicalUrl => "canonical url",
+            Field::IsArticle => "is article",
+            Field::IsIndex => "is index",
+            Field::HasVideo => "has video",
+            Field::HasImage => "has image",
+            Field::WordCount => "word count",
+            Field::Lang => "language",
+            Field::TimeToRead => "time to read",
+            Field::TopImageUrl => "top image",
+            Field::TimeRead => "time read",
+            Field::TimeFavorited => "time favorited",
+            Field::LinkStatus => "link status",
+            Field::LinkFinalUrl => "final url",
+            Field::WaybackUrl => "wayback url",
+            Field::Content => "content",
+            Field::Highlight => "highlight",
+        }
+    }
+}
+
+/// One value of an item, before and after a change
+#[derive(Deserialize, Serialize, Debug, sqlx::FromRow, Clone, PartialEq, Eq)]
+pub struct Change {
+    pub id: i64,
+    pub operation_id: i64,
+    pub item_id: i64,
+    pub field: Field,
+    pub before: Option<String>,
+    pub after: Option<String>,
+}
+
+/// Changes made at once, such as archiving several items, and undone
+/// together
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Operation {
+    pub id: i64,
+    pub source: Source,
+    pub time: i64,
+    /// Operation this one reverted
+    pub undo_of: Option<i64>,
+    /// Reverted by a later operation
+    pub undone: bool,
+    pub changes: Vec<Change>,
+}
@@ -0,0 +1,9 @@
+use serde::{Deserialize, Serialize};
+
+/// Extracted article body of an item, stored as html.
+#[derive(Deserialize, Serialize, Debug, sqlx::FromRow, Clone, PartialEq, Eq)]
+pub struct Content {
+    pub item_id: i64,
+    pub html: String,
+    pub time_fetched: Option<i32>,
+}
@@ -0,0 +1,15 @@
+use serde::{Deserialize, Serialize};
+
+/// Passage of an item marked by the user
+#[derive(Deserialize, Serialize, Debug, sqlx::FromRow, Clone, PartialEq, Eq, Default)]
+pub struct Highlight {
+    pub id: i64,
+    pub item_id: i64,
+    pub text: String,
+    pub note: Option<String>,
+    pub color: Option<String>,
+    /// Where the passage is in the content, opaque to readlater
+    pub position: Option<String>,
+    pub time_added: i64,
+    pub time_updated: Option<i64>,
+}
@@ -10,19 +10,6 @@ pub struct Image {
     pub credit: Option<String>,
 }
 
-impl From<pocket::item::Images> for Image {
-    fn from(image: pocket::item::Images) -> Self {
-        Image {
-            id: image.image_id,
-            src: image.src,
-            width: image.width,
-            height: image.height,
-            caption: Some(image.caption),
-            credit: Some(image.credit),
-        }
-    }
-}
-
 impl Default for Image {
     fn default() -> Self {
         Image {
@@ -5,14 +5,16 @@ use serde::Deserialize;
 use serde::Serialize;
 use std::collections::HashSet;
 use std::hash::Hash;
+use std::str::FromStr;
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct Item {
     pub id: i64,
-    pub pocket_id: Option<i64>,
     pub title: String,
     pub url: String,
     pub excerpt: Option<String>,
+    /// Url the page declares as its preferred address
+    pub canonical_url: Option<String>,
 
     /// Extended metadata about the url
     pub is_article: Option<bool>,
@@ -31,63 +33,30 @@ pub struct Item {
     // fields related to the status of the url with respect to the user
     pub images: HashSet<Image>,
     pub status: ItemStatus,
+    #[serde(default)]
+    pub favorite: bool,
     pub time_added: i32,
     pub time_updated: Option<i32>,
     pub time_read: Option<i32>,
     pub time_favorited: Option<i32>,
-}
 
-impl From<&pocket::Item> for Item {
-    fn from(value: &pocket::Item) -> Self {
-        Self {
-            id: value.item_id,
-            pocket_id: Some(value.item_id),
-            title: value.resolved_title.clone(),
-            url: value.resolved_url.clone(),
-            excerpt: Some(value.excerpt.clone()),
-            is_article: Some(*value.is_article),
-            is_index: Some(*value.is_article),
-            has_video: Some(value.has_video.into()),
-            has_image: Some(value.has_image.into()),
-            word_count: Some(value.word_count),
-            lang: Some(value.lang.clone()),
-            time_to_read: Some(value.time_to_read),
-            top_image_url: value.top_image_url.clone(),
-            listen_duration_estimate: Some(value.listen_duration_estimate),
-            tags: value.tags.values().map(Tag::from).collect(),
-            authors: value
-                .authors
-                .as_ref()
-                .map(|authors| authors.values().cloned().map(Author::from).collect())
-                .unwrap_or_default(),
-            images: value
-                .images
-                .as_ref()
-                .map(|images| images.values().cloned().map(Image::from).collect())
-                .unwrap_or_default(),
-            videos: value
-                .videos
-                .as_ref()
-                .map(|videos| videos.values().cloned().map(Video::from).collect())
-                .unwrap_or_default(),
-            status: value.status.into(),
-            time_added: value.time_added,
-            time_updated: Some(value.time_updated),
-            time_read: Some(value.time_read),
-            time_favorited: Some(value.time_favorited),
-        }
-    }
+    // result of the last dead link check
+    pub link_status: Option<LinkStatus>,
+    pub link_final_url: Option<String>,
+    pub time_link_checked: Option<i32>,
+    pub wayback_url: Option<String>,
 }
 
 impl Default for Item {
     fn default() -> Self {
         Item {
             id: 0,
-            pocket_id: None,
             title: "Example URL".to_string(),
             url: "http://example.com".to_string(),
             excerpt: None,
+            canonical_url: None,
             status: ItemStatus::Unread,
+            favorite: false,
             time_added: 0,
             time_updated: None,
             time_read: None,
@@ -105,6 +74,10 @@ impl Default for Item {
             time_to_read: None,
             top_image_url: None,
             listen_duration_estimate: None,
+            link_status: None,
+            link_final_url: None,
+            time_link_checked: None,
+            wayback_url: None,
         }
     }
 }
@@ -126,17 +99,7 @@ impl Default for Tag {
     }
 }
 
-impl From<&pocket::item::Tag> for Tag {
-    fn from(tag: &pocket::item::Tag) -> Self {
-        Tag {
-            id: 0,
-            tag: tag.tag.clone(),
-            name: None,
-        }
-    }
-}
-
-#[derive(Deserialize, Debug, sqlx::Type, PartialEq, Eq, Clone, Copy)]
+#[derive(Deserialize, Serialize, Debug, sqlx::Type, PartialEq, Eq, Clone, Copy)]
 #[repr(i32)]
 pub enum ItemStatus {
     Unread = 0,
@@ -144,67 +107,64 @@ pub enum ItemStatus {
     Deleted = 2,
 }
 
-impl From<pocket::item::ItemStatus> for ItemStatus {
-    fn from(status: pocket::item::ItemStatus) -> Self {
-        match status {
-            pocket::item::ItemStatus::Unread => ItemStatus::Unread,
-            pocket::item::ItemStatus::Archived => ItemStatus::Archived,
-            pocket::item::ItemStatus::Deleted => ItemStatus::Deleted,
+impl ItemStatus {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ItemStatus::Unread => "unread",
+            ItemStatus::Archived => "archived",
+            ItemStatus::Deleted => "deleted",
         }
     }
 }
 
-impl From<ItemStatus> for pocket::item::ItemStatus {
-    fn from(status: ItemStatus) -> Self {
-        match status {
-            ItemStatus::Unread => pocket::item::ItemStatus::Unread,
-            ItemStatus::Archived => pocket::item::ItemStatus::Archived,
-            ItemStatus::Deleted => pocket::item::ItemStatus::Deleted,
+impl FromStr for ItemStatus {
+    type Err = crate::DBError;
+
+    fn from_str(name: &str) -> crate::Result<Self> {
+        match name {
+            "unread" => Ok(ItemStatus::Unread),
+            "archived" => Ok(ItemStatus::Archived),
+            "deleted" => Ok(ItemStatus::Deleted),
+            _ => Err(crate::DBError::ParseError),
         }
     }
 }
-#[derive(Deserialize, Debug, Clone, sqlx::Type, Copy, PartialEq, Eq)]
+
+#[derive(Deserialize, Serialize, Debug, Clone, sqlx::Type, Copy, PartialEq, Eq)]
 #[repr(i32)]
-pub enum HasVideo {
-    No = 0,
-    Yes = 1,
-    IsVideo = 2,
+pub enum LinkStatus {
+    Ok = 0,
+    /// The url redirects to a different page that is reachable
+    Redirected = 1,
+    NotFound = 2,
+    Gone = 3,
+    DnsFailure = 4,
+    /// Any other failure, such as a server error or a timeout
+    Error = 5,
 }
 
-impl From<pocket::item::HasVideo> for HasVideo {
-    fn from(has_video: pocket::item::HasVideo) -> Self {
-        match has_video {
-            pocket::item::HasVideo::No => HasVideo::No,
-            pocket::item::HasVideo::Yes => HasVideo::Yes,
-            pocket::item::HasVideo::IsVideo => HasVideo::IsVideo,
-        }
+impl LinkStatus {
+    /// Whether the page is unlikely to ever come back
+    pub fn is_dead(&self) -> bool {
+        matches!(
+            self,
+            LinkStatus::NotFound | LinkStatus::Gone | LinkStatus::DnsFailure
+        )
     }
 }
 
-impl From<HasVideo> for pocket::item::HasVideo {
-    fn from(has_video: HasVideo) -> Self {
-        match has_video {
-            HasVideo::No => pocket::item::HasVideo::No,
-            HasVideo::Yes => pocket::item::HasVideo::Yes,
-            HasVideo::IsVideo => pocket::item::HasVideo::IsVideo,
-        }
-    }
+#[derive(Deserialize, Serialize, Debug, Clone, sqlx::Type, Copy, PartialEq, Eq)]
+#[repr(i32)]
+pub enum HasVideo {
+    No = 0,
+    Yes = 1,
+    IsVideo = 2,
 }
 
-#[derive(Deserialize, Debug, Clone, sqlx::Type, Copy)]
+#[derive(Deserialize, Serialize, Debug, Clone, sqlx::Type, Copy)]
 #[repr(i32)]
 pub enum HasImage {
     No = 0,
     Yes = 1,
     IsImage = 2,
 }
-
-impl From<pocket::item::HasImage> for HasImage {
-    fn from(has_image: pocket::item::HasImage) -> Self {
-        match has_image {
-            pocket::item::HasImage::No => HasImage::No,
-            pocket::item::HasImage::Yes => HasImage::Yes,
-            pocket::item::HasImage::IsImage => HasImage::IsImage,
-        }
-    }
-}
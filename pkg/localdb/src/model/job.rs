@@ -0,0 +1,40 @@
+use serde::{Deserialize, Serialize};
+
+/// Slow work on an item that is done in the background
+#[derive(Deserialize, Serialize, Debug, Clone, sqlx::Type, Copy, PartialEq, Eq, Hash)]
+#[repr(i32)]
+pub enum JobKind {
+    /// Download the page and store its article
+    Extract = 0,
+    /// Fill in missing metadata from the page
+    Enrich = 1,
+    /// Cache the images of the item
+    Assets = 2,
+    CheckLink = 3,
+    /// Capture a snapshot of the page
+    Snapshot = 4,
+    /// Send the queued changes of the item to its remotes
+    Push = 5,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, sqlx::Type, Copy, PartialEq, Eq)]
+#[repr(i32)]
+pub enum JobState {
+    Pending = 0,
+    Running = 1,
+    /// Gave up after too many attempts, until retried by hand
+    Failed = 2,
+}
+
+#[derive(Deserialize, Serialize, Debug, sqlx::FromRow, Clone, PartialEq, Eq)]
+pub struct Job {
+    pub id: i64,
+    pub kind: JobKind,
+    pub item_id: i64,
+    pub state: JobState,
+    pub attempts: i32,
+    /// Time from which a pending job may run
+    pub next_run: i64,
+    pub last_error: Option<String>,
+    pub time_added: i64,
+}
@@ -1,9 +1,25 @@
+mod asset;
 mod author;
+mod change;
+mod content;
+mod highlight;
 mod image;
 mod item;
+mod job;
+mod progress;
+mod remote;
+mod snapshot;
 mod video;
 
+pub use asset::Asset;
 pub use author::Author;
+pub use change::{Change, Field, Operation, Source};
+pub use content::Content;
+pub use highlight::Highlight;
 pub use image::*;
 pub use item::*;
+pub use job::{Job, JobKind, JobState};
+pub use progress::Progress;
+pub use remote::{OutboxEntry, Remote};
+pub use snapshot::Snapshot;
 pub use video::Video;
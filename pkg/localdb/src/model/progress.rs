@@ -0,0 +1,14 @@
+use serde::{Deserialize, Serialize};
+
+/// Reading position of a document reported by a KOReader device
+#[derive(Deserialize, Serialize, Debug, sqlx::FromRow, Clone, PartialEq, Default)]
+pub struct Progress {
+    /// Hash identifying the document, see the `documents` table
+    pub document: String,
+    /// Position in a format only KOReader understands
+    pub progress: String,
+    pub percentage: f64,
+    pub device: String,
+    pub device_id: String,
+    pub timestamp: i64,
+}
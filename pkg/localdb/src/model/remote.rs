@@ -0,0 +1,29 @@
+use serde::{Deserialize, Serialize};
+
+/// A configured sync service, such as a Pocket or wallabag account
+#[derive(Deserialize, Serialize, Debug, sqlx::FromRow, Clone, PartialEq, Eq, Default)]
+pub struct Remote {
+    pub id: i64,
+    pub name: String,
+    /// Kind of backend, e.g. `pocket`
+    pub backend: String,
+    /// Backend specific settings and credentials, as json
+    pub settings: String,
+    /// Opaque position of the last pull, understood by the backend
+    pub cursor: Option<String>,
+    pub time_added: i64,
+    pub time_synced: Option<i64>,
+}
+
+/// A local change waiting to be pushed to a remote
+#[derive(Deserialize, Serialize, Debug, sqlx::FromRow, Clone, PartialEq, Eq)]
+pub struct OutboxEntry {
+    pub id: i64,
+    pub remote_id: i64,
+    pub item_id: i64,
+    /// The change, as json understood by the backend
+    pub mutation: String,
+    pub attempts: i32,
+    pub last_error: Option<String>,
+    pub time_added: i64,
+}
@@ -0,0 +1,18 @@
+use serde::{Deserialize, Serialize};
+
+/// Self contained copy of the page of an item at a point in time
+#[derive(Deserialize, Serialize, Debug, sqlx::FromRow, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    pub id: i32,
+    pub item_id: i64,
+    /// Url of the page after following redirects
+    pub url: String,
+    /// HTTP status of the page
+    pub status: i32,
+    /// Path of the single file html, relative to the snapshot store
+    pub html_path: String,
+    /// Path of the WARC file, relative to the snapshot store
+    pub warc_path: Option<String>,
+    pub size: i64,
+    pub time_fetched: i32,
+}
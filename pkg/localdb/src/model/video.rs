@@ -9,18 +9,6 @@ pub struct Video {
     pub kind: Option<String>,
 }
 
-impl From<pocket::item::Videos> for Video {
-    fn from(image: pocket::item::Videos) -> Self {
-        Self {
-            id: image.video_id,
-            src: image.src,
-            width: image.width,
-            height: image.height,
-            kind: Some(image.kind),
-        }
-    }
-}
-
 impl Default for Video {
     fn default() -> Self {
         Self {
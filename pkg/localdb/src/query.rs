@@ -0,0 +1,178 @@
+use crate::{Item, ItemStatus};
+use serde::Deserialize;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortBy {
+    #[default]
+    Newest,
+    Oldest,
+}
+
+/// Filters applied on top of the items stored in the local database.
+#[derive(Debug, Clone, Default)]
+pub struct ItemQuery {
+    pub status: Option<ItemStatus>,
+    pub tag: Option<String>,
+    pub favorite: Option<bool>,
+    /// Case insensitive match against title, url and excerpt
+    pub search: Option<String>,
+    pub sort: SortBy,
+    pub limit: Option<usize>,
+}
+
+impl ItemQuery {
+    pub fn status(mut self, status: ItemStatus) -> Self {
+        self.status = Some(status);
+        self
+    }
+
+    pub fn tag(mut self, tag: &str) -> Self {
+        self.tag = Some(tag.to_string());
+        self
+    }
+
+    pub fn favorite(mut self, favorite: bool) -> Self {
+        self.favorite = Some(favorite);
+        self
+    }
+
+    pub fn search(mut self, search: &str) -> Self {
+        self.search = Some(search.to_string());
+        self
+    }
+
+    pub fn sort(mut self, sort: SortBy) -> Self {
+        self.sort = sort;
+        self
+    }
+
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    pub fn matches(&self, item: &Item) -> bool {
+        if let Some(status) = self.status {
+            if item.status != status {
+                return false;
+            }
+        }
+
+        if let Some(tag) = &self.tag {
+            if !item.tags.iter().any(|t| t.tag.eq_ignore_ascii_case(tag)) {
+                return false;
+            }
+        }
+
+        if let Some(favorite) = self.favorite {
+            if item.favorite != favorite {
+                return false;
+            }
+        }
+
+        if let Some(search) = &self.search {
+            let search = search.to_lowercase();
+            let found = item.title.to_lowercase().contains(&search)
+                || item.url.to_lowercase().contains(&search)
+                || item
+                    .excerpt
+                    .as_ref()
+                    .is_some_and(|excerpt| excerpt.to_lowercase().contains(&search));
+            if !found {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    pub fn apply(&self, items: Vec<Item>) -> Vec<Item> {
+        let mut items: Vec<Item> = items.into_iter().filter(|i| self.matches(i)).collect();
+        match self.sort {
+            SortBy::Newest => items.sort_by_key(|i| (std::cmp::Reverse(i.time_added), i.id)),
+            SortBy::Oldest => items.sort_by_key(|i| (i.time_added, i.id)),
+        }
+        if let Some(limit) = self.limit {
+            items.truncate(limit);
+        }
+        items
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Tag;
+    use std::collections::HashSet;
+
+    fn items() -> Vec<Item> {
+        vec![
+            Item {
+                id: 1,
+                title: "Rust in production".to_string(),
+                url: "https://example.com/rust".to_string(),
+                time_added: 10,
+                tags: HashSet::from([Tag {
+                    tag: "rust".to_string(),
+                    ..Default::default()
+                }]),
+                ..Default::default()
+            },
+            Item {
+                id: 2,
+                title: "Gardening".to_string(),
+                url: "https://example.com/garden".to_string(),
+                excerpt: Some("Growing rust resistant roses".to_string()),
+                time_added: 20,
+                status: ItemStatus::Archived,
+                ..Default::default()
+            },
+            Item {
+                id: 3,
+                title: "Cooking".to_string(),
+                url: "https://example.com/cooking".to_string(),
+                time_added: 5,
+                ..Default::default()
+            },
+        ]
+    }
+
+    fn ids(items: Vec<Item>) -> Vec<i64> {
+        items.into_iter().map(|i| i.id).collect()
+    }
+
+    #[test]
+    fn test_default_sorts_newest_first() {
+        assert_eq!(ids(ItemQuery::default().apply(items())), vec![2, 1, 3]);
+    }
+
+    #[test]
+    fn test_oldest_unread() {
+        let query = ItemQuery::default()
+            .status(ItemStatus::Unread)
+            .sort(SortBy::Oldest)
+            .limit(1);
+        assert_eq!(ids(query.apply(items())), vec![3]);
+    }
+
+    #[test]
+    fn test_tag() {
+        let query = ItemQuery::default().tag("RUST");
+        assert_eq!(ids(query.apply(items())), vec![1]);
+    }
+
+    #[test]
+    fn test_favorite() {
+        let mut items = items();
+        items[2].favorite = true;
+        let query = ItemQuery::default().favorite(true);
+        assert_eq!(ids(query.apply(items)), vec![3]);
+    }
+
+    #[test]
+    fn test_search() {
+        let query = ItemQuery::default().search("Rust");
+        assert_eq!(ids(query.apply(items())), vec![2, 1]);
+    }
+}
@@ -0,0 +1,135 @@
+use scraper::node::Element;
+use scraper::{ElementRef, Html, Node, Selector};
+use url::Url;
+
+/// Elements pages commonly wrap their main text in
+const CANDIDATES: &str = r#"article, main, [role="main"], [itemprop="articleBody"]"#;
+/// Attributes holding a url
+const URL_ATTRIBUTES: [&str; 4] = ["href", "src", "poster", "cite"];
+
+fn text_len(element: &ElementRef) -> usize {
+    element.text().map(|text| text.trim().len()).sum()
+}
+
+/// Picks the part of a page holding the article: the candidate element with
+/// the most text, the innermost one on ties, or the whole body. Returns its
+/// inner html, unsanitized, with its urls made absolute against `url` so
+/// that they still work away from the page.
+pub fn extract(html: &str, url: &Url) -> Option<String> {
+    let mut document = Html::parse_document(html);
+    let candidates = Selector::parse(CANDIDATES).expect("valid selector");
+    let body = Selector::parse("body").expect("valid selector");
+    let element = document
+        .select(&candidates)
+        .max_by_key(text_len)
+        .or_else(|| document.select(&body).next())?;
+    if text_len(&element) == 0 {
+        return None;
+    }
+    let id = element.id();
+    let nodes: Vec<_> = element.descendants().map(|node| node.id()).collect();
+    let base = base_url(&document, url);
+    for node in nodes {
+        let mut node = document.tree.get_mut(node)?;
+        if let Node::Element(element) = node.value() {
+            absolutize(element, &base);
+        }
+    }
+    let element = ElementRef::wrap(document.tree.get(id)?)?;
+    Some(element.inner_html().trim().to_string())
+}
+
+/// The url relative urls of the page resolve against, from its `<base>`
+fn base_url(document: &Html, url: &Url) -> Url {
+    let base = Selector::parse("base[href]").expect("valid selector");
+    document
+        .select(&base)
+        .next()
+        .and_then(|base| url.join(base.value().attr("href")?).ok())
+        .unwrap_or_else(|| url.clone())
+}
+
+fn absolutize(element: &mut Element, base: &Url) {
+    for (name, value) in element.attrs.iter_mut() {
+        let resolved = match &*name.local {
+            "srcset" => resolve_srcset(base, value),
+            name if URL_ATTRIBUTES.contains(&name) => resolve(base, value),
+            _ => None,
+        };
+        if let Some(resolved) = resolved {
+            *value = resolved.into();
+        }
+    }
+}
+
+/// Resolves `href` against `base`, leaving links within the page alone
+fn resolve(base: &Url, href: &str) -> Option<String> {
+    let href = href.trim();
+    if href.is_empty() || href.starts_with('#') {
+        return None;
+    }
+    base.join(href).ok().map(String::from)
+}
+
+fn resolve_srcset(base: &Url, srcset: &str) -> Option<String> {
+    let candidates: Vec<String> = srcset
+        .split(',')
+        .map(|candidate| {
+            let candidate = candidate.trim();
+            let (src, descriptor) = candidate.split_once(' ').unwrap_or((candidate, ""));
+            match resolve(base, src) {
+                Some(src) if descriptor.is_empty() => src,
+                Some(src) => format!("{} {}", src, descriptor.trim()),
+                None => candidate.to_string(),
+            }
+        })
+        .collect();
+    Some(candidates.join(", "))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_extract() {
+        let html = r#"<html><body>
+            <nav>Home</nav>
+            <article><h1>Short teaser</h1></article>
+            <main><article><p>The article itself, which is longer.</p></article></main>
+        </body></html>"#;
+        let url = Url::parse("https://example.com/posts/1").unwrap();
+        assert_eq!(
+            extract(html, &url).as_deref(),
+            Some("<p>The article itself, which is longer.</p>")
+        );
+        assert_eq!(
+            extract("<p>No wrapper</p>", &url).as_deref(),
+            Some("<p>No wrapper</p>")
+        );
+        assert_eq!(extract("<body> </body>", &url), None);
+    }
+
+    #[test]
+    fn test_extract_resolves_urls() {
+        let url = Url::parse("https://example.com/posts/1").unwrap();
+        let html = r##"<article>
+            <p>See <a href="../about">this</a> and <a href="#notes">the notes</a>.</p>
+            <img src="img/a.png" srcset="img/a.png 1x, /img/a@2x.png 2x">
+            <a href="https://other.example/x">elsewhere</a>
+        </article>"##;
+        let article = extract(html, &url).unwrap();
+        assert!(article.contains(r#"href="https://example.com/about""#));
+        assert!(article.contains(r##"href="#notes""##));
+        assert!(article.contains(r#"src="https://example.com/posts/img/a.png""#));
+        assert!(article.contains(
+            r#"srcset="https://example.com/posts/img/a.png 1x, https://example.com/img/a@2x.png 2x""#
+        ));
+        assert!(article.contains(r#"href="https://other.example/x""#));
+
+        let html = r#"<head><base href="https://cdn.example/"></head>
+            <article><img src="b.png"><p>Text</p></article>"#;
+        let article = extract(html, &url).unwrap();
+        assert!(article.contains(r#"src="https://cdn.example/b.png""#));
+    }
+}
@@ -0,0 +1,137 @@
+use serde_json::Value;
+
+/// The parts of a schema.org Article we care about
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Article {
+    pub headline: Option<String>,
+    pub description: Option<String>,
+    pub image: Option<String>,
+    pub authors: Vec<(String, Option<String>)>,
+    pub language: Option<String>,
+    pub url: Option<String>,
+}
+
+/// Finds the first Article like object in a JSON-LD document, looking into
+/// arrays and `@graph` as well.
+pub fn article(json: &str) -> Option<Article> {
+    let value: Value = serde_json::from_str(json).ok()?;
+    find_article(&value).map(parse_article)
+}
+
+fn find_article(value: &Value) -> Option<&Value> {
+    match value {
+        Value::Array(values) => values.iter().find_map(find_article),
+        Value::Object(object) => {
+            if is_article(object.get("@type")) {
+                return Some(value);
+            }
+            object.get("@graph").and_then(find_article)
+        }
+        _ => None,
+    }
+}
+
+fn is_article(kind: Option<&Value>) -> bool {
+    let is_article = |kind: &str| {
+        kind.ends_with("Article") || matches!(kind, "BlogPosting" | "SocialMediaPosting" | "Report")
+    };
+    match kind {
+        Some(Value::String(kind)) => is_article(kind),
+        Some(Value::Array(kinds)) => kinds.iter().filter_map(Value::as_str).any(is_article),
+        _ => false,
+    }
+}
+
+fn parse_article(value: &Value) -> Article {
+    Article {
+        headline: string(value.get("headline")).or_else(|| string(value.get("name"))),
+        description: string(value.get("description")),
+        image: value.get("image").and_then(url),
+        authors: value.get("author").map(authors).unwrap_or_default(),
+        language: string(value.get("inLanguage")),
+        url: string(value.get("url")).or_else(|| string(value.get("mainEntityOfPage"))),
+    }
+}
+
+fn string(value: Option<&Value>) -> Option<String> {
+    value
+        .and_then(Value::as_str)
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(str::to_string)
+}
+
+/// Images are either a url, an ImageObject or a list of those
+fn url(value: &Value) -> Option<String> {
+    match value {
+        Value::String(_) => string(Some(value)),
+        Value::Array(values) => values.iter().find_map(url),
+        Value::Object(object) => string(object.get("url")),
+        _ => None,
+    }
+}
+
+/// Authors are either a name, a Person or a list of those
+fn authors(value: &Value) -> Vec<(String, Option<String>)> {
+    match value {
+        Value::String(name) => vec![(name.trim().to_string(), None)],
+        Value::Array(values) => values.iter().flat_map(authors).collect(),
+        Value::Object(object) => string(object.get("name"))
+            .map(|name| vec![(name, string(object.get("url")))])
+            .unwrap_or_default(),
+        _ => vec![],
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_article() {
+        let json = r#"{
+            "@context": "https://schema.org",
+            "@type": "NewsArticle",
+            "headline": "Headline",
+            "image": ["https://example.com/1.jpg", "https://example.com/2.jpg"],
+            "author": [
+                {"@type": "Person", "name": "Jane Doe", "url": "https://example.com/jane"},
+                "John Doe"
+            ],
+            "inLanguage": "en-GB"
+        }"#;
+        assert_eq!(
+            article(json),
+            Some(Article {
+                headline: Some("Headline".to_string()),
+                image: Some("https://example.com/1.jpg".to_string()),
+                authors: vec![
+                    (
+                        "Jane Doe".to_string(),
+                        Some("https://example.com/jane".to_string())
+                    ),
+                    ("John Doe".to_string(), None),
+                ],
+                language: Some("en-GB".to_string()),
+                ..Default::default()
+            })
+        );
+    }
+
+    #[test]
+    fn test_graph() {
+        let json = r#"{"@graph": [
+            {"@type": "WebSite", "name": "Site"},
+            {"@type": ["BlogPosting"], "name": "Post", "image": {"@type": "ImageObject", "url": "/a.png"}}
+        ]}"#;
+        let article = article(json).unwrap();
+        assert_eq!(article.headline.as_deref(), Some("Post"));
+        assert_eq!(article.image.as_deref(), Some("/a.png"));
+    }
+
+    #[test]
+    fn test_not_an_article() {
+        assert_eq!(article(r#"{"@type": "Organization", "name": "x"}"#), None);
+        assert_eq!(article("not json"), None);
+    }
+}
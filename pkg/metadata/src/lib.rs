@@ -0,0 +1,419 @@
+mod article;
+mod jsonld;
+
+pub use article::extract;
+
+use localdb::{Author, Image, Item};
+use reqwest::Client;
+use scraper::{Html, Selector};
+use std::collections::HashMap;
+use std::time::Duration;
+use url::Url;
+
+#[derive(Debug, thiserror::Error)]
+pub enum MetadataError {
+    #[error("Request error for URL <{url}>: {source}")]
+    Reqwest { url: String, source: reqwest::Error },
+}
+
+pub type MetadataResult<T> = Result<T, MetadataError>;
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct PageImage {
+    pub src: String,
+    pub width: i32,
+    pub height: i32,
+}
+
+/// Metadata a page publishes about itself, in order of preference from
+/// JSON-LD, OpenGraph, Twitter cards and plain html.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Metadata {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub canonical_url: Option<String>,
+    pub site_name: Option<String>,
+    pub lang: Option<String>,
+    pub image: Option<PageImage>,
+    /// Name and profile url of each author
+    pub authors: Vec<(String, Option<String>)>,
+}
+
+/// Extracts the metadata of a page. Relative urls are resolved against `url`.
+pub fn parse(html: &str, url: &Url) -> Metadata {
+    let document = Html::parse_document(html);
+    let meta = meta_tags(&document);
+    let get = |keys: &[&str]| {
+        keys.iter()
+            .find_map(|key| meta.get(*key).and_then(|values| values.first()))
+            .cloned()
+    };
+    let article = select(&document, r#"script[type="application/ld+json"]"#)
+        .into_iter()
+        .find_map(|json| jsonld::article(&json))
+        .unwrap_or_default();
+    let resolve = |href: String| url.join(&href).ok().map(|url| url.to_string());
+
+    let title = article
+        .headline
+        .or_else(|| get(&["og:title", "twitter:title"]))
+        .or_else(|| select(&document, "title").into_iter().next());
+
+    let description = article
+        .description
+        .or_else(|| get(&["og:description", "twitter:description", "description"]));
+
+    let canonical_url = select_attr(&document, r#"link[rel~="canonical"]"#, "href")
+        .or(article.url)
+        .or_else(|| get(&["og:url"]))
+        .and_then(resolve);
+
+    let lang = select_attr(&document, "html[lang]", "lang")
+        .or(article.language)
+        .or_else(|| get(&["og:locale"]).map(|locale| locale.replace('_', "-")));
+
+    let image = article
+        .image
+        .or_else(|| get(&["og:image", "og:image:url", "og:image:secure_url"]))
+        .or_else(|| get(&["twitter:image", "twitter:image:src"]))
+        .and_then(resolve)
+        .map(|src| PageImage {
+            src,
+            width: get(&["og:image:width"])
+                .and_then(|w| w.parse().ok())
+                .unwrap_or_default(),
+            height: get(&["og:image:height"])
+                .and_then(|h| h.parse().ok())
+                .unwrap_or_default(),
+        });
+
+    let mut authors = article.authors;
+    if authors.is_empty() {
+        // `article:author` is either a name or a link to the author profile
+        authors = meta
+            .get("article:author")
+            .into_iter()
+            .chain(meta.get("author"))
+            .flatten()
+            .filter(|author| Url::parse(author).is_err())
+            .map(|name| (name.clone(), None))
+            .collect();
+    }
+    if authors.is_empty() {
+        if let Some(creator) = get(&["twitter:creator"]) {
+            let handle = creator.trim_start_matches('@');
+            authors.push((creator.clone(), Some(format!("https://x.com/{handle}"))));
+        }
+    }
+
+    Metadata {
+        title,
+        description,
+        canonical_url,
+        site_name: get(&["og:site_name", "application-name"]),
+        lang,
+        image,
+        authors,
+    }
+}
+
+/// Values of `<meta>` tags by lowercased `property` or `name`
+fn meta_tags(document: &Html) -> HashMap<String, Vec<String>> {
+    let selector = Selector::parse("meta[content]").expect("valid selector");
+    let mut meta: HashMap<String, Vec<String>> = HashMap::new();
+    for element in document.select(&selector) {
+        let Some(key) = element.attr("property").or_else(|| element.attr("name")) else {
+            continue;
+        };
+        let content = element.attr("content").unwrap_or_default().trim();
+        if content.is_empty() {
+            continue;
+        }
+        meta.entry(key.to_lowercase())
+            .or_default()
+            .push(content.to_string());
+    }
+    meta
+}
+
+fn select(document: &Html, selector: &str) -> Vec<String> {
+    let selector = Selector::parse(selector).expect("valid selector");
+    document
+        .select(&selector)
+        .map(|element| element.text().collect::<String>().trim().to_string())
+        .filter(|text| !text.is_empty())
+        .collect()
+}
+
+fn select_attr(document: &Html, selector: &str, attr: &str) -> Option<String> {
+    let selector = Selector::parse(selector).expect("valid selector");
+    document
+        .select(&selector)
+        .filter_map(|element| element.attr(attr))
+        .map(str::trim)
+        .find(|value| !value.is_empty())
+        .map(str::to_string)
+}
+
+/// Fills the empty fields of an item with the metadata of its page. With
+/// `overwrite`, fields that are already set are replaced as well. Returns
+/// whether the item changed.
+pub fn apply(item: &mut Item, metadata: &Metadata, overwrite: bool) -> bool {
+    let mut changed = false;
+    let mut set = |field: &mut Option<String>, value: &Option<String>| {
+        let empty = field.as_deref().is_none_or(str::is_empty);
+        if (overwrite || empty) && value.is_some() && field != value {
+            *field = value.clone();
+            changed = true;
+        }
+    };
+
+    set(&mut item.excerpt, &metadata.description);
+    set(&mut item.lang, &metadata.lang);
+    set(&mut item.canonical_url, &metadata.canonical_url);
+    set(
+        &mut item.top_image_url,
+        &metadata.image.as_ref().map(|image| image.src.clone()),
+    );
+
+    if let Some(title) = &metadata.title {
+        let untitled = item.title.is_empty() || item.title == item.url;
+        if (overwrite || untitled) && &item.title != title {
+            item.title = title.clone();
+            changed = true;
+        }
+    }
+
+    if let Some(image) = &metadata.image {
+        if !item.images.iter().any(|i| i.src == image.src) {
+            item.images.insert(Image {
+                id: 0,
+                src: image.src.clone(),
+                width: image.width,
+                height: image.height,
+                caption: None,
+                credit: None,
+            });
+            changed = true;
+        }
+    }
+
+    if (overwrite || item.authors.is_empty()) && !metadata.authors.is_empty() {
+        for (name, url) in &metadata.authors {
+            if !item.authors.iter().any(|a| &a.name == name) {
+                item.authors.insert(Author {
+                    id: 0,
+                    name: name.clone(),
+                    url: url.clone(),
+                });
+                changed = true;
+            }
+        }
+    }
+
+    changed
+}
+
+/// A downloaded page, with the url it was served from
+pub struct Page {
+    pub url: Url,
+    pub html: String,
+}
+
+pub struct MetadataFetcher {
+    client: Client,
+}
+
+impl Default for MetadataFetcher {
+    fn default() -> Self {
+        MetadataFetcher::new()
+    }
+}
+
+impl MetadataFetcher {
+    pub fn new() -> MetadataFetcher {
+        MetadataFetcher {
+            client: Client::new(),
+        }
+    }
+
+    /// Fetcher giving up on pages after `timeout`
+    pub fn with_options(timeout: Duration, user_agent: &str) -> MetadataFetcher {
+        let client = Client::builder()
+            .timeout(timeout)
+            .user_agent(user_agent)
+            .build()
+            .expect("valid http client");
+        MetadataFetcher { client }
+    }
+
+    pub async fn fetch(&self, url: &str) -> MetadataResult<Metadata> {
+        let page = self.fetch_page(url).await?;
+        Ok(parse(&page.html, &page.url))
+    }
+
+    /// Downloads a page, following redirects
+    pub async fn fetch_page(&self, url: &str) -> MetadataResult<Page> {
+        let reqwest_error = |source| MetadataError::Reqwest {
+            url: url.to_string(),
+            source,
+        };
+        let response = self
+            .client
+            .get(url)
+            .send()
+            .await
+            .and_then(|res| res.error_for_status())
+            .map_err(reqwest_error)?;
+        let page_url = response.url().clone();
+        let html = response.text().await.map_err(reqwest_error)?;
+        Ok(Page {
+            url: page_url,
+            html,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::HashSet;
+    use wiremock::{matchers::path, Mock, MockServer, ResponseTemplate};
+
+    const PAGE: &str = r#"<!doctype html>
+<html lang="en">
+<head>
+<title>Page title | Site</title>
+<link rel="canonical" href="/posts/canonical">
+<meta property="og:title" content="OpenGraph title">
+<meta property="og:description" content="OpenGraph description">
+<meta property="og:image" content="/images/cover.jpg">
+<meta property="og:image:width" content="1200">
+<meta property="og:image:height" content="630">
+<meta property="og:site_name" content="Site">
+<meta name="twitter:creator" content="@jane">
+<meta name="author" content="Jane Doe">
+</head>
+<body></body>
+</html>"#;
+
+    fn url() -> Url {
+        Url::parse("https://example.com/posts/1?utm_source=feed").unwrap()
+    }
+
+    #[test]
+    fn test_opengraph() {
+        let metadata = parse(PAGE, &url());
+        assert_eq!(
+            metadata,
+            Metadata {
+                title: Some("OpenGraph title".to_string()),
+                description: Some("OpenGraph description".to_string()),
+                canonical_url: Some("https://example.com/posts/canonical".to_string()),
+                site_name: Some("Site".to_string()),
+                lang: Some("en".to_string()),
+                image: Some(PageImage {
+                    src: "https://example.com/images/cover.jpg".to_string(),
+                    width: 1200,
+                    height: 630,
+                }),
+                authors: vec![("Jane Doe".to_string(), None)],
+            }
+        );
+    }
+
+    #[test]
+    fn test_jsonld_takes_precedence() {
+        let html = PAGE.replace(
+            "</head>",
+            r#"<script type="application/ld+json">
+{"@type": "Article", "headline": "JSON-LD headline", "author": {"name": "John Roe", "url": "https://example.com/john"}}
+</script></head>"#,
+        );
+        let metadata = parse(&html, &url());
+        assert_eq!(metadata.title.as_deref(), Some("JSON-LD headline"));
+        assert_eq!(
+            metadata.authors,
+            vec![(
+                "John Roe".to_string(),
+                Some("https://example.com/john".to_string())
+            )]
+        );
+        assert_eq!(
+            metadata.description.as_deref(),
+            Some("OpenGraph description")
+        );
+    }
+
+    #[test]
+    fn test_twitter_and_title_fallback() {
+        let html = r#"<html><head><title> Plain title </title>
+<meta name="twitter:description" content="Card description">
+<meta name="twitter:image" content="https://cdn.example.com/card.png">
+<meta name="twitter:creator" content="@jane">
+<meta property="og:locale" content="en_GB">
+</head></html>"#;
+        let metadata = parse(html, &url());
+        assert_eq!(metadata.title.as_deref(), Some("Plain title"));
+        assert_eq!(metadata.description.as_deref(), Some("Card description"));
+        assert_eq!(metadata.lang.as_deref(), Some("en-GB"));
+        assert_eq!(
+            metadata.image.map(|image| image.src).as_deref(),
+            Some("https://cdn.example.com/card.png")
+        );
+        assert_eq!(
+            metadata.authors,
+            vec![("@jane".to_string(), Some("https://x.com/jane".to_string()))]
+        );
+    }
+
+    #[test]
+    fn test_apply() {
+        let metadata = parse(PAGE, &url());
+        let mut item = Item {
+            title: url().to_string(),
+            url: url().to_string(),
+            excerpt: Some("Kept".to_string()),
+            ..Default::default()
+        };
+        assert!(apply(&mut item, &metadata, false));
+        assert_eq!(item.title, "OpenGraph title");
+        assert_eq!(item.excerpt.as_deref(), Some("Kept"));
+        assert_eq!(item.lang.as_deref(), Some("en"));
+        assert_eq!(
+            item.top_image_url.as_deref(),
+            Some("https://example.com/images/cover.jpg")
+        );
+        assert_eq!(item.images.len(), 1);
+        assert_eq!(
+            item.authors,
+            HashSet::from([Author {
+                id: 0,
+                name: "Jane Doe".to_string(),
+                url: None
+            }])
+        );
+        assert!(!apply(&mut item, &metadata, false));
+
+        assert!(apply(&mut item, &metadata, true));
+        assert_eq!(item.excerpt.as_deref(), Some("OpenGraph description"));
+    }
+
+    #[tokio::test]
+    async fn test_fetch() {
+        let server = MockServer::start().await;
+        Mock::given(path("/posts/1"))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(PAGE, "text/html"))
+            .mount(&server)
+            .await;
+
+        let metadata = MetadataFetcher::new()
+            .fetch(&format!("{}/posts/1", server.uri()))
+            .await
+            .unwrap();
+        assert_eq!(
+            metadata.canonical_url,
+            Some(format!("{}/posts/canonical", server.uri()))
+        );
+    }
+}
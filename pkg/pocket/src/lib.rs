@@ -75,6 +75,11 @@ impl<'a> PocketClient<'a> {
             .into_iter()
             .map(|item_id| ModifyItem::new(Action::Archive, item_id))
             .collect();
+        self.send(actions).await
+    }
+
+    /// Sends any of the actions of the modify api, see [`modify`]
+    pub async fn send<T: Serialize>(&mut self, actions: Vec<T>) -> PocketResult<()> {
         let request = PocketSendRequest::new(self.consumer_key, self.access_token, actions);
         let _res = req(&self.client, SEND_URL, &request).await?;
         Ok(())
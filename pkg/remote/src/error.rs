@@ -0,0 +1,45 @@
+#[derive(Debug, thiserror::Error)]
+pub enum RemoteError {
+    #[error("database error: {0}")]
+    Db(#[from] localdb::DBError),
+
+    #[error("pocket error: {0}")]
+    Pocket(#[from] pocket::PocketError),
+
+    #[error("wallabag error: {0}")]
+    Wallabag(#[from] wallabag::WallabagError),
+
+    #[error("Request error for URL <{url}>: {source}")]
+    Http { url: String, source: reqwest::Error },
+
+    #[error("remote returned {status} for <{url}>: {body}")]
+    Api {
+        url: String,
+        status: u16,
+        body: String,
+    },
+
+    #[error("invalid url: {0}")]
+    InvalidUrl(#[from] url::ParseError),
+
+    #[error("invalid settings: {0}")]
+    Settings(#[from] serde_json::Error),
+
+    #[error("unknown backend `{0}`")]
+    UnknownBackend(String),
+
+    #[error("not authenticated, run `readlater auth login`")]
+    NotAuthenticated,
+
+    #[error("credential store error: {0}")]
+    Credentials(#[from] credentials::CredentialError),
+
+    #[error("no remote named `{0}`")]
+    UnknownRemote(String),
+
+    /// The change is for an item the remote has not told the id of yet
+    #[error("the item is not linked to the remote yet")]
+    NotLinked,
+}
+
+pub type RemoteResult<T> = Result<T, RemoteError>;
@@ -0,0 +1,103 @@
+use crate::{RemoteError, RemoteResult};
+use reqwest::{header::HeaderValue, Client, Method, RequestBuilder, Response, StatusCode};
+use serde::{de::DeserializeOwned, Serialize};
+use url::Url;
+
+/// Minimal json client shared by the self hosted backends
+pub(crate) struct Api {
+    base_url: Url,
+    authorization: String,
+    client: Client,
+}
+
+impl Api {
+    /// `base_url` is where the api paths are joined, `authorization` the full
+    /// value of the authorization header.
+    pub fn new(base_url: &str, authorization: String) -> RemoteResult<Api> {
+        let mut base_url = Url::parse(base_url)?;
+        if !base_url.path().ends_with('/') {
+            base_url.set_path(&format!("{}/", base_url.path()));
+        }
+        Ok(Api {
+            base_url,
+            authorization,
+            client: Client::new(),
+        })
+    }
+
+    pub fn request(&self, method: Method, path: &str) -> RemoteResult<(Url, RequestBuilder)> {
+        let url = self.base_url.join(path)?;
+        let authorization = HeaderValue::from_str(&self.authorization)
+            .map_err(|_| RemoteError::NotAuthenticated)?;
+        let request = self
+            .client
+            .request(method, url.clone())
+            .header(reqwest::header::AUTHORIZATION, authorization);
+        Ok((url, request))
+    }
+
+    pub async fn send(&self, url: &Url, request: RequestBuilder) -> RemoteResult<Response> {
+        let res = request.send().await.map_err(|source| RemoteError::Http {
+            url: url.to_string(),
+            source,
+        })?;
+        let status = res.status();
+        if status == StatusCode::UNAUTHORIZED || status == StatusCode::FORBIDDEN {
+            return Err(RemoteError::NotAuthenticated);
+        }
+        if !status.is_success() {
+            return Err(RemoteError::Api {
+                url: url.to_string(),
+                status: status.as_u16(),
+                body: res.text().await.unwrap_or_default(),
+            });
+        }
+        Ok(res)
+    }
+
+    pub async fn json<T: DeserializeOwned>(&self, url: &Url, res: Response) -> RemoteResult<T> {
+        res.json().await.map_err(|source| RemoteError::Http {
+            url: url.to_string(),
+            source,
+        })
+    }
+
+    pub async fn get<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        query: &impl Serialize,
+    ) -> RemoteResult<T> {
+        let (url, request) = self.request(Method::GET, path)?;
+        let res = self.send(&url, request.query(query)).await?;
+        self.json(&url, res).await
+    }
+
+    /// Sends `body` as json, ignoring the response
+    pub async fn call(
+        &self,
+        method: Method,
+        path: &str,
+        body: &impl Serialize,
+    ) -> RemoteResult<()> {
+        let (url, request) = self.request(method, path)?;
+        self.send(&url, request.json(body)).await?;
+        Ok(())
+    }
+
+    pub async fn call_json<T: DeserializeOwned>(
+        &self,
+        method: Method,
+        path: &str,
+        body: &impl Serialize,
+    ) -> RemoteResult<T> {
+        let (url, request) = self.request(method, path)?;
+        let res = self.send(&url, request.json(body)).await?;
+        self.json(&url, res).await
+    }
+
+    pub async fn delete(&self, path: &str) -> RemoteResult<()> {
+        let (url, request) = self.request(Method::DELETE, path)?;
+        self.send(&url, request).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,454 @@
+use crate::http::Api;
+use crate::RemoteResult;
+use crate::{Capabilities, Mutation, Prompt, Pull, RemoteBackend, RemoteError, RemoteItem};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use localdb::{Author, Item, ItemStatus, Tag};
+use reqwest::Method;
+use serde::{Deserialize, Serialize};
+
+pub const NAME: &str = "karakeep";
+
+const LIMIT: usize = 50;
+
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub struct KarakeepSettings {
+    /// Address of the instance, e.g. https://karakeep.example.com
+    pub url: Option<String>,
+    pub api_key: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Bookmark {
+    pub id: String,
+    pub created_at: DateTime<Utc>,
+    pub modified_at: Option<DateTime<Utc>>,
+    /// Title set by the user
+    pub title: Option<String>,
+    pub archived: bool,
+    pub favourited: bool,
+    #[serde(default)]
+    pub tags: Vec<BookmarkTag>,
+    pub content: Content,
+}
+
+impl Bookmark {
+    fn modified_at(&self) -> DateTime<Utc> {
+        self.modified_at.unwrap_or(self.created_at)
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct BookmarkTag {
+    pub name: String,
+}
+
+/// Only links map to items, notes and uploaded assets have no url
+#[derive(Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Content {
+    #[serde(rename_all = "camelCase")]
+    Link {
+        url: String,
+        title: Option<String>,
+        description: Option<String>,
+        image_url: Option<String>,
+        html_content: Option<String>,
+        author: Option<String>,
+    },
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct Bookmarks {
+    bookmarks: Vec<Bookmark>,
+    next_cursor: Option<String>,
+}
+
+#[derive(Serialize)]
+struct NewBookmark<'a> {
+    #[serde(rename = "type")]
+    kind: &'a str,
+    url: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    title: Option<&'a str>,
+}
+
+#[derive(Serialize, Default)]
+struct BookmarkPatch {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    archived: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    favourited: Option<bool>,
+}
+
+#[derive(Serialize)]
+struct TagsRequest {
+    tags: Vec<TagName>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TagName {
+    tag_name: String,
+}
+
+impl TagsRequest {
+    fn new(tags: &[String]) -> TagsRequest {
+        TagsRequest {
+            tags: tags
+                .iter()
+                .map(|tag| TagName {
+                    tag_name: tag.clone(),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// The api has no way to list changes, every pull walks the whole library
+/// and only keeps what changed since the last one.
+#[derive(Serialize, Deserialize, Default)]
+struct Cursor {
+    next: Option<String>,
+    since: Option<DateTime<Utc>>,
+    latest: Option<DateTime<Utc>>,
+}
+
+pub struct KarakeepBackend {
+    settings: KarakeepSettings,
+}
+
+impl KarakeepBackend {
+    pub fn new(settings: KarakeepSettings) -> KarakeepBackend {
+        KarakeepBackend { settings }
+    }
+
+    fn api(&self) -> RemoteResult<Api> {
+        let (Some(url), Some(key)) = (&self.settings.url, &self.settings.api_key) else {
+            return Err(RemoteError::NotAuthenticated);
+        };
+        Api::new(
+            &format!("{}/api/v1", url.trim_end_matches('/')),
+            format!("Bearer {key}"),
+        )
+    }
+}
+
+#[async_trait]
+impl RemoteBackend for KarakeepBackend {
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            add: true,
+            archive: true,
+            favorite: true,
+            delete: true,
+            tags: true,
+            content: true,
+        }
+    }
+
+    fn settings(&self) -> serde_json::Value {
+        serde_json::to_value(&self.settings).expect("karakeep settings are serializable")
+    }
+
+    async fn authenticate(&mut self, prompt: &mut dyn Prompt) -> RemoteResult<()> {
+        if self.settings.url.is_none() {
+            self.settings.url = Some(prompt.ask("Address of the karakeep instance:"));
+        }
+        prompt.show("Create an API key in User Settings > API Keys");
+        self.settings.api_key = Some(prompt.password("API key:"));
+        let _: Bookmarks = self.api()?.get("bookmarks", &[("limit", 1)]).await?;
+        Ok(())
+    }
+
+    async fn pull(&mut self, cursor: Option<&str>) -> RemoteResult<Pull> {
+        let cursor: Cursor = match cursor {
+            Some(cursor) => serde_json::from_str(cursor)?,
+            None => Cursor::default(),
+        };
+        let mut query = vec![
+            ("limit", LIMIT.to_string()),
+            ("includeContent", "true".to_string()),
+        ];
+        if let Some(next) = &cursor.next {
+            query.push(("cursor", next.clone()));
+        }
+        let page: Bookmarks = self.api()?.get("bookmarks", &query).await?;
+
+        let latest = page
+            .bookmarks
+            .iter()
+            .map(Bookmark::modified_at)
+            .chain(cursor.latest)
+            .chain(cursor.since)
+            .max();
+        let items = page
+            .bookmarks
+            .iter()
+            .filter(|bookmark| {
+                cursor
+                    .since
+                    .is_none_or(|since| bookmark.modified_at() > since)
+            })
+            .filter_map(|bookmark| {
+                let (item, content) = item(bookmark)?;
+                Some(RemoteItem {
+                    id: bookmark.id.clone(),
+                    item,
+                    content,
+                })
+            })
+            .collect();
+
+        let has_more = page.next_cursor.is_some();
+        let next = if has_more {
+            Cursor {
+                next: page.next_cursor,
+                since: cursor.since,
+                latest,
+            }
+        } else {
+            Cursor {
+                next: None,
+                since: latest,
+                latest,
+            }
+        };
+        Ok(Pull {
+            items,
+            cursor: serde_json::to_string(&next)?,
+            has_more,
+        })
+    }
+
+    async fn push(
+        &mut self,
+        id: Option<&str>,
+        mutation: &Mutation,
+    ) -> RemoteResult<Option<String>> {
+        let api = self.api()?;
+        if let Mutation::Add { url, title, tags } = mutation {
+            let body = NewBookmark {
+                kind: "link",
+                url,
+                title: title.as_deref(),
+            };
+            let bookmark: Bookmark = api.call_json(Method::POST, "bookmarks", &body).await?;
+            if !tags.is_empty() {
+                let path = format!("bookmarks/{}/tags", bookmark.id);
+                api.call(Method::POST, &path, &TagsRequest::new(tags))
+                    .await?;
+            }
+            return Ok(Some(bookmark.id));
+        }
+
+        let Some(id) = id else {
+            return Err(RemoteError::NotLinked);
+        };
+        let path = format!("bookmarks/{id}");
+        let patch = match mutation {
+            Mutation::Add { .. } => unreachable!("handled above"),
+            Mutation::Delete => return api.delete(&path).await.map(|_| None),
+            Mutation::AddTags { tags } => {
+                let path = format!("{path}/tags");
+                api.call(Method::POST, &path, &TagsRequest::new(tags))
+                    .await?;
+                return Ok(None);
+            }
+            Mutation::RemoveTags { tags } => {
+                let path = format!("{path}/tags");
+                api.call(Method::DELETE, &path, &TagsRequest::new(tags))
+                    .await?;
+                return Ok(None);
+            }
+            Mutation::Archive | Mutation::Unarchive => BookmarkPatch {
+                archived: Some(*mutation == Mutation::Archive),
+                ..Default::default()
+            },
+            Mutation::Favorite | Mutation::Unfavorite => BookmarkPatch {
+                favourited: Some(*mutation == Mutation::Favorite),
+                ..Default::default()
+            },
+        };
+        api.call(Method::PATCH, &path, &patch).await?;
+        Ok(None)
+    }
+}
+
+/// Maps a karakeep link bookmark to a local item and its crawled content
+pub fn item(value: &Bookmark) -> Option<(Item, Option<String>)> {
+    let Content::Link {
+        url,
+        title,
+        description,
+        image_url,
+        html_content,
+        author,
+    } = &value.content
+    else {
+        return None;
+    };
+    let status = if value.archived {
+        ItemStatus::Archived
+    } else {
+        ItemStatus::Unread
+    };
+    let modified = value.modified_at().timestamp() as i32;
+    let item = Item {
+        title: value
+            .title
+            .clone()
+            .or_else(|| title.clone())
+            .unwrap_or_else(|| url.clone()),
+        url: url.clone(),
+        excerpt: description.clone(),
+        top_image_url: image_url.clone(),
+        tags: value
+            .tags
+            .iter()
+            .map(|tag| Tag {
+                id: 0,
+                tag: tag.name.clone(),
+                name: None,
+            })
+            .collect(),
+        authors: author
+            .iter()
+            .map(|name| Author {
+                id: 0,
+                name: name.clone(),
+                url: None,
+            })
+            .collect(),
+        status,
+        time_added: value.created_at.timestamp() as i32,
+        time_updated: Some(modified),
+        time_read: value.archived.then_some(modified),
+        favorite: value.favourited,
+        time_favorited: value.favourited.then_some(modified),
+        ..Default::default()
+    };
+    Some((item, html_content.clone()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use wiremock::matchers::{
+        body_json, header, method, path, query_param, query_param_is_missing,
+    };
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    const BOOKMARKS: &str = include_str!("../fixtures/karakeep/bookmarks.json");
+    const BOOKMARKS_2: &str = include_str!("../fixtures/karakeep/bookmarks-2.json");
+
+    fn backend(server: &MockServer) -> KarakeepBackend {
+        KarakeepBackend::new(KarakeepSettings {
+            url: Some(server.uri()),
+            api_key: Some("secret".to_string()),
+        })
+    }
+
+    async fn server() -> MockServer {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/api/v1/bookmarks"))
+            .and(header("authorization", "Bearer secret"))
+            .and(query_param_is_missing("cursor"))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(BOOKMARKS, "application/json"))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/api/v1/bookmarks"))
+            .and(query_param(
+                "cursor",
+                "eyJpZCI6IngycGw2cWswZjBjM20xamJ2OHdxMXJ5ZCJ9",
+            ))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(BOOKMARKS_2, "application/json"))
+            .mount(&server)
+            .await;
+        server
+    }
+
+    #[tokio::test]
+    async fn test_pull_links() {
+        let server = server().await;
+        let mut backend = backend(&server);
+
+        let first = backend.pull(None).await.unwrap();
+        assert!(first.has_more);
+        // the note has no url
+        assert_eq!(first.items.len(), 1);
+        let link = &first.items[0];
+        assert_eq!(link.id, "ieidlxygmwj87oxz5hxttoc8");
+        assert_eq!(link.item.title, "Understanding async Rust");
+        assert!(link.item.favorite);
+        assert_eq!(link.item.tags.len(), 2);
+        assert!(link.item.authors.iter().any(|a| a.name == "Jane Doe"));
+        assert!(link
+            .content
+            .as_deref()
+            .unwrap()
+            .contains("<h2>Futures</h2>"));
+
+        let second = backend.pull(Some(&first.cursor)).await.unwrap();
+        assert!(!second.has_more);
+        assert_eq!(second.items[0].item.title, "Kept for later");
+        assert_eq!(second.items[0].item.status, ItemStatus::Archived);
+
+        // nothing changed since the last walk
+        let third = backend.pull(Some(&second.cursor)).await.unwrap();
+        assert!(third.items.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_push_mutations() {
+        let server = MockServer::start().await;
+        let bookmarks: serde_json::Value = serde_json::from_str(BOOKMARKS).unwrap();
+        Mock::given(method("POST"))
+            .and(path("/api/v1/bookmarks"))
+            .and(body_json(serde_json::json!({
+                "type": "link",
+                "url": "https://blog.example.com/posts/rust-async"
+            })))
+            .respond_with(
+                ResponseTemplate::new(201).set_body_json(bookmarks["bookmarks"][0].clone()),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/api/v1/bookmarks/ieidlxygmwj87oxz5hxttoc8/tags"))
+            .and(body_json(
+                serde_json::json!({"tags": [{"tagName": "rust"}]}),
+            ))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({})))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("PATCH"))
+            .and(path("/api/v1/bookmarks/ieidlxygmwj87oxz5hxttoc8"))
+            .and(body_json(serde_json::json!({"favourited": false})))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({})))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let mut backend = backend(&server);
+        let add = Mutation::Add {
+            url: "https://blog.example.com/posts/rust-async".to_string(),
+            title: None,
+            tags: vec!["rust".to_string()],
+        };
+        let id = backend.push(None, &add).await.unwrap();
+        assert_eq!(id.as_deref(), Some("ieidlxygmwj87oxz5hxttoc8"));
+        backend
+            .push(id.as_deref(), &Mutation::Unfavorite)
+            .await
+            .unwrap();
+    }
+}
@@ -0,0 +1,173 @@
+mod error;
+mod http;
+pub mod karakeep;
+pub mod linkding;
+pub mod pocket;
+pub mod readeck;
+mod sync;
+pub mod wallabag;
+
+pub use error::{RemoteError, RemoteResult};
+pub use sync::{enqueue, enqueue_to, pull, push, sync, SyncReport};
+
+use async_trait::async_trait;
+use localdb::Item;
+use serde::{Deserialize, Serialize};
+
+/// Names of the backends [`open`] knows about
+pub const BACKENDS: &[&str] = &[
+    pocket::NAME,
+    wallabag::NAME,
+    readeck::NAME,
+    linkding::NAME,
+    karakeep::NAME,
+];
+
+/// What a backend is able to do on the remote side
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Capabilities {
+    pub add: bool,
+    pub archive: bool,
+    pub favorite: bool,
+    pub delete: bool,
+    pub tags: bool,
+    /// Pulled items come with the extracted article
+    pub content: bool,
+}
+
+impl Capabilities {
+    pub fn supports(&self, mutation: &Mutation) -> bool {
+        match mutation {
+            Mutation::Add { .. } => self.add,
+            Mutation::Archive | Mutation::Unarchive => self.archive,
+            Mutation::Favorite | Mutation::Unfavorite => self.favorite,
+            Mutation::Delete => self.delete,
+            Mutation::AddTags { .. } | Mutation::RemoveTags { .. } => self.tags,
+        }
+    }
+}
+
+/// A local change to replay on a remote
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum Mutation {
+    Add {
+        url: String,
+        title: Option<String>,
+        tags: Vec<String>,
+    },
+    Archive,
+    Unarchive,
+    Favorite,
+    Unfavorite,
+    Delete,
+    AddTags {
+        tags: Vec<String>,
+    },
+    RemoveTags {
+        tags: Vec<String>,
+    },
+}
+
+/// An item as seen by a remote
+#[derive(Debug, Clone)]
+pub struct RemoteItem {
+    /// Id of the item on the remote
+    pub id: String,
+    pub item: Item,
+    /// Extracted article html, when the backend provides it
+    pub content: Option<String>,
+}
+
+/// One page of changes from a remote
+#[derive(Debug)]
+pub struct Pull {
+    pub items: Vec<RemoteItem>,
+    /// Where the next pull starts from
+    pub cursor: String,
+    pub has_more: bool,
+}
+
+/// Asks the user for what a backend needs to log in
+pub trait Prompt: Send {
+    fn show(&mut self, message: &str);
+    fn ask(&mut self, question: &str) -> String;
+    fn password(&mut self, question: &str) -> String;
+}
+
+#[async_trait]
+pub trait RemoteBackend: Send {
+    fn capabilities(&self) -> Capabilities;
+
+    /// Settings and credentials to store, they change after authenticating
+    /// and whenever a backend refreshes its tokens.
+    fn settings(&self) -> serde_json::Value;
+
+    async fn authenticate(&mut self, prompt: &mut dyn Prompt) -> RemoteResult<()>;
+
+    /// Fetches the items changed since `cursor`, or every item without one
+    async fn pull(&mut self, cursor: Option<&str>) -> RemoteResult<Pull>;
+
+    /// Applies a change to the item the remote knows as `id`. Returns the id
+    /// of newly added items, when the remote tells it. Changes to an item
+    /// without `id` fail with [`RemoteError::NotLinked`].
+    async fn push(&mut self, id: Option<&str>, mutation: &Mutation)
+        -> RemoteResult<Option<String>>;
+}
+
+/// Creates the backend called `backend` from its stored settings
+pub fn open(backend: &str, settings: &str) -> RemoteResult<Box<dyn RemoteBackend>> {
+    let settings = if settings.trim().is_empty() {
+        "{}"
+    } else {
+        settings
+    };
+    match backend {
+        pocket::NAME => Ok(Box::new(pocket::PocketBackend::new(serde_json::from_str(
+            settings,
+        )?))),
+        wallabag::NAME => Ok(Box::new(wallabag::WallabagBackend::new(
+            serde_json::from_str(settings)?,
+        ))),
+        readeck::NAME => Ok(Box::new(readeck::ReadeckBackend::new(
+            serde_json::from_str(settings)?,
+        ))),
+        linkding::NAME => Ok(Box::new(linkding::LinkdingBackend::new(
+            serde_json::from_str(settings)?,
+        ))),
+        karakeep::NAME => Ok(Box::new(karakeep::KarakeepBackend::new(
+            serde_json::from_str(settings)?,
+        ))),
+        _ => Err(RemoteError::UnknownBackend(backend.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_mutation_json() {
+        let mutation = Mutation::AddTags {
+            tags: vec!["rust".to_string()],
+        };
+        let json = serde_json::to_string(&mutation).unwrap();
+        assert_eq!(json, r#"{"action":"add_tags","tags":["rust"]}"#);
+        assert_eq!(serde_json::from_str::<Mutation>(&json).unwrap(), mutation);
+        assert_eq!(
+            serde_json::to_string(&Mutation::Archive).unwrap(),
+            r#"{"action":"archive"}"#
+        );
+    }
+
+    #[test]
+    fn test_open_backends() {
+        for backend in BACKENDS {
+            assert!(open(backend, "").is_ok());
+        }
+        assert!(matches!(
+            open("delicious", "{}"),
+            Err(RemoteError::UnknownBackend(_))
+        ));
+    }
+}
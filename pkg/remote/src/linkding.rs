@@ -0,0 +1,407 @@
+use crate::http::Api;
+use crate::RemoteResult;
+use crate::{Capabilities, Mutation, Prompt, Pull, RemoteBackend, RemoteError, RemoteItem};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use localdb::{Item, ItemStatus, Tag};
+use reqwest::Method;
+use serde::{Deserialize, Serialize};
+
+pub const NAME: &str = "linkding";
+
+const LIMIT: usize = 100;
+
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub struct LinkdingSettings {
+    /// Address of the instance, e.g. https://links.example.com
+    pub url: Option<String>,
+    /// REST API token from the integrations settings
+    pub token: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Bookmark {
+    pub id: i64,
+    pub url: String,
+    /// Title set by the user, empty when the website title is used
+    pub title: String,
+    pub description: String,
+    pub preview_image_url: Option<String>,
+    pub is_archived: bool,
+    pub tag_names: Vec<String>,
+    pub date_added: DateTime<Utc>,
+    pub date_modified: DateTime<Utc>,
+    pub website_title: Option<String>,
+    pub website_description: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Bookmarks {
+    next: Option<String>,
+    results: Vec<Bookmark>,
+}
+
+#[derive(Serialize)]
+struct NewBookmark<'a> {
+    url: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    title: Option<&'a str>,
+    tag_names: &'a [String],
+    unread: bool,
+}
+
+#[derive(Serialize)]
+struct TagsPatch<'a> {
+    tag_names: &'a [String],
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct Cursor {
+    since: Option<DateTime<Utc>>,
+    offset: usize,
+    /// Archived bookmarks have their own listing, paged after the others
+    archived: bool,
+    latest: Option<DateTime<Utc>>,
+}
+
+pub struct LinkdingBackend {
+    settings: LinkdingSettings,
+}
+
+impl LinkdingBackend {
+    pub fn new(settings: LinkdingSettings) -> LinkdingBackend {
+        LinkdingBackend { settings }
+    }
+
+    fn api(&self) -> RemoteResult<Api> {
+        let (Some(url), Some(token)) = (&self.settings.url, &self.settings.token) else {
+            return Err(RemoteError::NotAuthenticated);
+        };
+        Api::new(
+            &format!("{}/api", url.trim_end_matches('/')),
+            format!("Token {token}"),
+        )
+    }
+}
+
+#[async_trait]
+impl RemoteBackend for LinkdingBackend {
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            add: true,
+            archive: true,
+            favorite: false,
+            delete: true,
+            tags: true,
+            content: false,
+        }
+    }
+
+    fn settings(&self) -> serde_json::Value {
+        serde_json::to_value(&self.settings).expect("linkding settings are serializable")
+    }
+
+    async fn authenticate(&mut self, prompt: &mut dyn Prompt) -> RemoteResult<()> {
+        if self.settings.url.is_none() {
+            self.settings.url = Some(prompt.ask("Address of the linkding instance:"));
+        }
+        prompt.show("The token is shown in Settings > Integrations");
+        self.settings.token = Some(prompt.password("REST API token:"));
+        let _: Bookmarks = self.api()?.get("bookmarks/", &[("limit", 1)]).await?;
+        Ok(())
+    }
+
+    async fn pull(&mut self, cursor: Option<&str>) -> RemoteResult<Pull> {
+        let cursor: Cursor = match cursor {
+            Some(cursor) => serde_json::from_str(cursor)?,
+            None => Cursor::default(),
+        };
+        let mut query = vec![
+            ("limit", LIMIT.to_string()),
+            ("offset", cursor.offset.to_string()),
+        ];
+        if let Some(since) = cursor.since {
+            query.push(("modified_since", since.to_rfc3339()));
+        }
+        let path = if cursor.archived {
+            "bookmarks/archived/"
+        } else {
+            "bookmarks/"
+        };
+        let bookmarks: Bookmarks = self.api()?.get(path, &query).await?;
+
+        let latest = bookmarks
+            .results
+            .iter()
+            .map(|bookmark| bookmark.date_modified)
+            .chain(cursor.latest)
+            .chain(cursor.since)
+            .max();
+        let (next, has_more) = match (bookmarks.next.is_some(), cursor.archived) {
+            (true, _) => {
+                let next = Cursor {
+                    offset: cursor.offset + LIMIT,
+                    latest,
+                    ..cursor
+                };
+                (next, true)
+            }
+            (false, false) => {
+                let next = Cursor {
+                    offset: 0,
+                    archived: true,
+                    latest,
+                    ..cursor
+                };
+                (next, true)
+            }
+            (false, true) => {
+                let next = Cursor {
+                    since: latest,
+                    offset: 0,
+                    archived: false,
+                    latest,
+                };
+                (next, false)
+            }
+        };
+        let items = bookmarks
+            .results
+            .iter()
+            .map(|bookmark| RemoteItem {
+                id: bookmark.id.to_string(),
+                item: item(bookmark),
+                content: None,
+            })
+            .collect();
+        Ok(Pull {
+            items,
+            cursor: serde_json::to_string(&next)?,
+            has_more,
+        })
+    }
+
+    async fn push(
+        &mut self,
+        id: Option<&str>,
+        mutation: &Mutation,
+    ) -> RemoteResult<Option<String>> {
+        let api = self.api()?;
+        if let Mutation::Add { url, title, tags } = mutation {
+            let body = NewBookmark {
+                url,
+                title: title.as_deref(),
+                tag_names: tags,
+                unread: true,
+            };
+            let bookmark: Bookmark = api.call_json(Method::POST, "bookmarks/", &body).await?;
+            return Ok(Some(bookmark.id.to_string()));
+        }
+
+        let Some(id) = id else {
+            return Err(RemoteError::NotLinked);
+        };
+        let path = format!("bookmarks/{id}/");
+        match mutation {
+            Mutation::Add { .. } => unreachable!("handled above"),
+            Mutation::Archive => {
+                api.call(Method::POST, &format!("{path}archive/"), &())
+                    .await?
+            }
+            Mutation::Unarchive => {
+                api.call(Method::POST, &format!("{path}unarchive/"), &())
+                    .await?
+            }
+            Mutation::Delete => api.delete(&path).await?,
+            Mutation::AddTags { tags } | Mutation::RemoveTags { tags } => {
+                // the api replaces the whole list of tags
+                let bookmark: Bookmark = api.get(&path, &()).await?;
+                let mut tag_names = bookmark.tag_names;
+                match mutation {
+                    Mutation::AddTags { .. } => {
+                        for tag in tags.iter() {
+                            if !tag_names.contains(tag) {
+                                tag_names.push(tag.clone());
+                            }
+                        }
+                    }
+                    _ => tag_names.retain(|t| !tags.contains(t)),
+                }
+                let patch = TagsPatch {
+                    tag_names: &tag_names,
+                };
+                api.call(Method::PATCH, &path, &patch).await?
+            }
+            Mutation::Favorite | Mutation::Unfavorite => {}
+        }
+        Ok(None)
+    }
+}
+
+/// Maps a linkding bookmark to a local item. Titles and descriptions set by
+/// the user win over the ones of the website.
+pub fn item(value: &Bookmark) -> Item {
+    let title = Some(value.title.clone())
+        .filter(|t| !t.is_empty())
+        .or_else(|| value.website_title.clone())
+        .unwrap_or_else(|| value.url.clone());
+    let excerpt = Some(value.description.clone())
+        .filter(|d| !d.is_empty())
+        .or_else(|| value.website_description.clone());
+    let status = if value.is_archived {
+        ItemStatus::Archived
+    } else {
+        ItemStatus::Unread
+    };
+    Item {
+        title,
+        url: value.url.clone(),
+        excerpt,
+        top_image_url: value.preview_image_url.clone(),
+        tags: value
+            .tag_names
+            .iter()
+            .map(|name| Tag {
+                id: 0,
+                tag: name.clone(),
+                name: None,
+            })
+            .collect(),
+        status,
+        time_added: value.date_added.timestamp() as i32,
+        time_updated: Some(value.date_modified.timestamp() as i32),
+        time_read: value
+            .is_archived
+            .then_some(value.date_modified.timestamp() as i32),
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use wiremock::matchers::{body_json, header, method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    const BOOKMARKS: &str = include_str!("../fixtures/linkding/bookmarks.json");
+    const ARCHIVED: &str = include_str!("../fixtures/linkding/archived.json");
+
+    fn backend(server: &MockServer) -> LinkdingBackend {
+        LinkdingBackend::new(LinkdingSettings {
+            url: Some(server.uri()),
+            token: Some("secret".to_string()),
+        })
+    }
+
+    #[tokio::test]
+    async fn test_pull_bookmarks_then_archived() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/api/bookmarks/"))
+            .and(header("authorization", "Token secret"))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(BOOKMARKS, "application/json"))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/api/bookmarks/archived/"))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(ARCHIVED, "application/json"))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let mut backend = backend(&server);
+        let first = backend.pull(None).await.unwrap();
+        assert!(first.has_more);
+        assert_eq!(first.items.len(), 2);
+        let item = &first.items[0].item;
+        assert_eq!(first.items[0].id, "12");
+        assert_eq!(item.title, "Understanding async Rust");
+        assert_eq!(
+            item.excerpt.as_deref(),
+            Some("A tour of futures, executors and pinning.")
+        );
+        assert_eq!(item.status, ItemStatus::Unread);
+        assert_eq!(first.items[1].item.title, "My own title");
+
+        let second = backend.pull(Some(&first.cursor)).await.unwrap();
+        assert!(!second.has_more);
+        assert_eq!(second.items[0].item.status, ItemStatus::Archived);
+        let cursor: Cursor = serde_json::from_str(&second.cursor).unwrap();
+        assert!(!cursor.archived);
+        assert_eq!(
+            cursor.since.unwrap().to_rfc3339(),
+            "2025-03-05T12:00:00+00:00"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_pull_since_cursor() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/api/bookmarks/"))
+            .and(query_param("modified_since", "2025-03-05T12:00:00+00:00"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "count": 0, "next": null, "previous": null, "results": []
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let cursor =
+            r#"{"since":"2025-03-05T12:00:00Z","offset":0,"archived":false,"latest":null}"#;
+        let pull = backend(&server).pull(Some(cursor)).await.unwrap();
+        assert!(pull.items.is_empty());
+        assert!(pull.has_more);
+    }
+
+    #[tokio::test]
+    async fn test_push_mutations() {
+        let server = MockServer::start().await;
+        let bookmark: serde_json::Value = serde_json::from_str(BOOKMARKS).unwrap();
+        let bookmark = bookmark["results"][0].clone();
+        Mock::given(method("POST"))
+            .and(path("/api/bookmarks/"))
+            .and(body_json(serde_json::json!({
+                "url": "https://blog.example.com/posts/rust-async",
+                "tag_names": [],
+                "unread": true
+            })))
+            .respond_with(ResponseTemplate::new(201).set_body_json(bookmark.clone()))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/api/bookmarks/12/archive/"))
+            .respond_with(ResponseTemplate::new(204))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/api/bookmarks/12/"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(bookmark))
+            .mount(&server)
+            .await;
+        Mock::given(method("PATCH"))
+            .and(path("/api/bookmarks/12/"))
+            .and(body_json(serde_json::json!({"tag_names": ["async"]})))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({})))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let mut backend = backend(&server);
+        let add = Mutation::Add {
+            url: "https://blog.example.com/posts/rust-async".to_string(),
+            title: None,
+            tags: vec![],
+        };
+        let id = backend.push(None, &add).await.unwrap();
+        assert_eq!(id.as_deref(), Some("12"));
+        backend.push(Some("12"), &Mutation::Archive).await.unwrap();
+        let remove = Mutation::RemoveTags {
+            tags: vec!["rust".to_string()],
+        };
+        backend.push(Some("12"), &remove).await.unwrap();
+    }
+}
@@ -0,0 +1,323 @@
+use crate::RemoteResult;
+use crate::{Capabilities, Mutation, Prompt, Pull, RemoteBackend, RemoteError, RemoteItem};
+use async_trait::async_trait;
+use localdb::{Author, HasImage, HasVideo, Image, Item, ItemStatus, Tag, Video};
+use pocket::modify::{AddUrlRequest, ModifyItem};
+use pocket::{GetOptions, PocketClient, State};
+use serde::{Deserialize, Serialize};
+
+pub const NAME: &str = "pocket";
+
+const REDIRECT_URI: &str = "https://localhost:3000";
+/// Items per request, the maximum pocket allows
+const COUNT: i32 = 30;
+
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub struct PocketSettings {
+    #[serde(default)]
+    pub consumer_key: String,
+    pub access_token: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct Cursor {
+    since: i32,
+    offset: i32,
+}
+
+pub struct PocketBackend {
+    settings: PocketSettings,
+}
+
+impl PocketBackend {
+    pub fn new(settings: PocketSettings) -> PocketBackend {
+        PocketBackend { settings }
+    }
+
+    fn client(&self) -> RemoteResult<PocketClient<'_>> {
+        let access_token = self
+            .settings
+            .access_token
+            .as_deref()
+            .ok_or(RemoteError::NotAuthenticated)?;
+        Ok(PocketClient::new(&self.settings.consumer_key, access_token))
+    }
+}
+
+#[async_trait]
+impl RemoteBackend for PocketBackend {
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            add: true,
+            archive: true,
+            favorite: true,
+            delete: true,
+            tags: false,
+            content: false,
+        }
+    }
+
+    fn settings(&self) -> serde_json::Value {
+        serde_json::to_value(&self.settings).expect("pocket settings are serializable")
+    }
+
+    async fn authenticate(&mut self, prompt: &mut dyn Prompt) -> RemoteResult<()> {
+        let auth_client = pocket::auth::PocketAuthClient::new(
+            self.settings.consumer_key.clone(),
+            REDIRECT_URI.to_string(),
+        );
+        let login_code = auth_client.login_code().await?;
+        let redirection_uri = pocket::auth::redirection_uri(&login_code, REDIRECT_URI);
+        prompt.show(&format!("Please visit {}", redirection_uri));
+        prompt.ask("Please press enter after you have authenticated");
+
+        let login_response = auth_client.access_token(&login_code).await?;
+        self.settings.access_token = Some(login_response.access_token);
+        Ok(())
+    }
+
+    async fn pull(&mut self, cursor: Option<&str>) -> RemoteResult<Pull> {
+        let cursor: Cursor = match cursor {
+            Some(cursor) => serde_json::from_str(cursor)?,
+            None => Cursor::default(),
+        };
+        let response = self
+            .client()?
+            .get(GetOptions {
+                state: Some(State::All),
+                since: Some(cursor.since),
+                offset: Some(cursor.offset),
+                detail_type: Some(pocket::DetailType::Complete),
+                count: COUNT,
+                ..GetOptions::for_pagination()
+            })
+            .await?;
+
+        let has_more = !response.list.is_empty() && response.has_more()?;
+        let next = if has_more {
+            Cursor {
+                since: cursor.since,
+                offset: cursor.offset + COUNT,
+            }
+        } else {
+            Cursor {
+                since: response.since,
+                offset: 0,
+            }
+        };
+        let items = response
+            .list
+            .values()
+            .map(|value| RemoteItem {
+                id: value.item_id.to_string(),
+                item: item(value),
+                content: None,
+            })
+            .collect();
+        Ok(Pull {
+            items,
+            cursor: serde_json::to_string(&next)?,
+            has_more,
+        })
+    }
+
+    async fn push(
+        &mut self,
+        id: Option<&str>,
+        mutation: &Mutation,
+    ) -> RemoteResult<Option<String>> {
+        let mut client = self.client()?;
+        if let Mutation::Add { url, title, tags } = mutation {
+            let Ok(url) = url.parse() else {
+                return Ok(None);
+            };
+            let mut request = AddUrlRequest::new(url).tags(tags.clone());
+            request.title = title.clone();
+            client.add(vec![request]).await?;
+            // pocket does not tell the id, the next pull links the item by url
+            return Ok(None);
+        }
+
+        let Some(id) = id.and_then(|id| id.parse().ok()) else {
+            return Err(RemoteError::NotLinked);
+        };
+        let action = match mutation {
+            Mutation::Archive => ModifyItem::archive(id),
+            Mutation::Unarchive => ModifyItem::readd(id),
+            Mutation::Favorite => ModifyItem::favorite(id),
+            Mutation::Unfavorite => ModifyItem::unfavorite(id),
+            Mutation::Delete => ModifyItem::delete(id),
+            _ => return Ok(None),
+        };
+        client.send(vec![action]).await?;
+        Ok(None)
+    }
+}
+
+/// Maps a pocket item to a local item
+pub fn item(value: &pocket::Item) -> Item {
+    Item {
+        id: value.item_id,
+        title: value.resolved_title.clone(),
+        url: value.resolved_url.clone(),
+        excerpt: Some(value.excerpt.clone()),
+        is_article: Some(*value.is_article),
+        is_index: Some(*value.is_article),
+        has_video: Some(has_video(value.has_video)),
+        has_image: Some(has_image(value.has_image)),
+        word_count: Some(value.word_count),
+        lang: Some(value.lang.clone()),
+        time_to_read: Some(value.time_to_read),
+        top_image_url: value.top_image_url.clone(),
+        listen_duration_estimate: Some(value.listen_duration_estimate),
+        tags: value
+            .tags
+            .values()
+            .map(|tag| Tag {
+                id: 0,
+                tag: tag.tag.clone(),
+                name: None,
+            })
+            .collect(),
+        authors: value
+            .authors
+            .iter()
+            .flat_map(|authors| authors.values())
+            .map(|author| Author {
+                id: author.author_id,
+                name: author.name.clone(),
+                url: author.url.clone(),
+            })
+            .collect(),
+        images: value
+            .images
+            .iter()
+            .flat_map(|images| images.values())
+            .map(|image| Image {
+                id: image.image_id,
+                src: image.src.clone(),
+                width: image.width,
+                height: image.height,
+                caption: Some(image.caption.clone()),
+                credit: Some(image.credit.clone()),
+            })
+            .collect(),
+        videos: value
+            .videos
+            .iter()
+            .flat_map(|videos| videos.values())
+            .map(|video| Video {
+                id: video.video_id,
+                src: video.src.clone(),
+                width: video.width,
+                height: video.height,
+                kind: Some(video.kind.clone()),
+            })
+            .collect(),
+        status: status(value.status),
+        time_added: value.time_added,
+        time_updated: Some(value.time_updated),
+        time_read: Some(value.time_read),
+        favorite: *value.favorite,
+        // 0 when the item was never favorited
+        time_favorited: (value.time_favorited > 0).then_some(value.time_favorited),
+        ..Default::default()
+    }
+}
+
+fn status(status: pocket::item::ItemStatus) -> ItemStatus {
+    match status {
+        pocket::item::ItemStatus::Unread => ItemStatus::Unread,
+        pocket::item::ItemStatus::Archived => ItemStatus::Archived,
+        pocket::item::ItemStatus::Deleted => ItemStatus::Deleted,
+    }
+}
+
+fn has_video(has_video: pocket::item::HasVideo) -> HasVideo {
+    match has_video {
+        pocket::item::HasVideo::No => HasVideo::No,
+        pocket::item::HasVideo::Yes => HasVideo::Yes,
+        pocket::item::HasVideo::IsVideo => HasVideo::IsVideo,
+    }
+}
+
+fn has_image(has_image: pocket::item::HasImage) -> HasImage {
+    match has_image {
+        pocket::item::HasImage::No => HasImage::No,
+        pocket::item::HasImage::Yes => HasImage::Yes,
+        pocket::item::HasImage::IsImage => HasImage::IsImage,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const ITEM: &str = r#"{
+        "item_id": "229279689",
+        "resolved_id": "229279689",
+        "given_url": "http://www.grantland.com/blog/the-triangle/post/_/id/38347/ryder-cup-preview",
+        "given_title": "The Massive Ryder Cup Preview",
+        "favorite": "1",
+        "status": "1",
+        "time_added": "1473020307",
+        "time_updated": "1473020800",
+        "time_read": "1473020800",
+        "time_favorited": "1473020500",
+        "sort_id": 0,
+        "resolved_title": "The Massive Ryder Cup Preview",
+        "resolved_url": "http://www.grantland.com/blog/the-triangle/post/_/id/38347/ryder-cup-preview",
+        "excerpt": "The list of things I love about the Ryder Cup is so long",
+        "is_article": "1",
+        "is_index": "0",
+        "has_video": "1",
+        "has_image": "1",
+        "word_count": "3197",
+        "lang": "en",
+        "time_to_read": 15,
+        "top_image_url": null,
+        "listen_duration_estimate": 1238,
+        "tags": {"golf": {"item_id": "229279689", "tag": "golf"}},
+        "authors": {"3": {"author_id": "3", "name": "Bill Barnwell", "url": "http://grantland.com/contributors/bill-barnwell/"}},
+        "images": {"1": {"item_id": "229279689", "image_id": "1", "src": "http://a.espncdn.com/i.jpg", "width": "0", "height": "0", "credit": "", "caption": ""}},
+        "videos": {"1": {"item_id": "229279689", "video_id": "1", "src": "http://www.youtube.com/v/Er34PbFkVGk", "width": "420", "height": "315", "type": "1", "vid": "Er34PbFkVGk"}}
+    }"#;
+
+    #[test]
+    fn test_map_item() {
+        let value: pocket::Item = serde_json::from_str(ITEM).unwrap();
+        let item = item(&value);
+        assert_eq!(item.title, "The Massive Ryder Cup Preview");
+        assert_eq!(item.status, ItemStatus::Archived);
+        assert_eq!(item.has_video, Some(HasVideo::Yes));
+        assert!(item.favorite);
+        assert_eq!(item.time_favorited, Some(1473020500));
+        assert!(item.tags.iter().any(|tag| tag.tag == "golf"));
+        assert!(item.authors.iter().any(|a| a.name == "Bill Barnwell"));
+        assert_eq!(item.images.len(), 1);
+        assert_eq!(item.videos.len(), 1);
+    }
+
+    #[test]
+    fn test_map_item_never_favorited() {
+        let json = ITEM
+            .replace(r#""favorite": "1""#, r#""favorite": "0""#)
+            .replace(
+                r#""time_favorited": "1473020500""#,
+                r#""time_favorited": "0""#,
+            );
+        let value: pocket::Item = serde_json::from_str(&json).unwrap();
+        let item = item(&value);
+        assert!(!item.favorite);
+        assert_eq!(item.time_favorited, None);
+    }
+
+    #[tokio::test]
+    async fn test_requires_access_token() {
+        let mut backend = PocketBackend::new(PocketSettings::default());
+        assert!(matches!(
+            backend.pull(None).await,
+            Err(RemoteError::NotAuthenticated)
+        ));
+    }
+}
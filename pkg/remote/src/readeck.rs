@@ -0,0 +1,490 @@
+use crate::http::Api;
+use crate::RemoteResult;
+use crate::{Capabilities, Mutation, Prompt, Pull, RemoteBackend, RemoteError, RemoteItem};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use localdb::{Author, HasVideo, Item, ItemStatus, Tag};
+use reqwest::Method;
+use serde::{Deserialize, Serialize};
+
+pub const NAME: &str = "readeck";
+
+const LIMIT: usize = 50;
+
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub struct ReadeckSettings {
+    /// Address of the instance, e.g. https://readeck.example.com
+    pub url: Option<String>,
+    /// API token created in the readeck settings
+    pub token: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Bookmark {
+    pub id: String,
+    pub created: DateTime<Utc>,
+    pub updated: DateTime<Utc>,
+    pub url: String,
+    pub title: String,
+    #[serde(default)]
+    pub authors: Vec<String>,
+    #[serde(default)]
+    pub lang: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub has_article: bool,
+    #[serde(default)]
+    pub description: String,
+    pub is_deleted: bool,
+    pub is_marked: bool,
+    pub is_archived: bool,
+    #[serde(default)]
+    pub labels: Vec<String>,
+    pub word_count: Option<i32>,
+    pub reading_time: Option<i32>,
+    #[serde(default)]
+    pub resources: Resources,
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub struct Resources {
+    pub image: Option<Resource>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Resource {
+    pub src: String,
+}
+
+#[derive(Serialize)]
+struct NewBookmark<'a> {
+    url: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    title: Option<&'a str>,
+    labels: &'a [String],
+}
+
+#[derive(Serialize, Default)]
+struct BookmarkPatch<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    is_archived: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    is_marked: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    add_labels: Option<&'a [String]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    remove_labels: Option<&'a [String]>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct Cursor {
+    since: Option<DateTime<Utc>>,
+    offset: usize,
+    /// Latest update seen while paging, the `since` of the next sync
+    latest: Option<DateTime<Utc>>,
+}
+
+pub struct ReadeckBackend {
+    settings: ReadeckSettings,
+}
+
+impl ReadeckBackend {
+    pub fn new(settings: ReadeckSettings) -> ReadeckBackend {
+        ReadeckBackend { settings }
+    }
+
+    fn api(&self) -> RemoteResult<Api> {
+        let (Some(url), Some(token)) = (&self.settings.url, &self.settings.token) else {
+            return Err(RemoteError::NotAuthenticated);
+        };
+        Api::new(
+            &format!("{}/api", url.trim_end_matches('/')),
+            format!("Bearer {token}"),
+        )
+    }
+
+    async fn article(&self, api: &Api, id: &str) -> RemoteResult<String> {
+        let (url, request) = api.request(Method::GET, &format!("bookmarks/{id}/article"))?;
+        let res = api.send(&url, request).await?;
+        res.text().await.map_err(|source| RemoteError::Http {
+            url: url.to_string(),
+            source,
+        })
+    }
+}
+
+#[async_trait]
+impl RemoteBackend for ReadeckBackend {
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            add: true,
+            archive: true,
+            favorite: true,
+            delete: true,
+            tags: true,
+            content: true,
+        }
+    }
+
+    fn settings(&self) -> serde_json::Value {
+        serde_json::to_value(&self.settings).expect("readeck settings are serializable")
+    }
+
+    async fn authenticate(&mut self, prompt: &mut dyn Prompt) -> RemoteResult<()> {
+        if self.settings.url.is_none() {
+            self.settings.url = Some(prompt.ask("Address of the readeck instance:"));
+        }
+        prompt.show("Create an API token in Settings > API Tokens");
+        self.settings.token = Some(prompt.password("API token:"));
+        // checks the token
+        let _: Vec<Bookmark> = self.api()?.get("bookmarks", &[("limit", 1)]).await?;
+        Ok(())
+    }
+
+    async fn pull(&mut self, cursor: Option<&str>) -> RemoteResult<Pull> {
+        let cursor: Cursor = match cursor {
+            Some(cursor) => serde_json::from_str(cursor)?,
+            None => Cursor::default(),
+        };
+        let api = self.api()?;
+        let mut query = vec![
+            ("limit", LIMIT.to_string()),
+            ("offset", cursor.offset.to_string()),
+        ];
+        if let Some(since) = cursor.since {
+            query.push(("updated_since", since.to_rfc3339()));
+        }
+        let bookmarks: Vec<Bookmark> = api.get("bookmarks", &query).await?;
+
+        let mut items = Vec::with_capacity(bookmarks.len());
+        for bookmark in bookmarks.iter() {
+            let content = if bookmark.has_article {
+                Some(self.article(&api, &bookmark.id).await?)
+            } else {
+                None
+            };
+            items.push(RemoteItem {
+                id: bookmark.id.clone(),
+                item: item(bookmark),
+                content,
+            });
+        }
+
+        let latest = bookmarks
+            .iter()
+            .map(|bookmark| bookmark.updated)
+            .chain(cursor.latest)
+            .chain(cursor.since)
+            .max();
+        let has_more = bookmarks.len() == LIMIT;
+        let next = if has_more {
+            Cursor {
+                offset: cursor.offset + LIMIT,
+                latest,
+                ..cursor
+            }
+        } else {
+            Cursor {
+                since: latest,
+                offset: 0,
+                latest,
+            }
+        };
+        Ok(Pull {
+            items,
+            cursor: serde_json::to_string(&next)?,
+            has_more,
+        })
+    }
+
+    async fn push(
+        &mut self,
+        id: Option<&str>,
+        mutation: &Mutation,
+    ) -> RemoteResult<Option<String>> {
+        let api = self.api()?;
+        if let Mutation::Add { url, title, tags } = mutation {
+            let body = NewBookmark {
+                url,
+                title: title.as_deref(),
+                labels: tags,
+            };
+            let (request_url, request) = api.request(Method::POST, "bookmarks")?;
+            let res = api.send(&request_url, request.json(&body)).await?;
+            // the bookmark is created asynchronously, its id comes in a header
+            let id = res
+                .headers()
+                .get("Bookmark-Id")
+                .and_then(|id| id.to_str().ok())
+                .map(String::from);
+            return Ok(id);
+        }
+
+        let Some(id) = id else {
+            return Err(RemoteError::NotLinked);
+        };
+        let path = format!("bookmarks/{id}");
+        let patch = match mutation {
+            Mutation::Add { .. } => unreachable!("handled above"),
+            Mutation::Delete => {
+                api.delete(&path).await?;
+                return Ok(None);
+            }
+            Mutation::Archive | Mutation::Unarchive => BookmarkPatch {
+                is_archived: Some(*mutation == Mutation::Archive),
+                ..Default::default()
+            },
+            Mutation::Favorite | Mutation::Unfavorite => BookmarkPatch {
+                is_marked: Some(*mutation == Mutation::Favorite),
+                ..Default::default()
+            },
+            Mutation::AddTags { tags } => BookmarkPatch {
+                add_labels: Some(tags),
+                ..Default::default()
+            },
+            Mutation::RemoveTags { tags } => BookmarkPatch {
+                remove_labels: Some(tags),
+                ..Default::default()
+            },
+        };
+        api.call(Method::PATCH, &path, &patch).await?;
+        Ok(None)
+    }
+}
+
+/// Maps a readeck bookmark to a local item
+pub fn item(value: &Bookmark) -> Item {
+    let status = if value.is_deleted {
+        ItemStatus::Deleted
+    } else if value.is_archived {
+        ItemStatus::Archived
+    } else {
+        ItemStatus::Unread
+    };
+    let updated = value.updated.timestamp() as i32;
+    Item {
+        title: value.title.clone(),
+        url: value.url.clone(),
+        excerpt: Some(value.description.clone()).filter(|d| !d.is_empty()),
+        is_article: Some(value.kind == "article"),
+        has_video: Some(if value.kind == "video" {
+            HasVideo::IsVideo
+        } else {
+            HasVideo::No
+        }),
+        word_count: value.word_count,
+        lang: Some(value.lang.clone()).filter(|l| !l.is_empty()),
+        time_to_read: value.reading_time,
+        top_image_url: value.resources.image.as_ref().map(|i| i.src.clone()),
+        tags: value
+            .labels
+            .iter()
+            .map(|label| Tag {
+                id: 0,
+                tag: label.clone(),
+                name: None,
+            })
+            .collect(),
+        authors: value
+            .authors
+            .iter()
+            .map(|name| Author {
+                id: 0,
+                name: name.clone(),
+                url: None,
+            })
+            .collect(),
+        status,
+        time_added: value.created.timestamp() as i32,
+        time_updated: Some(updated),
+        // readeck does not record when, the last update is the closest
+        time_read: value.is_archived.then_some(updated),
+        favorite: value.is_marked,
+        time_favorited: value.is_marked.then_some(updated),
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use wiremock::matchers::{body_json, header, method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    const BOOKMARKS: &str = include_str!("../fixtures/readeck/bookmarks.json");
+    const ARTICLE: &str = include_str!("../fixtures/readeck/article.html");
+
+    fn backend(server: &MockServer) -> ReadeckBackend {
+        ReadeckBackend::new(ReadeckSettings {
+            url: Some(server.uri()),
+            token: Some("secret".to_string()),
+        })
+    }
+
+    #[tokio::test]
+    async fn test_pull_bookmarks() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/api/bookmarks"))
+            .and(header("authorization", "Bearer secret"))
+            .and(query_param("offset", "0"))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(BOOKMARKS, "application/json"))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/api/bookmarks/CyAqz4Ymz29Zn6BQLtNnX9/article"))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(ARTICLE, "text/html"))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let pull = backend(&server).pull(None).await.unwrap();
+        assert!(!pull.has_more);
+        assert_eq!(pull.items.len(), 2);
+
+        let article = &pull.items[0];
+        assert_eq!(article.id, "CyAqz4Ymz29Zn6BQLtNnX9");
+        assert_eq!(article.item.title, "Understanding async Rust");
+        assert_eq!(article.item.status, ItemStatus::Unread);
+        assert!(article.item.favorite);
+        assert_eq!(article.item.time_to_read, Some(11));
+        assert_eq!(article.item.tags.len(), 2);
+        assert!(article
+            .content
+            .as_deref()
+            .unwrap()
+            .contains("<h2>Futures</h2>"));
+
+        let video = &pull.items[1];
+        assert_eq!(video.item.status, ItemStatus::Archived);
+        assert_eq!(video.item.has_video, Some(HasVideo::IsVideo));
+        assert!(video.content.is_none());
+
+        let cursor: Cursor = serde_json::from_str(&pull.cursor).unwrap();
+        assert_eq!(
+            cursor.since.unwrap().to_rfc3339(),
+            "2025-03-04T08:30:00+00:00"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_pull_since_cursor() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/api/bookmarks"))
+            .and(query_param("updated_since", "2025-03-04T08:30:00+00:00"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([])))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let cursor = r#"{"since":"2025-03-04T08:30:00Z","offset":0,"latest":null}"#;
+        let pull = backend(&server).pull(Some(cursor)).await.unwrap();
+        assert!(pull.items.is_empty());
+        let next: Cursor = serde_json::from_str(&pull.cursor).unwrap();
+        assert_eq!(next.since.unwrap().timestamp(), 1741077000);
+    }
+
+    #[tokio::test]
+    async fn test_push_mutations() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/api/bookmarks"))
+            .and(body_json(serde_json::json!({
+                "url": "https://example.com",
+                "labels": ["rust"]
+            })))
+            .respond_with(ResponseTemplate::new(202).insert_header("Bookmark-Id", "NewId"))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("PATCH"))
+            .and(path("/api/bookmarks/NewId"))
+            .and(body_json(serde_json::json!({"is_archived": true})))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({})))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("PATCH"))
+            .and(path("/api/bookmarks/NewId"))
+            .and(body_json(serde_json::json!({"remove_labels": ["rust"]})))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({})))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let mut backend = backend(&server);
+        let add = Mutation::Add {
+            url: "https://example.com".to_string(),
+            title: None,
+            tags: vec!["rust".to_string()],
+        };
+        let id = backend.push(None, &add).await.unwrap();
+        assert_eq!(id.as_deref(), Some("NewId"));
+        backend
+            .push(id.as_deref(), &Mutation::Archive)
+            .await
+            .unwrap();
+        let remove = Mutation::RemoveTags {
+            tags: vec!["rust".to_string()],
+        };
+        backend.push(id.as_deref(), &remove).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_failed_add_holds_back_the_archive() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/api/bookmarks"))
+            .respond_with(ResponseTemplate::new(500))
+            .mount(&server)
+            .await;
+        Mock::given(method("PATCH"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(0)
+            .mount(&server)
+            .await;
+
+        let pool = localdb::open_database(":memory:").await.unwrap();
+        let mut db = localdb::LocalDb::new(pool);
+        db.add_remote("readeck", NAME, "{}").await.unwrap();
+        let remote = db.get_remote("readeck").await.unwrap().unwrap();
+        let item = db.add(&Item::default()).await.unwrap() as i64;
+        let add = Mutation::Add {
+            url: Item::default().url,
+            title: None,
+            tags: vec![],
+        };
+        for mutation in [add, Mutation::Archive] {
+            let json = serde_json::to_string(&mutation).unwrap();
+            db.enqueue(remote.id, item, &json).await.unwrap();
+        }
+
+        let mut backend = backend(&server);
+        let report = crate::push(&mut db, &remote, &mut backend).await.unwrap();
+        assert_eq!(report, (0, 1));
+        let outbox = db.get_outbox(remote.id).await.unwrap();
+        assert_eq!(outbox.len(), 2);
+        assert_eq!(
+            outbox[1].mutation,
+            serde_json::to_string(&Mutation::Archive).unwrap()
+        );
+        assert_eq!(outbox[1].attempts, 0);
+    }
+
+    #[tokio::test]
+    async fn test_invalid_token() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/api/bookmarks"))
+            .respond_with(ResponseTemplate::new(401))
+            .mount(&server)
+            .await;
+
+        assert!(matches!(
+            backend(&server).pull(None).await,
+            Err(RemoteError::NotAuthenticated)
+        ));
+    }
+}
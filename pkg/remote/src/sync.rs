@@ -0,0 +1,524 @@
+use crate::{open, Mutation, RemoteBackend, RemoteError, RemoteItem, RemoteResult};
+use localdb::{Content, Item, JobKind, LocalDb, Remote, Source};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyncReport {
+    pub pushed: usize,
+    pub failed: usize,
+    pub pulled: usize,
+}
+
+/// Pushes the pending local changes of a remote, then pulls its changes.
+/// Backends may rotate credentials meanwhile, callers store their settings
+/// afterwards.
+pub async fn sync(
+    db: &mut LocalDb,
+    remote: &Remote,
+    backend: &mut dyn RemoteBackend,
+) -> RemoteResult<SyncReport> {
+    let (pushed, failed) = push(db, remote, backend).await?;
+    let pulled = pull(db, remote, backend).await?;
+    db.set_remote_synced(remote.id, chrono::Utc::now().timestamp())
+        .await?;
+    Ok(SyncReport {
+        pushed,
+        failed,
+        pulled,
+    })
+}
+
+/// Replays the outbox of a remote, oldest change first. Failed changes stay
+/// in the outbox with their error, and hold back the later changes of their
+/// item so that they are applied in order. Changes to items the remote has
+/// not told the id of wait for the pull that links them. Returns the number
+/// of pushed and failed changes.
+pub async fn push(
+    db: &mut LocalDb,
+    remote: &Remote,
+    backend: &mut dyn RemoteBackend,
+) -> RemoteResult<(usize, usize)> {
+    let (mut pushed, mut failed) = (0, 0);
+    let mut held = HashSet::new();
+    for entry in db.get_outbox(remote.id).await? {
+        if held.contains(&entry.item_id) {
+            continue;
+        }
+        let mutation: Mutation = serde_json::from_str(&entry.mutation)?;
+        let id = db.get_remote_item_id(remote.id, entry.item_id).await?;
+        match backend.push(id.as_deref(), &mutation).await {
+            Ok(new_id) => {
+                if let Some(new_id) = new_id {
+                    db.link_remote_item(remote.id, &new_id, entry.item_id)
+                        .await?;
+                }
+                db.remove_outbox(entry.id).await?;
+                pushed += 1;
+            }
+            Err(RemoteError::NotLinked) => {
+                held.insert(entry.item_id);
+            }
+            Err(e) => {
+                db.fail_outbox(entry.id, &e.to_string()).await?;
+                held.insert(entry.item_id);
+                failed += 1;
+            }
+        }
+    }
+    Ok((pushed, failed))
+}
+
+/// Pulls every page of changes since the stored cursor. The cursor is
+/// stored after each page so an interrupted pull resumes where it stopped.
+/// The changes are recorded as one operation.
+pub async fn pull(
+    db: &mut LocalDb,
+    remote: &Remote,
+    backend: &mut dyn RemoteBackend,
+) -> RemoteResult<usize> {
+    let mut cursor = remote.cursor.clone();
+    let mut pulled = 0;
+    let favorites = backend.capabilities().favorite;
+    let mut db = db.with_source(Source::Sync);
+    db.begin_operation();
+    loop {
+        let page = backend.pull(cursor.as_deref()).await?;
+        for item in page.items.iter() {
+            store(&mut db, remote, item, favorites).await?;
+        }
+        pulled += page.items.len();
+        db.set_remote_cursor(remote.id, &page.cursor).await?;
+        cursor = Some(page.cursor);
+        if !page.has_more {
+            return Ok(pulled);
+        }
+    }
+}
+
+/// Stores a pulled item, matching it to a local item by its remote id or
+/// its url. New items get queued for the work they still need. Existing
+/// items keep their title and the fields the remote leaves empty, remotes
+/// without `favorites` leave the local favorite alone.
+async fn store(
+    db: &mut LocalDb,
+    remote: &Remote,
+    pulled: &RemoteItem,
+    favorites: bool,
+) -> RemoteResult<i64> {
+    let existing = match db.find_remote_item(remote.id, &pulled.id).await? {
+        Some(id) => Some(id),
+        None => db.get_item_id_by_url(&pulled.item.url).await?,
+    };
+    let id = match existing {
+        Some(id) => {
+            let mut item = match db.get_item(id).await? {
+                Some(local) => merge(local, &pulled.item),
+                None => Item {
+                    id,
+                    ..pulled.item.clone()
+                },
+            };
+            if favorites {
+                item.favorite = pulled.item.favorite;
+                item.time_favorited = pulled.item.time_favorited;
+            }
+            db.update_item(&item).await?;
+            db.set_remote_state(&item).await?;
+            id
+        }
+        None => {
+            let id = db.add(&pulled.item).await? as i64;
+            let fetch = match pulled.content {
+                Some(_) => JobKind::Assets,
+                None => JobKind::Extract,
+            };
+            db.add_job(fetch, id).await?;
+            db.add_job(JobKind::Enrich, id).await?;
+            id
+        }
+    };
+    db.link_remote_item(remote.id, &pulled.id, id).await?;
+
+    if let Some(html) = &pulled.content {
+        let content = Content {
+            item_id: id,
+            html: html.clone(),
+            time_fetched: pulled.item.time_updated,
+        };
+        db.set_content(&content).await?;
+    }
+    Ok(id)
+}
+
+/// The local item with the state and the metadata of the pulled one
+fn merge(local: Item, pulled: &Item) -> Item {
+    let text = |remote: &Option<String>, local: Option<String>| {
+        remote
+            .clone()
+            .filter(|text| !text.trim().is_empty())
+            .or(local)
+    };
+    Item {
+        excerpt: text(&pulled.excerpt, local.excerpt),
+        canonical_url: text(&pulled.canonical_url, local.canonical_url),
+        lang: text(&pulled.lang, local.lang),
+        top_image_url: text(&pulled.top_image_url, local.top_image_url),
+        is_article: pulled.is_article.or(local.is_article),
+        is_index: pulled.is_index.or(local.is_index),
+        has_video: pulled.has_video.or(local.has_video),
+        has_image: pulled.has_image.or(local.has_image),
+        word_count: pulled.word_count.or(local.word_count),
+        time_to_read: pulled.time_to_read.or(local.time_to_read),
+        tags: pulled.tags.clone(),
+        authors: pulled.authors.clone(),
+        images: pulled.images.clone(),
+        videos: pulled.videos.clone(),
+        status: pulled.status,
+        time_updated: pulled.time_updated,
+        time_read: pulled.time_read,
+        ..local
+    }
+}
+
+/// Queues a local change of an item. New items go to every remote, other
+/// changes only to the remotes whose account holds the item.
+pub async fn enqueue(db: &mut LocalDb, item: i64, mutation: &Mutation) -> RemoteResult<()> {
+    let remotes = match mutation {
+        Mutation::Add { .. } => db.get_remotes().await?,
+        _ => db.get_item_remotes(item).await?,
+    };
+    enqueue_to(db, &remotes, item, mutation).await
+}
+
+/// Queues a local change of an item for those of `remotes` able to apply it
+pub async fn enqueue_to(
+    db: &mut LocalDb,
+    remotes: &[Remote],
+    item: i64,
+    mutation: &Mutation,
+) -> RemoteResult<()> {
+    let json = serde_json::to_string(mutation)?;
+    for remote in remotes {
+        let backend = open(&remote.backend, &remote.settings)?;
+        if backend.capabilities().supports(mutation) {
+            db.enqueue(remote.id, item, &json).await?;
+            db.add_item_remote(item, remote.id).await?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Capabilities, Prompt, Pull};
+    use async_trait::async_trait;
+    use localdb::{open_database, ItemStatus};
+
+    /// Remote keeping its items in memory, two per page
+    #[derive(Default)]
+    struct Fake {
+        items: Vec<RemoteItem>,
+        pushed: Vec<(Option<String>, Mutation)>,
+        offline: bool,
+        /// Adds do not return the id, like pocket
+        anonymous: bool,
+    }
+
+    #[async_trait]
+    impl RemoteBackend for Fake {
+        fn capabilities(&self) -> Capabilities {
+            Capabilities {
+                add: true,
+                archive: true,
+                ..Default::default()
+            }
+        }
+
+        fn settings(&self) -> serde_json::Value {
+            serde_json::json!({"token": "fake"})
+        }
+
+        async fn authenticate(&mut self, _prompt: &mut dyn Prompt) -> RemoteResult<()> {
+            Ok(())
+        }
+
+        async fn pull(&mut self, cursor: Option<&str>) -> RemoteResult<Pull> {
+            let start: usize = cursor.map(|c| c.parse().unwrap()).unwrap_or(0);
+            let end = (start + 2).min(self.items.len());
+            Ok(Pull {
+                items: self.items[start..end].to_vec(),
+                cursor: end.to_string(),
+                has_more: end < self.items.len(),
+            })
+        }
+
+        async fn push(
+            &mut self,
+            id: Option<&str>,
+            mutation: &Mutation,
+        ) -> RemoteResult<Option<String>> {
+            if self.offline {
+                return Err(RemoteError::NotAuthenticated);
+            }
+            if id.is_none() && !matches!(mutation, Mutation::Add { .. }) {
+                return Err(RemoteError::NotLinked);
+            }
+            self.pushed.push((id.map(String::from), mutation.clone()));
+            match mutation {
+                Mutation::Add { .. } if !self.anonymous => Ok(Some("new".to_string())),
+                _ => Ok(None),
+            }
+        }
+    }
+
+    fn remote_item(id: &str, url: &str, status: ItemStatus) -> RemoteItem {
+        RemoteItem {
+            id: id.to_string(),
+            item: Item {
+                url: url.to_string(),
+                title: format!("item {id}"),
+                status,
+                ..Default::default()
+            },
+            content: Some("<p>text</p>".to_string()),
+        }
+    }
+
+    async fn setup() -> (LocalDb, Remote) {
+        let pool = open_database(":memory:").await.unwrap();
+        let mut db = LocalDb::new(pool);
+        db.add_remote("fake", "fake", "{}").await.unwrap();
+        let remote = db.get_remote("fake").await.unwrap().unwrap();
+        (db, remote)
+    }
+
+    #[tokio::test]
+    async fn test_pull_pages_and_matches_items() {
+        let (mut db, remote) = setup().await;
+        // already saved locally, matched by url
+        let local = db
+            .add(&Item {
+                url: "https://example.com/1".to_string(),
+                favorite: true,
+                ..Default::default()
+            })
+            .await
+            .unwrap() as i64;
+
+        let mut fake = Fake {
+            items: vec![
+                remote_item("1", "https://example.com/1", ItemStatus::Unread),
+                remote_item("2", "https://example.com/2", ItemStatus::Unread),
+                remote_item("3", "https://example.com/3", ItemStatus::Unread),
+            ],
+            ..Default::default()
+        };
+        let report = sync(&mut db, &remote, &mut fake).await.unwrap();
+        assert_eq!(report.pulled, 3);
+        assert_eq!(db.get_items().await.unwrap().len(), 3);
+        assert_eq!(
+            db.find_remote_item(remote.id, "1").await.unwrap(),
+            Some(local)
+        );
+        assert!(db.get_content(local).await.unwrap().is_some());
+        // the fake remote has no favorites to overwrite the local one with
+        assert!(db.get_item(local).await.unwrap().unwrap().favorite);
+        // the new items are queued for their metadata and images
+        assert_eq!(db.get_jobs().await.unwrap().len(), 4);
+
+        let remote = db.get_remote("fake").await.unwrap().unwrap();
+        assert_eq!(remote.cursor.as_deref(), Some("3"));
+        assert!(remote.time_synced.is_some());
+
+        // changes are matched by remote id even when the url changed
+        fake.items = vec![remote_item(
+            "2",
+            "https://example.com/moved",
+            ItemStatus::Archived,
+        )];
+        let remote = Remote {
+            cursor: None,
+            ..remote
+        };
+        sync(&mut db, &remote, &mut fake).await.unwrap();
+        let items = db.get_items().await.unwrap();
+        assert_eq!(items.len(), 3);
+        let id = db.find_remote_item(remote.id, "2").await.unwrap().unwrap();
+        let item = items.iter().find(|i| i.id == id).unwrap();
+        assert_eq!(item.status, ItemStatus::Archived);
+    }
+
+    #[tokio::test]
+    async fn test_pull_keeps_local_fields() {
+        let (mut db, remote) = setup().await;
+        let local = db
+            .add(&Item {
+                url: "https://example.com/1".to_string(),
+                title: "Renamed".to_string(),
+                excerpt: Some("Enriched".to_string()),
+                lang: Some("en".to_string()),
+                ..Default::default()
+            })
+            .await
+            .unwrap() as i64;
+
+        let mut pulled = remote_item("1", "https://example.com/1", ItemStatus::Archived);
+        pulled.item.title = String::new();
+        pulled.item.lang = Some(String::new());
+        pulled.item.word_count = Some(120);
+        let mut fake = Fake {
+            items: vec![pulled],
+            ..Default::default()
+        };
+        sync(&mut db, &remote, &mut fake).await.unwrap();
+
+        let item = db.get_item(local).await.unwrap().unwrap();
+        assert_eq!(item.title, "Renamed");
+        assert_eq!(item.excerpt.as_deref(), Some("Enriched"));
+        assert_eq!(item.lang.as_deref(), Some("en"));
+        assert_eq!(item.word_count, Some(120));
+        assert_eq!(item.status, ItemStatus::Archived);
+    }
+
+    #[tokio::test]
+    async fn test_same_id_on_two_accounts() {
+        let (mut db, work) = setup().await;
+        db.add_remote("home", "fake", "{}").await.unwrap();
+        let home = db.get_remote("home").await.unwrap().unwrap();
+
+        let mut fake = Fake {
+            items: vec![remote_item(
+                "42",
+                "https://work.example.com/42",
+                ItemStatus::Unread,
+            )],
+            ..Default::default()
+        };
+        sync(&mut db, &work, &mut fake).await.unwrap();
+        fake.items = vec![remote_item(
+            "42",
+            "https://home.example.com/42",
+            ItemStatus::Archived,
+        )];
+        sync(&mut db, &home, &mut fake).await.unwrap();
+
+        assert_eq!(db.get_items().await.unwrap().len(), 2);
+        let at_work = db.find_remote_item(work.id, "42").await.unwrap().unwrap();
+        let at_home = db.find_remote_item(home.id, "42").await.unwrap().unwrap();
+        assert_ne!(at_work, at_home);
+        let item = db.get_item(at_work).await.unwrap().unwrap();
+        assert_eq!(item.url, "https://work.example.com/42");
+        assert_eq!(item.status, ItemStatus::Unread);
+        let item = db.get_item(at_home).await.unwrap().unwrap();
+        assert_eq!(item.url, "https://home.example.com/42");
+        assert_eq!(item.status, ItemStatus::Archived);
+    }
+
+    #[tokio::test]
+    async fn test_push_outbox() {
+        let (mut db, remote) = setup().await;
+        let added = db.add(&Item::default()).await.unwrap() as i64;
+        let add = Mutation::Add {
+            url: Item::default().url,
+            title: None,
+            tags: vec![],
+        };
+        db.enqueue(remote.id, added, &serde_json::to_string(&add).unwrap())
+            .await
+            .unwrap();
+        db.enqueue(
+            remote.id,
+            added,
+            &serde_json::to_string(&Mutation::Archive).unwrap(),
+        )
+        .await
+        .unwrap();
+
+        let mut fake = Fake {
+            offline: true,
+            ..Default::default()
+        };
+        // the archive waits for the add that failed
+        assert_eq!(push(&mut db, &remote, &mut fake).await.unwrap(), (0, 1));
+        let outbox = db.get_outbox(remote.id).await.unwrap();
+        assert_eq!(outbox.len(), 2);
+        assert_eq!(outbox[0].attempts, 1);
+        assert_eq!(outbox[1].attempts, 0);
+
+        fake.offline = false;
+        assert_eq!(push(&mut db, &remote, &mut fake).await.unwrap(), (2, 0));
+        assert!(db.get_outbox(remote.id).await.unwrap().is_empty());
+        // the archive went to the id returned by the add
+        assert_eq!(fake.pushed[1], (Some("new".to_string()), Mutation::Archive));
+    }
+
+    #[tokio::test]
+    async fn test_push_waits_for_the_remote_id() {
+        let (mut db, remote) = setup().await;
+        let item = Item {
+            url: "https://example.com/1".to_string(),
+            ..Default::default()
+        };
+        let added = db.add(&item).await.unwrap() as i64;
+        let add = Mutation::Add {
+            url: item.url.clone(),
+            title: None,
+            tags: vec![],
+        };
+        for mutation in [add, Mutation::Archive] {
+            let json = serde_json::to_string(&mutation).unwrap();
+            db.enqueue(remote.id, added, &json).await.unwrap();
+        }
+
+        let mut fake = Fake {
+            anonymous: true,
+            ..Default::default()
+        };
+        assert_eq!(push(&mut db, &remote, &mut fake).await.unwrap(), (1, 0));
+        let outbox = db.get_outbox(remote.id).await.unwrap();
+        assert_eq!(outbox.len(), 1);
+        assert_eq!(outbox[0].attempts, 0);
+
+        // the pull links the item by its url, then the archive goes out
+        fake.items = vec![remote_item("1", &item.url, ItemStatus::Unread)];
+        sync(&mut db, &remote, &mut fake).await.unwrap();
+        assert_eq!(push(&mut db, &remote, &mut fake).await.unwrap(), (1, 0));
+        assert_eq!(fake.pushed[1], (Some("1".to_string()), Mutation::Archive));
+    }
+
+    #[tokio::test]
+    async fn test_changes_follow_the_item_accounts() {
+        let pool = open_database(":memory:").await.unwrap();
+        let mut db = LocalDb::new(pool);
+        db.add_remote("work", "pocket", "{}").await.unwrap();
+        db.add_remote("home", "pocket", "{}").await.unwrap();
+        let work = db.get_remote("work").await.unwrap().unwrap();
+        let home = db.get_remote("home").await.unwrap().unwrap();
+        let item = db.add(&Item::default()).await.unwrap() as i64;
+
+        let add = Mutation::Add {
+            url: Item::default().url,
+            title: None,
+            tags: vec![],
+        };
+        enqueue_to(&mut db, std::slice::from_ref(&work), item, &add)
+            .await
+            .unwrap();
+        enqueue(&mut db, item, &Mutation::Archive).await.unwrap();
+        assert_eq!(db.get_outbox(work.id).await.unwrap().len(), 2);
+        assert!(db.get_outbox(home.id).await.unwrap().is_empty());
+
+        // items saved without choosing go to every account
+        let other = db
+            .add(&Item {
+                url: "https://example.com/other".to_string(),
+                ..Default::default()
+            })
+            .await
+            .unwrap() as i64;
+        enqueue(&mut db, other, &add).await.unwrap();
+        assert_eq!(db.get_item_remotes(other).await.unwrap().len(), 2);
+    }
+}
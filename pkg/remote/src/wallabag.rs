@@ -0,0 +1,404 @@
+use crate::RemoteResult;
+use crate::{Capabilities, Mutation, Prompt, Pull, RemoteBackend, RemoteError, RemoteItem};
+use async_trait::async_trait;
+use localdb::{Author, Item, ItemStatus, Tag};
+use serde::{Deserialize, Serialize};
+use wallabag::{
+    EntriesQuery, Entry, EntryPatch, NewEntry, WallabagAuthClient, WallabagClient, WallabagError,
+};
+
+pub const NAME: &str = "wallabag";
+
+const PER_PAGE: i32 = 30;
+
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub struct WallabagSettings {
+    /// Address of the instance, e.g. https://app.wallabag.it
+    pub url: Option<String>,
+    pub client_id: Option<String>,
+    pub client_secret: Option<String>,
+    pub refresh_token: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Cursor {
+    since: i64,
+    page: i32,
+    /// Latest update seen while paging, the `since` of the next sync
+    #[serde(default)]
+    latest: i64,
+}
+
+impl Default for Cursor {
+    fn default() -> Self {
+        Cursor {
+            since: 0,
+            page: 1,
+            latest: 0,
+        }
+    }
+}
+
+pub struct WallabagBackend {
+    settings: WallabagSettings,
+    client: Option<WallabagClient>,
+}
+
+impl WallabagBackend {
+    pub fn new(settings: WallabagSettings) -> WallabagBackend {
+        WallabagBackend {
+            settings,
+            client: None,
+        }
+    }
+
+    fn auth_client(&self) -> RemoteResult<WallabagAuthClient> {
+        let settings = &self.settings;
+        let (Some(url), Some(id), Some(secret)) =
+            (&settings.url, &settings.client_id, &settings.client_secret)
+        else {
+            return Err(RemoteError::NotAuthenticated);
+        };
+        Ok(WallabagAuthClient::new(url, id.clone(), secret.clone())?)
+    }
+
+    /// Access tokens only live for an hour, so every session starts by
+    /// exchanging the refresh token for a new one.
+    async fn client(&mut self) -> RemoteResult<&WallabagClient> {
+        if self.client.is_none() {
+            let refresh_token = self
+                .settings
+                .refresh_token
+                .clone()
+                .ok_or(RemoteError::NotAuthenticated)?;
+            let token = match self.auth_client()?.refresh(&refresh_token).await {
+                Ok(token) => token,
+                Err(WallabagError::Api { status: 400, .. }) => {
+                    return Err(RemoteError::NotAuthenticated)
+                }
+                Err(e) => return Err(e.into()),
+            };
+            self.settings.refresh_token = Some(token.refresh_token);
+            let url = self.settings.url.as_deref().unwrap_or_default();
+            self.client = Some(WallabagClient::new(url, &token.access_token)?);
+        }
+        Ok(self.client.as_ref().expect("client was just created"))
+    }
+}
+
+#[async_trait]
+impl RemoteBackend for WallabagBackend {
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            add: true,
+            archive: true,
+            favorite: true,
+            delete: true,
+            tags: true,
+            content: true,
+        }
+    }
+
+    fn settings(&self) -> serde_json::Value {
+        serde_json::to_value(&self.settings).expect("wallabag settings are serializable")
+    }
+
+    async fn authenticate(&mut self, prompt: &mut dyn Prompt) -> RemoteResult<()> {
+        if self.settings.url.is_none() {
+            self.settings.url = Some(prompt.ask("Address of the wallabag instance:"));
+        }
+        prompt.show("Create an API client on the \"API clients management\" page");
+        if self.settings.client_id.is_none() {
+            self.settings.client_id = Some(prompt.ask("Client ID:"));
+        }
+        if self.settings.client_secret.is_none() {
+            self.settings.client_secret = Some(prompt.password("Client secret:"));
+        }
+        let username = prompt.ask("Username:");
+        let password = prompt.password("Password:");
+
+        let token = self.auth_client()?.password(&username, &password).await?;
+        self.settings.refresh_token = Some(token.refresh_token);
+        let url = self.settings.url.as_deref().unwrap_or_default();
+        self.client = Some(WallabagClient::new(url, &token.access_token)?);
+        Ok(())
+    }
+
+    async fn pull(&mut self, cursor: Option<&str>) -> RemoteResult<Pull> {
+        let cursor: Cursor = match cursor {
+            Some(cursor) => serde_json::from_str(cursor)?,
+            None => Cursor::default(),
+        };
+        let query = EntriesQuery::changes_since(cursor.since)
+            .page(cursor.page)
+            .per_page(PER_PAGE);
+        let response = self.client().await?.entries(&query).await?;
+
+        let latest = response
+            .embedded
+            .items
+            .iter()
+            .map(|entry| entry.updated_at.timestamp())
+            .fold(cursor.latest.max(cursor.since), i64::max);
+        let has_more = response.has_more();
+        let next = if has_more {
+            Cursor {
+                since: cursor.since,
+                page: cursor.page + 1,
+                latest,
+            }
+        } else {
+            Cursor {
+                since: latest,
+                page: 1,
+                latest,
+            }
+        };
+        let items = response
+            .embedded
+            .items
+            .iter()
+            .map(|entry| RemoteItem {
+                id: entry.id.to_string(),
+                item: item(entry),
+                content: entry.content.clone().filter(|c| !c.is_empty()),
+            })
+            .collect();
+        Ok(Pull {
+            items,
+            cursor: serde_json::to_string(&next)?,
+            has_more,
+        })
+    }
+
+    async fn push(
+        &mut self,
+        id: Option<&str>,
+        mutation: &Mutation,
+    ) -> RemoteResult<Option<String>> {
+        let client = self.client().await?;
+        if let Mutation::Add { url, title, tags } = mutation {
+            let mut entry = NewEntry::new(url).tags(tags);
+            entry.title = title.clone();
+            let entry = client.create_entry(&entry).await?;
+            return Ok(Some(entry.id.to_string()));
+        }
+
+        let Some(id) = id.and_then(|id| id.parse().ok()) else {
+            return Err(RemoteError::NotLinked);
+        };
+        match mutation {
+            Mutation::Add { .. } => unreachable!("handled above"),
+            Mutation::Archive => {
+                client.update_entry(id, &EntryPatch::archive(true)).await?;
+            }
+            Mutation::Unarchive => {
+                client.update_entry(id, &EntryPatch::archive(false)).await?;
+            }
+            Mutation::Favorite => {
+                client.update_entry(id, &EntryPatch::starred(true)).await?;
+            }
+            Mutation::Unfavorite => {
+                client.update_entry(id, &EntryPatch::starred(false)).await?;
+            }
+            Mutation::Delete => {
+                client.delete_entry(id).await?;
+            }
+            Mutation::AddTags { tags } => {
+                client.add_tags(id, tags).await?;
+            }
+            Mutation::RemoveTags { tags } => {
+                let entry = client.entry(id).await?;
+                for tag in entry.tags.iter().filter(|t| tags.contains(&t.label)) {
+                    client.remove_tag(id, tag.id).await?;
+                }
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Maps a wallabag entry to a local item
+pub fn item(value: &Entry) -> Item {
+    let status = if value.is_archived {
+        ItemStatus::Archived
+    } else {
+        ItemStatus::Unread
+    };
+    Item {
+        title: value.title.clone().unwrap_or_default(),
+        url: value.url.clone(),
+        lang: value.language.clone(),
+        time_to_read: Some(value.reading_time),
+        top_image_url: value.preview_picture.clone(),
+        tags: value
+            .tags
+            .iter()
+            .map(|tag| Tag {
+                id: 0,
+                tag: tag.label.clone(),
+                name: None,
+            })
+            .collect(),
+        authors: value
+            .published_by
+            .iter()
+            .flatten()
+            .map(|name| Author {
+                id: 0,
+                name: name.clone(),
+                url: None,
+            })
+            .collect(),
+        status,
+        time_added: value.created_at.timestamp() as i32,
+        time_updated: Some(value.updated_at.timestamp() as i32),
+        time_read: value.archived_at.map(|t| t.timestamp() as i32),
+        favorite: value.is_starred,
+        time_favorited: value.starred_at.map(|t| t.timestamp() as i32),
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+    use wiremock::matchers::{body_string_contains, method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn entry(id: i64, updated_at: &str) -> serde_json::Value {
+        json!({
+            "id": id,
+            "url": format!("https://example.com/{id}"),
+            "title": "An article",
+            "content": "<p>Hello</p>",
+            "is_archived": 1,
+            "is_starred": 0,
+            "created_at": "2025-03-01T10:00:00+0000",
+            "updated_at": updated_at,
+            "archived_at": updated_at,
+            "tags": [{"id": 3, "label": "rust", "slug": "rust"}],
+            "reading_time": 4,
+            "domain_name": "example.com",
+            "preview_picture": null,
+            "mimetype": "text/html",
+            "language": "en",
+            "http_status": "200"
+        })
+    }
+
+    async fn server() -> MockServer {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/oauth/v2/token"))
+            .and(body_string_contains("grant_type=refresh_token"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "access_token": "access",
+                "expires_in": 3600,
+                "refresh_token": "rotated",
+                "token_type": "bearer"
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        server
+    }
+
+    fn backend(server: &MockServer) -> WallabagBackend {
+        WallabagBackend::new(WallabagSettings {
+            url: Some(server.uri()),
+            client_id: Some("id".to_string()),
+            client_secret: Some("secret".to_string()),
+            refresh_token: Some("refresh".to_string()),
+        })
+    }
+
+    #[tokio::test]
+    async fn test_pull_pages() {
+        let server = server().await;
+        Mock::given(method("GET"))
+            .and(path("/api/entries.json"))
+            .and(query_param("page", "1"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "page": 1, "limit": 30, "pages": 2, "total": 2,
+                "_embedded": {"items": [entry(1, "2025-03-02T10:00:00+0000")]}
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/api/entries.json"))
+            .and(query_param("page", "2"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "page": 2, "limit": 30, "pages": 2, "total": 2,
+                "_embedded": {"items": [entry(2, "2025-03-01T12:00:00+0000")]}
+            })))
+            .mount(&server)
+            .await;
+
+        let mut backend = backend(&server);
+        let first = backend.pull(None).await.unwrap();
+        assert!(first.has_more);
+        assert_eq!(first.items[0].id, "1");
+        assert_eq!(first.items[0].item.status, ItemStatus::Archived);
+        assert_eq!(first.items[0].content.as_deref(), Some("<p>Hello</p>"));
+
+        let second = backend.pull(Some(&first.cursor)).await.unwrap();
+        assert!(!second.has_more);
+        let cursor: Cursor = serde_json::from_str(&second.cursor).unwrap();
+        assert_eq!(cursor.since, 1740909600);
+        assert_eq!(cursor.page, 1);
+
+        assert_eq!(backend.settings()["refresh_token"], "rotated");
+    }
+
+    #[tokio::test]
+    async fn test_push_mutations() {
+        let server = server().await;
+        Mock::given(method("POST"))
+            .and(path("/api/entries.json"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(entry(9, "2025-03-02T10:00:00+0000")),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("PATCH"))
+            .and(path("/api/entries/9.json"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(entry(9, "2025-03-02T10:00:00+0000")),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let mut backend = backend(&server);
+        let add = Mutation::Add {
+            url: "https://example.com/9".to_string(),
+            title: None,
+            tags: vec![],
+        };
+        let id = backend.push(None, &add).await.unwrap();
+        assert_eq!(id.as_deref(), Some("9"));
+        backend.push(Some("9"), &Mutation::Archive).await.unwrap();
+        // items the remote does not know yet wait for their id
+        let result = backend.push(None, &Mutation::Archive).await;
+        assert!(matches!(result, Err(RemoteError::NotLinked)));
+    }
+
+    #[tokio::test]
+    async fn test_expired_refresh_token() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/oauth/v2/token"))
+            .respond_with(ResponseTemplate::new(400).set_body_string("invalid_grant"))
+            .mount(&server)
+            .await;
+
+        let mut backend = backend(&server);
+        assert!(matches!(
+            backend.pull(None).await,
+            Err(RemoteError::NotAuthenticated)
+        ));
+    }
+}
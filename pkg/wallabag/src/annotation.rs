@@ -0,0 +1,40 @@
+use serde::{Deserialize, Serialize};
+
+/// Position of an annotation in the entry content, as used by annotator.js
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Range {
+    pub start: String,
+    pub start_offset: String,
+    pub end: String,
+    pub end_offset: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Annotation {
+    pub id: i64,
+    pub text: String,
+    pub quote: Option<String>,
+    #[serde(default)]
+    pub ranges: Vec<Range>,
+    pub created_at: Option<String>,
+    pub updated_at: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Annotations {
+    pub total: i32,
+    pub rows: Vec<Annotation>,
+}
+
+#[derive(Serialize)]
+pub struct NewAnnotation {
+    pub text: String,
+    pub quote: String,
+    pub ranges: Vec<Range>,
+}
+
+#[derive(Serialize)]
+pub(crate) struct AnnotationPatch<'a> {
+    pub text: &'a str,
+}
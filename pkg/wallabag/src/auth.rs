@@ -0,0 +1,160 @@
+use crate::{check, WallabagError, WallabagResult};
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+const TOKEN_PATH: &str = "oauth/v2/token";
+
+#[derive(Serialize)]
+#[serde(tag = "grant_type", rename_all = "snake_case")]
+enum Grant<'a> {
+    Password {
+        client_id: &'a str,
+        client_secret: &'a str,
+        username: &'a str,
+        password: &'a str,
+    },
+    RefreshToken {
+        client_id: &'a str,
+        client_secret: &'a str,
+        refresh_token: &'a str,
+    },
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Token {
+    pub access_token: String,
+    /// Lifetime of the access token in seconds
+    pub expires_in: i64,
+    pub refresh_token: String,
+    pub token_type: String,
+}
+
+/// OAuth2 client for a wallabag instance. Client id and secret come from the
+/// "API clients management" page of the instance.
+pub struct WallabagAuthClient {
+    base_url: Url,
+    client_id: String,
+    client_secret: String,
+    client: reqwest::Client,
+}
+
+impl WallabagAuthClient {
+    pub fn new(
+        base_url: &str,
+        client_id: String,
+        client_secret: String,
+    ) -> WallabagResult<WallabagAuthClient> {
+        Ok(WallabagAuthClient {
+            base_url: crate::base_url(base_url)?,
+            client_id,
+            client_secret,
+            client: reqwest::Client::new(),
+        })
+    }
+
+    /// Exchanges user credentials for a token (password grant)
+    pub async fn password(&self, username: &str, password: &str) -> WallabagResult<Token> {
+        self.token(&Grant::Password {
+            client_id: &self.client_id,
+            client_secret: &self.client_secret,
+            username,
+            password,
+        })
+        .await
+    }
+
+    pub async fn refresh(&self, refresh_token: &str) -> WallabagResult<Token> {
+        self.token(&Grant::RefreshToken {
+            client_id: &self.client_id,
+            client_secret: &self.client_secret,
+            refresh_token,
+        })
+        .await
+    }
+
+    async fn token(&self, grant: &Grant<'_>) -> WallabagResult<Token> {
+        let url = self.base_url.join(TOKEN_PATH)?;
+        let reqwest_error = |source| WallabagError::Reqwest {
+            url: url.to_string(),
+            source,
+        };
+        let res = self
+            .client
+            .post(url.clone())
+            .form(grant)
+            .send()
+            .await
+            .map_err(reqwest_error)?;
+        check(res)
+            .await?
+            .json::<Token>()
+            .await
+            .map_err(reqwest_error)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use wiremock::matchers::{body_string_contains, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn token() -> serde_json::Value {
+        serde_json::json!({
+            "access_token": "access",
+            "expires_in": 3600,
+            "refresh_token": "refresh",
+            "scope": null,
+            "token_type": "bearer"
+        })
+    }
+
+    #[tokio::test]
+    async fn test_password_grant() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/oauth/v2/token"))
+            .and(body_string_contains("grant_type=password"))
+            .and(body_string_contains("username=alice"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(token()))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let auth = WallabagAuthClient::new(&server.uri(), "id".into(), "secret".into()).unwrap();
+        let token = auth.password("alice", "hunter2").await.unwrap();
+        assert_eq!(token.access_token, "access");
+        assert_eq!(token.refresh_token, "refresh");
+        assert_eq!(token.expires_in, 3600);
+    }
+
+    #[tokio::test]
+    async fn test_refresh_grant() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/oauth/v2/token"))
+            .and(body_string_contains("grant_type=refresh_token"))
+            .and(body_string_contains("refresh_token=old"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(token()))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let auth = WallabagAuthClient::new(&server.uri(), "id".into(), "secret".into()).unwrap();
+        assert_eq!(auth.refresh("old").await.unwrap().access_token, "access");
+    }
+
+    #[tokio::test]
+    async fn test_bad_credentials() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/oauth/v2/token"))
+            .respond_with(ResponseTemplate::new(400).set_body_string("invalid_grant"))
+            .mount(&server)
+            .await;
+
+        let auth = WallabagAuthClient::new(&server.uri(), "id".into(), "secret".into()).unwrap();
+        let err = auth.password("alice", "wrong").await.unwrap_err();
+        assert!(matches!(err, WallabagError::Api { status: 400, .. }));
+    }
+}
@@ -0,0 +1,242 @@
+use chrono::{DateTime, FixedOffset};
+use serde::{Deserialize, Deserializer, Serialize};
+use util::der::bool_from_number;
+use util::ser::serialize_option_bool_as_int;
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Tag {
+    pub id: i64,
+    pub label: String,
+    pub slug: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Entry {
+    pub id: i64,
+    pub url: String,
+    pub given_url: Option<String>,
+    pub origin_url: Option<String>,
+    pub title: Option<String>,
+    pub content: Option<String>,
+    #[serde(deserialize_with = "bool_from_number")]
+    pub is_archived: bool,
+    #[serde(deserialize_with = "bool_from_number")]
+    pub is_starred: bool,
+    #[serde(deserialize_with = "date")]
+    pub created_at: DateTime<FixedOffset>,
+    #[serde(deserialize_with = "date")]
+    pub updated_at: DateTime<FixedOffset>,
+    #[serde(default, deserialize_with = "opt_date")]
+    pub archived_at: Option<DateTime<FixedOffset>>,
+    #[serde(default, deserialize_with = "opt_date")]
+    pub starred_at: Option<DateTime<FixedOffset>>,
+    #[serde(default, deserialize_with = "opt_date")]
+    pub published_at: Option<DateTime<FixedOffset>>,
+    #[serde(default)]
+    pub published_by: Option<Vec<String>>,
+    #[serde(default)]
+    pub tags: Vec<Tag>,
+    /// Estimated reading time in minutes
+    #[serde(default)]
+    pub reading_time: i32,
+    pub domain_name: Option<String>,
+    pub preview_picture: Option<String>,
+    pub mimetype: Option<String>,
+    pub language: Option<String>,
+    pub http_status: Option<String>,
+}
+
+/// Wallabag formats dates as `2025-03-01T10:00:00+0000`
+const DATE_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%z";
+
+fn date<'de, D>(deserializer: D) -> Result<DateTime<FixedOffset>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let value: &str = Deserialize::deserialize(deserializer)?;
+    DateTime::parse_from_str(value, DATE_FORMAT)
+        .or_else(|_| DateTime::parse_from_rfc3339(value))
+        .map_err(serde::de::Error::custom)
+}
+
+fn opt_date<'de, D>(deserializer: D) -> Result<Option<DateTime<FixedOffset>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    struct Wrapper(#[serde(deserialize_with = "date")] DateTime<FixedOffset>);
+    let value: Option<Wrapper> = Deserialize::deserialize(deserializer)?;
+    Ok(value.map(|Wrapper(date)| date))
+}
+
+#[derive(Deserialize, Debug)]
+pub struct EntriesPage {
+    pub page: i32,
+    pub limit: i32,
+    pub pages: i32,
+    pub total: i32,
+    #[serde(rename = "_embedded")]
+    pub embedded: EmbeddedEntries,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct EmbeddedEntries {
+    pub items: Vec<Entry>,
+}
+
+impl EntriesPage {
+    pub fn has_more(&self) -> bool {
+        self.page < self.pages
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Sort {
+    Created,
+    Updated,
+    Archived,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Order {
+    Asc,
+    Desc,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Detail {
+    /// Entries without their content
+    Metadata,
+    Full,
+}
+
+#[derive(Serialize, Default)]
+pub struct EntriesQuery {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(serialize_with = "serialize_option_bool_as_int")]
+    pub archive: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(serialize_with = "serialize_option_bool_as_int")]
+    pub starred: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sort: Option<Sort>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub order: Option<Order>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub page: Option<i32>,
+    #[serde(rename = "perPage", skip_serializing_if = "Option::is_none")]
+    pub per_page: Option<i32>,
+    /// Comma separated list of tag labels
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tags: Option<String>,
+    /// Only entries updated after this unix timestamp
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub since: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<Detail>,
+}
+
+impl EntriesQuery {
+    /// Every entry changed since `since`, oldest change first
+    pub fn changes_since(since: i64) -> EntriesQuery {
+        EntriesQuery {
+            sort: Some(Sort::Updated),
+            order: Some(Order::Asc),
+            since: Some(since),
+            detail: Some(Detail::Full),
+            ..Default::default()
+        }
+    }
+
+    pub fn page(mut self, page: i32) -> EntriesQuery {
+        self.page = Some(page);
+        self
+    }
+
+    pub fn per_page(mut self, per_page: i32) -> EntriesQuery {
+        self.per_page = Some(per_page);
+        self
+    }
+}
+
+#[derive(Serialize, Default)]
+pub struct NewEntry {
+    pub url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// Comma separated list of tag labels
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tags: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(serialize_with = "serialize_option_bool_as_int")]
+    pub archive: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(serialize_with = "serialize_option_bool_as_int")]
+    pub starred: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub preview_picture: Option<String>,
+    /// Comma separated list of author names
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub authors: Option<String>,
+}
+
+impl NewEntry {
+    pub fn new(url: &str) -> NewEntry {
+        NewEntry {
+            url: url.to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn title(mut self, title: &str) -> NewEntry {
+        self.title = Some(title.to_string());
+        self
+    }
+
+    pub fn tags(mut self, tags: &[String]) -> NewEntry {
+        self.tags = Some(tags.join(","));
+        self
+    }
+}
+
+#[derive(Serialize, Default)]
+pub struct EntryPatch {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// Comma separated list of tag labels, added to the existing tags
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tags: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(serialize_with = "serialize_option_bool_as_int")]
+    pub archive: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(serialize_with = "serialize_option_bool_as_int")]
+    pub starred: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub preview_picture: Option<String>,
+}
+
+impl EntryPatch {
+    pub fn archive(archive: bool) -> EntryPatch {
+        EntryPatch {
+            archive: Some(archive),
+            ..Default::default()
+        }
+    }
+
+    pub fn starred(starred: bool) -> EntryPatch {
+        EntryPatch {
+            starred: Some(starred),
+            ..Default::default()
+        }
+    }
+}
@@ -0,0 +1,20 @@
+#[derive(Debug, thiserror::Error)]
+pub enum WallabagError {
+    #[error("Request error for URL <{url}>: {source}")]
+    Reqwest { url: String, source: reqwest::Error },
+
+    #[error("access token is invalid or expired")]
+    Unauthorized,
+
+    #[error("wallabag returned {status} for <{url}>: {body}")]
+    Api {
+        url: String,
+        status: u16,
+        body: String,
+    },
+
+    #[error("invalid url: {0}")]
+    InvalidUrl(#[from] url::ParseError),
+}
+
+pub type WallabagResult<T> = Result<T, WallabagError>;
@@ -0,0 +1,423 @@
+pub mod annotation;
+pub mod auth;
+pub mod entry;
+mod error;
+
+use annotation::{Annotation, AnnotationPatch, Annotations, NewAnnotation};
+pub use auth::{Token, WallabagAuthClient};
+pub use entry::{EntriesPage, EntriesQuery, Entry, EntryPatch, NewEntry, Tag};
+pub use error::{WallabagError, WallabagResult};
+use reqwest::{Client, Method, Response, StatusCode};
+use serde::{de::DeserializeOwned, Serialize};
+use url::Url;
+
+/// Parses the instance url, making sure relative api paths are joined below it
+/// even when the instance lives in a subdirectory.
+pub(crate) fn base_url(base_url: &str) -> WallabagResult<Url> {
+    let mut url = Url::parse(base_url)?;
+    if !url.path().ends_with('/') {
+        url.set_path(&format!("{}/", url.path()));
+    }
+    Ok(url)
+}
+
+pub(crate) async fn check(res: Response) -> WallabagResult<Response> {
+    let status = res.status();
+    if status == StatusCode::UNAUTHORIZED {
+        return Err(WallabagError::Unauthorized);
+    }
+    if !status.is_success() {
+        return Err(WallabagError::Api {
+            url: res.url().to_string(),
+            status: status.as_u16(),
+            body: res.text().await.unwrap_or_default(),
+        });
+    }
+    Ok(res)
+}
+
+#[derive(Serialize)]
+struct TagsRequest<'a> {
+    tags: &'a str,
+}
+
+pub struct WallabagClient {
+    base_url: Url,
+    access_token: String,
+    client: Client,
+}
+
+impl WallabagClient {
+    pub fn new(base_url: &str, access_token: &str) -> WallabagResult<WallabagClient> {
+        Ok(WallabagClient {
+            base_url: crate::base_url(base_url)?,
+            access_token: access_token.to_string(),
+            client: Client::new(),
+        })
+    }
+
+    async fn send<T: DeserializeOwned>(
+        &self,
+        method: Method,
+        path: &str,
+        query: Option<&impl Serialize>,
+        body: Option<&impl Serialize>,
+    ) -> WallabagResult<T> {
+        let url = self.base_url.join(path)?;
+        let reqwest_error = |source| WallabagError::Reqwest {
+            url: url.to_string(),
+            source,
+        };
+        let mut request = self
+            .client
+            .request(method, url.clone())
+            .bearer_auth(&self.access_token);
+        if let Some(query) = query {
+            request = request.query(query);
+        }
+        if let Some(body) = body {
+            request = request.json(body);
+        }
+        let res = request.send().await.map_err(reqwest_error)?;
+        check(res).await?.json::<T>().await.map_err(reqwest_error)
+    }
+
+    async fn get<T: DeserializeOwned>(&self, path: &str) -> WallabagResult<T> {
+        self.send(Method::GET, path, None::<&()>, None::<&()>).await
+    }
+
+    async fn delete<T: DeserializeOwned>(&self, path: &str) -> WallabagResult<T> {
+        self.send(Method::DELETE, path, None::<&()>, None::<&()>)
+            .await
+    }
+
+    /// Fetches a single page of entries
+    pub async fn entries(&self, query: &EntriesQuery) -> WallabagResult<EntriesPage> {
+        self.send(Method::GET, "api/entries.json", Some(query), None::<&()>)
+            .await
+    }
+
+    pub async fn entry(&self, id: i64) -> WallabagResult<Entry> {
+        self.get(&format!("api/entries/{id}.json")).await
+    }
+
+    pub async fn create_entry(&self, entry: &NewEntry) -> WallabagResult<Entry> {
+        self.send(Method::POST, "api/entries.json", None::<&()>, Some(entry))
+            .await
+    }
+
+    pub async fn update_entry(&self, id: i64, patch: &EntryPatch) -> WallabagResult<Entry> {
+        let path = format!("api/entries/{id}.json");
+        self.send(Method::PATCH, &path, None::<&()>, Some(patch))
+            .await
+    }
+
+    pub async fn delete_entry(&self, id: i64) -> WallabagResult<Entry> {
+        self.delete(&format!("api/entries/{id}.json")).await
+    }
+
+    /// All tags of the user
+    pub async fn tags(&self) -> WallabagResult<Vec<Tag>> {
+        self.get("api/tags.json").await
+    }
+
+    pub async fn add_tags(&self, entry_id: i64, tags: &[String]) -> WallabagResult<Entry> {
+        let path = format!("api/entries/{entry_id}/tags.json");
+        let tags = tags.join(",");
+        let request = TagsRequest { tags: &tags };
+        self.send(Method::POST, &path, None::<&()>, Some(&request))
+            .await
+    }
+
+    pub async fn remove_tag(&self, entry_id: i64, tag_id: i64) -> WallabagResult<Entry> {
+        self.delete(&format!("api/entries/{entry_id}/tags/{tag_id}.json"))
+            .await
+    }
+
+    /// Removes the tag from every entry
+    pub async fn delete_tag(&self, tag_id: i64) -> WallabagResult<Tag> {
+        self.delete(&format!("api/tags/{tag_id}.json")).await
+    }
+
+    pub async fn annotations(&self, entry_id: i64) -> WallabagResult<Vec<Annotation>> {
+        let res: Annotations = self
+            .get(&format!("api/annotations/{entry_id}.json"))
+            .await?;
+        Ok(res.rows)
+    }
+
+    pub async fn create_annotation(
+        &self,
+        entry_id: i64,
+        annotation: &NewAnnotation,
+    ) -> WallabagResult<Annotation> {
+        let path = format!("api/annotations/{entry_id}.json");
+        self.send(Method::POST, &path, None::<&()>, Some(annotation))
+            .await
+    }
+
+    pub async fn update_annotation(&self, id: i64, text: &str) -> WallabagResult<Annotation> {
+        let path = format!("api/annotations/{id}.json");
+        let patch = AnnotationPatch { text };
+        self.send(Method::PUT, &path, None::<&()>, Some(&patch))
+            .await
+    }
+
+    pub async fn delete_annotation(&self, id: i64) -> WallabagResult<Annotation> {
+        self.delete(&format!("api/annotations/{id}.json")).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use annotation::Range;
+    use serde_json::json;
+    use wiremock::matchers::{body_json, header, method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn entry(id: i64) -> serde_json::Value {
+        json!({
+            "id": id,
+            "url": format!("https://example.com/{id}"),
+            "given_url": format!("https://example.com/{id}"),
+            "origin_url": null,
+            "title": "An article",
+            "content": "<p>Hello</p>",
+            "is_archived": 0,
+            "is_starred": 1,
+            "created_at": "2025-03-01T10:00:00+0000",
+            "updated_at": "2025-03-02T11:30:00+0100",
+            "archived_at": null,
+            "starred_at": "2025-03-02T11:30:00+0100",
+            "published_at": null,
+            "published_by": ["Jane Doe"],
+            "tags": [{"id": 3, "label": "rust", "slug": "rust"}],
+            "reading_time": 4,
+            "domain_name": "example.com",
+            "preview_picture": "https://example.com/cover.png",
+            "mimetype": "text/html",
+            "language": "en",
+            "http_status": "200",
+            "_links": {"self": {"href": format!("/api/entries/{id}")}}
+        })
+    }
+
+    fn page(page: i32, pages: i32, items: Vec<serde_json::Value>) -> serde_json::Value {
+        json!({
+            "page": page,
+            "limit": 30,
+            "pages": pages,
+            "total": items.len(),
+            "_links": {},
+            "_embedded": {"items": items}
+        })
+    }
+
+    #[tokio::test]
+    async fn test_list_entries() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/api/entries.json"))
+            .and(header("authorization", "Bearer token"))
+            .and(query_param("since", "1700000000"))
+            .and(query_param("sort", "updated"))
+            .and(query_param("page", "2"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(page(2, 2, vec![entry(7)])))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = WallabagClient::new(&server.uri(), "token").unwrap();
+        let res = client
+            .entries(&EntriesQuery::changes_since(1700000000).page(2))
+            .await
+            .unwrap();
+        assert!(!res.has_more());
+        let entry = &res.embedded.items[0];
+        assert_eq!(entry.id, 7);
+        assert!(!entry.is_archived);
+        assert!(entry.is_starred);
+        assert_eq!(entry.created_at.timestamp(), 1740823200);
+        assert_eq!(entry.updated_at.timestamp(), 1740911400);
+        assert_eq!(entry.archived_at, None);
+        assert_eq!(entry.tags[0].label, "rust");
+        assert_eq!(entry.published_by, Some(vec!["Jane Doe".to_string()]));
+    }
+
+    #[tokio::test]
+    async fn test_instance_in_subdirectory() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/wallabag/api/entries/7.json"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(entry(7)))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = WallabagClient::new(&format!("{}/wallabag", server.uri()), "token").unwrap();
+        assert_eq!(client.entry(7).await.unwrap().id, 7);
+    }
+
+    #[tokio::test]
+    async fn test_create_update_delete_entry() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/api/entries.json"))
+            .and(body_json(json!({
+                "url": "https://example.com/7",
+                "title": "Title",
+                "tags": "a,b"
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(entry(7)))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("PATCH"))
+            .and(path("/api/entries/7.json"))
+            .and(body_json(json!({"archive": 1})))
+            .respond_with(ResponseTemplate::new(200).set_body_json(entry(7)))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("DELETE"))
+            .and(path("/api/entries/7.json"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(entry(7)))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = WallabagClient::new(&server.uri(), "token").unwrap();
+        let new = NewEntry::new("https://example.com/7")
+            .title("Title")
+            .tags(&["a".into(), "b".into()]);
+        let entry = client.create_entry(&new).await.unwrap();
+        client
+            .update_entry(entry.id, &EntryPatch::archive(true))
+            .await
+            .unwrap();
+        client.delete_entry(entry.id).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_tags() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/api/tags.json"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(json!([{"id": 3, "label": "rust", "slug": "rust"}])),
+            )
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/api/entries/7/tags.json"))
+            .and(body_json(json!({"tags": "rust,web"})))
+            .respond_with(ResponseTemplate::new(200).set_body_json(entry(7)))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("DELETE"))
+            .and(path("/api/entries/7/tags/3.json"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(entry(7)))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("DELETE"))
+            .and(path("/api/tags/3.json"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(json!({"id": 3, "label": "rust", "slug": "rust"})),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = WallabagClient::new(&server.uri(), "token").unwrap();
+        let tags = client.tags().await.unwrap();
+        assert_eq!(tags[0].slug, "rust");
+        client
+            .add_tags(7, &["rust".into(), "web".into()])
+            .await
+            .unwrap();
+        client.remove_tag(7, 3).await.unwrap();
+        assert_eq!(client.delete_tag(3).await.unwrap().id, 3);
+    }
+
+    #[tokio::test]
+    async fn test_annotations() {
+        let server = MockServer::start().await;
+        let annotation = json!({
+            "id": 11,
+            "text": "note",
+            "quote": "Hello",
+            "ranges": [{"start": "/p[1]", "startOffset": "0", "end": "/p[1]", "endOffset": "5"}],
+            "created_at": "2025-03-01T10:00:00+0000",
+            "updated_at": "2025-03-01T10:00:00+0000"
+        });
+        Mock::given(method("GET"))
+            .and(path("/api/annotations/7.json"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(json!({"total": 1, "rows": [annotation.clone()]})),
+            )
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/api/annotations/7.json"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(annotation.clone()))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("PUT"))
+            .and(path("/api/annotations/11.json"))
+            .and(body_json(json!({"text": "edited"})))
+            .respond_with(ResponseTemplate::new(200).set_body_json(annotation.clone()))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("DELETE"))
+            .and(path("/api/annotations/11.json"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(annotation))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = WallabagClient::new(&server.uri(), "token").unwrap();
+        let annotations = client.annotations(7).await.unwrap();
+        assert_eq!(annotations[0].ranges[0].start_offset, "0");
+
+        let range = Range {
+            start: "/p[1]".into(),
+            start_offset: "0".into(),
+            end: "/p[1]".into(),
+            end_offset: "5".into(),
+        };
+        let new = NewAnnotation {
+            text: "note".into(),
+            quote: "Hello".into(),
+            ranges: vec![range],
+        };
+        assert_eq!(client.create_annotation(7, &new).await.unwrap().id, 11);
+        client.update_annotation(11, "edited").await.unwrap();
+        client.delete_annotation(11).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_expired_token() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/api/tags.json"))
+            .respond_with(
+                ResponseTemplate::new(401).set_body_json(json!({"error": "invalid_grant"})),
+            )
+            .mount(&server)
+            .await;
+
+        let client = WallabagClient::new(&server.uri(), "token").unwrap();
+        assert!(matches!(
+            client.tags().await.unwrap_err(),
+            WallabagError::Unauthorized
+        ));
+    }
+}
@@ -0,0 +1,210 @@
+//! Changes made from the API, the web reader or the terminal. They are
+//! applied to the library right away and queued for the remotes.
+
+use localdb::{Change, Field, Item, ItemStatus, LocalDb, Operation, Source, Tag};
+use remote::{Mutation, RemoteResult};
+
+pub async fn set_status(db: &mut LocalDb, item: &Item, status: ItemStatus) -> RemoteResult<()> {
+    if item.status == status {
+        return Ok(());
+    }
+    db.set_status(item.id, status).await?;
+    let mutation = match status {
+        ItemStatus::Unread => Mutation::Unarchive,
+        ItemStatus::Archived => Mutation::Archive,
+        ItemStatus::Deleted => Mutation::Delete,
+    };
+    remote::enqueue(db, item.id, &mutation).await?;
+    Ok(())
+}
+
+pub async fn set_favorite(db: &mut LocalDb, item: &Item, favorite: bool) -> RemoteResult<()> {
+    if item.favorite == favorite {
+        return Ok(());
+    }
+    db.set_favorite(item.id, favorite).await?;
+    let mutation = if favorite {
+        Mutation::Favorite
+    } else {
+        Mutation::Unfavorite
+    };
+    remote::enqueue(db, item.id, &mutation).await?;
+    Ok(())
+}
+
+/// Renames an item. Titles stay local, the remotes keep their own.
+pub async fn set_title(db: &mut LocalDb, item: &Item, title: &str) -> RemoteResult<()> {
+    if item.title == title {
+        return Ok(());
+    }
+    db.set_title(item.id, title).await?;
+    Ok(())
+}
+
+pub async fn add_tags(db: &mut LocalDb, item: &Item, tags: Vec<String>) -> RemoteResult<()> {
+    if tags.is_empty() {
+        return Ok(());
+    }
+    for tag in &tags {
+        let tag = db
+            .add_tag(&Tag {
+                id: 0,
+                tag: tag.clone(),
+                name: None,
+            })
+            .await?;
+        db.link_tag(tag, item.id as i32).await?;
+    }
+    remote::enqueue(db, item.id, &Mutation::AddTags { tags }).await?;
+    Ok(())
+}
+
+pub async fn remove_tags(db: &mut LocalDb, item: &Item, tags: Vec<String>) -> RemoteResult<()> {
+    if tags.is_empty() {
+        return Ok(());
+    }
+    for tag in &tags {
+        db.unlink_tag(tag, item.id).await?;
+    }
+    remote::enqueue(db, item.id, &Mutation::RemoveTags { tags }).await?;
+    Ok(())
+}
+
+/// Reverts the latest `count` operations that were not undone yet, newest
+/// first, and queues the reverts for the remotes. Each operation is reverted
+/// in one transaction, marked undone only when all of it was. Purged items
+/// are gone and stay so. Returns the operations that were undone.
+pub async fn undo(db: &LocalDb, count: i64) -> RemoteResult<Vec<Operation>> {
+    let operations = db.get_undoable(count).await?;
+    for operation in &operations {
+        let mut tx = db.with_source(Source::Undo).transaction().await?;
+        tx.begin_undo(operation.id).await?;
+        for change in operation.changes.iter().rev() {
+            revert(&mut tx, operation, change).await?;
+        }
+        tx.commit().await?;
+    }
+    Ok(operations)
+}
+
+async fn revert(db: &mut LocalDb, operation: &Operation, change: &Change) -> RemoteResult<()> {
+    let Some(item) = db.get_item(change.item_id).await? else {
+        return Ok(());
+    };
+    let before = change.before.clone().unwrap_or_default();
+    match change.field {
+        // items pulled from a remote are only dropped here, a delete queued
+        // for the remote would remove them there too
+        Field::Added if operation.source == Source::Sync => Ok(db.revert(change).await?),
+        Field::Added => set_status(db, &item, ItemStatus::Deleted).await,
+        Field::Status => set_status(db, &item, before.parse()?).await,
+        Field::Favorite => set_favorite(db, &item, before == "true").await,
+        Field::Title => set_title(db, &item, &before).await,
+        Field::Tag => match &change.after {
+            Some(added) => remove_tags(db, &item, vec![added.clone()]).await,
+            None => add_tags(db, &item, vec![before]).await,
+        },
+        _ => Ok(db.revert(change).await?),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use remote::Mutation;
+
+    #[tokio::test]
+    async fn test_undo() {
+        let pool = localdb::open_database(":memory:").await.unwrap();
+        let mut db = LocalDb::new(pool);
+        let settings = r#"{"url":"https://example.com","client_id":"id","client_secret":"s","refresh_token":"r"}"#;
+        let remote = db.add_remote("work", "wallabag", settings).await.unwrap();
+        let id = db
+            .add(&Item {
+                url: "https://example.com/a".to_string(),
+                ..Default::default()
+            })
+            .await
+            .unwrap() as i64;
+        db.add_item_remote(id, remote).await.unwrap();
+
+        db.begin_operation();
+        let item = db.get_item(id).await.unwrap().unwrap();
+        set_status(&mut db, &item, ItemStatus::Archived)
+            .await
+            .unwrap();
+        let item = db.get_item(id).await.unwrap().unwrap();
+        set_favorite(&mut db, &item, true).await.unwrap();
+
+        let undone = undo(&db, 1).await.unwrap();
+        assert_eq!(undone.len(), 1);
+        let item = db.get_item(id).await.unwrap().unwrap();
+        assert_eq!(item.status, ItemStatus::Unread);
+        assert!(!item.favorite);
+
+        let mutations: Vec<Mutation> = db
+            .get_outbox(remote)
+            .await
+            .unwrap()
+            .iter()
+            .map(|entry| serde_json::from_str(&entry.mutation).unwrap())
+            .collect();
+        assert_eq!(
+            mutations,
+            vec![
+                Mutation::Archive,
+                Mutation::Favorite,
+                Mutation::Unfavorite,
+                Mutation::Unarchive
+            ]
+        );
+
+        // the undo itself is skipped, the save comes next
+        undo(&db, 1).await.unwrap();
+        let item = db.get_item(id).await.unwrap().unwrap();
+        assert_eq!(item.status, ItemStatus::Deleted);
+        assert!(undo(&db, 1).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_undo_sync_stays_local() {
+        let pool = localdb::open_database(":memory:").await.unwrap();
+        let mut db = LocalDb::new(pool);
+        let settings = r#"{"url":"https://example.com","client_id":"id","client_secret":"s","refresh_token":"r"}"#;
+        let remote = db.add_remote("work", "wallabag", settings).await.unwrap();
+
+        let mut sync = db.with_source(Source::Sync);
+        sync.begin_operation();
+        let id = sync.add(&Item::default()).await.unwrap() as i64;
+        sync.add_item_remote(id, remote).await.unwrap();
+
+        undo(&db, 1).await.unwrap();
+        let item = db.get_item(id).await.unwrap().unwrap();
+        assert_eq!(item.status, ItemStatus::Deleted);
+        assert!(db.get_outbox(remote).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_failed_undo_rolls_back() {
+        let pool = localdb::open_database(":memory:").await.unwrap();
+        let mut db = LocalDb::new(pool.clone());
+        let id = db.add(&Item::default()).await.unwrap() as i64;
+        db.begin_operation();
+        db.set_status(id, ItemStatus::Archived).await.unwrap();
+        db.set_title(id, "Renamed").await.unwrap();
+        sqlx::query("UPDATE changes SET before = 'lost' WHERE field = ?")
+            .bind(Field::Status)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        assert!(undo(&db, 1).await.is_err());
+        // the title reverted first is back to the change
+        let item = db.get_item(id).await.unwrap().unwrap();
+        assert_eq!(item.title, "Renamed");
+        assert_eq!(item.status, ItemStatus::Archived);
+        let undoable = db.get_undoable(1).await.unwrap();
+        assert_eq!(undoable[0].changes.len(), 3);
+        assert!(db.get_operations(1).await.unwrap()[0].undo_of.is_none());
+    }
+}
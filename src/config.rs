@@ -1,27 +1,501 @@
+//! Settings loaded from `config.toml`, overridden by the environment and the
+//! command line. Named profiles keep their own database and assets.
+
+use anyhow::{anyhow, Context};
 use directories::ProjectDirs;
-use serde::Deserialize;
-use std::path::PathBuf;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
 
+pub const CONFIG_PATH: &str = "config.toml";
 pub const DATABASE_PATH: &str = "readlater.sqlite";
+pub const ASSETS_PATH: &str = "assets";
+pub const SNAPSHOTS_PATH: &str = "snapshots";
+pub const PROFILES_PATH: &str = "profiles";
+pub const CREDENTIALS_PATH: &str = "credentials.json";
+pub const SOCKET_PATH: &str = "readlater.sock";
+pub const ASSETS_BUDGET: u64 = 512 * 1024 * 1024;
 pub const POCKET_CONSUMER_KEY: &str = "113896-1812a82dd99b90ac1835fd5";
 pub const POCKET_REDIRECT_URI: &str = "https://localhost:8080/auth/pocket/callback";
+pub const DEFAULT_TAGS: &[&str] = &["readlater"];
+pub const FETCH_TIMEOUT: u64 = 30;
+pub const USER_AGENT: &str = concat!("readlater/", env!("CARGO_PKG_VERSION"));
+
+pub const CONFIG_ENV: &str = "READLATER_CONFIG";
+pub const PROFILE_ENV: &str = "READLATER_PROFILE";
+pub const DATABASE_ENV: &str = "READLATER_DATABASE";
+pub const POCKET_CONSUMER_KEY_ENV: &str = "READLATER_POCKET_CONSUMER_KEY";
 
-#[derive(Deserialize)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Config {
+    /// Name of the profile in use, None for the default one
+    pub profile: Option<String>,
+    /// File the settings were read from
+    pub file: PathBuf,
     pub pocket_consumer_key: String,
     pub database_dir: PathBuf,
+    pub assets_dir: PathBuf,
+    pub assets_budget: u64,
+    pub snapshots_dir: PathBuf,
+    /// Control socket of the daemon
+    pub socket_path: PathBuf,
+    /// Tags given to items saved from the browser
+    pub default_tags: Vec<String>,
+    /// Remotes new items are sent to, every remote when empty
+    pub save_to: Vec<String>,
+    pub fetch: FetchConfig,
+    pub server: ServerConfig,
+    /// Minutes between two syncs of the daemon
+    pub sync_interval: u64,
+    pub credentials: CredentialsConfig,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FetchConfig {
+    /// Number of background jobs run at once
+    pub workers: usize,
+    /// Seconds to wait for a page
+    pub timeout: u64,
+    pub user_agent: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ServerConfig {
+    pub bind: IpAddr,
+    pub port: u16,
+    /// Origins allowed to call the API from a browser
+    pub allow_origins: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CredentialsConfig {
+    pub store: CredentialBackend,
+    /// Encrypted file used by the `file` store
+    pub file: PathBuf,
+}
+
+/// Where the secrets of the remotes are kept
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CredentialBackend {
+    /// The freedesktop Secret Service, such as GNOME Keyring
+    #[default]
+    SecretService,
+    /// A file encrypted with a passphrase
+    File,
+}
+
+/// Settings of the file, and of each of its profiles
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct Settings {
+    data_dir: Option<PathBuf>,
+    database: Option<PathBuf>,
+    assets_dir: Option<PathBuf>,
+    assets_budget: Option<u64>,
+    snapshots_dir: Option<PathBuf>,
+    default_tags: Option<Vec<String>>,
+    save_to: Option<Vec<String>>,
+    backends: BackendSettings,
+    fetch: FetchSettings,
+    server: ServerSettings,
+    daemon: DaemonSettings,
+    credentials: CredentialsSettings,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct BackendSettings {
+    pocket: PocketSettings,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct PocketSettings {
+    consumer_key: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct FetchSettings {
+    workers: Option<usize>,
+    timeout: Option<u64>,
+    user_agent: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ServerSettings {
+    bind: Option<IpAddr>,
+    port: Option<u16>,
+    allow_origins: Option<Vec<String>>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct DaemonSettings {
+    interval: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct CredentialsSettings {
+    store: Option<CredentialBackend>,
+    file: Option<PathBuf>,
+}
+
+impl Settings {
+    /// Settings of `self`, replaced by the ones `other` sets
+    fn merge(self, other: Settings) -> Settings {
+        Settings {
+            data_dir: other.data_dir.or(self.data_dir),
+            database: other.database.or(self.database),
+            assets_dir: other.assets_dir.or(self.assets_dir),
+            assets_budget: other.assets_budget.or(self.assets_budget),
+            snapshots_dir: other.snapshots_dir.or(self.snapshots_dir),
+            default_tags: other.default_tags.or(self.default_tags),
+            save_to: other.save_to.or(self.save_to),
+            backends: BackendSettings {
+                pocket: PocketSettings {
+                    consumer_key: other
+                        .backends
+                        .pocket
+                        .consumer_key
+                        .or(self.backends.pocket.consumer_key),
+                },
+            },
+            fetch: FetchSettings {
+                workers: other.fetch.workers.or(self.fetch.workers),
+                timeout: other.fetch.timeout.or(self.fetch.timeout),
+                user_agent: other.fetch.user_agent.or(self.fetch.user_agent),
+            },
+            server: ServerSettings {
+                bind: other.server.bind.or(self.server.bind),
+                port: other.server.port.or(self.server.port),
+                allow_origins: other.server.allow_origins.or(self.server.allow_origins),
+            },
+            daemon: DaemonSettings {
+                interval: other.daemon.interval.or(self.daemon.interval),
+            },
+            credentials: CredentialsSettings {
+                store: other.credentials.store.or(self.credentials.store),
+                file: other.credentials.file.or(self.credentials.file),
+            },
+        }
+    }
+
+    /// Settings of the file, with those of `profile` on top
+    fn parse(text: &str, profile: Option<&str>) -> anyhow::Result<Settings> {
+        let mut table: toml::Table = toml::from_str(text)?;
+        let mut profiles: HashMap<String, Settings> = match table.remove("profiles") {
+            Some(profiles) => profiles.try_into()?,
+            None => HashMap::new(),
+        };
+        let mut settings: Settings = table.try_into()?;
+        let Some(name) = profile else {
+            return Ok(settings);
+        };
+        // the paths at the top belong to the default profile, the others
+        // keep their library under their own directory unless they set one
+        settings.database = None;
+        settings.assets_dir = None;
+        settings.snapshots_dir = None;
+        settings.credentials.file = None;
+        match profiles.remove(name) {
+            Some(overrides) => Ok(settings.merge(overrides)),
+            None => Ok(settings),
+        }
+    }
+
+    /// Settings given by environment variables
+    fn from_env() -> Settings {
+        Settings {
+            database: std::env::var_os(DATABASE_ENV).map(PathBuf::from),
+            backends: BackendSettings {
+                pocket: PocketSettings {
+                    consumer_key: std::env::var(POCKET_CONSUMER_KEY_ENV).ok(),
+                },
+            },
+            ..Default::default()
+        }
+    }
+}
+
+/// Replaces a leading `~` with the home directory
+fn expand(path: PathBuf) -> PathBuf {
+    let Ok(rest) = path.strip_prefix("~") else {
+        return path;
+    };
+    match directories::BaseDirs::new() {
+        Some(dirs) => dirs.home_dir().join(rest),
+        None => path,
+    }
 }
 
 impl Config {
+    /// Loads the default file, honoring the environment
     pub fn new() -> anyhow::Result<Self> {
+        Config::load(None, None)
+    }
+
+    /// Loads `path`, or the file in the config directory, with the settings of
+    /// `profile` on top. Both fall back to their environment variables.
+    pub fn load(path: Option<&Path>, profile: Option<&str>) -> anyhow::Result<Self> {
         let project_dirs =
             ProjectDirs::from("", "", "readlater").expect("Could not find project directory");
-        let database_dir = project_dirs.data_local_dir().join(DATABASE_PATH);
         std::fs::create_dir_all(project_dirs.config_local_dir())?;
-        std::fs::create_dir_all(project_dirs.data_local_dir())?;
-        Ok(Self {
-            pocket_consumer_key: POCKET_CONSUMER_KEY.to_string(),
-            database_dir,
+
+        let path = path
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os(CONFIG_ENV).map(PathBuf::from));
+        let explicit = path.is_some();
+        let path = path.unwrap_or_else(|| project_dirs.config_local_dir().join(CONFIG_PATH));
+        let text = match std::fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound && !explicit => String::new(),
+            Err(e) => return Err(e).with_context(|| format!("reading {}", path.display())),
+        };
+
+        let profile = profile
+            .map(String::from)
+            .or_else(|| std::env::var(PROFILE_ENV).ok())
+            .filter(|profile| !profile.is_empty());
+        let settings = Settings::parse(&text, profile.as_deref())
+            .with_context(|| format!("parsing {}", path.display()))?
+            .merge(Settings::from_env());
+
+        let runtime_dir = project_dirs
+            .runtime_dir()
+            .unwrap_or(project_dirs.data_local_dir());
+        let config = Config::resolve(
+            settings,
+            profile,
+            path,
+            project_dirs.data_local_dir(),
+            runtime_dir,
+        )?;
+        if let Some(parent) = config.database_dir.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::create_dir_all(&config.assets_dir)?;
+        std::fs::create_dir_all(&config.snapshots_dir)?;
+        Ok(config)
+    }
+
+    /// Default settings with everything kept in `dir`
+    pub fn in_dir(dir: &Path) -> Self {
+        Config::resolve(Settings::default(), None, dir.join(CONFIG_PATH), dir, dir)
+            .expect("default settings are valid")
+    }
+
+    fn resolve(
+        settings: Settings,
+        profile: Option<String>,
+        file: PathBuf,
+        data_dir: &Path,
+        runtime_dir: &Path,
+    ) -> anyhow::Result<Self> {
+        let mut data_dir = settings
+            .data_dir
+            .map(expand)
+            .unwrap_or_else(|| data_dir.to_path_buf());
+        let mut socket = SOCKET_PATH.to_string();
+        if let Some(profile) = &profile {
+            if profile.contains(['/', '\\']) || profile.starts_with('.') {
+                return Err(anyhow!("invalid profile name {:?}", profile));
+            }
+            data_dir = data_dir.join(PROFILES_PATH).join(profile);
+            socket = format!("readlater-{}.sock", profile);
+        }
+        Ok(Config {
+            file,
+            pocket_consumer_key: settings
+                .backends
+                .pocket
+                .consumer_key
+                .unwrap_or_else(|| POCKET_CONSUMER_KEY.to_string()),
+            database_dir: settings
+                .database
+                .map(expand)
+                .unwrap_or_else(|| data_dir.join(DATABASE_PATH)),
+            assets_dir: settings
+                .assets_dir
+                .map(expand)
+                .unwrap_or_else(|| data_dir.join(ASSETS_PATH)),
+            assets_budget: settings.assets_budget.unwrap_or(ASSETS_BUDGET),
+            snapshots_dir: settings
+                .snapshots_dir
+                .map(expand)
+                .unwrap_or_else(|| data_dir.join(SNAPSHOTS_PATH)),
+            socket_path: runtime_dir.join(socket),
+            default_tags: settings
+                .default_tags
+                .unwrap_or_else(|| DEFAULT_TAGS.iter().map(|t| t.to_string()).collect()),
+            save_to: settings.save_to.unwrap_or_default(),
+            fetch: FetchConfig {
+                workers: settings
+                    .fetch
+                    .workers
+                    .unwrap_or(crate::jobs::WORKERS)
+                    .max(1),
+                timeout: settings.fetch.timeout.unwrap_or(FETCH_TIMEOUT),
+                user_agent: settings
+                    .fetch
+                    .user_agent
+                    .unwrap_or_else(|| USER_AGENT.to_string()),
+            },
+            server: ServerConfig {
+                bind: settings.server.bind.unwrap_or(IpAddr::from([127, 0, 0, 1])),
+                port: settings.server.port.unwrap_or(crate::server::DEFAULT_PORT),
+                allow_origins: settings.server.allow_origins.unwrap_or_default(),
+            },
+            sync_interval: settings
+                .daemon
+                .interval
+                .unwrap_or(crate::daemon::DEFAULT_INTERVAL),
+            credentials: CredentialsConfig {
+                store: settings.credentials.store.unwrap_or_default(),
+                file: settings
+                    .credentials
+                    .file
+                    .map(expand)
+                    .unwrap_or_else(|| data_dir.join(CREDENTIALS_PATH)),
+            },
+            profile,
         })
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const FILE: &str = r#"
+        default_tags = ["inbox"]
+
+        [backends.pocket]
+        consumer_key = "key"
+
+        [fetch]
+        timeout = 10
+
+        [credentials]
+        store = "file"
+
+        [server]
+        port = 9000
+
+        [profiles.work]
+        default_tags = ["work"]
+        save_to = ["work-pocket"]
+        fetch.timeout = 5
+
+        [profiles.shared]
+        database = "/srv/readlater.sqlite"
+    "#;
+
+    fn config(profile: Option<&str>) -> Config {
+        let settings = Settings::parse(FILE, profile).unwrap();
+        let dir = Path::new("/data");
+        Config::resolve(
+            settings,
+            profile.map(String::from),
+            dir.join(CONFIG_PATH),
+            dir,
+            dir,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_file_overrides_defaults() {
+        let config = config(None);
+        assert_eq!(config.default_tags, vec!["inbox"]);
+        assert_eq!(config.pocket_consumer_key, "key");
+        assert_eq!(config.fetch.timeout, 10);
+        assert_eq!(config.fetch.workers, crate::jobs::WORKERS);
+        assert_eq!(config.server.port, 9000);
+        assert!(config.save_to.is_empty());
+        assert_eq!(config.database_dir, Path::new("/data/readlater.sqlite"));
+        assert_eq!(config.socket_path, Path::new("/data/readlater.sock"));
+    }
+
+    #[test]
+    fn test_profiles() {
+        let work = config(Some("work"));
+        assert_eq!(work.default_tags, vec!["work"]);
+        assert_eq!(work.fetch.timeout, 5);
+        assert_eq!(work.save_to, vec!["work-pocket"]);
+        assert_eq!(work.server.port, 9000);
+        assert_eq!(
+            work.database_dir,
+            Path::new("/data/profiles/work/readlater.sqlite")
+        );
+        assert_eq!(work.assets_dir, Path::new("/data/profiles/work/assets"));
+        assert_eq!(work.credentials.store, CredentialBackend::File);
+        assert_eq!(
+            work.credentials.file,
+            Path::new("/data/profiles/work/credentials.json")
+        );
+        assert_eq!(work.socket_path, Path::new("/data/readlater-work.sock"));
+
+        let shared = config(Some("shared"));
+        assert_eq!(shared.database_dir, Path::new("/srv/readlater.sqlite"));
+
+        // profiles the file doesn't mention still get their own database
+        let other = config(Some("other"));
+        assert_eq!(other.default_tags, vec!["inbox"]);
+        assert_eq!(
+            other.database_dir,
+            Path::new("/data/profiles/other/readlater.sqlite")
+        );
+    }
+
+    #[test]
+    fn test_profiles_keep_their_own_paths() {
+        let file = r#"
+            database = "/srv/readlater.sqlite"
+            assets_dir = "/srv/assets"
+
+            [credentials]
+            file = "/srv/credentials.json"
+
+            [profiles.work]
+            snapshots_dir = "/work/snapshots"
+        "#;
+        let dir = Path::new("/data");
+        let resolve = |profile: Option<&str>| {
+            let settings = Settings::parse(file, profile).unwrap();
+            let profile = profile.map(String::from);
+            Config::resolve(settings, profile, dir.join(CONFIG_PATH), dir, dir).unwrap()
+        };
+
+        let default = resolve(None);
+        assert_eq!(default.database_dir, Path::new("/srv/readlater.sqlite"));
+        assert_eq!(default.assets_dir, Path::new("/srv/assets"));
+
+        let work = resolve(Some("work"));
+        assert_eq!(
+            work.database_dir,
+            Path::new("/data/profiles/work/readlater.sqlite")
+        );
+        assert_eq!(work.assets_dir, Path::new("/data/profiles/work/assets"));
+        assert_eq!(work.snapshots_dir, Path::new("/work/snapshots"));
+        assert_eq!(
+            work.credentials.file,
+            Path::new("/data/profiles/work/credentials.json")
+        );
+        let other = resolve(Some("other"));
+        assert_eq!(
+            other.database_dir,
+            Path::new("/data/profiles/other/readlater.sqlite")
+        );
+    }
+
+    #[test]
+    fn test_rejects_unknown_settings() {
+        assert!(Settings::parse("databse = \"x\"", None).is_err());
+        assert!(Settings::parse("[profiles.work]\nfetch.timout = 1", Some("work")).is_err());
+    }
+}
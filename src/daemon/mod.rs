@@ -0,0 +1,319 @@
+//! Long running process that syncs the remotes on a schedule and runs the
+//! queued jobs. It is driven through a unix socket speaking one JSON request
+//! and one JSON reply per line.
+
+pub mod systemd;
+
+use crate::config::Config;
+use crate::jobs::{self, Worker};
+use crate::remotes;
+use crate::vault::Vault;
+use localdb::{JobState, LocalDb};
+use remote::SyncReport;
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::Notify;
+
+/// Minutes between two syncs
+pub const DEFAULT_INTERVAL: u64 = 30;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "lowercase")]
+pub enum Request {
+    Status,
+    /// Syncs right away, even when paused
+    Sync,
+    /// Stops the scheduled syncs and the jobs until resumed
+    Pause,
+    Resume,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Reply {
+    Status(Status),
+    Error(String),
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Status {
+    pub paused: bool,
+    pub syncing: bool,
+    pub last_sync: Option<i64>,
+    pub next_sync: Option<i64>,
+    /// Jobs waiting to run
+    pub queued: usize,
+    pub remotes: Vec<RemoteStatus>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RemoteStatus {
+    pub name: String,
+    pub report: Option<SyncReport>,
+    pub error: Option<String>,
+}
+
+#[derive(Default)]
+struct Shared {
+    status: Mutex<Status>,
+    sync_now: Notify,
+    /// Wakes the idle workers
+    jobs_ready: Notify,
+}
+
+pub struct Daemon {
+    config: Arc<Config>,
+    pool: SqlitePool,
+    vault: Arc<Vault>,
+    interval: Duration,
+    workers: usize,
+    socket: PathBuf,
+    shared: Arc<Shared>,
+}
+
+impl Daemon {
+    pub fn new(config: Arc<Config>, pool: SqlitePool, vault: Arc<Vault>, socket: &Path) -> Daemon {
+        Daemon {
+            config,
+            pool,
+            vault,
+            interval: Duration::from_secs(DEFAULT_INTERVAL * 60),
+            workers: jobs::WORKERS,
+            socket: socket.to_path_buf(),
+            shared: Arc::default(),
+        }
+    }
+
+    pub fn interval(mut self, interval: Duration) -> Daemon {
+        self.interval = interval;
+        self
+    }
+
+    pub fn workers(mut self, workers: usize) -> Daemon {
+        self.workers = workers;
+        self
+    }
+
+    /// Runs until `shutdown` completes, then removes the socket
+    pub async fn run(self, shutdown: impl Future<Output = ()>) -> anyhow::Result<()> {
+        let listener = bind(&self.socket).await?;
+        tracing::info!("listening on {}", self.socket.display());
+
+        // jobs of a previous run that stopped while running them
+        LocalDb::new(self.pool.clone()).reset_running_jobs().await?;
+
+        let control = tokio::spawn(accept(listener, self.pool.clone(), self.shared.clone()));
+        let worker = Arc::new(Worker::new(
+            self.config.clone(),
+            self.pool.clone(),
+            self.vault.clone(),
+        ));
+        let workers: Vec<_> = (0..self.workers.max(1))
+            .map(|_| tokio::spawn(work(worker.clone(), self.shared.clone())))
+            .collect();
+        let scheduler = tokio::spawn(schedule(
+            self.config.clone(),
+            self.pool.clone(),
+            self.vault.clone(),
+            self.shared.clone(),
+            self.interval,
+        ));
+
+        shutdown.await;
+        control.abort();
+        scheduler.abort();
+        for worker in workers {
+            worker.abort();
+        }
+        std::fs::remove_file(&self.socket)?;
+        Ok(())
+    }
+}
+
+/// Binds the socket, replacing the file left behind by a daemon that did
+/// not shut down cleanly
+async fn bind(socket: &Path) -> anyhow::Result<UnixListener> {
+    if socket.exists() {
+        if UnixStream::connect(socket).await.is_ok() {
+            anyhow::bail!("a daemon is already listening on {}", socket.display());
+        }
+        std::fs::remove_file(socket)?;
+    }
+    if let Some(dir) = socket.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    Ok(UnixListener::bind(socket)?)
+}
+
+/// Sends a request to the daemon listening on `socket`
+pub async fn control(socket: &Path, request: Request) -> anyhow::Result<Reply> {
+    let stream = UnixStream::connect(socket)
+        .await
+        .map_err(|e| anyhow::anyhow!("daemon not reachable at {}: {}", socket.display(), e))?;
+    let (reader, mut writer) = stream.into_split();
+    let mut line = serde_json::to_string(&request)?;
+    line.push('\n');
+    writer.write_all(line.as_bytes()).await?;
+
+    let mut reply = String::new();
+    BufReader::new(reader).read_line(&mut reply).await?;
+    Ok(serde_json::from_str(&reply)?)
+}
+
+async fn accept(listener: UnixListener, pool: SqlitePool, shared: Arc<Shared>) {
+    loop {
+        match listener.accept().await {
+            Ok((stream, _)) => {
+                tokio::spawn(serve(stream, pool.clone(), shared.clone()));
+            }
+            Err(e) => tracing::warn!("error accepting control connection: {}", e),
+        }
+    }
+}
+
+async fn serve(stream: UnixStream, pool: SqlitePool, shared: Arc<Shared>) {
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        let reply = match serde_json::from_str::<Request>(&line) {
+            Ok(request) => match handle(&pool, &shared, request).await {
+                Ok(status) => Reply::Status(status),
+                Err(e) => Reply::Error(e.to_string()),
+            },
+            Err(e) => Reply::Error(e.to_string()),
+        };
+        let mut reply = serde_json::to_string(&reply).expect("reply is serializable");
+        reply.push('\n');
+        if writer.write_all(reply.as_bytes()).await.is_err() {
+            return;
+        }
+    }
+}
+
+async fn handle(pool: &SqlitePool, shared: &Shared, request: Request) -> localdb::Result<Status> {
+    match request {
+        Request::Status => {}
+        Request::Sync => shared.sync_now.notify_one(),
+        Request::Pause => shared.status.lock().unwrap().paused = true,
+        Request::Resume => {
+            shared.status.lock().unwrap().paused = false;
+            shared.jobs_ready.notify_waiters();
+        }
+    }
+    let jobs = LocalDb::new(pool.clone()).get_jobs().await?;
+    let mut status = shared.status.lock().unwrap().clone();
+    status.queued = jobs
+        .iter()
+        .filter(|job| job.state != JobState::Failed)
+        .count();
+    Ok(status)
+}
+
+async fn schedule(
+    config: Arc<Config>,
+    pool: SqlitePool,
+    vault: Arc<Vault>,
+    shared: Arc<Shared>,
+    interval: Duration,
+) {
+    let mut forced = false;
+    loop {
+        if forced || !shared.status.lock().unwrap().paused {
+            shared.status.lock().unwrap().syncing = true;
+            let remotes = sync_all(&config, &vault, &pool).await;
+            let now = chrono::Utc::now().timestamp();
+            let mut status = shared.status.lock().unwrap();
+            status.syncing = false;
+            status.last_sync = Some(now);
+            status.remotes = remotes;
+            // pulled items come with jobs
+            shared.jobs_ready.notify_waiters();
+        }
+
+        let next = chrono::Utc::now().timestamp() + interval.as_secs() as i64;
+        shared.status.lock().unwrap().next_sync = Some(next);
+        forced = tokio::select! {
+            _ = tokio::time::sleep(interval) => false,
+            _ = shared.sync_now.notified() => true,
+        };
+    }
+}
+
+async fn sync_all(config: &Config, vault: &Vault, pool: &SqlitePool) -> Vec<RemoteStatus> {
+    let mut db = LocalDb::new(pool.clone());
+    let remotes = match db.get_remotes().await {
+        Ok(remotes) => remotes,
+        Err(e) => {
+            tracing::warn!("error reading remotes: {}", e);
+            return vec![];
+        }
+    };
+
+    let mut statuses = vec![];
+    for remote in remotes {
+        let result = remotes::sync(config, vault, &mut db, &remote).await;
+        let (report, error) = match result {
+            Ok(report) => (Some(report), None),
+            Err(e) => {
+                tracing::warn!("error syncing {}: {}", remote.name, e);
+                (None, Some(e.to_string()))
+            }
+        };
+        statuses.push(RemoteStatus {
+            name: remote.name,
+            report,
+            error,
+        });
+    }
+    statuses
+}
+
+async fn work(worker: Arc<Worker>, shared: Arc<Shared>) {
+    let paused = || shared.status.lock().unwrap().paused;
+    worker.run_forever(&shared.jobs_ready, paused).await;
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_control() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket = dir.path().join("readlater.sock");
+        let pool = localdb::open_database(":memory:").await.unwrap();
+        let config = Config::in_dir(dir.path());
+        let vault = Arc::new(crate::vault::test_vault(dir.path()));
+        let daemon = Daemon::new(Arc::new(config), pool, vault, &socket);
+        let (stop, stopped) = tokio::sync::oneshot::channel::<()>();
+        let running = tokio::spawn(daemon.run(async {
+            stopped.await.ok();
+        }));
+        while !socket.exists() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        let Reply::Status(status) = control(&socket, Request::Pause).await.unwrap() else {
+            panic!("expected a status");
+        };
+        assert!(status.paused);
+        let Reply::Status(status) = control(&socket, Request::Resume).await.unwrap() else {
+            panic!("expected a status");
+        };
+        assert!(!status.paused);
+        assert!(matches!(
+            control(&socket, Request::Sync).await.unwrap(),
+            Reply::Status(_)
+        ));
+
+        stop.send(()).unwrap();
+        running.await.unwrap().unwrap();
+        assert!(!socket.exists());
+    }
+}
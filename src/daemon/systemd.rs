@@ -0,0 +1,45 @@
+use std::{env, path::Path, path::PathBuf};
+
+pub const UNIT_NAME: &str = "readlater.service";
+
+/// User unit starting the daemon with the session
+pub fn unit(cli: &Path) -> String {
+    format!(
+        r#"[Unit]
+Description=readlater sync daemon
+After=network-online.target
+
+[Service]
+ExecStart={} daemon
+Restart=on-failure
+
+[Install]
+WantedBy=default.target
+"#,
+        cli.display()
+    )
+}
+
+/// Writes the unit to the systemd user directory, it still has to be enabled
+/// with `systemctl --user enable --now readlater`
+pub fn install_unit(cli: &Path) -> std::io::Result<PathBuf> {
+    let config_home = env::var("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| PathBuf::from(env::var("HOME").unwrap_or_default()).join(".config"));
+    let dir = config_home.join("systemd/user");
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join(UNIT_NAME);
+    std::fs::write(&path, unit(cli))?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_unit() {
+        let unit = unit(Path::new("/usr/bin/readlater"));
+        assert!(unit.contains("ExecStart=/usr/bin/readlater daemon\n"));
+    }
+}
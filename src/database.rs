@@ -0,0 +1,264 @@
+//! Opens the library, encrypts it at rest with a key kept in the credential
+//! store, and backs it up
+
+use crate::config::Config;
+use crate::vault::Vault;
+use anyhow::{anyhow, bail, Context};
+use sqlx::SqlitePool;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+/// Opens the library, taking its key from the credential store when it is
+/// encrypted
+pub async fn open(config: &Config) -> anyhow::Result<SqlitePool> {
+    let key = key(config).await?;
+    Ok(localdb::open_database_with_key(path(config)?, key.as_deref()).await?)
+}
+
+/// Opens the library without migrating it, to inspect it
+pub async fn inspect(config: &Config) -> anyhow::Result<SqlitePool> {
+    let key = key(config).await?;
+    Ok(localdb::connect_database(path(config)?, key.as_deref()).await?)
+}
+
+/// Key of the library, none when it is stored in plain text
+pub async fn key(config: &Config) -> anyhow::Result<Option<String>> {
+    let path = &config.database_dir;
+    if !localdb::is_encrypted(path)? {
+        return Ok(None);
+    }
+    let vault = Vault::connect(config).await?;
+    Ok(Some(stored_key(&vault, path).await?))
+}
+
+fn path(config: &Config) -> anyhow::Result<&str> {
+    config
+        .database_dir
+        .to_str()
+        .context("database path is not valid UTF-8")
+}
+
+/// Copies the library to `dest`, by default a new file in the backups
+/// directory, while it stays in use. Returns where the copy is.
+pub async fn backup(config: &Config, dest: Option<PathBuf>) -> anyhow::Result<PathBuf> {
+    let path = &config.database_dir;
+    let dest = dest.unwrap_or_else(|| localdb::backup_path(path, None));
+    if dest.exists() {
+        bail!("{} already exists", dest.display());
+    }
+    if let Some(dir) = dest.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    localdb::backup(path, key(config).await?.as_deref(), &dest).await?;
+    Ok(dest)
+}
+
+/// Replaces the library with the backup at `backup`. The library is backed
+/// up first, with the automatic backups. Returns where that copy is.
+pub async fn restore(config: &Config, backup: &Path) -> anyhow::Result<PathBuf> {
+    if !backup.exists() {
+        bail!("{} does not exist", backup.display());
+    }
+    let path = &config.database_dir;
+    let key = key(config).await?;
+    let saved = localdb::backup_path(path, Some("pre-restore"));
+    std::fs::create_dir_all(localdb::backup_dir(path))?;
+    localdb::backup(path, key.as_deref(), &saved).await?;
+    localdb::restore(path, key.as_deref(), backup)
+        .await
+        .with_context(|| format!("restoring {}", backup.display()))?;
+    Ok(saved)
+}
+
+/// Takes the lock long running processes hold while they have the library
+/// open, so that it is not replaced under them. Dropping the file releases
+/// it.
+pub fn hold(config: &Config) -> anyhow::Result<File> {
+    let lock = lock_file(config)?;
+    lock.lock_shared()?;
+    Ok(lock)
+}
+
+/// Takes the library for a command replacing its file, failing while the
+/// daemon, the server or the TUI have it open
+fn take(config: &Config) -> anyhow::Result<File> {
+    let busy = || {
+        anyhow!(
+            "{} is in use, stop the daemon, the server and the TUI first",
+            config.database_dir.display()
+        )
+    };
+    #[cfg(unix)]
+    if std::os::unix::net::UnixStream::connect(&config.socket_path).is_ok() {
+        return Err(busy());
+    }
+    let lock = lock_file(config)?;
+    lock.try_lock().map_err(|_| busy())?;
+    Ok(lock)
+}
+
+fn lock_file(config: &Config) -> anyhow::Result<File> {
+    let mut name = config.database_dir.as_os_str().to_os_string();
+    name.push(".lock");
+    Ok(std::fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(name)?)
+}
+
+/// Encrypts the library with a new key, and its backups with it. Returns the
+/// backups that could not be encrypted.
+pub async fn encrypt(config: &Config) -> anyhow::Result<Vec<PathBuf>> {
+    let path = &config.database_dir;
+    if localdb::is_encrypted(path)? {
+        bail!("{} is already encrypted", path.display());
+    }
+    let _lock = take(config)?;
+    let vault = Vault::connect(config).await?;
+    let key = credentials::random_secret();
+    let staged = staging(path);
+    localdb::export_database(path, None, &staged, Some(&key)).await?;
+    vault.set_database_key(&key).await?;
+    replace(&staged, path)?;
+    convert_backups(path, None, Some(&key)).await
+}
+
+/// Stores the library and its backups in plain text again. Returns the
+/// backups that could not be decrypted.
+pub async fn decrypt(config: &Config) -> anyhow::Result<Vec<PathBuf>> {
+    let path = &config.database_dir;
+    if !localdb::is_encrypted(path)? {
+        bail!("{} is not encrypted", path.display());
+    }
+    let _lock = take(config)?;
+    let vault = Vault::connect(config).await?;
+    let key = stored_key(&vault, path).await?;
+    let staged = staging(path);
+    localdb::export_database(path, Some(&key), &staged, None).await?;
+    replace(&staged, path)?;
+    let failed = convert_backups(path, Some(&key), None).await?;
+    // backups left encrypted still need it
+    if failed.is_empty() {
+        vault.forget_database_key().await?;
+    }
+    Ok(failed)
+}
+
+/// Encrypts the library and its backups again under a new key. Returns the
+/// backups left under the old key.
+pub async fn rekey(config: &Config) -> anyhow::Result<Vec<PathBuf>> {
+    let path = &config.database_dir;
+    if !localdb::is_encrypted(path)? {
+        bail!("{} is not encrypted", path.display());
+    }
+    let _lock = take(config)?;
+    let vault = Vault::connect(config).await?;
+    let old = stored_key(&vault, path).await?;
+    let new = credentials::random_secret();
+    let staged = staging(path);
+    localdb::export_database(path, Some(&old), &staged, Some(&new)).await?;
+    vault.set_database_key(&new).await?;
+    if let Err(e) = replace(&staged, path) {
+        // the library is still under the old key
+        vault.set_database_key(&old).await?;
+        return Err(e);
+    }
+    convert_backups(path, Some(&old), Some(&new)).await
+}
+
+/// Moves the backups of the library at `path` from `key` to `new_key`, so
+/// that they stay restorable and leak no more than the library. Returns
+/// the backups that could not be moved, such as ones under an older key.
+async fn convert_backups(
+    path: &Path,
+    key: Option<&str>,
+    new_key: Option<&str>,
+) -> anyhow::Result<Vec<PathBuf>> {
+    let dir = localdb::backup_dir(path);
+    let entries = match std::fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(e.into()),
+    };
+    let mut failed = vec![];
+    for entry in entries {
+        let backup = entry?.path();
+        if backup.extension().is_none_or(|ext| ext != "sqlite") {
+            continue;
+        }
+        let staged = staging(&backup);
+        let converted = match localdb::export_database(&backup, key, &staged, new_key).await {
+            Ok(()) => replace(&staged, &backup),
+            Err(e) => Err(e.into()),
+        };
+        if converted.is_err() {
+            let _ = std::fs::remove_file(&staged);
+            failed.push(backup);
+        }
+    }
+    failed.sort();
+    Ok(failed)
+}
+
+async fn stored_key(vault: &Vault, path: &Path) -> anyhow::Result<String> {
+    vault.database_key().await?.ok_or_else(|| {
+        anyhow!(
+            "{} is encrypted but the credential store has no key for it",
+            path.display()
+        )
+    })
+}
+
+/// Where the new copy of the library is written before replacing it
+fn staging(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".new");
+    path.with_file_name(name)
+}
+
+fn replace(staged: &Path, path: &Path) -> anyhow::Result<()> {
+    std::fs::rename(staged, path)
+        .with_context(|| format!("replacing {} with {}", path.display(), staged.display()))?;
+    // journals of the replaced file, left when it was not closed cleanly
+    for suffix in ["-wal", "-shm"] {
+        let mut journal = path.as_os_str().to_os_string();
+        journal.push(suffix);
+        match std::fs::remove_file(&journal) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_convert_backups() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("library.sqlite");
+        let backups = localdb::backup_dir(&path);
+        std::fs::create_dir_all(&backups).unwrap();
+        let plain = backups.join("plain.sqlite");
+        let other = backups.join("other.sqlite");
+        let pool = localdb::open_database(plain.to_str().unwrap())
+            .await
+            .unwrap();
+        pool.close().await;
+        localdb::export_database(&plain, None, &other, Some("older"))
+            .await
+            .unwrap();
+
+        let failed = convert_backups(&path, None, Some("key")).await.unwrap();
+        assert_eq!(failed, vec![other.clone()]);
+        assert!(localdb::is_encrypted(&plain).unwrap());
+        let pool = localdb::open_database_with_key(plain.to_str().unwrap(), Some("key"))
+            .await
+            .unwrap();
+        pool.close().await;
+        assert!(!staging(&other).exists());
+    }
+}
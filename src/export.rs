@@ -0,0 +1,194 @@
+use assets::AssetStore;
+use chrono::{DateTime, Utc};
+use epub::{xhtml, Chapter, EpubBuilder};
+use localdb::{Item, LocalDb};
+use md5::{Digest, Md5};
+use std::collections::HashMap;
+
+const DATE_FORMAT: &str = "%Y-%m-%dT%H:%M:%SZ";
+
+/// Builds a single EPUB with one chapter per item. Images are embedded from
+/// the asset store, only cached images are included.
+pub async fn bundle_epub(
+    db: &mut LocalDb,
+    assets: &AssetStore,
+    title: &str,
+    items: &[Item],
+) -> anyhow::Result<Vec<u8>> {
+    let now = Utc::now();
+    let mut book = EpubBuilder::new(&format!("urn:readlater:bundle:{}", now.timestamp()), title)
+        .creator("readlater")
+        .modified(&now.format(DATE_FORMAT).to_string());
+
+    for item in items {
+        add_item(&mut book, db, assets, item).await?;
+    }
+    Ok(book.to_bytes()?)
+}
+
+/// Builds an EPUB for a single item. The output only depends on the stored
+/// item, so exporting the same article twice yields the same file.
+pub async fn article_epub(
+    db: &mut LocalDb,
+    assets: &AssetStore,
+    item: &Item,
+) -> anyhow::Result<Vec<u8>> {
+    let modified = item.time_updated.unwrap_or(item.time_added);
+    let modified = DateTime::from_timestamp(modified as i64, 0).unwrap_or_default();
+    let mut book = EpubBuilder::new(&format!("urn:readlater:item:{}", item.id), &title(item))
+        .language(item.lang.as_deref().unwrap_or_default())
+        .modified(&modified.format(DATE_FORMAT).to_string());
+    for author in author_names(item) {
+        book = book.creator(author);
+    }
+
+    add_item(&mut book, db, assets, item).await?;
+    let bytes = book.to_bytes()?;
+    record_documents(db, item, &bytes).await?;
+    Ok(bytes)
+}
+
+/// Hash KOReader uses to identify a file: the md5 of 1 KiB samples taken at
+/// growing offsets.
+pub fn partial_md5(data: &[u8]) -> String {
+    let mut hasher = Md5::new();
+    for i in -1..=10 {
+        let offset = if i < 0 { 0 } else { 1024 << (2 * i) };
+        if offset >= data.len() {
+            break;
+        }
+        hasher.update(&data[offset..data.len().min(offset + 1024)]);
+    }
+    format!("{:x}", hasher.finalize())
+}
+
+/// Records the hashes KOReader may report progress of an article EPUB under,
+/// by file content or by file name.
+async fn record_documents(db: &mut LocalDb, item: &Item, epub: &[u8]) -> anyhow::Result<()> {
+    db.add_document(&partial_md5(epub), item.id).await?;
+    let name = format!("{:x}", Md5::digest(file_name(item)));
+    db.add_document(&name, item.id).await?;
+    Ok(())
+}
+
+/// File name for the per-article export of an item
+pub fn file_name(item: &Item) -> String {
+    let slug = title(item)
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<_>>()
+        .join("-");
+    let slug: String = slug.chars().take(50).collect();
+    format!("{}-{}.epub", item.id, slug.trim_end_matches('-'))
+}
+
+async fn add_item(
+    book: &mut EpubBuilder,
+    db: &mut LocalDb,
+    assets: &AssetStore,
+    item: &Item,
+) -> anyhow::Result<()> {
+    let content = db.get_content(item.id).await?;
+
+    // maps the src of every cached image to its path inside the book
+    let mut images: HashMap<String, String> = HashMap::new();
+    if let Some(content) = &content {
+        for src in xhtml::image_sources(&content.html) {
+            if images.contains_key(&src) {
+                continue;
+            }
+            let Some(url) = assets::resolve(&item.url, &src) else {
+                continue;
+            };
+            if let Some((asset, data)) = assets.get(db, &url).await? {
+                images.insert(src, book.add_resource(&asset.mime, data));
+            }
+        }
+    }
+
+    let body = match (content, &item.excerpt) {
+        (Some(content), _) => xhtml::from_html(&content.html, |src| images.get(src).cloned()),
+        (None, Some(excerpt)) if !excerpt.is_empty() => {
+            format!("<p>{}</p>", xhtml::escape(excerpt))
+        }
+        _ => "<p>No content has been saved for this item yet.</p>".to_string(),
+    };
+
+    book.add_chapter(Chapter {
+        title: title(item),
+        body: format!("{}\n{}", source(item), body),
+    });
+    Ok(())
+}
+
+fn title(item: &Item) -> String {
+    if item.title.is_empty() {
+        item.url.clone()
+    } else {
+        item.title.clone()
+    }
+}
+
+/// The authors of the item by name, so that they come out in the same order
+/// on every export
+fn author_names(item: &Item) -> Vec<&str> {
+    let mut names: Vec<&str> = item.authors.iter().map(|a| a.name.as_str()).collect();
+    names.sort_unstable();
+    names
+}
+
+fn source(item: &Item) -> String {
+    let host = url::Url::parse(&item.url)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_string))
+        .unwrap_or_else(|| item.url.clone());
+    let mut source = format!(
+        r#"<p class="source"><a href="{}">{}</a>"#,
+        xhtml::escape(&item.url),
+        xhtml::escape(&host)
+    );
+    let authors = author_names(item);
+    if !authors.is_empty() {
+        source.push_str(&format!(" · {}", xhtml::escape(&authors.join(", "))));
+    }
+    source.push_str("</p>");
+    source
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use localdb::Author;
+
+    #[test]
+    fn test_source_sorts_authors() {
+        let item = Item {
+            url: "https://example.com/a".to_string(),
+            authors: ["Zoe", "Ada", "Max"]
+                .into_iter()
+                .map(|name| Author {
+                    name: name.to_string(),
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        };
+        assert_eq!(
+            source(&item),
+            r#"<p class="source"><a href="https://example.com/a">example.com</a> · Ada, Max, Zoe</p>"#
+        );
+    }
+
+    #[test]
+    fn test_partial_md5() {
+        // small files are hashed whole
+        assert_eq!(partial_md5(b"abc"), "900150983cd24fb0d6963f7d28e17f72");
+
+        // samples at 0, 1 KiB and 4 KiB
+        let data: Vec<u8> = (0..5000).map(|i| (i % 251) as u8).collect();
+        let mut sampled = data[..2048].to_vec();
+        sampled.extend_from_slice(&data[4096..]);
+        assert_eq!(partial_md5(&data), format!("{:x}", Md5::digest(&sampled)));
+    }
+}
@@ -0,0 +1,266 @@
+use assets::AssetStore;
+use chrono::DateTime;
+use feed::{Entry, FeedBuilder};
+use localdb::{Asset, Item, ItemQuery, ItemStatus, LocalDb};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+
+/// Number of newest items a feed holds
+pub const FEED_LIMIT: usize = 50;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FeedKind {
+    Unread,
+    Favorites,
+    Tag(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum FeedFormat {
+    Atom,
+    Rss,
+}
+
+impl FeedFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            FeedFormat::Atom => "atom",
+            FeedFormat::Rss => "rss",
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            FeedFormat::Atom => "application/atom+xml",
+            FeedFormat::Rss => "application/rss+xml",
+        }
+    }
+
+    /// Splits `name.atom` or `name.rss` into the name and the format
+    pub fn split(file: &str) -> Option<(&str, FeedFormat)> {
+        let (name, extension) = file.rsplit_once('.')?;
+        match extension {
+            "atom" => Some((name, FeedFormat::Atom)),
+            "rss" => Some((name, FeedFormat::Rss)),
+            _ => None,
+        }
+    }
+}
+
+impl FeedKind {
+    pub fn title(&self) -> String {
+        match self {
+            FeedKind::Unread => "readlater: unread".to_string(),
+            FeedKind::Favorites => "readlater: favorites".to_string(),
+            FeedKind::Tag(tag) => format!("readlater: {}", tag),
+        }
+    }
+
+    /// Path the server publishes the feed at
+    pub fn path(&self, format: FeedFormat) -> String {
+        match self {
+            FeedKind::Unread => format!("/feeds/unread.{}", format.extension()),
+            FeedKind::Favorites => format!("/feeds/favorites.{}", format.extension()),
+            FeedKind::Tag(tag) => format!(
+                "/feeds/tags/{}.{}",
+                url::form_urlencoded::byte_serialize(tag.as_bytes()).collect::<String>(),
+                format.extension()
+            ),
+        }
+    }
+
+    /// Unread lists the queue, the other feeds are curated lists and also
+    /// keep archived items.
+    fn query(&self) -> ItemQuery {
+        match self {
+            FeedKind::Unread => ItemQuery::default().status(ItemStatus::Unread),
+            FeedKind::Favorites => ItemQuery::default().favorite(true),
+            FeedKind::Tag(tag) => ItemQuery::default().tag(tag),
+        }
+    }
+}
+
+/// Key that opens a single feed without giving access to anything else, so
+/// that feeds can be shared.
+pub fn feed_key(token: &str, path: &str) -> String {
+    let digest = Sha256::digest(format!("{}:{}", token, path));
+    digest[..16].iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn entry(item: &Item, content: Option<String>) -> Entry {
+    let published = DateTime::from_timestamp(item.time_added as i64, 0).unwrap_or_default();
+    let updated = item
+        .time_updated
+        .and_then(|time| DateTime::from_timestamp(time as i64, 0))
+        .unwrap_or(published)
+        .max(published);
+    let mut categories: Vec<String> = item.tags.iter().map(|tag| tag.tag.clone()).collect();
+    categories.sort();
+    Entry {
+        id: item.url.clone(),
+        title: item.title.clone(),
+        link: item.url.clone(),
+        summary: item.excerpt.clone(),
+        content,
+        authors: item.authors.iter().map(|a| a.name.clone()).collect(),
+        categories,
+        published,
+        updated,
+    }
+}
+
+/// Cached images and the url each of them is served at
+pub struct Images<'a> {
+    pub store: &'a AssetStore,
+    pub link: &'a (dyn Fn(&Asset) -> String + Sync),
+}
+
+/// Maps the `src` of every image of `html` to where a browser finds it: the
+/// cached copy when `images` has one, else the original resolved against the
+/// page of the item
+pub async fn image_links(
+    db: &LocalDb,
+    item: &Item,
+    html: &str,
+    images: Option<&Images<'_>>,
+) -> anyhow::Result<HashMap<String, String>> {
+    let mut links = HashMap::new();
+    for src in epub::xhtml::image_sources(html) {
+        let Some(url) = assets::resolve(&item.url, &src) else {
+            continue;
+        };
+        let cached = match images {
+            Some(images) => images
+                .store
+                .find(db, &url)
+                .await?
+                .map(|asset| (images.link)(&asset)),
+            None => None,
+        };
+        links.insert(src, cached.unwrap_or(url));
+    }
+    Ok(links)
+}
+
+/// Builds a feed of the newest items of a kind, with their extracted content.
+/// `link` is the page the feed belongs to.
+pub async fn build(
+    db: &LocalDb,
+    kind: &FeedKind,
+    link: &str,
+    images: Option<&Images<'_>>,
+) -> anyhow::Result<FeedBuilder> {
+    let items = db.query_items(&kind.query()).await?;
+    let items = items
+        .into_iter()
+        .filter(|item| item.status != ItemStatus::Deleted)
+        .take(FEED_LIMIT);
+
+    let id = format!("urn:readlater:{}", kind.path(FeedFormat::Atom));
+    let mut feed = FeedBuilder::new(&id, &kind.title(), link);
+    for item in items {
+        let content = match db.get_content(item.id).await? {
+            Some(content) => {
+                let links = image_links(db, &item, &content.html, images).await?;
+                Some(epub::xhtml::from_html(&content.html, |src| {
+                    links.get(src).cloned()
+                }))
+            }
+            None => None,
+        };
+        feed.add_entry(entry(&item, content));
+    }
+    Ok(feed)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use localdb::{Content, Tag};
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_paths() {
+        let tag = FeedKind::Tag("rust lang".to_string());
+        assert_eq!(tag.path(FeedFormat::Rss), "/feeds/tags/rust+lang.rss");
+        assert_eq!(
+            FeedFormat::split("rust+lang.rss"),
+            Some(("rust+lang", FeedFormat::Rss))
+        );
+        assert_ne!(
+            feed_key("token", &tag.path(FeedFormat::Rss)),
+            feed_key("token", &FeedKind::Unread.path(FeedFormat::Rss))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_build() {
+        let pool = localdb::open_database(":memory:").await.unwrap();
+        let mut db = LocalDb::new(pool);
+        let id = db
+            .add(&Item {
+                url: "https://example.com/a".to_string(),
+                tags: HashSet::from([Tag::default()]),
+                status: ItemStatus::Archived,
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        db.set_content(&Content {
+            item_id: id as i64,
+            html: "<p>Body<script>x()</script></p>".to_string(),
+            time_fetched: None,
+        })
+        .await
+        .unwrap();
+
+        let unread = build(&db, &FeedKind::Unread, "http://localhost/", None)
+            .await
+            .unwrap();
+        assert!(unread.entries().is_empty());
+
+        let tag = FeedKind::Tag("example".to_string());
+        let feed = build(&db, &tag, "http://localhost/", None).await.unwrap();
+        assert_eq!(feed.entries().len(), 1);
+        assert_eq!(feed.entries()[0].content.as_deref(), Some("<p>Body</p>"));
+    }
+
+    #[tokio::test]
+    async fn test_image_links() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = AssetStore::new(dir.path(), 1024, std::time::Duration::from_secs(1));
+        let pool = localdb::open_database(":memory:").await.unwrap();
+        let mut db = LocalDb::new(pool);
+        let asset = Asset {
+            id: 0,
+            url: "https://example.com/img/cached.png".to_string(),
+            hash: "abc".to_string(),
+            path: "ab/abc".to_string(),
+            mime: "image/png".to_string(),
+            size: 3,
+            time_fetched: 1,
+            time_accessed: 1,
+        };
+        db.add_asset(&asset).await.unwrap();
+        std::fs::create_dir_all(dir.path().join("ab")).unwrap();
+        std::fs::write(store.path(&asset), "png").unwrap();
+
+        let item = Item {
+            url: "https://example.com/posts/1".to_string(),
+            ..Default::default()
+        };
+        let html = r#"<img src="/img/cached.png"><img src="other.png"><img src="data:,x">"#;
+        let link = |asset: &Asset| format!("/assets/{}", asset.hash);
+        let images = Images {
+            store: &store,
+            link: &link,
+        };
+        let links = image_links(&db, &item, html, Some(&images)).await.unwrap();
+        assert_eq!(links.len(), 2);
+        assert_eq!(links["/img/cached.png"], "/assets/abc");
+        assert_eq!(links["other.png"], "https://example.com/posts/other.png");
+
+        let links = image_links(&db, &item, html, None).await.unwrap();
+        assert_eq!(links["/img/cached.png"], asset.url);
+    }
+}
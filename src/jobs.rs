@@ -0,0 +1,285 @@
+//! Slow work on items, such as downloading their article, done in the
+//! background from the persistent job queue.
+
+use crate::config::Config;
+use crate::remotes;
+use crate::vault::Vault;
+use archiver::{Archiver, SnapshotStore};
+use assets::AssetStore;
+use linkcheck::LinkChecker;
+use localdb::{Content, Job, JobKind, LocalDb, Source};
+use metadata::MetadataFetcher;
+use sqlx::SqlitePool;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Notify;
+
+/// Attempts before a job is given up
+pub const MAX_ATTEMPTS: i32 = 5;
+/// Jobs running at the same time
+pub const WORKERS: usize = 4;
+/// Longest wait before idle workers check the queue again
+const POLL: Duration = Duration::from_secs(60);
+
+/// Seconds until the next attempt of a job that failed `attempts` times,
+/// doubling from a minute up to six hours
+pub fn backoff(attempts: i32) -> i64 {
+    (60i64 << attempts.clamp(0, 16)).min(6 * 60 * 60)
+}
+
+/// Queues the work a newly saved item needs
+pub async fn enqueue_new(db: &mut LocalDb, item: i64) -> localdb::Result<()> {
+    db.add_job(JobKind::Extract, item).await?;
+    db.add_job(JobKind::Enrich, item).await
+}
+
+pub struct Worker {
+    config: Arc<Config>,
+    pool: SqlitePool,
+    vault: Arc<Vault>,
+    fetcher: MetadataFetcher,
+    checker: LinkChecker,
+    assets: AssetStore,
+    archiver: Archiver,
+    snapshots: SnapshotStore,
+}
+
+impl Worker {
+    pub fn new(config: Arc<Config>, pool: SqlitePool, vault: Arc<Vault>) -> Worker {
+        Worker {
+            pool,
+            vault,
+            fetcher: MetadataFetcher::with_options(
+                Duration::from_secs(config.fetch.timeout),
+                &config.fetch.user_agent,
+            ),
+            checker: LinkChecker::with_options(
+                1,
+                Duration::from_secs(config.fetch.timeout),
+                &config.fetch.user_agent,
+            ),
+            assets: AssetStore::new(
+                &config.assets_dir,
+                config.assets_budget,
+                Duration::from_secs(config.fetch.timeout),
+            ),
+            archiver: Archiver::new(
+                Duration::from_secs(config.fetch.timeout),
+                &config.fetch.user_agent,
+            ),
+            snapshots: SnapshotStore::new(&config.snapshots_dir),
+            config,
+        }
+    }
+
+    /// Runs the next due job, returns false when none is due
+    pub async fn run_next(&self) -> localdb::Result<bool> {
+        let mut db = LocalDb::new(self.pool.clone()).with_source(Source::Job);
+        let now = chrono::Utc::now().timestamp();
+        let Some(job) = db.claim_job(now).await? else {
+            return Ok(false);
+        };
+        match self.run(&mut db, &job).await {
+            Ok(()) => db.complete_job(job.id).await?,
+            Err(e) => {
+                tracing::warn!("{:?} job of item {} failed: {}", job.kind, job.item_id, e);
+                let attempts = job.attempts + 1;
+                let retry_at = (attempts < MAX_ATTEMPTS).then(|| now + backoff(attempts));
+                db.fail_job(job.id, &e.to_string(), retry_at).await?;
+            }
+        }
+        Ok(true)
+    }
+
+    /// Runs jobs until none is due, returns how many ran
+    pub async fn run_due(&self) -> localdb::Result<usize> {
+        let mut count = 0;
+        while self.run_next().await? {
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    /// Runs jobs as they become due. Idle workers sleep until the next job
+    /// is due or `wake` is notified, and do nothing while `paused` holds.
+    pub async fn run_forever(&self, wake: &Notify, paused: impl Fn() -> bool) {
+        loop {
+            if !paused() {
+                match self.run_next().await {
+                    Ok(true) => continue,
+                    Ok(false) => {}
+                    Err(e) => tracing::warn!("error running jobs: {}", e),
+                }
+            }
+            let db = LocalDb::new(self.pool.clone());
+            let now = chrono::Utc::now().timestamp();
+            let wait = match db.next_job_time().await {
+                Ok(Some(next)) if !paused() => Duration::from_secs((next - now).max(1) as u64),
+                _ => POLL,
+            };
+            tokio::select! {
+                _ = tokio::time::sleep(wait.min(POLL)) => {}
+                _ = wake.notified() => {}
+            }
+        }
+    }
+
+    async fn run(&self, db: &mut LocalDb, job: &Job) -> anyhow::Result<()> {
+        // the item may have been removed since
+        let Some(mut item) = db.get_item(job.item_id).await? else {
+            return Ok(());
+        };
+        match job.kind {
+            JobKind::Extract => {
+                if db.get_content(item.id).await?.is_none() {
+                    let page = self.fetcher.fetch_page(&item.url).await?;
+                    let html = metadata::extract(&page.html, &page.url)
+                        .ok_or_else(|| anyhow::anyhow!("no article found"))?;
+                    db.set_content(&Content {
+                        item_id: item.id,
+                        html,
+                        time_fetched: Some(chrono::Utc::now().timestamp() as i32),
+                    })
+                    .await?;
+                }
+                db.add_job(JobKind::Assets, item.id).await?;
+            }
+            JobKind::Enrich => {
+                let metadata = self.fetcher.fetch(&item.url).await?;
+                if metadata::apply(&mut item, &metadata, false) {
+                    db.update_item(&item).await?;
+                }
+            }
+            JobKind::Assets => {
+                let results = self.assets.cache_item(db, &item).await?;
+                let failed: Vec<String> = results
+                    .into_iter()
+                    .filter_map(|(url, result)| result.err().map(|e| format!("{}: {}", url, e)))
+                    .collect();
+                if !failed.is_empty() {
+                    anyhow::bail!("{}", failed.join(", "));
+                }
+            }
+            JobKind::CheckLink => {
+                let check = self.checker.check(&item.url).await;
+                let now = chrono::Utc::now().timestamp() as i32;
+                db.set_link_status(item.id, check.status, check.final_url.as_deref(), now)
+                    .await?;
+            }
+            JobKind::Snapshot => {
+                let capture = self.archiver.capture(&item.url).await?;
+                self.snapshots.save(db, item.id, &capture).await?;
+            }
+            JobKind::Push => self.push(db, item.id).await?,
+        }
+        Ok(())
+    }
+
+    /// Pushes the outbox of the remotes with changes of `item` queued,
+    /// failing while a change of the item is refused
+    async fn push(&self, db: &mut LocalDb, item: i64) -> anyhow::Result<()> {
+        let mut failed = vec![];
+        for remote in db.get_remotes().await? {
+            let queued = db.get_outbox(remote.id).await?;
+            if !queued.iter().any(|entry| entry.item_id == item) {
+                continue;
+            }
+            let mut backend = remotes::open(&self.config, &self.vault, &remote).await?;
+            let pushed = remote::push(db, &remote, backend.as_mut()).await;
+            self.vault
+                .store_settings(db, &remote, backend.settings())
+                .await?;
+            pushed?;
+            let refused = db
+                .get_outbox(remote.id)
+                .await?
+                .into_iter()
+                .find_map(|entry| {
+                    (entry.item_id == item)
+                        .then_some(entry.last_error)
+                        .flatten()
+                });
+            if let Some(error) = refused {
+                failed.push(format!("{}: {}", remote.name, error));
+            }
+        }
+        if !failed.is_empty() {
+            anyhow::bail!("{}", failed.join(", "));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use localdb::{Item, JobState};
+
+    #[test]
+    fn test_backoff() {
+        assert_eq!(backoff(1), 120);
+        assert_eq!(backoff(3), 480);
+        assert_eq!(backoff(MAX_ATTEMPTS * 4), 6 * 60 * 60);
+    }
+
+    #[tokio::test]
+    async fn test_failed_jobs_back_off() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = localdb::open_database(":memory:").await.unwrap();
+        let config = Config::in_dir(dir.path());
+        let mut db = LocalDb::new(pool.clone());
+        let id = db
+            .add(&Item {
+                url: "http://127.0.0.1:9/unreachable".to_string(),
+                ..Default::default()
+            })
+            .await
+            .unwrap() as i64;
+        enqueue_new(&mut db, id).await.unwrap();
+
+        let vault = Arc::new(crate::vault::test_vault(dir.path()));
+        let worker = Worker::new(Arc::new(config), pool, vault);
+        assert_eq!(worker.run_due().await.unwrap(), 2);
+        let jobs = db.get_jobs().await.unwrap();
+        assert!(jobs.iter().all(|job| job.state == JobState::Pending
+            && job.attempts == 1
+            && job.last_error.is_some()));
+        // nothing is due until the backoff passed
+        assert_eq!(worker.run_due().await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_saved_items_are_pushed_by_a_job() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = localdb::open_database(":memory:").await.unwrap();
+        let config = Config::in_dir(dir.path());
+        let mut db = LocalDb::new(pool.clone());
+        let settings = r#"{"url":"http://127.0.0.1:9","client_id":"id","client_secret":"s","refresh_token":"r"}"#;
+        let remote = db.add_remote("work", "wallabag", settings).await.unwrap();
+        let url = url::Url::parse("http://127.0.0.1:9/unreachable").unwrap();
+
+        let id = remotes::save(&config, &mut db, &url, None, vec![], &[])
+            .await
+            .unwrap();
+        // saving does not wait for the remote
+        assert_eq!(db.get_outbox(remote).await.unwrap().len(), 1);
+        let kinds: Vec<_> = db
+            .get_jobs()
+            .await
+            .unwrap()
+            .iter()
+            .map(|job| job.kind)
+            .collect();
+        assert!(kinds.contains(&JobKind::Push));
+
+        let vault = Arc::new(crate::vault::test_vault(dir.path()));
+        let worker = Worker::new(Arc::new(config), pool, vault);
+        worker.run_due().await.unwrap();
+        let jobs = db.get_jobs().await.unwrap();
+        let push = jobs.iter().find(|job| job.kind == JobKind::Push).unwrap();
+        assert_eq!(push.item_id, id);
+        assert_eq!(push.state, JobState::Pending);
+        assert!(push.last_error.is_some());
+        assert_eq!(db.get_outbox(remote).await.unwrap().len(), 1);
+    }
+}
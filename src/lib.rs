@@ -1,3 +1,15 @@
+pub mod actions;
 pub mod config;
+pub mod daemon;
+pub mod database;
+pub mod export;
+pub mod feeds;
+pub mod jobs;
 pub mod native_host;
+pub mod pager;
 pub mod proto_handler;
+pub mod remotes;
+pub mod render;
+pub mod server;
+pub mod tui;
+pub mod vault;
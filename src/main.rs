@@ -1,39 +1,383 @@
+use archiver::{Archiver, SnapshotStore};
+use assets::AssetStore;
 use chrono::DateTime;
 use clap::{Parser, Subcommand};
-use localdb::KvConfig;
-use pocket::{modify::AddUrlRequest, GetOptions, PocketClient};
+use futures::StreamExt;
+use localdb::{Field, ItemQuery, ItemStatus, JobKind, JobState, SortBy, Source};
 use readlater::{
-    config::Config,
+    actions,
+    config::{Config, CredentialBackend},
+    daemon::{self, Daemon, Reply, Request},
+    export,
+    feeds::{self, FeedFormat, FeedKind},
+    jobs::Worker,
     native_host::{
         install::{install_linux, Manifest},
         native_host_handler,
     },
+    pager, render,
+    vault::Vault,
 };
-use std::{collections::HashMap, path::PathBuf};
+use std::{collections::HashMap, io::IsTerminal, path::PathBuf};
 use url::Url;
 
 #[derive(Parser)]
 #[command(author, version, about)]
 struct Args {
+    /// Settings file, instead of config.toml in the config directory
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+    /// Named profile with its own library
+    #[arg(long, global = true)]
+    profile: Option<String>,
     #[command(subcommand)]
     command: Commands,
 }
 
 #[derive(Subcommand)]
 enum Commands {
+    /// Sync services such as Pocket or wallabag
+    Remote {
+        #[clap(subcommand)]
+        subcommand: RemoteCommands,
+    },
+    /// Deprecated, use `remote` with the remote named pocket
     Pocket {
         #[clap(subcommand)]
         subcommand: PocketCommands,
     },
+    /// Deprecated, use `remote` with the remote named wallabag
+    Wallabag {
+        #[clap(subcommand)]
+        subcommand: WallabagCommands,
+    },
+    /// Back up, check and encrypt the library
+    Db {
+        #[clap(subcommand)]
+        subcommand: DbCommands,
+    },
+    /// Manage the credentials of the remotes
+    Auth {
+        #[clap(subcommand)]
+        subcommand: AuthCommands,
+    },
     Setup,
     Handle {
         #[arg(long)]
         url: Url,
+        /// Remote to send the item to, instead of the configured ones
+        #[arg(long = "remote")]
+        remotes: Vec<String>,
+    },
+    /// Export unread items as EPUB for offline reading
+    Epub {
+        /// Only export items with this tag
+        #[arg(long)]
+        tag: Option<String>,
+        /// Only export items whose title, url or excerpt contain this text
+        #[arg(long)]
+        search: Option<String>,
+        /// Only export the N oldest items
+        #[arg(long)]
+        oldest: Option<usize>,
+        /// Export these items regardless of their status
+        #[arg(long = "id")]
+        ids: Vec<i64>,
+        /// Output file, or output directory with --split
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+        #[arg(long)]
+        title: Option<String>,
+        /// Write one EPUB per item instead of a single book
+        #[arg(long)]
+        split: bool,
+        /// Archive the items after exporting them
+        #[arg(long)]
+        archive: bool,
+    },
+    Assets {
+        #[clap(subcommand)]
+        subcommand: AssetCommands,
+    },
+    /// Find items whose page moved or disappeared
+    CheckLinks {
+        /// Only check these items
+        ids: Vec<i64>,
+        /// Number of pages probed at the same time
+        #[arg(long, default_value_t = 8)]
+        concurrency: usize,
+        /// Tag items whose page is gone with this tag
+        #[arg(long)]
+        tag_dead: Option<String>,
+        /// Look up a Wayback Machine copy of dead items without a local snapshot
+        #[arg(long)]
+        wayback: bool,
+    },
+    /// Fill in missing titles, excerpts, authors and images from the pages
+    Enrich {
+        /// Only enrich these items
+        ids: Vec<i64>,
+        /// Replace fields that are already set
+        #[arg(long)]
+        force: bool,
+    },
+    /// Self contained copies of saved pages
+    Snapshot {
+        #[clap(subcommand)]
+        subcommand: SnapshotCommands,
+    },
+    /// Write an Atom or RSS feed of the unread items, of a tag or of favorites
+    Feed {
+        #[arg(long, conflicts_with = "favorites")]
+        tag: Option<String>,
+        #[arg(long)]
+        favorites: bool,
+        #[arg(long, value_enum, default_value_t = FeedFormat::Atom)]
+        format: FeedFormat,
+        /// Output file, the feed is printed by default
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+        /// Print the address of the feed on the server at this base url,
+        /// with a key that only opens this feed
+        #[arg(long)]
+        share: Option<Url>,
+    },
+    /// Sync the remotes periodically and fetch new items in the background,
+    /// or control the running daemon
+    Daemon {
+        #[clap(subcommand)]
+        subcommand: Option<DaemonCommands>,
+        /// Minutes between two syncs, 30 unless configured
+        #[arg(long)]
+        interval: Option<u64>,
+    },
+    /// Inspect the background jobs, or run the due ones now
+    Jobs {
+        #[clap(subcommand)]
+        subcommand: Option<JobCommands>,
+    },
+    /// Browse and read the library in the terminal
+    Tui,
+    /// Print the settings in use and the file they come from
+    Config,
+    /// Read an item in the terminal, marking it read once the end was shown
+    Read {
+        id: i64,
+        /// Wrap the text at this many columns, the terminal width by default
+        #[arg(long)]
+        width: Option<usize>,
+        /// Leave out colors and styles
+        #[arg(long)]
+        plain: bool,
+        /// Page through this command instead, such as `less -R`. The item is
+        /// then not marked as read.
+        #[arg(long)]
+        pager: Option<String>,
+    },
+    /// List the items of the library, newest first
+    List {
+        #[arg(long, value_enum)]
+        status: Option<StatusArg>,
+        /// Only list the favorites
+        #[arg(long)]
+        favorites: bool,
+        #[arg(long)]
+        tag: Option<String>,
+        /// Only list items whose title, url or excerpt contain this text
+        #[arg(long)]
+        search: Option<String>,
+        #[arg(long)]
+        limit: Option<usize>,
+    },
+    /// Mark items as read
+    Archive {
+        #[arg(required = true)]
+        ids: Vec<i64>,
+    },
+    /// Move items back to the unread list
+    Unarchive {
+        #[arg(required = true)]
+        ids: Vec<i64>,
+    },
+    /// Add items to the favorites
+    Favorite {
+        #[arg(required = true)]
+        ids: Vec<i64>,
+        /// Take them out of the favorites instead
+        #[arg(long)]
+        remove: bool,
+    },
+    /// Delete items, on the remotes too at their next sync
+    Delete {
+        #[arg(required = true)]
+        ids: Vec<i64>,
+        /// Remove them from the library right away, without telling the
+        /// remotes
+        #[arg(long)]
+        purge: bool,
+    },
+    /// Change the title or the tags of an item
+    Edit {
+        id: i64,
+        #[arg(long)]
+        title: Option<String>,
+        /// Tag to add
+        #[arg(long = "tag")]
+        add_tags: Vec<String>,
+        /// Tag to remove
+        #[arg(long = "untag")]
+        remove_tags: Vec<String>,
+    },
+    /// Show the latest changes to the library
+    History {
+        #[arg(long, default_value_t = 20)]
+        limit: i64,
+    },
+    /// Revert the latest changes and queue the reverts for the remotes
+    Undo {
+        /// Number of operations to undo
+        #[arg(default_value_t = 1)]
+        count: i64,
+    },
+    /// Serve the library as a JSON API and a web reader
+    Serve {
+        /// Address to listen on, 0.0.0.0 makes the reader reachable on the LAN
+        #[arg(long)]
+        bind: Option<std::net::IpAddr>,
+        #[arg(long)]
+        port: Option<u16>,
+        /// Token clients must send, a generated one is kept by default
+        #[arg(long)]
+        token: Option<String>,
+        /// Origin allowed to call the API from a browser, such as the extension
+        #[arg(long = "allow-origin")]
+        origins: Vec<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum JobCommands {
+    /// List the queued and failed jobs
+    List,
+    /// Queue jobs for the given items, or for every item
+    Add {
+        #[arg(value_enum)]
+        kind: JobKindArg,
+        ids: Vec<i64>,
+    },
+    /// Run jobs again right away, every failed job by default
+    Retry { ids: Vec<i64> },
+    Cancel {
+        #[arg(required = true)]
+        ids: Vec<i64>,
+    },
+    /// Run the due jobs and exit
+    Run,
+}
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum JobKindArg {
+    Extract,
+    Enrich,
+    Assets,
+    CheckLink,
+    Snapshot,
+    Push,
+}
+
+impl From<JobKindArg> for JobKind {
+    fn from(kind: JobKindArg) -> JobKind {
+        match kind {
+            JobKindArg::Extract => JobKind::Extract,
+            JobKindArg::Enrich => JobKind::Enrich,
+            JobKindArg::Assets => JobKind::Assets,
+            JobKindArg::CheckLink => JobKind::CheckLink,
+            JobKindArg::Snapshot => JobKind::Snapshot,
+            JobKindArg::Push => JobKind::Push,
+        }
+    }
+}
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum StatusArg {
+    Unread,
+    Archived,
+    Deleted,
+}
+
+impl From<StatusArg> for ItemStatus {
+    fn from(status: StatusArg) -> ItemStatus {
+        match status {
+            StatusArg::Unread => ItemStatus::Unread,
+            StatusArg::Archived => ItemStatus::Archived,
+            StatusArg::Deleted => ItemStatus::Deleted,
+        }
+    }
+}
+
+#[derive(Subcommand)]
+enum DaemonCommands {
+    Status,
+    /// Sync now, even when paused
+    Sync,
+    /// Stop syncing and fetching until resumed
+    Pause,
+    Resume,
+}
+
+#[derive(Subcommand)]
+enum SnapshotCommands {
+    /// Capture the pages of the given items, or of every item without a snapshot
+    Create {
+        ids: Vec<i64>,
+        /// Also record the original responses in a WARC file
+        #[arg(long)]
+        warc: bool,
+    },
+    List {
+        id: i64,
+    },
+    /// Open the latest snapshot of an item in the browser
+    Open {
+        id: i64,
+    },
+}
+
+#[derive(Subcommand)]
+enum AssetCommands {
+    /// Download the images of the given items, or of every item
+    Fetch {
+        ids: Vec<i64>,
+    },
+    /// Evict least recently used files until the cache fits its budget
+    Prune,
+    Stats,
+}
+
+#[derive(Subcommand)]
+enum RemoteCommands {
+    /// Configure a sync service and log in to it
+    Add {
+        name: String,
+        #[arg(value_parser = clap::builder::PossibleValuesParser::new(remote::BACKENDS))]
+        backend: String,
+        /// Address of self hosted services
+        #[arg(long)]
+        url: Option<Url>,
+    },
+    List,
+    Remove {
+        name: String,
+    },
+    /// Push local changes and pull remote changes, of every remote by default
+    Sync {
+        names: Vec<String>,
     },
 }
 
 #[derive(Subcommand)]
 enum PocketCommands {
+    /// List the items of the remote, after syncing it
     Get {
         #[arg(long, default_value_t = 30)]
         count: i32,
@@ -43,6 +387,7 @@ enum PocketCommands {
     Add {
         url: Url,
     },
+    /// Archive items by their Pocket id
     Archive {
         items: Vec<u64>,
     },
@@ -50,10 +395,121 @@ enum PocketCommands {
     Sync,
 }
 
+#[derive(Subcommand)]
+enum WallabagCommands {
+    /// Log in with the credentials of an API client created on the instance
+    Auth {
+        /// Address of the instance, e.g. https://app.wallabag.it
+        url: Url,
+        #[arg(long)]
+        client_id: String,
+        #[arg(long)]
+        client_secret: String,
+        /// Asked again when logging in
+        #[arg(long)]
+        username: String,
+    },
+    Add {
+        url: Url,
+        #[arg(long)]
+        tags: Vec<String>,
+    },
+    /// Archive items by their wallabag id
+    Archive {
+        ids: Vec<i64>,
+    },
+    Sync,
+}
+
+/// What the deprecated `pocket` and `wallabag` commands did, done through
+/// the remote of the same name
+enum LegacyCommand {
+    Get { count: i32, offset: i32 },
+    Add { url: Url, tags: Vec<String> },
+    Archive { ids: Vec<String> },
+    Auth { settings: serde_json::Value },
+    Sync,
+}
+
+impl From<PocketCommands> for LegacyCommand {
+    fn from(command: PocketCommands) -> Self {
+        match command {
+            PocketCommands::Get { count, offset } => LegacyCommand::Get { count, offset },
+            PocketCommands::Add { url } => LegacyCommand::Add { url, tags: vec![] },
+            PocketCommands::Archive { items } => LegacyCommand::Archive {
+                ids: items.iter().map(u64::to_string).collect(),
+            },
+            PocketCommands::Auth => LegacyCommand::Auth {
+                settings: serde_json::json!({}),
+            },
+            PocketCommands::Sync => LegacyCommand::Sync,
+        }
+    }
+}
+
+impl From<WallabagCommands> for LegacyCommand {
+    fn from(command: WallabagCommands) -> Self {
+        match command {
+            WallabagCommands::Auth {
+                url,
+                client_id,
+                client_secret,
+                ..
+            } => LegacyCommand::Auth {
+                settings: serde_json::json!({
+                    "url": url.as_str(),
+                    "client_id": client_id,
+                    "client_secret": client_secret,
+                }),
+            },
+            WallabagCommands::Add { url, tags } => LegacyCommand::Add { url, tags },
+            WallabagCommands::Archive { ids } => LegacyCommand::Archive {
+                ids: ids.iter().map(i64::to_string).collect(),
+            },
+            WallabagCommands::Sync => LegacyCommand::Sync,
+        }
+    }
+}
+
+#[derive(Subcommand)]
+enum DbCommands {
+    /// Encrypt the library with a new key
+    Encrypt,
+    /// Store the library in plain text again
+    Decrypt,
+    /// Encrypt the library again under a new key
+    Rekey,
+    /// Copy the library while it is in use, by default to the backups directory
+    Backup { dest: Option<PathBuf> },
+    /// Replace the library with a backup
+    Restore { backup: PathBuf },
+    /// Give back the space of deleted rows
+    Vacuum,
+    /// Check the integrity of the library, and look for orphaned rows
+    Check {
+        /// Delete the orphaned rows of the link tables
+        #[clap(long)]
+        fix: bool,
+    },
+    /// Show the rows and size of each table
+    Stats,
+    /// List the migrations and whether they were applied
+    MigrateStatus,
+}
+
+#[derive(Subcommand)]
+enum AuthCommands {
+    /// Show where credentials are kept and which remotes have some
+    Status,
+    /// Log in to a remote again, replacing its credentials
+    Login { name: String },
+    /// Forget the credentials of remotes, of every remote by default
+    Logout { names: Vec<String> },
+}
+
 #[tokio::main]
 async fn main() {
     tracing_subscriber::fmt::init();
-    let config: Config = Config::new().expect("error loading config");
     let args: Vec<String> = std::env::args().collect();
 
     // todo: proper handling so that we don't have to do this
@@ -62,135 +518,763 @@ async fn main() {
         .any(|arg| arg == "readlater@dbhattarai.info.np")
     {
         // we were called from firefox extension
+        let config: Config = Config::new().expect("error loading config");
         native_host_handler(config).await;
         return;
     }
 
-    let pool = localdb::open_database(config.database_dir.to_str().unwrap())
-        .await
-        .unwrap();
-    let mut kv_config = KvConfig::new(pool.clone());
-
     let args = Args::parse();
+    let config = match Config::load(args.config.as_deref(), args.profile.as_deref()) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("error loading config: {:#}", e);
+            std::process::exit(1);
+        }
+    };
+    if let Commands::Config = args.command {
+        println!("# {}", config.file.display());
+        print!(
+            "{}",
+            toml::to_string_pretty(&config).expect("error printing config")
+        );
+        return;
+    }
+
+    if let Commands::Db { subcommand } = args.command {
+        // some commands replace the library, so it is not opened here
+        if let Err(e) = db_command(&config, subcommand).await {
+            eprintln!("error: {:#}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let pool = match readlater::database::open(&config).await {
+        Ok(pool) => pool,
+        Err(e) => {
+            eprintln!("error opening {}: {:#}", config.database_dir.display(), e);
+            std::process::exit(1);
+        }
+    };
+
     match args.command {
+        Commands::Remote { subcommand } => remote_command(&config, &pool, subcommand).await,
         Commands::Pocket { subcommand } => {
-            let access_token = kv_config.get_pocket_access_token().await;
+            legacy_command(&config, &pool, "pocket", subcommand.into()).await
+        }
+        Commands::Wallabag { subcommand } => {
+            legacy_command(&config, &pool, "wallabag", subcommand.into()).await
+        }
+        Commands::Auth { subcommand } => auth_command(&config, &pool, subcommand).await,
+        Commands::Setup => {
+            let cli = std::env::current_exe().unwrap();
+            let cli = cli.to_str().to_owned().unwrap();
+            readlater::proto_handler::register_url_handler();
+            let manifest = Manifest {
+                name: "readlater".to_string(),
+                description: "readlater native messaging host".to_string(),
+                path: PathBuf::from(cli),
+                io_type: "stdio".to_string(),
+                allowed_extensions: Some(vec!["readlater@dbhattarai.info.np".to_string()]),
+            };
 
-            match subcommand {
-                PocketCommands::Get { count, offset } => {
-                    let access_token = access_token.expect("no access token available");
-                    let mut pocket = PocketClient::new(&config.pocket_consumer_key, &access_token);
-
-                    let get_options = GetOptions::default().count(count).offset(offset).build();
-                    let article = pocket.get(get_options).await.unwrap();
-                    for (_, article) in article.list {
-                        println!("{} {}", article.item_id, article.resolved_title);
-                        dbg!(&article);
-                    }
+            install_linux(&manifest).unwrap();
+
+            let unit = readlater::daemon::systemd::install_unit(&PathBuf::from(cli))
+                .expect("error writing systemd unit");
+            println!(
+                "Wrote {}, start the daemon with `systemctl --user enable --now {}`",
+                unit.display(),
+                readlater::daemon::systemd::UNIT_NAME
+            );
+            if config.credentials.store == CredentialBackend::File {
+                println!(
+                    "The browser and the daemon start readlater without a terminal to ask the \
+                    passphrase of {} on: set {} in their environment, such as with \
+                    `systemctl --user set-environment`, or use the Secret Service store",
+                    config.credentials.file.display(),
+                    readlater::vault::PASSPHRASE_ENV
+                );
+            }
+        }
+        Commands::Epub {
+            tag,
+            search,
+            oldest,
+            ids,
+            output,
+            title,
+            split,
+            archive,
+        } => {
+            let mut db = localdb::LocalDb::new(pool.clone());
+            let items = if ids.is_empty() {
+                let mut query = ItemQuery::default().status(ItemStatus::Unread);
+                if let Some(tag) = &tag {
+                    query = query.tag(tag);
                 }
-                PocketCommands::Add { url } => {
-                    let access_token = access_token.expect("no access token available");
-                    let mut pocket = PocketClient::new(&config.pocket_consumer_key, &access_token);
-                    pocket.add(vec![AddUrlRequest::new(url)]).await.unwrap();
+                if let Some(search) = &search {
+                    query = query.search(search);
                 }
-                PocketCommands::Auth => {
-                    let redirect_uri = "https://localhost:3000".to_string();
-                    let auth_client = pocket::auth::PocketAuthClient::new(
-                        config.pocket_consumer_key.clone(),
-                        redirect_uri.clone(),
-                    );
-                    let login_code = auth_client.login_code().await.unwrap();
-                    let redirection_uri = pocket::auth::redirection_uri(&login_code, &redirect_uri);
-                    println!("Please visit {}", redirection_uri);
-                    println!("Please press enter after you have authenticated");
-                    let mut input = String::new();
-                    std::io::stdin().read_line(&mut input).unwrap();
-
-                    let login_response = auth_client
-                        .access_token(&login_code)
+                if let Some(oldest) = oldest {
+                    query = query.sort(SortBy::Oldest).limit(oldest);
+                }
+                db.query_items(&query).await.expect("error reading items")
+            } else {
+                let mut items = db.get_items().await.expect("error reading items");
+                items.retain(|item| ids.contains(&item.id));
+                items
+            };
+
+            if items.is_empty() {
+                eprintln!("No items to export");
+                return;
+            }
+
+            let assets = AssetStore::new(
+                &config.assets_dir,
+                config.assets_budget,
+                std::time::Duration::from_secs(config.fetch.timeout),
+            );
+            for item in &items {
+                cache_assets(&assets, &mut db, item).await;
+            }
+
+            if split {
+                let dir = output.unwrap_or_else(|| PathBuf::from("."));
+                std::fs::create_dir_all(&dir).expect("error creating output directory");
+                for item in &items {
+                    let path = dir.join(export::file_name(item));
+                    let book = export::article_epub(&mut db, &assets, item)
                         .await
-                        .expect("error getting access token");
+                        .expect("error building epub");
+                    std::fs::write(&path, book).expect("error writing epub");
+                    println!("{}", path.display());
+                }
+            } else {
+                let date = chrono::Local::now().format("%Y-%m-%d");
+                let title = title.unwrap_or_else(|| format!("readlater {date}"));
+                let path =
+                    output.unwrap_or_else(|| PathBuf::from(format!("readlater-{date}.epub")));
+                let book = export::bundle_epub(&mut db, &assets, &title, &items)
+                    .await
+                    .expect("error building epub");
+                std::fs::write(&path, book).expect("error writing epub");
+                println!("Exported {} items to {}", items.len(), path.display());
+            }
 
-                    kv_config
-                        .set_pocket_access_token(&login_response.access_token)
+            if archive {
+                db.begin_operation();
+                for item in &items {
+                    actions::set_status(&mut db, item, ItemStatus::Archived)
                         .await
-                        .expect("error setting pocket access token");
+                        .expect("error archiving item");
                 }
-                PocketCommands::Archive { items } => {
-                    if items.is_empty() {
-                        eprintln!("No items to archive");
-                        return;
+            }
+        }
+        Commands::Assets { subcommand } => {
+            let mut db = localdb::LocalDb::new(pool.clone());
+            let assets = AssetStore::new(
+                &config.assets_dir,
+                config.assets_budget,
+                std::time::Duration::from_secs(config.fetch.timeout),
+            );
+            match subcommand {
+                AssetCommands::Fetch { ids } => {
+                    let mut items = db.get_items().await.expect("error reading items");
+                    if !ids.is_empty() {
+                        items.retain(|item| ids.contains(&item.id));
+                    }
+                    for item in &items {
+                        cache_assets(&assets, &mut db, item).await;
                     }
-                    let access_token = access_token.expect("no access token available");
-                    let mut pocket = PocketClient::new(&config.pocket_consumer_key, &access_token);
-                    pocket.archive(items).await.unwrap();
                 }
-                PocketCommands::Sync => {
-                    let pool = localdb::open_database(config.database_dir.to_str().unwrap())
-                        .await
-                        .unwrap();
-                    let mut db = localdb::LocalDb::new(pool.clone());
-                    let since = kv_config.get_pocket_since().await;
-                    let offset = kv_config.get_pocket_offset().await;
-
-                    let (since, mut offset) = match (since, offset) {
-                        (Some(since), _) => (since, 0),
-                        (_, Some(offset)) => (0, offset),
-                        _ => (0, 0),
-                    };
+                AssetCommands::Prune => {
+                    let freed = assets.evict(&mut db).await.expect("error pruning assets");
+                    println!("Freed {} bytes", freed);
+                }
+                AssetCommands::Stats => {
+                    let (files, size) = assets.usage(&db).await.expect("error reading assets");
+                    println!(
+                        "{} files, {} of {} bytes used in {}",
+                        files,
+                        size,
+                        config.assets_budget,
+                        config.assets_dir.display()
+                    );
+                }
+            }
+        }
+        Commands::Snapshot { subcommand } => {
+            let mut db = localdb::LocalDb::new(pool.clone());
+            let store = SnapshotStore::new(&config.snapshots_dir);
+            match subcommand {
+                SnapshotCommands::Create { ids, warc } => {
+                    let mut items = db.get_items().await.expect("error reading items");
+                    if ids.is_empty() {
+                        let mut missing = vec![];
+                        for item in items {
+                            let snapshots = db
+                                .get_snapshots(item.id)
+                                .await
+                                .expect("error reading snapshots");
+                            if snapshots.is_empty() {
+                                missing.push(item);
+                            }
+                        }
+                        items = missing;
+                    } else {
+                        items.retain(|item| ids.contains(&item.id));
+                    }
 
-                    let datetime =
-                        DateTime::from_timestamp(since as i64, 0).expect("unexpected date time");
-                    println!("Syncing data since {} with offset {}", datetime, offset);
-
-                    let access_token = access_token.expect("no access token available");
-                    let mut pocket = PocketClient::new(&config.pocket_consumer_key, &access_token);
-                    let count = 30;
-                    loop {
-                        let response = pocket
-                            .get(GetOptions {
-                                since: Some(since),
-                                offset: Some(offset.max(0)),
-                                detail_type: Some(pocket::DetailType::Complete),
-                                count,
-                                ..GetOptions::for_pagination()
-                            })
+                    let archiver = Archiver::new(
+                        std::time::Duration::from_secs(config.fetch.timeout),
+                        &config.fetch.user_agent,
+                    )
+                    .warc(warc);
+                    for item in &items {
+                        let capture = match archiver.capture(&item.url).await {
+                            Ok(capture) => capture,
+                            Err(e) => {
+                                eprintln!("error capturing {}: {}", item.url, e);
+                                continue;
+                            }
+                        };
+                        let snapshot = store
+                            .save(&mut db, item.id, &capture)
                             .await
-                            .unwrap();
+                            .expect("error saving snapshot");
+                        println!("{} {} {}", item.id, snapshot.status, snapshot.url);
+                    }
+                }
+                SnapshotCommands::List { id } => {
+                    let snapshots = db.get_snapshots(id).await.expect("error reading snapshots");
+                    for snapshot in snapshots {
+                        let date = DateTime::from_timestamp(snapshot.time_fetched as i64, 0)
+                            .expect("unexpected date time");
+                        println!(
+                            "{} {} {} {}",
+                            date,
+                            snapshot.status,
+                            store.path(&snapshot.html_path).display(),
+                            snapshot.url
+                        );
+                    }
+                }
+                SnapshotCommands::Open { id } => {
+                    let snapshots = db.get_snapshots(id).await.expect("error reading snapshots");
+                    match snapshots.first() {
+                        Some(snapshot) => open::that(store.path(&snapshot.html_path))
+                            .expect("error opening snapshot"),
+                        None => eprintln!("No snapshot for item {}", id),
+                    }
+                }
+            }
+        }
+        Commands::CheckLinks {
+            ids,
+            concurrency,
+            tag_dead,
+            wayback,
+        } => {
+            let mut db = localdb::LocalDb::new(pool.clone());
+            let mut items = db.get_items().await.expect("error reading items");
+            if !ids.is_empty() {
+                items.retain(|item| ids.contains(&item.id));
+            }
+
+            let dead_tag = match &tag_dead {
+                Some(tag) => Some(
+                    db.add_tag(&localdb::Tag {
+                        id: 0,
+                        tag: tag.to_string(),
+                        name: None,
+                    })
+                    .await
+                    .expect("error adding tag"),
+                ),
+                None => None,
+            };
+            let timeout = std::time::Duration::from_secs(config.fetch.timeout);
+            let archive = linkcheck::Wayback::with_options(
+                linkcheck::WAYBACK_AVAILABLE_URL,
+                timeout,
+                &config.fetch.user_agent,
+            );
+            let checker = linkcheck::LinkChecker::with_options(
+                concurrency,
+                timeout,
+                &config.fetch.user_agent,
+            );
+            let urls = items.iter().map(|item| (item.id, item.url.clone()));
+            let mut results = std::pin::pin!(checker.check_all(urls));
+            while let Some((id, check)) = results.next().await {
+                let now = chrono::Utc::now().timestamp() as i32;
+                db.set_link_status(id, check.status, check.final_url.as_deref(), now)
+                    .await
+                    .expect("error saving link status");
+                println!(
+                    "{} {:?} {}",
+                    id,
+                    check.status,
+                    check.final_url.unwrap_or_default()
+                );
+                if !check.status.is_dead() {
+                    continue;
+                }
 
-                        for article in response.list.values() {
-                            let article: localdb::Item = article.into();
-                            println!("{} {}", article.id, article.title);
-                            db.add(&article).await.expect("error adding item");
+                if let Some(tag_id) = dead_tag {
+                    db.link_tag(tag_id, id as i32)
+                        .await
+                        .expect("error tagging item");
+                }
+                let snapshots = db.get_snapshots(id).await.expect("error reading snapshots");
+                if wayback && snapshots.is_empty() {
+                    let url = &items
+                        .iter()
+                        .find(|item| item.id == id)
+                        .expect("checked item exists")
+                        .url;
+                    match archive.closest(url).await {
+                        Ok(Some(wayback_url)) => {
+                            db.set_wayback_url(id, &wayback_url)
+                                .await
+                                .expect("error saving wayback url");
+                            println!("{} archived at {}", id, wayback_url);
                         }
+                        Ok(None) => {}
+                        Err(e) => eprintln!("error looking up {}: {}", url, e),
+                    }
+                }
+            }
+        }
+        Commands::Enrich { ids, force } => {
+            let mut db = localdb::LocalDb::new(pool.clone());
+            let mut items = db.get_items().await.expect("error reading items");
+            if !ids.is_empty() {
+                items.retain(|item| ids.contains(&item.id));
+            } else if !force {
+                items.retain(|item| {
+                    item.excerpt.as_deref().is_none_or(str::is_empty)
+                        || item.lang.as_deref().is_none_or(str::is_empty)
+                        || item.top_image_url.is_none()
+                        || item.authors.is_empty()
+                });
+            }
 
-                        offset += count;
-                        kv_config.set_pocket_offset(offset).await.unwrap();
+            let fetcher = metadata::MetadataFetcher::with_options(
+                std::time::Duration::from_secs(config.fetch.timeout),
+                &config.fetch.user_agent,
+            );
+            let mut results = futures::stream::iter(items)
+                .map(|item| {
+                    let fetcher = &fetcher;
+                    async move {
+                        let metadata = fetcher.fetch(&item.url).await;
+                        (item, metadata)
+                    }
+                })
+                .buffer_unordered(8);
+            while let Some((mut item, metadata)) = results.next().await {
+                let metadata = match metadata {
+                    Ok(metadata) => metadata,
+                    Err(e) => {
+                        eprintln!("error fetching {}: {}", item.url, e);
+                        continue;
+                    }
+                };
+                if metadata::apply(&mut item, &metadata, force) {
+                    db.update_item(&item).await.expect("error updating item");
+                    println!("{} {}", item.id, item.title);
+                }
+            }
+        }
+        Commands::Feed {
+            tag,
+            favorites,
+            format,
+            output,
+            share,
+        } => {
+            let kind = match (tag, favorites) {
+                (Some(tag), _) => FeedKind::Tag(tag),
+                (None, true) => FeedKind::Favorites,
+                (None, false) => FeedKind::Unread,
+            };
+            if let Some(base) = share {
+                let token = readlater::server::token(&pool)
+                    .await
+                    .expect("error loading token");
+                let path = kind.path(format);
+                let key = feeds::feed_key(&token, &path);
+                let url = base.join(&path).expect("invalid base url");
+                println!("{}?key={}", url, key);
+                return;
+            }
 
-                        let has_more = response.has_more().expect("invalid request");
-                        if response.list.is_empty() || !has_more {
-                            kv_config.set_pocket_since(response.since).await.unwrap();
-                            break;
+            let db = localdb::LocalDb::new(pool.clone());
+            let link = format!("http://localhost:{}/", readlater::server::DEFAULT_PORT);
+            let feed = feeds::build(&db, &kind, &link, None)
+                .await
+                .expect("error building feed");
+            let xml = match format {
+                FeedFormat::Atom => feed.atom(),
+                FeedFormat::Rss => feed.rss(),
+            };
+            match output {
+                Some(path) => std::fs::write(path, xml).expect("error writing feed"),
+                None => print!("{}", xml),
+            }
+        }
+        Commands::Jobs { subcommand } => {
+            let mut db = localdb::LocalDb::new(pool.clone());
+            match subcommand.unwrap_or(JobCommands::List) {
+                JobCommands::List => {
+                    for job in db.get_jobs().await.expect("error reading jobs") {
+                        let next_run = DateTime::from_timestamp(job.next_run, 0)
+                            .expect("unexpected date time");
+                        println!(
+                            "{}\t{:?}\titem {}\t{:?}\t{} attempts\t{}\t{}",
+                            job.id,
+                            job.kind,
+                            job.item_id,
+                            job.state,
+                            job.attempts,
+                            next_run,
+                            job.last_error.unwrap_or_default()
+                        );
+                    }
+                }
+                JobCommands::Add { kind, ids } => {
+                    let items = if ids.is_empty() {
+                        db.get_items()
+                            .await
+                            .expect("error reading items")
+                            .into_iter()
+                            .map(|item| item.id)
+                            .collect()
+                    } else {
+                        ids
+                    };
+                    for id in &items {
+                        db.add_job(kind.into(), *id)
+                            .await
+                            .expect("error adding job");
+                    }
+                    println!("Queued {} jobs", items.len());
+                }
+                JobCommands::Retry { ids } => {
+                    let ids = if ids.is_empty() {
+                        db.get_jobs()
+                            .await
+                            .expect("error reading jobs")
+                            .into_iter()
+                            .filter(|job| job.state == JobState::Failed)
+                            .map(|job| job.id)
+                            .collect()
+                    } else {
+                        ids
+                    };
+                    for id in ids {
+                        if let Err(e) = db.retry_job(id).await {
+                            eprintln!("error retrying job {}: {}", id, e);
                         }
                     }
                 }
+                JobCommands::Cancel { ids } => {
+                    for id in ids {
+                        if let Err(e) = db.cancel_job(id).await {
+                            eprintln!("error cancelling job {}: {}", id, e);
+                        }
+                    }
+                }
+                JobCommands::Run => {
+                    let vault = std::sync::Arc::new(open_vault(&config, &pool).await);
+                    let worker = Worker::new(std::sync::Arc::new(config), pool.clone(), vault);
+                    let count = worker.run_due().await.expect("error running jobs");
+                    println!("Ran {} jobs", count);
+                }
             }
         }
-        Commands::Setup => {
-            let cli = std::env::current_exe().unwrap();
-            let cli = cli.to_str().to_owned().unwrap();
-            readlater::proto_handler::register_url_handler();
-            let manifest = Manifest {
-                name: "readlater".to_string(),
-                description: "readlater native messaging host".to_string(),
-                path: PathBuf::from(cli),
-                io_type: "stdio".to_string(),
-                allowed_extensions: Some(vec!["readlater@dbhattarai.info.np".to_string()]),
+        Commands::List {
+            status,
+            favorites,
+            tag,
+            search,
+            limit,
+        } => {
+            let query = ItemQuery {
+                status: status.map(ItemStatus::from),
+                tag,
+                favorite: favorites.then_some(true),
+                search,
+                sort: SortBy::Newest,
+                limit,
+            };
+            let db = localdb::LocalDb::new(pool.clone());
+            for item in db.query_items(&query).await.expect("error reading items") {
+                let star = if item.favorite { "★" } else { " " };
+                println!("{:>6} {} {}  {}", item.id, star, item.title, item.url);
+            }
+        }
+        Commands::Archive { ids } => {
+            let mut db = localdb::LocalDb::new(pool.clone());
+            db.begin_operation();
+            for item in find_items(&db, &ids).await {
+                actions::set_status(&mut db, &item, ItemStatus::Archived)
+                    .await
+                    .expect("error archiving item");
+                println!("Archived {}", item.title);
+            }
+        }
+        Commands::Unarchive { ids } => {
+            let mut db = localdb::LocalDb::new(pool.clone());
+            db.begin_operation();
+            for item in find_items(&db, &ids).await {
+                actions::set_status(&mut db, &item, ItemStatus::Unread)
+                    .await
+                    .expect("error unarchiving item");
+                println!("Unarchived {}", item.title);
+            }
+        }
+        Commands::Favorite { ids, remove } => {
+            let mut db = localdb::LocalDb::new(pool.clone());
+            db.begin_operation();
+            for item in find_items(&db, &ids).await {
+                actions::set_favorite(&mut db, &item, !remove)
+                    .await
+                    .expect("error changing favorite");
+                match remove {
+                    true => println!("Removed {} from the favorites", item.title),
+                    false => println!("Added {} to the favorites", item.title),
+                }
+            }
+        }
+        Commands::Delete { ids, purge } => {
+            let mut db = localdb::LocalDb::new(pool.clone());
+            db.begin_operation();
+            for item in find_items(&db, &ids).await {
+                if purge {
+                    db.delete_item(item.id).await.expect("error deleting item");
+                } else {
+                    actions::set_status(&mut db, &item, ItemStatus::Deleted)
+                        .await
+                        .expect("error deleting item");
+                }
+                println!("Deleted {}", item.title);
+            }
+        }
+        Commands::Edit {
+            id,
+            title,
+            add_tags,
+            remove_tags,
+        } => {
+            let mut db = localdb::LocalDb::new(pool.clone());
+            db.begin_operation();
+            let item = find_items(&db, &[id]).await.remove(0);
+            if let Some(title) = title {
+                actions::set_title(&mut db, &item, &title)
+                    .await
+                    .expect("error renaming item");
+            }
+            actions::add_tags(&mut db, &item, add_tags)
+                .await
+                .expect("error adding tags");
+            actions::remove_tags(&mut db, &item, remove_tags)
+                .await
+                .expect("error removing tags");
+            let item = db.get_item(id).await.expect("error reading item").unwrap();
+            let mut tags: Vec<_> = item.tags.iter().map(|tag| tag.tag.as_str()).collect();
+            tags.sort();
+            println!("{} [{}]", item.title, tags.join(", "));
+        }
+        Commands::History { limit } => {
+            let db = localdb::LocalDb::new(pool.clone());
+            let operations = db
+                .get_operations(limit)
+                .await
+                .expect("error reading history");
+            for operation in operations {
+                let time =
+                    DateTime::from_timestamp(operation.time, 0).expect("unexpected date time");
+                let mut line = format!("#{} {} {}", operation.id, time, operation.source.name());
+                if let Some(undo_of) = operation.undo_of {
+                    line.push_str(&format!(" of #{undo_of}"));
+                }
+                if operation.undone {
+                    line.push_str(" (undone)");
+                }
+                println!("{line}");
+                for change in operation.changes {
+                    println!(
+                        "    item {} {}: {} → {}",
+                        change.item_id,
+                        change.field.name(),
+                        history_value(change.before.as_deref()),
+                        history_value(change.after.as_deref())
+                    );
+                }
+            }
+        }
+        Commands::Undo { count } => {
+            let db = localdb::LocalDb::new(pool.clone());
+            let operations = actions::undo(&db, count).await.expect("error undoing");
+            if operations.is_empty() {
+                println!("Nothing to undo");
+            }
+            for operation in operations {
+                println!(
+                    "Undid #{} from {} ({} changes)",
+                    operation.id,
+                    operation.source.name(),
+                    operation.changes.len()
+                );
+                for change in operation.changes {
+                    if change.field == Field::Purged {
+                        eprintln!(
+                            "Item {} was purged and stays so, `readlater db restore` brings back a backup",
+                            change.item_id
+                        );
+                    }
+                }
+            }
+        }
+        Commands::Read {
+            id,
+            width,
+            plain,
+            pager,
+        } => {
+            let mut db = localdb::LocalDb::new(pool.clone());
+            let Some(item) = db.get_item(id).await.expect("error reading item") else {
+                eprintln!("No item {}", id);
+                return;
+            };
+            let html = match db.get_content(id).await.expect("error reading content") {
+                Some(content) => content.html,
+                None => format!(
+                    "<p>{}</p><p><i>No content was extracted for this page.</i></p>",
+                    item.excerpt.as_deref().unwrap_or_default()
+                ),
             };
 
-            install_linux(&manifest).unwrap();
+            let terminal = std::io::stdout().is_terminal();
+            let width = width.unwrap_or_else(|| match ratatui::crossterm::terminal::size() {
+                Ok((columns, _)) if terminal => (columns as usize).min(100),
+                _ => 80,
+            });
+            let options = render::Options {
+                width,
+                plain: plain || !terminal,
+            };
+            let title = render::sanitize(&item.title);
+            let mut lines = match options.plain {
+                true => vec![format!("# {}", title)],
+                false => vec![format!("\x1b[1m{}\x1b[0m", title)],
+            };
+            lines.push(render::sanitize(&item.url));
+            let authors: Vec<&str> = item.authors.iter().map(|a| a.name.as_str()).collect();
+            if !authors.is_empty() {
+                lines.push(render::sanitize(&authors.join(", ")));
+            }
+            lines.push(String::new());
+            lines.extend(render::render(&html, options));
+
+            if !terminal {
+                for line in lines {
+                    println!("{}", line);
+                }
+                return;
+            }
+            if let Some(pager) = pager {
+                pager::pipe(&lines, &pager).expect("error running pager");
+                return;
+            }
+            let finished = pager::page(&lines, &title).expect("error paging");
+            if finished && item.status == ItemStatus::Unread {
+                actions::set_status(&mut db, &item, ItemStatus::Archived)
+                    .await
+                    .expect("error marking item read");
+                println!("Marked {} as read", title);
+            }
+        }
+        Commands::Config | Commands::Db { .. } => {
+            unreachable!("handled before opening the database")
+        }
+        Commands::Tui => {
+            let _lock = readlater::database::hold(&config).expect("error locking the library");
+            let mut db = localdb::LocalDb::new(pool.clone()).with_source(Source::Tui);
+            readlater::tui::run(&mut db)
+                .await
+                .expect("error running tui");
         }
-        Commands::Handle { url } => {
+        Commands::Daemon {
+            subcommand,
+            interval,
+        } => {
+            let request = match subcommand {
+                None => {
+                    let _lock =
+                        readlater::database::hold(&config).expect("error locking the library");
+                    let socket = config.socket_path.clone();
+                    let interval = interval.unwrap_or(config.sync_interval);
+                    let workers = config.fetch.workers;
+                    let vault = std::sync::Arc::new(open_vault(&config, &pool).await);
+                    let daemon =
+                        Daemon::new(std::sync::Arc::new(config), pool.clone(), vault, &socket)
+                            .interval(std::time::Duration::from_secs(interval * 60))
+                            .workers(workers);
+                    daemon
+                        .run(shutdown_signal())
+                        .await
+                        .expect("error running daemon");
+                    return;
+                }
+                Some(DaemonCommands::Status) => Request::Status,
+                Some(DaemonCommands::Sync) => Request::Sync,
+                Some(DaemonCommands::Pause) => Request::Pause,
+                Some(DaemonCommands::Resume) => Request::Resume,
+            };
+            match daemon::control(&config.socket_path, request).await {
+                Ok(Reply::Status(status)) => print_status(&status),
+                Ok(Reply::Error(e)) => eprintln!("{}", e),
+                Err(e) => eprintln!("{}", e),
+            }
+        }
+        Commands::Serve {
+            bind,
+            port,
+            token,
+            origins,
+        } => {
+            let token = match token {
+                Some(token) => token,
+                None => readlater::server::token(&pool)
+                    .await
+                    .expect("error loading token"),
+            };
+            let addr = std::net::SocketAddr::new(
+                bind.unwrap_or(config.server.bind),
+                port.unwrap_or(config.server.port),
+            );
+            let mut origins = origins;
+            origins.extend(config.server.allow_origins.iter().cloned());
+            let _lock = readlater::database::hold(&config).expect("error locking the library");
+            println!("Listening on http://{}", addr);
+            println!("API token: {}", token);
+            let vault = open_vault(&config, &pool).await;
+            let state = readlater::server::AppState {
+                pool: pool.clone(),
+                config: std::sync::Arc::new(config),
+                vault: std::sync::Arc::new(vault),
+                token,
+            };
+            readlater::server::serve(state, addr, &origins)
+                .await
+                .expect("error serving api");
+        }
+        Commands::Handle { url, remotes } => {
             let url_parts = url::Url::parse(url.as_ref()).unwrap();
             assert_eq!(url_parts.scheme(), "readlater");
             let query_params = url_parts.query_pairs().collect::<HashMap<_, _>>();
@@ -213,17 +1297,452 @@ async fn main() {
                 .map(|tag| tag.to_string())
                 .filter(|s| !s.is_empty())
                 .collect::<Vec<_>>();
-            tags.push("readlater".to_string());
+            tags.extend(config.default_tags.iter().cloned());
 
-            let access_token = kv_config
-                .get_pocket_access_token()
+            let mut remotes = remotes;
+            if let Some(names) = query_params.get("remote") {
+                remotes.extend(
+                    names
+                        .split(',')
+                        .filter(|name| !name.is_empty())
+                        .map(String::from),
+                );
+            }
+
+            let mut db = localdb::LocalDb::new(pool.clone());
+            readlater::remotes::save(&config, &mut db, &url, None, tags, &remotes)
                 .await
-                .expect("no access token available");
-            let mut pocket = PocketClient::new(&config.pocket_consumer_key, &access_token);
-            pocket
-                .add(vec![AddUrlRequest::new(url).tags(tags)])
+                .expect("error saving url");
+        }
+    };
+}
+
+/// Configures a remote and logs in to it, `settings` holding what is
+/// already known of it
+async fn add_remote(
+    config: &Config,
+    pool: &sqlx::SqlitePool,
+    name: &str,
+    backend: &str,
+    settings: serde_json::Value,
+) {
+    let mut db = localdb::LocalDb::new(pool.clone());
+    if db.get_remote(name).await.unwrap().is_some() {
+        eprintln!("A remote named {} already exists", name);
+        return;
+    }
+    let mut remote = localdb::Remote {
+        name: name.to_string(),
+        backend: backend.to_string(),
+        settings: settings.to_string(),
+        ..Default::default()
+    };
+    let vault = open_vault(config, pool).await;
+    let mut client = readlater::remotes::open(config, &vault, &remote)
+        .await
+        .expect("error opening remote");
+    client
+        .authenticate(&mut readlater::remotes::TerminalPrompt)
+        .await
+        .expect("error authenticating");
+    remote.id = db
+        .add_remote(name, backend, "{}")
+        .await
+        .expect("error adding remote");
+    vault
+        .store_settings(&mut db, &remote, client.settings())
+        .await
+        .expect("error storing credentials");
+    println!("Added {} remote {}", backend, name);
+}
+
+async fn remote_command(config: &Config, pool: &sqlx::SqlitePool, command: RemoteCommands) {
+    let mut db = localdb::LocalDb::new(pool.clone());
+    match command {
+        RemoteCommands::Add { name, backend, url } => {
+            let settings = match url {
+                Some(url) => serde_json::json!({ "url": url.as_str() }),
+                None => serde_json::json!({}),
+            };
+            add_remote(config, pool, &name, &backend, settings).await;
+        }
+        RemoteCommands::List => {
+            for remote in db.get_remotes().await.unwrap() {
+                let synced = remote
+                    .time_synced
+                    .and_then(|time| DateTime::from_timestamp(time, 0))
+                    .map(|time| time.to_string())
+                    .unwrap_or_else(|| "never".to_string());
+                let pending = db.get_outbox(remote.id).await.unwrap().len();
+                let items = db.count_remote_items(remote.id).await.unwrap();
+                println!(
+                    "{}\t{}\t{} items\tsynced {}\t{} pending",
+                    remote.name, remote.backend, items, synced, pending
+                );
+            }
+        }
+        RemoteCommands::Remove { name } => {
+            let Some(remote) = db.get_remote(&name).await.unwrap() else {
+                eprintln!("No remote named {}", name);
+                return;
+            };
+            db.remove_remote(remote.id).await.unwrap();
+            let vault = open_vault(config, pool).await;
+            vault
+                .forget(&name)
                 .await
-                .unwrap();
+                .expect("error deleting credentials");
+        }
+        RemoteCommands::Sync { names } => {
+            let remotes = db.get_remotes().await.unwrap();
+            let remotes = remotes
+                .into_iter()
+                .filter(|remote| names.is_empty() || names.contains(&remote.name));
+            let vault = open_vault(config, pool).await;
+            for remote in remotes {
+                match readlater::remotes::sync(config, &vault, &mut db, &remote).await {
+                    Ok(report) => println!(
+                        "{}: pulled {}, pushed {}, {} failed",
+                        remote.name, report.pulled, report.pushed, report.failed
+                    ),
+                    Err(e) => eprintln!("error syncing {}: {}", remote.name, e),
+                }
+            }
         }
+    }
+}
+
+async fn auth_command(config: &Config, pool: &sqlx::SqlitePool, command: AuthCommands) {
+    let mut db = localdb::LocalDb::new(pool.clone());
+    let vault = open_vault(config, pool).await;
+    let remotes = db.get_remotes().await.unwrap();
+    match command {
+        AuthCommands::Status => {
+            match config.credentials.store {
+                CredentialBackend::File => println!(
+                    "Credentials are kept in {}",
+                    config.credentials.file.display()
+                ),
+                CredentialBackend::SecretService => {
+                    println!("Credentials are kept by the Secret Service")
+                }
+            }
+            for remote in remotes {
+                let secrets = vault.secrets(&remote.name).await.unwrap();
+                let status = match secrets.is_empty() {
+                    true => "logged out",
+                    false => "logged in",
+                };
+                println!("{}\t{}\t{}", remote.name, remote.backend, status);
+            }
+        }
+        AuthCommands::Login { name } => {
+            let Some(remote) = remotes.into_iter().find(|r| r.name == name) else {
+                eprintln!("No remote named {}", name);
+                return;
+            };
+            let mut client = readlater::remotes::open(config, &vault, &remote)
+                .await
+                .expect("error opening remote");
+            client
+                .authenticate(&mut readlater::remotes::TerminalPrompt)
+                .await
+                .expect("error authenticating");
+            vault
+                .store_settings(&mut db, &remote, client.settings())
+                .await
+                .expect("error storing credentials");
+            println!("Logged in to {}", name);
+        }
+        AuthCommands::Logout { names } => {
+            for remote in remotes {
+                if !names.is_empty() && !names.contains(&remote.name) {
+                    continue;
+                }
+                if vault.forget(&remote.name).await.unwrap() {
+                    println!("Logged out of {}", remote.name);
+                }
+            }
+        }
+    }
+}
+
+/// Runs a deprecated `pocket` or `wallabag` command on the remote named
+/// after its backend
+async fn legacy_command(
+    config: &Config,
+    pool: &sqlx::SqlitePool,
+    backend: &str,
+    command: LegacyCommand,
+) {
+    eprintln!(
+        "`readlater {}` is deprecated, use `readlater remote` instead",
+        backend
+    );
+    let mut db = localdb::LocalDb::new(pool.clone());
+    let remote = db.get_remote(backend).await.expect("error reading remotes");
+    let sync = RemoteCommands::Sync {
+        names: vec![backend.to_string()],
     };
+    match (command, remote) {
+        (LegacyCommand::Auth { settings }, None) => {
+            add_remote(config, pool, backend, backend, settings).await;
+        }
+        (LegacyCommand::Auth { .. }, Some(_)) => {
+            let login = AuthCommands::Login {
+                name: backend.to_string(),
+            };
+            auth_command(config, pool, login).await;
+        }
+        (_, None) => {
+            eprintln!(
+                "No remote named {}, add it with `readlater remote add {} {}`",
+                backend, backend, backend
+            );
+            std::process::exit(1);
+        }
+        (LegacyCommand::Sync, Some(_)) => remote_command(config, pool, sync).await,
+        (LegacyCommand::Add { url, tags }, Some(remote)) => {
+            let remotes = [remote.name];
+            readlater::remotes::save(config, &mut db, &url, None, tags, &remotes)
+                .await
+                .expect("error saving url");
+        }
+        (LegacyCommand::Archive { ids }, Some(remote)) => {
+            db.begin_operation();
+            for id in ids {
+                let item = match db.find_remote_item(remote.id, &id).await.unwrap() {
+                    Some(item) => db.get_item(item).await.expect("error reading item"),
+                    None => None,
+                };
+                let Some(item) = item else {
+                    eprintln!("No item {} on {}", id, remote.name);
+                    continue;
+                };
+                actions::set_status(&mut db, &item, ItemStatus::Archived)
+                    .await
+                    .expect("error archiving item");
+            }
+            remote_command(config, pool, sync).await;
+        }
+        (LegacyCommand::Get { count, offset }, Some(remote)) => {
+            remote_command(config, pool, sync).await;
+            let mut items = db.get_items().await.expect("error reading items");
+            items.sort_by_key(|item| std::cmp::Reverse(item.time_added));
+            let mut listed = 0;
+            for item in items {
+                if listed == offset + count {
+                    break;
+                }
+                let id = db.get_remote_item_id(remote.id, item.id).await.unwrap();
+                let Some(id) = id else {
+                    continue;
+                };
+                if listed >= offset {
+                    println!("{} {}", id, item.title);
+                }
+                listed += 1;
+            }
+        }
+    }
+}
+
+/// Items with the given ids, exits when one is missing
+async fn find_items(db: &localdb::LocalDb, ids: &[i64]) -> Vec<localdb::Item> {
+    let mut items = vec![];
+    for id in ids {
+        match db.get_item(*id).await.expect("error reading item") {
+            Some(item) => items.push(item),
+            None => {
+                eprintln!("No item {}", id);
+                std::process::exit(1);
+            }
+        }
+    }
+    items
+}
+
+fn warn_backups(backups: &[PathBuf], problem: &str) {
+    if backups.is_empty() {
+        return;
+    }
+    eprintln!("Warning: these backups {}:", problem);
+    for backup in backups {
+        eprintln!("  {}", backup.display());
+    }
+}
+
+async fn db_command(config: &Config, command: DbCommands) -> anyhow::Result<()> {
+    use readlater::database;
+    let path = &config.database_dir;
+    match command {
+        DbCommands::Encrypt => {
+            let failed = database::encrypt(config).await?;
+            println!("Encrypted {}", path.display());
+            warn_backups(&failed, "are still in plain text, delete them");
+            for dir in [&config.snapshots_dir, &config.assets_dir] {
+                if std::fs::read_dir(dir).is_ok_and(|mut entries| entries.next().is_some()) {
+                    eprintln!("Warning: {} is not encrypted", dir.display());
+                }
+            }
+        }
+        DbCommands::Decrypt => {
+            let failed = database::decrypt(config).await?;
+            println!("Decrypted {}", path.display());
+            warn_backups(&failed, "could not be decrypted, the key is kept for them");
+        }
+        DbCommands::Rekey => {
+            let failed = database::rekey(config).await?;
+            println!("Changed the key of {}", path.display());
+            warn_backups(&failed, "are still under the old key");
+        }
+        DbCommands::Backup { dest } => {
+            let dest = database::backup(config, dest).await?;
+            println!("Backed up {} to {}", path.display(), dest.display());
+        }
+        DbCommands::Restore { backup } => {
+            let saved = database::restore(config, &backup).await?;
+            println!(
+                "Restored {}, the replaced library is in {}",
+                backup.display(),
+                saved.display()
+            );
+        }
+        DbCommands::Vacuum => {
+            let pool = database::open(config).await?;
+            let (before, after) = localdb::vacuum(&pool).await?;
+            println!("{} bytes before, {} after", before, after);
+        }
+        DbCommands::Check { fix } => {
+            let pool = database::open(config).await?;
+            let mut report = localdb::check(&pool).await?;
+            for problem in &report.problems {
+                println!("{}", problem);
+            }
+            for orphans in &report.orphans {
+                println!(
+                    "{} rows of {} point to missing {}",
+                    orphans.count, orphans.table, orphans.parent
+                );
+            }
+            if fix && !report.orphans.is_empty() {
+                let removed = localdb::remove_orphans(&pool).await?;
+                println!("Removed {} orphaned rows", removed);
+                // only the link tables are fixed
+                report = localdb::check(&pool).await?;
+            }
+            if !report.is_ok() {
+                anyhow::bail!("{} has problems", path.display());
+            }
+            println!("No problems found");
+        }
+        DbCommands::Stats => {
+            let pool = database::open(config).await?;
+            let stats = localdb::stats(&pool).await?;
+            println!(
+                "{}: {} bytes, {} free",
+                path.display(),
+                stats.bytes(),
+                stats.free_pages * stats.page_size
+            );
+            for table in &stats.tables {
+                println!("{:>10} {:>8} rows  {}", table.bytes, table.rows, table.name);
+            }
+        }
+        DbCommands::MigrateStatus => {
+            let pool = database::inspect(config).await?;
+            for migration in localdb::migration_status(&pool).await? {
+                let state = match migration.state {
+                    localdb::MigrationState::Applied => "applied",
+                    localdb::MigrationState::Pending => "pending",
+                    localdb::MigrationState::Modified => "modified",
+                    localdb::MigrationState::Unknown => "unknown",
+                };
+                println!(
+                    "{} {:<8} {} {}",
+                    migration.version,
+                    state,
+                    migration.description,
+                    migration.installed_on.unwrap_or_default()
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Opens the credential store, or exits explaining why it could not
+async fn open_vault(config: &Config, pool: &sqlx::SqlitePool) -> Vault {
+    let mut db = localdb::LocalDb::new(pool.clone());
+    match Vault::open(config, &mut db).await {
+        Ok(vault) => vault,
+        Err(e) => {
+            eprintln!("error opening the credential store: {:#}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Completes on ctrl-c or when systemd stops the service
+async fn shutdown_signal() {
+    let mut terminate = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+        .expect("error listening for signals");
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => {}
+        _ = terminate.recv() => {}
+    }
+}
+
+/// A value of the history on one line, contents and highlights shortened
+fn history_value(value: Option<&str>) -> String {
+    let Some(value) = value else {
+        return "-".to_string();
+    };
+    let line = value.split_whitespace().collect::<Vec<_>>().join(" ");
+    match line.char_indices().nth(60) {
+        Some((end, _)) => format!("{}…", &line[..end]),
+        None => line,
+    }
+}
+
+fn print_status(status: &daemon::Status) {
+    let time = |time: Option<i64>| {
+        time.and_then(|time| DateTime::from_timestamp(time, 0))
+            .map(|time| time.to_string())
+            .unwrap_or_else(|| "never".to_string())
+    };
+    let state = match (status.paused, status.syncing) {
+        (true, _) => "paused",
+        (false, true) => "syncing",
+        (false, false) => "running",
+    };
+    println!("{}", state);
+    println!("last sync\t{}", time(status.last_sync));
+    println!("next sync\t{}", time(status.next_sync));
+    println!("fetch queue\t{} items", status.queued);
+    for remote in &status.remotes {
+        match (&remote.report, &remote.error) {
+            (Some(report), _) => println!(
+                "{}\tpulled {}, pushed {}, {} failed",
+                remote.name, report.pulled, report.pushed, report.failed
+            ),
+            (None, Some(error)) => println!("{}\terror: {}", remote.name, error),
+            (None, None) => println!("{}", remote.name),
+        }
+    }
+}
+
+async fn cache_assets(assets: &AssetStore, db: &mut localdb::LocalDb, item: &localdb::Item) {
+    let results = match assets.cache_item(db, item).await {
+        Ok(results) => results,
+        Err(e) => {
+            eprintln!("error caching assets of {}: {}", item.id, e);
+            return;
+        }
+    };
+    for (url, result) in results {
+        if let Err(e) = result {
+            eprintln!("error caching {}: {}", url, e);
+        }
+    }
 }
@@ -19,9 +19,8 @@ pub struct Manifest {
 }
 
 pub fn install_linux(manifest: &Manifest) -> std::io::Result<()> {
-    let manifest_json = serde_json::to_string_pretty(manifest).map_err(|e| {
-        io::Error::new(io::ErrorKind::Other, format!("Serialization failed: {}", e))
-    })?;
+    let manifest_json = serde_json::to_string_pretty(manifest)
+        .map_err(|e| io::Error::other(format!("Serialization failed: {}", e)))?;
     let home_dir = env::var("HOME").unwrap_or_else(|_| "/tmp".to_string());
     let path = PathBuf::from(format!(
         "{}/.mozilla/native-messaging-hosts/{}.json",
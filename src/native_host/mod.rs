@@ -1,15 +1,22 @@
 pub mod install;
 
 use crate::config::Config;
-use localdb::KvDB;
+use crate::remotes;
+use anyhow::Context;
+use localdb::{Item, ItemQuery, LocalDb, Source};
 use native_messaging::host::{get_message, send_message};
-use pocket::{modify::AddUrlRequest, PocketClient};
 
 #[derive(serde::Deserialize)]
 struct Message {
     pub action: String,
-    pub url: url::Url,
+    /// Page to save or favorite, unused when listing
+    #[serde(default)]
+    pub url: Option<url::Url>,
+    #[serde(default)]
     pub title: String,
+    /// Remote to send the item to, instead of the configured ones
+    #[serde(default)]
+    pub remote: Option<String>,
 }
 
 #[derive(serde::Serialize)]
@@ -18,10 +25,30 @@ pub enum Status {
     Error,
 }
 
+/// Item as listed to the extension, which only shows and opens it
+#[derive(serde::Serialize)]
+struct Entry {
+    pub id: i64,
+    pub title: String,
+    pub url: String,
+}
+
+impl From<Item> for Entry {
+    fn from(item: Item) -> Self {
+        Self {
+            id: item.id,
+            title: item.title,
+            url: item.url,
+        }
+    }
+}
+
 #[derive(serde::Serialize)]
 struct Result {
     pub status: Status,
     pub message: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub items: Vec<Entry>,
 }
 
 impl Result {
@@ -29,6 +56,7 @@ impl Result {
         Self {
             status: Status::Ok,
             message: message.to_string(),
+            items: vec![],
         }
     }
 
@@ -36,39 +64,81 @@ impl Result {
         Self {
             status: Status::Error,
             message: message.to_string(),
+            items: vec![],
+        }
+    }
+
+    pub fn items(items: Vec<Item>) -> Self {
+        Self {
+            message: format!("{} items", items.len()),
+            items: items.into_iter().map(Entry::from).collect(),
+            ..Self::ok("")
         }
     }
 }
 
+/// Marks the saved item at `url` as a favorite
+async fn favorite(db: &mut LocalDb, url: &url::Url) -> anyhow::Result<Result> {
+    let Some(id) = db.get_item_id_by_url(url.as_str()).await? else {
+        return Ok(Result::error("URL not saved"));
+    };
+    let item = db.get_item(id).await?.context("item disappeared")?;
+    crate::actions::set_favorite(db, &item, true).await?;
+    Ok(Result::ok("URL added to the favorites"))
+}
+
 pub async fn native_host_handler(config: Config) {
-    let pool = localdb::open_database(config.database_dir.to_str().unwrap())
-        .await
-        .unwrap();
-    let kv_db = KvDB::new(pool.clone());
-    let access_token = kv_db.get_kv::<String>("pocket_access_token").await.unwrap();
-    let mut pocket = PocketClient::new(&config.pocket_consumer_key, &access_token.value);
+    let pool = match crate::database::open(&config).await {
+        Ok(pool) => pool,
+        Err(e) => {
+            // the extension waits for a reply to its message, even when the
+            // library can't be opened, such as without its key
+            let _ = get_message().await;
+            let reply = Result::error(&format!("Could not open the library: {:#}", e));
+            send_message(&reply).await.unwrap();
+            return;
+        }
+    };
+    let mut db = LocalDb::new(pool).with_source(Source::NativeHost);
 
     match get_message().await {
         Ok(message) => {
             let msg = serde_json::from_str::<Message>(&message).unwrap();
-            match msg.action.as_str() {
-                "save" => {
-                    let req = AddUrlRequest {
-                        url: msg.url,
-                        title: Some(msg.title),
-                        tags: vec!["readlater".to_string()],
-                        tweet_id: None,
-                        action: pocket::modify::Action::Add,
+            match (msg.action.as_str(), &msg.url) {
+                ("save" | "favorite", None) => {
+                    send_message(&Result::error("Missing url")).await.unwrap()
+                }
+                ("save", Some(url)) => {
+                    let tags = config.default_tags.clone();
+                    let remotes: Vec<String> = msg.remote.into_iter().collect();
+                    let result =
+                        remotes::save(&config, &mut db, url, Some(&msg.title), tags, &remotes)
+                            .await;
+                    let reply = match result {
+                        Ok(_) => Result::ok("URL saved"),
+                        Err(e) => Result::error(&e.to_string()),
+                    };
+                    send_message(&reply).await.unwrap();
+                }
+                ("favorite", Some(url)) => {
+                    let reply = match favorite(&mut db, url).await {
+                        Ok(reply) => reply,
+                        Err(e) => Result::error(&format!("{:#}", e)),
+                    };
+                    send_message(&reply).await.unwrap();
+                }
+                ("favorites", _) => {
+                    let query = ItemQuery::default().favorite(true);
+                    let reply = match db.query_items(&query).await {
+                        Ok(items) => Result::items(items),
+                        Err(e) => Result::error(&e.to_string()),
                     };
-                    pocket.add(vec![req]).await.unwrap();
+                    send_message(&reply).await.unwrap();
                 }
                 _ => send_message(&Result::error("Invalid action"))
                     .await
                     .unwrap(),
             }
-            send_message(&Result::ok("URL added to Pocket"))
-                .await
-                .unwrap();
         }
         Err(e) => eprintln!("Error receiving message: {}", e),
     }
@@ -0,0 +1,95 @@
+//! Shows long text one screen at a time
+
+use ratatui::crossterm::{
+    cursor,
+    event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
+    execute, queue,
+    style::{Attribute, Print, SetAttribute},
+    terminal::{self, ClearType},
+};
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+
+/// Pages `lines` in the terminal. Returns whether the last line was shown.
+pub fn page(lines: &[String], title: &str) -> io::Result<bool> {
+    let (_, rows) = terminal::size()?;
+    let mut stdout = io::stdout();
+    if lines.len() < rows as usize {
+        for line in lines {
+            writeln!(stdout, "{}", line)?;
+        }
+        return Ok(true);
+    }
+
+    terminal::enable_raw_mode()?;
+    execute!(stdout, terminal::EnterAlternateScreen, cursor::Hide)?;
+    let result = run(&mut stdout, lines, title);
+    execute!(stdout, cursor::Show, terminal::LeaveAlternateScreen)?;
+    terminal::disable_raw_mode()?;
+    result
+}
+
+fn run(stdout: &mut io::Stdout, lines: &[String], title: &str) -> io::Result<bool> {
+    let mut top = 0;
+    let mut reached_end = false;
+    loop {
+        let (_, rows) = terminal::size()?;
+        let height = (rows as usize).saturating_sub(1).max(1);
+        let max_top = lines.len().saturating_sub(height);
+        top = top.min(max_top);
+        reached_end |= top == max_top;
+
+        queue!(stdout, terminal::Clear(ClearType::All))?;
+        for (row, line) in lines.iter().skip(top).take(height).enumerate() {
+            queue!(stdout, cursor::MoveTo(0, row as u16), Print(line))?;
+        }
+        let percent = (top + height).min(lines.len()) * 100 / lines.len().max(1);
+        let status = format!(" {}  {}%  space/b page, j/k line, q quit ", title, percent);
+        queue!(
+            stdout,
+            cursor::MoveTo(0, height as u16),
+            SetAttribute(Attribute::Reverse),
+            Print(status),
+            SetAttribute(Attribute::Reset)
+        )?;
+        stdout.flush()?;
+
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return Ok(reached_end),
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                return Ok(reached_end)
+            }
+            KeyCode::Char('j') | KeyCode::Down | KeyCode::Enter => top += 1,
+            KeyCode::Char('k') | KeyCode::Up => top = top.saturating_sub(1),
+            KeyCode::Char(' ') | KeyCode::PageDown | KeyCode::Char('f') => top += height,
+            KeyCode::Char('b') | KeyCode::PageUp => top = top.saturating_sub(height),
+            KeyCode::Char('g') | KeyCode::Home => top = 0,
+            KeyCode::Char('G') | KeyCode::End => top = max_top,
+            _ => {}
+        }
+    }
+}
+
+/// Pipes `lines` through an external pager, such as `less -R`
+pub fn pipe(lines: &[String], pager: &str) -> io::Result<()> {
+    let mut child = Command::new("sh")
+        .args(["-c", pager])
+        .stdin(Stdio::piped())
+        .spawn()?;
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    for line in lines {
+        // the pager may be closed before reading everything
+        if writeln!(stdin, "{}", line).is_err() {
+            break;
+        }
+    }
+    drop(stdin);
+    child.wait()?;
+    Ok(())
+}
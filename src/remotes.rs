@@ -0,0 +1,170 @@
+use crate::config::Config;
+use crate::vault::Vault;
+use localdb::{Item, JobKind, LocalDb, Remote, Tag};
+use remote::{Mutation, Prompt, RemoteBackend, RemoteError, RemoteResult, SyncReport};
+use std::io::Write;
+use url::Url;
+
+/// Opens the backend of a remote with its secrets, filling in the pocket
+/// consumer key of readlater when the remote does not have its own.
+pub async fn open(
+    config: &Config,
+    vault: &Vault,
+    remote: &Remote,
+) -> RemoteResult<Box<dyn RemoteBackend>> {
+    let mut settings = vault.settings(remote).await?;
+    if remote.backend == remote::pocket::NAME {
+        let key = &mut settings["consumer_key"];
+        if key.as_str().is_none_or(str::is_empty) {
+            *key = config.pocket_consumer_key.clone().into();
+        }
+    }
+    remote::open(&remote.backend, &settings.to_string())
+}
+
+/// Syncs a remote, then stores its settings since backends may rotate
+/// credentials
+pub async fn sync(
+    config: &Config,
+    vault: &Vault,
+    db: &mut LocalDb,
+    remote: &Remote,
+) -> RemoteResult<SyncReport> {
+    let mut backend = open(config, vault, remote).await?;
+    let result = remote::sync(db, remote, backend.as_mut()).await;
+    vault.store_settings(db, remote, backend.settings()).await?;
+    result
+}
+
+/// Prompts on the terminal
+pub struct TerminalPrompt;
+
+impl TerminalPrompt {
+    fn read_line(&mut self, question: &str) -> String {
+        print!("{} ", question);
+        std::io::stdout().flush().unwrap();
+        let mut input = String::new();
+        std::io::stdin().read_line(&mut input).unwrap();
+        input.trim().to_string()
+    }
+}
+
+impl Prompt for TerminalPrompt {
+    fn show(&mut self, message: &str) {
+        println!("{}", message);
+    }
+
+    fn ask(&mut self, question: &str) -> String {
+        self.read_line(question)
+    }
+
+    fn password(&mut self, question: &str) -> String {
+        rpassword::prompt_password(format!("{} ", question)).unwrap()
+    }
+}
+
+/// Saves a url to the library and queues it for the remotes named in
+/// `remotes`, the configured ones when empty, or else every remote. The
+/// tags are linked to the item even when the url was already saved. The
+/// job workers send it to the remotes and fetch the article and metadata
+/// later.
+pub async fn save(
+    config: &Config,
+    db: &mut LocalDb,
+    url: &Url,
+    title: Option<&str>,
+    tags: Vec<String>,
+    remotes: &[String],
+) -> RemoteResult<i64> {
+    let names = match remotes {
+        [] => config.save_to.as_slice(),
+        names => names,
+    };
+    let all = db.get_remotes().await?;
+    let targets = match names {
+        [] => all,
+        names => {
+            if let Some(name) = names
+                .iter()
+                .find(|name| !all.iter().any(|r| &r.name == *name))
+            {
+                return Err(RemoteError::UnknownRemote(name.clone()));
+            }
+            all.into_iter()
+                .filter(|remote| names.contains(&remote.name))
+                .collect()
+        }
+    };
+
+    let id = match db.get_item_id_by_url(url.as_str()).await? {
+        Some(id) => {
+            for tag in &tags {
+                let tag = db
+                    .add_tag(&Tag {
+                        id: 0,
+                        tag: tag.clone(),
+                        name: None,
+                    })
+                    .await?;
+                db.link_tag(tag, id as i32).await?;
+            }
+            id
+        }
+        None => {
+            let item = Item {
+                title: title.unwrap_or(url.as_str()).to_string(),
+                url: url.to_string(),
+                tags: tags
+                    .iter()
+                    .map(|tag| Tag {
+                        id: 0,
+                        tag: tag.clone(),
+                        name: None,
+                    })
+                    .collect(),
+                time_added: chrono::Utc::now().timestamp() as i32,
+                ..Default::default()
+            };
+            let id = db.add(&item).await? as i64;
+            crate::jobs::enqueue_new(db, id).await?;
+            id
+        }
+    };
+
+    let mutation = Mutation::Add {
+        url: url.to_string(),
+        title: title.map(String::from),
+        tags,
+    };
+    remote::enqueue_to(db, &targets, id, &mutation).await?;
+    if !targets.is_empty() {
+        db.add_job(JobKind::Push, id).await?;
+    }
+    Ok(id)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_save_existing_url_links_tags() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config::in_dir(dir.path());
+        let pool = localdb::open_database(":memory:").await.unwrap();
+        let mut db = LocalDb::new(pool);
+        let url = Url::parse("https://example.com/a").unwrap();
+
+        let id = save(&config, &mut db, &url, None, vec!["a".into()], &[])
+            .await
+            .unwrap();
+        let again = save(&config, &mut db, &url, None, vec!["b".into()], &[])
+            .await
+            .unwrap();
+        assert_eq!(id, again);
+        let item = db.get_item(id).await.unwrap().unwrap();
+        let mut tags: Vec<_> = item.tags.iter().map(|tag| tag.tag.as_str()).collect();
+        tags.sort();
+        assert_eq!(tags, ["a", "b"]);
+    }
+}
@@ -0,0 +1,488 @@
+//! Renders extracted articles as text for the terminal, styled with ANSI
+//! escapes or as plain Markdown-like text. Links become numbered footnotes.
+
+use scraper::{ElementRef, Html, Node};
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const DIM: &str = "\x1b[2m";
+const ITALIC: &str = "\x1b[3m";
+const UNDERLINE: &str = "\x1b[4m";
+
+const SKIPPED: &[&str] = &["script", "style", "noscript", "template", "head", "svg"];
+const BLOCKS: &[&str] = &[
+    "address",
+    "article",
+    "aside",
+    "dd",
+    "div",
+    "dl",
+    "dt",
+    "figcaption",
+    "figure",
+    "footer",
+    "header",
+    "main",
+    "nav",
+    "p",
+    "section",
+    "table",
+    "tr",
+];
+
+#[derive(Debug, Clone, Copy)]
+pub struct Options {
+    pub width: usize,
+    /// Leave out the ANSI styles
+    pub plain: bool,
+}
+
+struct Renderer {
+    options: Options,
+    lines: Vec<String>,
+    /// Text of the paragraph being built
+    current: String,
+    /// Prefix of the first line of the paragraph, such as a list marker
+    first_indent: Option<String>,
+    styles: Vec<&'static str>,
+    /// Counter of each open list, None for bullet lists
+    lists: Vec<Option<usize>>,
+    quotes: usize,
+    links: Vec<String>,
+}
+
+impl Renderer {
+    fn new(options: Options) -> Renderer {
+        Renderer {
+            options,
+            lines: vec![],
+            current: String::new(),
+            first_indent: None,
+            styles: vec![],
+            lists: vec![],
+            quotes: 0,
+            links: vec![],
+        }
+    }
+
+    fn indent(&self) -> String {
+        let mut indent = "│ ".repeat(self.quotes);
+        indent.push_str(&"  ".repeat(self.lists.len()));
+        indent
+    }
+
+    fn push_style(&mut self, style: &'static str) {
+        self.styles.push(style);
+        if !self.options.plain {
+            self.current.push_str(style);
+        }
+    }
+
+    fn pop_style(&mut self) {
+        self.styles.pop();
+        if !self.options.plain {
+            self.current.push_str(RESET);
+            self.current.push_str(&self.styles.concat());
+        }
+    }
+
+    fn push_text(&mut self, text: &str) {
+        let text = &sanitize(text);
+        for (i, word) in text.split_whitespace().enumerate() {
+            if (i > 0 || text.starts_with(char::is_whitespace)) && !self.at_line_start() {
+                self.current.push(' ');
+            }
+            self.current.push_str(word);
+        }
+        if text.ends_with(char::is_whitespace) && !self.at_line_start() {
+            self.current.push(' ');
+        }
+    }
+
+    /// Whether nothing but styles has been written to the paragraph
+    fn at_line_start(&self) -> bool {
+        let visible = strip_ansi(&self.current);
+        visible.is_empty() || visible.ends_with(' ')
+    }
+
+    fn blank_line(&mut self) {
+        if self.lines.last().is_some_and(|line| !is_blank(line)) {
+            let indent = self.indent();
+            self.lines.push(indent.trim_end().to_string());
+        }
+    }
+
+    /// Wraps the paragraph being built into lines
+    fn finish(&mut self) {
+        let text = self.current.trim().to_string();
+        self.current = self.styles.concat();
+        let indent = self.indent();
+        let first = self.first_indent.take().unwrap_or_else(|| indent.clone());
+        if strip_ansi(&text).trim().is_empty() {
+            return;
+        }
+        let options = textwrap::Options::new(self.options.width.max(20))
+            .initial_indent(&first)
+            .subsequent_indent(&indent);
+        let wrapped: Vec<String> = textwrap::wrap(&text, options)
+            .into_iter()
+            .map(String::from)
+            .collect();
+        self.lines.extend(carry_styles(wrapped, self.options.plain));
+    }
+
+    fn block(&mut self) {
+        self.finish();
+        self.blank_line();
+    }
+
+    fn walk(&mut self, element: ElementRef) {
+        let name = element.value().name();
+        if SKIPPED.contains(&name) {
+            return;
+        }
+        match name {
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                self.block();
+                let level = name[1..].parse::<usize>().unwrap_or(1);
+                self.current.push_str(&"#".repeat(level));
+                self.current.push(' ');
+                self.push_style(BOLD);
+                if level == 1 {
+                    self.push_style(UNDERLINE);
+                }
+                self.children(element);
+                if level == 1 {
+                    self.pop_style();
+                }
+                self.pop_style();
+                self.block();
+            }
+            "pre" => {
+                self.block();
+                let code = sanitize(&element.text().collect::<String>());
+                let indent = format!("{}    ", self.indent());
+                for line in code.trim_end().lines() {
+                    let line = line.replace('\t', "    ");
+                    match self.options.plain {
+                        true => self.lines.push(format!("{}{}", indent, line)),
+                        false => self
+                            .lines
+                            .push(format!("{}{}{}{}", indent, DIM, line, RESET)),
+                    }
+                }
+                self.blank_line();
+            }
+            "code" | "kbd" | "samp" => {
+                if self.options.plain {
+                    self.current.push('`');
+                    self.children(element);
+                    self.current.push('`');
+                } else {
+                    self.push_style(DIM);
+                    self.children(element);
+                    self.pop_style();
+                }
+            }
+            "b" | "strong" => self.styled(element, BOLD, "**"),
+            "i" | "em" | "cite" => self.styled(element, ITALIC, "_"),
+            "a" => {
+                let href = &sanitize(element.value().attr("href").unwrap_or_default());
+                let footnote = href.starts_with("http://") || href.starts_with("https://");
+                if footnote && !self.options.plain {
+                    self.push_style(UNDERLINE);
+                }
+                self.children(element);
+                if footnote {
+                    if !self.options.plain {
+                        self.pop_style();
+                    }
+                    let number = match self.links.iter().position(|link| link == href) {
+                        Some(i) => i + 1,
+                        None => {
+                            self.links.push(href.to_string());
+                            self.links.len()
+                        }
+                    };
+                    self.current.push_str(&format!("[{}]", number));
+                }
+            }
+            "img" => {
+                let alt = element.value().attr("alt").unwrap_or_default().trim();
+                if !alt.is_empty() {
+                    let text = format!("[image: {}]", alt);
+                    self.styled_text(&text, ITALIC);
+                }
+            }
+            "br" => self.finish(),
+            "hr" => {
+                self.block();
+                let rule = "─".repeat(self.options.width.clamp(3, 40));
+                self.lines.push(format!("{}{}", self.indent(), rule));
+                self.blank_line();
+            }
+            "ul" | "ol" => {
+                self.finish();
+                if self.lists.is_empty() {
+                    self.blank_line();
+                }
+                self.lists.push((name == "ol").then_some(0));
+                self.children(element);
+                self.finish();
+                self.lists.pop();
+                if self.lists.is_empty() {
+                    self.blank_line();
+                }
+            }
+            "li" => {
+                self.finish();
+                let marker = match self.lists.last_mut() {
+                    Some(Some(counter)) => {
+                        *counter += 1;
+                        format!("{}. ", counter)
+                    }
+                    _ => "• ".to_string(),
+                };
+                let indent = self.indent();
+                let parent = indent.strip_suffix("  ").unwrap_or(&indent).to_string();
+                self.first_indent = Some(format!("{}{}", parent, marker));
+                self.children(element);
+                self.finish();
+            }
+            "blockquote" => {
+                self.block();
+                self.quotes += 1;
+                self.children(element);
+                self.finish();
+                self.quotes -= 1;
+                while self.lines.last().is_some_and(|line| is_blank(line)) {
+                    self.lines.pop();
+                }
+                self.blank_line();
+            }
+            "td" | "th" => {
+                self.children(element);
+                self.current.push_str("  ");
+            }
+            _ if BLOCKS.contains(&name) => {
+                self.block();
+                self.children(element);
+                self.block();
+            }
+            _ => self.children(element),
+        }
+    }
+
+    fn styled(&mut self, element: ElementRef, style: &'static str, markup: &str) {
+        match self.options.plain {
+            true => {
+                self.current.push_str(markup);
+                self.children(element);
+                self.current.push_str(markup);
+            }
+            false => {
+                self.push_style(style);
+                self.children(element);
+                self.pop_style();
+            }
+        }
+    }
+
+    fn styled_text(&mut self, text: &str, style: &'static str) {
+        if !self.options.plain {
+            self.push_style(style);
+        }
+        self.push_text(text);
+        if !self.options.plain {
+            self.pop_style();
+        }
+    }
+
+    fn children(&mut self, element: ElementRef) {
+        for child in element.children() {
+            match child.value() {
+                Node::Text(text) => self.push_text(text),
+                Node::Element(_) => {
+                    if let Some(child) = ElementRef::wrap(child) {
+                        self.walk(child);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Page text without control characters but newlines and tabs, so that it
+/// cannot send its own escape sequences to the terminal
+pub fn sanitize(text: &str) -> String {
+    text.chars()
+        .filter(|c| !c.is_control() || matches!(c, '\n' | '\t'))
+        .collect()
+}
+
+/// Whether a line only holds indentation
+fn is_blank(line: &str) -> bool {
+    strip_ansi(line)
+        .trim_matches(|c: char| c == '│' || c.is_whitespace())
+        .is_empty()
+}
+
+/// Text of a line without its escape sequences
+fn strip_ansi(line: &str) -> String {
+    let mut text = String::with_capacity(line.len());
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        } else {
+            text.push(c);
+        }
+    }
+    text
+}
+
+/// Makes every line stand on its own: styles open at the end of a line are
+/// reset there and opened again on the next one, so pages can start anywhere
+fn carry_styles(lines: Vec<String>, plain: bool) -> Vec<String> {
+    if plain {
+        return lines;
+    }
+    let mut active: Vec<String> = vec![];
+    lines
+        .into_iter()
+        .map(|line| {
+            let mut out = active.concat();
+            out.push_str(&line);
+            let mut rest = line.as_str();
+            while let Some(start) = rest.find('\x1b') {
+                let Some(end) = rest[start..].find('m') else {
+                    break;
+                };
+                let code = &rest[start..start + end + 1];
+                if code == RESET {
+                    active.clear();
+                } else {
+                    active.push(code.to_string());
+                }
+                rest = &rest[start + end + 1..];
+            }
+            if !active.is_empty() {
+                out.push_str(RESET);
+            }
+            out
+        })
+        .collect()
+}
+
+/// Lines of an article, followed by the footnotes of its links
+pub fn render(html: &str, options: Options) -> Vec<String> {
+    let fragment = Html::parse_fragment(html);
+    let mut renderer = Renderer::new(options);
+    renderer.children(fragment.root_element());
+    renderer.finish();
+    while renderer.lines.last().is_some_and(|line| is_blank(line)) {
+        renderer.lines.pop();
+    }
+
+    let mut lines = renderer.lines;
+    if !renderer.links.is_empty() {
+        lines.push(String::new());
+        for (i, link) in renderer.links.iter().enumerate() {
+            lines.push(format!("[{}] {}", i + 1, link));
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const HTML: &str = r#"<h2>Intro</h2>
+        <p>Read <a href="https://example.com/a">the <em>docs</em></a> and
+        <a href="https://example.com/a">again</a>, run <code>cargo</code>.</p>
+        <ul><li>one</li><li>two<ol><li>nested</li></ol></li></ul>
+        <img alt="A chart">
+        <pre>fn main() {
+    println!();
+}</pre>
+        <blockquote><p>Quoted</p></blockquote>"#;
+
+    #[test]
+    fn test_plain() {
+        let lines = render(
+            HTML,
+            Options {
+                width: 80,
+                plain: true,
+            },
+        );
+        assert_eq!(
+            lines,
+            vec![
+                "## Intro",
+                "",
+                "Read the _docs_[1] and again[1], run `cargo`.",
+                "",
+                "• one",
+                "• two",
+                "  1. nested",
+                "",
+                "[image: A chart]",
+                "",
+                "    fn main() {",
+                "        println!();",
+                "    }",
+                "",
+                "│ Quoted",
+                "",
+                "[1] https://example.com/a",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_wraps_and_carries_styles() {
+        let lines = render(
+            "<p><b>one two three four five six seven eight nine ten eleven twelve</b></p>",
+            Options {
+                width: 20,
+                plain: false,
+            },
+        );
+        assert!(lines.len() > 1);
+        for line in &lines {
+            assert!(line.starts_with(BOLD), "{:?}", line);
+            assert!(line.ends_with(RESET), "{:?}", line);
+            assert!(strip_ansi(line).chars().count() <= 20);
+        }
+    }
+
+    #[test]
+    fn test_strips_escapes_of_the_page() {
+        let html = r#"<p>Hi &#27;]52;c;ZXZpbA==&#7; there</p>
+            <pre>a&#27;[2J&#155;b</pre>
+            <a href="https://example.com/&#27;]0;x">link</a>"#;
+        let lines = render(
+            html,
+            Options {
+                width: 80,
+                plain: false,
+            },
+        );
+        for line in &lines {
+            let text = line
+                .replace(UNDERLINE, "")
+                .replace(DIM, "")
+                .replace(RESET, "");
+            assert!(!text.chars().any(|c| c.is_control()), "{:?}", line);
+        }
+        assert_eq!(strip_ansi(&lines[0]), "Hi ]52;c;ZXZpbA== there");
+        assert_eq!(lines.last().unwrap(), "[1] https://example.com/]0;x");
+    }
+}
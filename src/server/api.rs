@@ -0,0 +1,276 @@
+use super::{ApiError, ApiResult, AppState};
+use crate::actions;
+use crate::remotes;
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use localdb::{Content, Highlight, Item, ItemQuery, ItemStatus, LocalDb, SortBy, Source, Tag};
+use remote::SyncReport;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use url::Url;
+
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/items", get(list_items).post(create_item))
+        .route(
+            "/items/{id}",
+            get(get_item).patch(update_item).delete(delete_item),
+        )
+        .route("/items/{id}/content", get(get_content))
+        .route(
+            "/items/{id}/highlights",
+            get(item_highlights).post(create_highlight),
+        )
+        .route("/tags", get(list_tags))
+        .route("/highlights", get(list_highlights))
+        .route(
+            "/highlights/{id}",
+            get(get_highlight)
+                .patch(update_highlight)
+                .delete(delete_highlight),
+        )
+        .route("/sync", post(sync))
+}
+
+fn db(state: &AppState) -> LocalDb {
+    LocalDb::new(state.pool.clone()).with_source(Source::Server)
+}
+
+async fn find_item(db: &LocalDb, id: i64) -> ApiResult<Item> {
+    db.get_item(id).await?.ok_or(ApiError::NotFound)
+}
+
+#[derive(Deserialize)]
+struct ListQuery {
+    status: Option<ItemStatus>,
+    tag: Option<String>,
+    favorite: Option<bool>,
+    search: Option<String>,
+    #[serde(default)]
+    sort: SortBy,
+    limit: Option<usize>,
+}
+
+async fn list_items(
+    State(state): State<AppState>,
+    Query(query): Query<ListQuery>,
+) -> ApiResult<Json<Vec<Item>>> {
+    let query = ItemQuery {
+        status: query.status,
+        tag: query.tag,
+        favorite: query.favorite,
+        search: query.search,
+        sort: query.sort,
+        limit: query.limit,
+    };
+    Ok(Json(db(&state).query_items(&query).await?))
+}
+
+#[derive(Deserialize)]
+struct NewItem {
+    url: String,
+    title: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+    /// Remotes to send the item to, instead of the configured ones
+    #[serde(default)]
+    remotes: Vec<String>,
+}
+
+async fn create_item(
+    State(state): State<AppState>,
+    Json(body): Json<NewItem>,
+) -> ApiResult<(StatusCode, Json<Item>)> {
+    let url = Url::parse(&body.url).map_err(|e| ApiError::BadRequest(e.to_string()))?;
+    let mut db = db(&state);
+    let id = remotes::save(
+        &state.config,
+        &mut db,
+        &url,
+        body.title.as_deref(),
+        body.tags,
+        &body.remotes,
+    )
+    .await?;
+    Ok((StatusCode::CREATED, Json(find_item(&db, id).await?)))
+}
+
+async fn get_item(State(state): State<AppState>, Path(id): Path<i64>) -> ApiResult<Json<Item>> {
+    Ok(Json(find_item(&db(&state), id).await?))
+}
+
+#[derive(Deserialize)]
+struct ItemPatch {
+    title: Option<String>,
+    excerpt: Option<String>,
+    status: Option<ItemStatus>,
+    favorite: Option<bool>,
+    #[serde(default)]
+    add_tags: Vec<String>,
+    #[serde(default)]
+    remove_tags: Vec<String>,
+}
+
+/// Applies the changes locally and queues them for the remotes
+async fn update_item(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    Json(patch): Json<ItemPatch>,
+) -> ApiResult<Json<Item>> {
+    let mut db = db(&state);
+    let mut item = find_item(&db, id).await?;
+
+    if patch.title.is_some() || patch.excerpt.is_some() {
+        item.title = patch.title.unwrap_or(item.title);
+        item.excerpt = patch.excerpt.or(item.excerpt);
+        db.update_item(&item).await?;
+    }
+    if let Some(status) = patch.status {
+        actions::set_status(&mut db, &item, status).await?;
+    }
+    if let Some(favorite) = patch.favorite {
+        actions::set_favorite(&mut db, &item, favorite).await?;
+    }
+    actions::add_tags(&mut db, &item, patch.add_tags).await?;
+    actions::remove_tags(&mut db, &item, patch.remove_tags).await?;
+
+    Ok(Json(find_item(&db, id).await?))
+}
+
+/// Items are only marked as deleted so that remotes can be told about it
+async fn delete_item(State(state): State<AppState>, Path(id): Path<i64>) -> ApiResult<StatusCode> {
+    let mut db = db(&state);
+    let item = find_item(&db, id).await?;
+    actions::set_status(&mut db, &item, ItemStatus::Deleted).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn get_content(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> ApiResult<Json<Content>> {
+    let content = db(&state).get_content(id).await?;
+    Ok(Json(content.ok_or(ApiError::NotFound)?))
+}
+
+async fn list_tags(State(state): State<AppState>) -> ApiResult<Json<Vec<Tag>>> {
+    Ok(Json(db(&state).get_tags().await?))
+}
+
+async fn list_highlights(State(state): State<AppState>) -> ApiResult<Json<Vec<Highlight>>> {
+    Ok(Json(db(&state).get_highlights(None).await?))
+}
+
+async fn item_highlights(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> ApiResult<Json<Vec<Highlight>>> {
+    let db = db(&state);
+    find_item(&db, id).await?;
+    Ok(Json(db.get_highlights(Some(id)).await?))
+}
+
+#[derive(Deserialize)]
+struct NewHighlight {
+    text: String,
+    note: Option<String>,
+    color: Option<String>,
+    position: Option<String>,
+}
+
+async fn create_highlight(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    Json(body): Json<NewHighlight>,
+) -> ApiResult<(StatusCode, Json<Highlight>)> {
+    let mut db = db(&state);
+    find_item(&db, id).await?;
+    let highlight = Highlight {
+        item_id: id,
+        text: body.text,
+        note: body.note,
+        color: body.color,
+        position: body.position,
+        ..Default::default()
+    };
+    let id = db.add_highlight(&highlight).await?;
+    let highlight = db.get_highlight(id).await?.ok_or(ApiError::NotFound)?;
+    Ok((StatusCode::CREATED, Json(highlight)))
+}
+
+async fn get_highlight(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> ApiResult<Json<Highlight>> {
+    let highlight = db(&state).get_highlight(id).await?;
+    Ok(Json(highlight.ok_or(ApiError::NotFound)?))
+}
+
+#[derive(Deserialize)]
+struct HighlightPatch {
+    text: Option<String>,
+    note: Option<String>,
+    color: Option<String>,
+    position: Option<String>,
+}
+
+async fn update_highlight(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    Json(patch): Json<HighlightPatch>,
+) -> ApiResult<Json<Highlight>> {
+    let mut db = db(&state);
+    let mut highlight = db.get_highlight(id).await?.ok_or(ApiError::NotFound)?;
+    highlight.text = patch.text.unwrap_or(highlight.text);
+    highlight.note = patch.note.or(highlight.note);
+    highlight.color = patch.color.or(highlight.color);
+    highlight.position = patch.position.or(highlight.position);
+    db.update_highlight(&highlight).await?;
+    Ok(Json(db.get_highlight(id).await?.ok_or(ApiError::NotFound)?))
+}
+
+async fn delete_highlight(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> ApiResult<StatusCode> {
+    let mut db = db(&state);
+    db.get_highlight(id).await?.ok_or(ApiError::NotFound)?;
+    db.delete_highlight(id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Deserialize)]
+struct SyncQuery {
+    remote: Option<String>,
+}
+
+/// Syncs every remote, or only the one named in the query, and reports the
+/// outcome per remote
+async fn sync(
+    State(state): State<AppState>,
+    Query(query): Query<SyncQuery>,
+) -> ApiResult<Json<BTreeMap<String, SyncReport>>> {
+    let mut db = db(&state);
+    let remotes = db.get_remotes().await?;
+    let remotes: Vec<_> = remotes
+        .into_iter()
+        .filter(|remote| {
+            query
+                .remote
+                .as_ref()
+                .is_none_or(|name| *name == remote.name)
+        })
+        .collect();
+    if remotes.is_empty() && query.remote.is_some() {
+        return Err(ApiError::NotFound);
+    }
+
+    let mut reports = BTreeMap::new();
+    for remote in remotes {
+        let report = remotes::sync(&state.config, &state.vault, &mut db, &remote).await?;
+        reports.insert(remote.name, report);
+    }
+    Ok(Json(reports))
+}
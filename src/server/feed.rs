@@ -0,0 +1,94 @@
+use super::{authorized, images, ApiError, ApiResult, AppState};
+use crate::feeds::{self, FeedFormat, FeedKind, Images};
+use axum::extract::{Query, State};
+use axum::http::{header, HeaderMap, Uri};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::Router;
+use localdb::{Asset, LocalDb};
+use serde::Deserialize;
+
+/// Feeds check the token themselves, as feed readers can only send a key in
+/// the url
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/feeds/{file}", get(feed))
+        .route("/feeds/tags/{file}", get(feed))
+}
+
+#[derive(Deserialize)]
+struct FeedQuery {
+    key: Option<String>,
+}
+
+fn kind(path: &str) -> Option<(FeedKind, FeedFormat)> {
+    if let Some(file) = path.strip_prefix("/feeds/tags/") {
+        let (name, format) = FeedFormat::split(file)?;
+        let (tag, _) = url::form_urlencoded::parse(name.as_bytes()).next()?;
+        return Some((FeedKind::Tag(tag.into_owned()), format));
+    }
+    let (name, format) = FeedFormat::split(path.strip_prefix("/feeds/")?)?;
+    match name {
+        "unread" => Some((FeedKind::Unread, format)),
+        "favorites" => Some((FeedKind::Favorites, format)),
+        _ => None,
+    }
+}
+
+async fn feed(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    uri: Uri,
+    Query(query): Query<FeedQuery>,
+) -> ApiResult<Response> {
+    let (kind, format) = kind(uri.path()).ok_or(ApiError::NotFound)?;
+    let path = kind.path(format);
+    let key = feeds::feed_key(&state.token, &path);
+    if !authorized(&headers, &state.token) && query.key.as_deref() != Some(key.as_str()) {
+        return Err(ApiError::Unauthorized);
+    }
+
+    let host = headers
+        .get(header::HOST)
+        .and_then(|host| host.to_str().ok())
+        .unwrap_or("localhost");
+    let base = format!("http://{}/", host);
+    let db = LocalDb::new(state.pool.clone());
+    let store = super::asset_store(&state.config);
+    let link = |asset: &Asset| {
+        let path = images::path(asset);
+        let key = feeds::feed_key(&state.token, &path);
+        format!("http://{}{}?key={}", host, path, key)
+    };
+    let images = Images {
+        store: &store,
+        link: &link,
+    };
+    let feed = feeds::build(&db, &kind, &base, Some(&images))
+        .await?
+        .self_link(&format!("http://{}{}?key={}", host, path, key));
+    let body = match format {
+        FeedFormat::Atom => feed.atom(),
+        FeedFormat::Rss => feed.rss(),
+    };
+    Ok(([(header::CONTENT_TYPE, format.content_type())], body).into_response())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_kind() {
+        assert_eq!(
+            kind("/feeds/unread.atom"),
+            Some((FeedKind::Unread, FeedFormat::Atom))
+        );
+        assert_eq!(
+            kind("/feeds/tags/rust+lang.rss"),
+            Some((FeedKind::Tag("rust lang".to_string()), FeedFormat::Rss))
+        );
+        assert_eq!(kind("/feeds/unread.json"), None);
+        assert_eq!(kind("/feeds/other.atom"), None);
+    }
+}
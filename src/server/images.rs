@@ -0,0 +1,60 @@
+use super::{authorized, ApiError, ApiResult, AppState};
+use crate::feeds;
+use axum::extract::{Path, Query, State};
+use axum::http::{header, HeaderMap};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::Router;
+use localdb::{Asset, LocalDb};
+use serde::Deserialize;
+
+/// Cached images of the items. They check the token themselves, as feed
+/// readers can only send a key in the url.
+pub fn routes() -> Router<AppState> {
+    Router::new().route("/assets/{hash}", get(image))
+}
+
+/// Path an asset is served at
+pub fn path(asset: &Asset) -> String {
+    format!("/assets/{}", asset.hash)
+}
+
+#[derive(Deserialize)]
+struct ImageQuery {
+    key: Option<String>,
+}
+
+async fn image(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(hash): Path<String>,
+    Query(query): Query<ImageQuery>,
+) -> ApiResult<Response> {
+    let key = feeds::feed_key(&state.token, &format!("/assets/{hash}"));
+    if !authorized(&headers, &state.token) && query.key.as_deref() != Some(key.as_str()) {
+        return Err(ApiError::Unauthorized);
+    }
+
+    let db = LocalDb::new(state.pool.clone());
+    let asset = db
+        .get_asset_by_hash(&hash)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    let data = match tokio::fs::read(super::asset_store(&state.config).path(&asset)).await {
+        Ok(data) => data,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Err(ApiError::NotFound),
+        Err(e) => return Err(anyhow::Error::from(e).into()),
+    };
+    Ok((
+        [
+            (header::CONTENT_TYPE, asset.mime),
+            // the content of a hash never changes
+            (
+                header::CACHE_CONTROL,
+                "private, max-age=31536000, immutable".to_string(),
+            ),
+        ],
+        data,
+    )
+        .into_response())
+}
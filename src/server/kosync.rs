@@ -0,0 +1,131 @@
+//! KOReader progress sync. KOReader is pointed at `http://<host>:<port>/kosync`
+//! and logs in with any user name and the API token as password.
+
+use super::{ApiResult, AppState};
+use crate::actions;
+use axum::extract::{Path, Request, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, put};
+use axum::{Json, Router};
+use localdb::{ItemStatus, LocalDb, Progress, Source};
+use md5::{Digest, Md5};
+use serde::Deserialize;
+use serde_json::json;
+
+/// Progress from which a document counts as read
+const FINISHED: f64 = 0.995;
+
+pub fn routes(state: AppState) -> Router<AppState> {
+    let authenticated = Router::new()
+        .route("/kosync/users/auth", get(auth))
+        .route("/kosync/syncs/progress", put(update_progress))
+        .route("/kosync/syncs/progress/{document}", get(get_progress))
+        .route_layer(middleware::from_fn_with_state(state, authorize));
+    Router::new()
+        .merge(authenticated)
+        .route("/kosync/users/create", axum::routing::post(create_user))
+        .route("/kosync/healthcheck", get(healthcheck))
+}
+
+/// KOReader sends the md5 of the password it was given
+fn key(token: &str) -> String {
+    format!("{:x}", Md5::digest(token))
+}
+
+fn unauthorized() -> Response {
+    let body = json!({ "code": 2001, "message": "Unauthorized" });
+    (StatusCode::UNAUTHORIZED, Json(body)).into_response()
+}
+
+async fn authorize(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let headers: &HeaderMap = request.headers();
+    let user = headers.get("x-auth-user");
+    let key = headers
+        .get("x-auth-key")
+        .and_then(|value| value.to_str().ok());
+    if user.is_none() || key != Some(self::key(&state.token).as_str()) {
+        return unauthorized();
+    }
+    next.run(request).await
+}
+
+async fn healthcheck() -> Json<serde_json::Value> {
+    Json(json!({ "state": "OK" }))
+}
+
+#[derive(Deserialize)]
+struct NewUser {
+    username: String,
+    password: String,
+}
+
+/// There are no accounts, registering only succeeds with the token so that
+/// either button of KOReader works
+async fn create_user(State(state): State<AppState>, Json(user): Json<NewUser>) -> Response {
+    if user.password != key(&state.token) {
+        let body = json!({ "code": 2005, "message": "User registration is disabled." });
+        return (StatusCode::PAYMENT_REQUIRED, Json(body)).into_response();
+    }
+    let body = json!({ "username": user.username });
+    (StatusCode::CREATED, Json(body)).into_response()
+}
+
+async fn auth() -> Json<serde_json::Value> {
+    Json(json!({ "authorized": "OK" }))
+}
+
+#[derive(Deserialize)]
+struct ProgressUpdate {
+    document: String,
+    progress: String,
+    percentage: f64,
+    device: String,
+    device_id: String,
+}
+
+/// Stores the position and archives the item once the device reached its
+/// end
+async fn update_progress(
+    State(state): State<AppState>,
+    Json(update): Json<ProgressUpdate>,
+) -> ApiResult<Json<serde_json::Value>> {
+    let mut db = LocalDb::new(state.pool.clone()).with_source(Source::Server);
+    let timestamp = chrono::Utc::now().timestamp();
+    db.set_progress(&Progress {
+        document: update.document.clone(),
+        progress: update.progress,
+        percentage: update.percentage,
+        device: update.device,
+        device_id: update.device_id,
+        timestamp,
+    })
+    .await?;
+
+    if update.percentage >= FINISHED {
+        if let Some(id) = db.find_document(&update.document).await? {
+            if let Some(item) = db.get_item(id).await? {
+                if item.status == ItemStatus::Unread {
+                    actions::set_status(&mut db, &item, ItemStatus::Archived).await?;
+                }
+            }
+        }
+    }
+
+    Ok(Json(
+        json!({ "document": update.document, "timestamp": timestamp }),
+    ))
+}
+
+async fn get_progress(
+    State(state): State<AppState>,
+    Path(document): Path<String>,
+) -> ApiResult<Json<serde_json::Value>> {
+    let db = LocalDb::new(state.pool.clone());
+    let progress = match db.get_progress(&document).await? {
+        Some(progress) => serde_json::to_value(progress).expect("progress is serializable"),
+        None => json!({}),
+    };
+    Ok(Json(progress))
+}
@@ -0,0 +1,479 @@
+mod api;
+mod feed;
+mod images;
+mod kosync;
+mod opds;
+mod ui;
+
+use crate::config::Config;
+use crate::vault::Vault;
+use assets::AssetStore;
+use axum::extract::{Request, State};
+use axum::http::{header, HeaderMap, HeaderValue, Method, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Redirect, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use localdb::{DBError, KvConfig};
+use remote::RemoteError;
+use sqlx::SqlitePool;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tower_http::cors::{AllowOrigin, CorsLayer};
+
+pub const DEFAULT_PORT: u16 = 7373;
+const TOKEN_KEY: &str = "api_token";
+/// Cookie set by the login page of the web reader
+const TOKEN_COOKIE: &str = "readlater_token";
+const OPENAPI: &str = include_str!("openapi.json");
+
+#[derive(Clone)]
+pub struct AppState {
+    pub pool: SqlitePool,
+    pub config: Arc<Config>,
+    pub vault: Arc<Vault>,
+    pub token: String,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ApiError {
+    #[error("not found")]
+    NotFound,
+
+    #[error("missing or invalid token")]
+    Unauthorized,
+
+    #[error("{0}")]
+    BadRequest(String),
+
+    #[error(transparent)]
+    Db(#[from] DBError),
+
+    #[error(transparent)]
+    Remote(#[from] RemoteError),
+
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let status = match self {
+            ApiError::NotFound => StatusCode::NOT_FOUND,
+            ApiError::Unauthorized => StatusCode::UNAUTHORIZED,
+            ApiError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            ApiError::Db(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            ApiError::Remote(_) => StatusCode::BAD_GATEWAY,
+            ApiError::Other(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        let body = serde_json::json!({ "error": self.to_string() });
+        (status, Json(body)).into_response()
+    }
+}
+
+pub type ApiResult<T> = Result<T, ApiError>;
+
+/// Returns the token clients must send, generating and storing one on first
+/// use.
+pub async fn token(pool: &SqlitePool) -> localdb::Result<String> {
+    let mut kv = KvConfig::new(pool.clone());
+    if let Some(token) = kv.get::<String>(TOKEN_KEY).await {
+        return Ok(token);
+    }
+    let token = uuid::Uuid::new_v4().simple().to_string();
+    kv.set(TOKEN_KEY, &token).await?;
+    Ok(token)
+}
+
+/// Builds the routes of the API and of the web reader. Cross origin requests
+/// are only answered for `origins`, such as the origin of the extension.
+pub fn router(state: AppState, origins: &[String]) -> Router {
+    let origins: Vec<HeaderValue> = origins
+        .iter()
+        .filter_map(|origin| HeaderValue::from_str(origin).ok())
+        .collect();
+    let cors = CorsLayer::new()
+        .allow_origin(AllowOrigin::list(origins))
+        .allow_methods([Method::GET, Method::POST, Method::PATCH, Method::DELETE])
+        .allow_headers([header::AUTHORIZATION, header::CONTENT_TYPE]);
+
+    let pages = ui::routes().route_layer(middleware::from_fn_with_state(
+        state.clone(),
+        authorize_page,
+    ));
+    Router::new()
+        .merge(api::routes())
+        .route_layer(middleware::from_fn_with_state(state.clone(), authorize))
+        .merge(pages)
+        .merge(ui::public_routes())
+        .merge(feed::routes())
+        .merge(images::routes())
+        .merge(opds::routes(state.clone()))
+        .merge(kosync::routes(state.clone()))
+        .route("/openapi.json", get(openapi))
+        .layer(cors)
+        .with_state(state)
+}
+
+fn asset_store(config: &Config) -> AssetStore {
+    AssetStore::new(
+        &config.assets_dir,
+        config.assets_budget,
+        std::time::Duration::from_secs(config.fetch.timeout),
+    )
+}
+
+fn cookie<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value)
+}
+
+/// Whether the request carries the token, either as a bearer token or in
+/// the cookie of the web reader
+fn authorized(headers: &HeaderMap, token: &str) -> bool {
+    let bearer = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    bearer.or_else(|| cookie(headers, TOKEN_COOKIE)) == Some(token)
+}
+
+async fn authorize(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Result<Response, ApiError> {
+    if !authorized(request.headers(), &state.token) {
+        return Err(ApiError::Unauthorized);
+    }
+    Ok(next.run(request).await)
+}
+
+async fn authorize_page(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    if !authorized(request.headers(), &state.token) {
+        return Redirect::to("/login").into_response();
+    }
+    next.run(request).await
+}
+
+async fn openapi() -> impl IntoResponse {
+    ([(header::CONTENT_TYPE, "application/json")], OPENAPI)
+}
+
+pub async fn serve(state: AppState, addr: SocketAddr, origins: &[String]) -> std::io::Result<()> {
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    axum::serve(listener, router(state, origins)).await
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use axum::body::Body;
+    use http_body_util::BodyExt;
+    use tower::ServiceExt;
+
+    const TOKEN: &str = "secret";
+
+    async fn app() -> Router {
+        let pool = localdb::open_database(":memory:").await.unwrap();
+        let dir = std::path::Path::new(".");
+        let state = AppState {
+            pool,
+            config: Arc::new(Config::in_dir(dir)),
+            vault: Arc::new(crate::vault::test_vault(dir)),
+            token: TOKEN.to_string(),
+        };
+        router(state, &["moz-extension://readlater".to_string()])
+    }
+
+    async fn call(
+        app: &Router,
+        method: Method,
+        uri: &str,
+        body: Option<serde_json::Value>,
+    ) -> (StatusCode, serde_json::Value) {
+        let mut request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(header::AUTHORIZATION, format!("Bearer {TOKEN}"));
+        let body = match body {
+            Some(body) => {
+                request = request.header(header::CONTENT_TYPE, "application/json");
+                Body::from(body.to_string())
+            }
+            None => Body::empty(),
+        };
+        let response = app
+            .clone()
+            .oneshot(request.body(body).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let bytes = response.into_body().collect().await.unwrap().to_bytes();
+        let json = serde_json::from_slice(&bytes).unwrap_or(serde_json::Value::Null);
+        (status, json)
+    }
+
+    #[tokio::test]
+    async fn test_rejects_missing_token() {
+        let app = app().await;
+        let request = Request::get("/items").body(Body::empty()).unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let request = Request::get("/openapi.json").body(Body::empty()).unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_answers_preflight_of_allowed_origins() {
+        let app = app().await;
+        let request = Request::builder()
+            .method(Method::OPTIONS)
+            .uri("/items")
+            .header(header::ORIGIN, "moz-extension://readlater")
+            .header(header::ACCESS_CONTROL_REQUEST_METHOD, "POST")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(
+            response.headers()[header::ACCESS_CONTROL_ALLOW_ORIGIN],
+            "moz-extension://readlater"
+        );
+
+        let request = Request::builder()
+            .method(Method::OPTIONS)
+            .uri("/items")
+            .header(header::ORIGIN, "https://evil.example.com")
+            .header(header::ACCESS_CONTROL_REQUEST_METHOD, "POST")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert!(!response
+            .headers()
+            .contains_key(header::ACCESS_CONTROL_ALLOW_ORIGIN));
+    }
+
+    #[tokio::test]
+    async fn test_pages_need_login() {
+        let app = app().await;
+        let request = Request::get("/").body(Body::empty()).unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.headers()[header::LOCATION], "/login");
+
+        let request = Request::post("/login")
+            .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .body(Body::from(format!("token={TOKEN}")))
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        let cookie = response.headers()[header::SET_COOKIE].to_str().unwrap();
+        let cookie = cookie.split(';').next().unwrap().to_string();
+
+        let request = Request::get("/")
+            .header(header::COOKIE, &cookie)
+            .body(Body::empty())
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response
+            .headers()
+            .contains_key(header::CONTENT_SECURITY_POLICY));
+
+        // the cookie also opens the api, for the pages of the reader
+        let request = Request::get("/tags")
+            .header(header::COOKIE, &cookie)
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_assets_need_the_token() {
+        let app = app().await;
+        let request = Request::get("/assets/abc").body(Body::empty()).unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let key = crate::feeds::feed_key(TOKEN, "/assets/abc");
+        let request = Request::get(format!("/assets/abc?key={key}"))
+            .body(Body::empty())
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let (status, _) = call(&app, Method::GET, "/assets/abc", None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_opds_catalog() {
+        let app = app().await;
+        call(
+            &app,
+            Method::POST,
+            "/items",
+            Some(serde_json::json!({ "url": "https://example.com/a", "tags": ["rust lang"] })),
+        )
+        .await;
+
+        let request = Request::get("/opds/tags").body(Body::empty()).unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert!(response.headers().contains_key(header::WWW_AUTHENTICATE));
+
+        let (status, _) = call(&app, Method::GET, "/opds/tags", None).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let request = Request::get("/opds/tags/rust+lang")
+            .header(header::AUTHORIZATION, format!("Bearer {TOKEN}"))
+            .body(Body::empty())
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        assert!(body.contains(r#"href="/opds/items/1.epub""#));
+
+        let request = Request::get("/opds/items/1.epub")
+            .header(header::AUTHORIZATION, format!("Bearer {TOKEN}"))
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            "application/epub+zip"
+        );
+    }
+
+    async fn kosync(
+        app: &Router,
+        method: Method,
+        uri: &str,
+        body: Option<serde_json::Value>,
+    ) -> (StatusCode, serde_json::Value) {
+        let key = format!("{:x}", <md5::Md5 as md5::Digest>::digest(TOKEN));
+        let request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("x-auth-user", "kobo")
+            .header("x-auth-key", key)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(body.map(|b| Body::from(b.to_string())).unwrap_or_default())
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = response.into_body().collect().await.unwrap().to_bytes();
+        (status, serde_json::from_slice(&bytes).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_kosync_archives_finished_documents() {
+        let app = app().await;
+        call(
+            &app,
+            Method::POST,
+            "/items",
+            Some(serde_json::json!({ "url": "https://example.com/a" })),
+        )
+        .await;
+        // handing out the epub records the hash the device reports
+        let request = Request::get("/opds/items/1.epub")
+            .header(header::AUTHORIZATION, format!("Bearer {TOKEN}"))
+            .body(Body::empty())
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        let book = response.into_body().collect().await.unwrap().to_bytes();
+        let document = crate::export::partial_md5(&book);
+
+        let request = Request::get("/kosync/users/auth")
+            .header("x-auth-user", "kobo")
+            .header("x-auth-key", "wrong")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let (status, _) = kosync(&app, Method::GET, "/kosync/users/auth", None).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let progress = serde_json::json!({
+            "document": document,
+            "progress": "/body/DocFragment[2]",
+            "percentage": 1.0,
+            "device": "Kobo",
+            "device_id": "0A1B",
+        });
+        let (status, body) =
+            kosync(&app, Method::PUT, "/kosync/syncs/progress", Some(progress)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["document"], document.as_str());
+
+        let uri = format!("/kosync/syncs/progress/{document}");
+        let (_, body) = kosync(&app, Method::GET, &uri, None).await;
+        assert_eq!(body["progress"], "/body/DocFragment[2]");
+        let (_, body) = kosync(&app, Method::GET, "/kosync/syncs/progress/other", None).await;
+        assert_eq!(body, serde_json::json!({}));
+
+        let (_, item) = call(&app, Method::GET, "/items/1", None).await;
+        assert_eq!(item["status"], "Archived");
+        assert!(item["time_read"].is_number());
+    }
+
+    #[tokio::test]
+    async fn test_items_and_highlights() {
+        let app = app().await;
+        let (status, item) = call(
+            &app,
+            Method::POST,
+            "/items",
+            Some(serde_json::json!({ "url": "https://example.com/a", "tags": ["rust"] })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+        let id = item["id"].as_i64().unwrap();
+
+        let (_, items) = call(&app, Method::GET, "/items?tag=rust", None).await;
+        assert_eq!(items.as_array().unwrap().len(), 1);
+
+        let (status, item) = call(
+            &app,
+            Method::PATCH,
+            &format!("/items/{id}"),
+            Some(serde_json::json!({
+                "title": "Renamed",
+                "status": "Archived",
+                "remove_tags": ["rust"]
+            })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(item["title"], "Renamed");
+        assert_eq!(item["status"], "Archived");
+        assert!(item["tags"].as_array().unwrap().is_empty());
+
+        let (status, highlight) = call(
+            &app,
+            Method::POST,
+            &format!("/items/{id}/highlights"),
+            Some(serde_json::json!({ "text": "worth quoting" })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+        let (_, highlights) = call(&app, Method::GET, "/highlights", None).await;
+        assert_eq!(highlights[0]["id"], highlight["id"]);
+
+        let (status, _) = call(&app, Method::DELETE, &format!("/items/{id}"), None).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let (_, items) = call(&app, Method::GET, "/items?status=Deleted", None).await;
+        assert_eq!(items[0]["id"], id);
+
+        let (status, _) = call(&app, Method::GET, "/items/999", None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,266 @@
+//! OPDS catalog for e-reader apps, articles are downloaded as EPUB built on
+//! the fly
+
+use super::{authorized, ApiError, ApiResult, AppState};
+use crate::export;
+use axum::extract::{Path, Query, Request, State};
+use axum::http::{header, HeaderMap, StatusCode, Uri};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::Router;
+use base64::Engine;
+use chrono::DateTime;
+use feed::opds::{
+    Catalog, CatalogEntry, Link, ACQUISITION_REL, ACQUISITION_TYPE, EPUB_TYPE, NAVIGATION_TYPE,
+};
+use localdb::{Item, ItemQuery, ItemStatus, LocalDb};
+use serde::Deserialize;
+
+const PAGE_SIZE: usize = 25;
+
+pub fn routes(state: AppState) -> Router<AppState> {
+    Router::new()
+        .route("/opds", get(root))
+        .route("/opds/tags", get(tags))
+        .route("/opds/tags/{tag}", get(tag))
+        .route("/opds/items/{file}", get(epub))
+        .route("/opds/{shelf}", get(shelf))
+        .route_layer(middleware::from_fn_with_state(state, authorize))
+}
+
+/// E-readers only know basic auth, the token is accepted as the password
+fn basic_auth(headers: &HeaderMap, token: &str) -> bool {
+    let Some(encoded) = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Basic "))
+    else {
+        return false;
+    };
+    let Ok(decoded) = base64::engine::general_purpose::STANDARD.decode(encoded) else {
+        return false;
+    };
+    String::from_utf8_lossy(&decoded)
+        .split_once(':')
+        .is_some_and(|(_, password)| password == token)
+}
+
+async fn authorize(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let headers = request.headers();
+    if authorized(headers, &state.token) || basic_auth(headers, &state.token) {
+        return next.run(request).await;
+    }
+    (
+        StatusCode::UNAUTHORIZED,
+        [(header::WWW_AUTHENTICATE, r#"Basic realm="readlater""#)],
+    )
+        .into_response()
+}
+
+fn xml(catalog: Catalog, media_type: &str) -> Response {
+    let content_type = format!("{media_type};charset=utf-8");
+    ([(header::CONTENT_TYPE, content_type)], catalog.to_xml()).into_response()
+}
+
+fn encode(tag: &str) -> String {
+    url::form_urlencoded::byte_serialize(tag.as_bytes()).collect()
+}
+
+async fn root() -> Response {
+    let mut catalog = Catalog::new("urn:readlater:opds", "readlater")
+        .link(Link::new("self", "/opds", NAVIGATION_TYPE))
+        .link(Link::new("start", "/opds", NAVIGATION_TYPE));
+    for (shelf, title) in [
+        ("unread", "Unread"),
+        ("favorites", "Favorites"),
+        ("recent", "Recently added"),
+        ("archived", "Archived"),
+    ] {
+        catalog.add_entry(CatalogEntry::navigation(
+            &format!("urn:readlater:opds:{shelf}"),
+            title,
+            &format!("/opds/{shelf}"),
+            ACQUISITION_TYPE,
+        ));
+    }
+    catalog.add_entry(CatalogEntry::navigation(
+        "urn:readlater:opds:tags",
+        "By tag",
+        "/opds/tags",
+        NAVIGATION_TYPE,
+    ));
+    xml(catalog, NAVIGATION_TYPE)
+}
+
+async fn tags(State(state): State<AppState>) -> ApiResult<Response> {
+    let mut db = LocalDb::new(state.pool.clone());
+    let mut tags = db.get_tags().await?;
+    tags.sort_by(|a, b| a.tag.cmp(&b.tag));
+
+    let mut catalog = Catalog::new("urn:readlater:opds:tags", "By tag")
+        .link(Link::new("self", "/opds/tags", NAVIGATION_TYPE))
+        .link(Link::new("start", "/opds", NAVIGATION_TYPE))
+        .link(Link::new("up", "/opds", NAVIGATION_TYPE));
+    for tag in tags {
+        catalog.add_entry(CatalogEntry::navigation(
+            &format!("urn:readlater:opds:tag:{}", tag.tag),
+            tag.name.as_deref().unwrap_or(&tag.tag),
+            &format!("/opds/tags/{}", encode(&tag.tag)),
+            ACQUISITION_TYPE,
+        ));
+    }
+    Ok(xml(catalog, NAVIGATION_TYPE))
+}
+
+#[derive(Deserialize)]
+struct PageQuery {
+    page: Option<usize>,
+}
+
+async fn shelf(
+    State(state): State<AppState>,
+    Path(shelf): Path<String>,
+    Query(query): Query<PageQuery>,
+) -> ApiResult<Response> {
+    let (title, filter) = match shelf.as_str() {
+        "unread" => ("Unread", ItemQuery::default().status(ItemStatus::Unread)),
+        "archived" => (
+            "Archived",
+            ItemQuery::default().status(ItemStatus::Archived),
+        ),
+        "favorites" => ("Favorites", ItemQuery::default().favorite(true)),
+        "recent" => ("Recently added", ItemQuery::default()),
+        _ => return Err(ApiError::NotFound),
+    };
+    let path = format!("/opds/{shelf}");
+    acquisition(&state, &path, title, filter, query.page.unwrap_or(1)).await
+}
+
+async fn tag(
+    State(state): State<AppState>,
+    uri: Uri,
+    Query(query): Query<PageQuery>,
+) -> ApiResult<Response> {
+    // the raw segment is decoded here as tags are encoded with `+` for spaces
+    let segment = uri.path().trim_start_matches("/opds/tags/");
+    let (tag, _) = url::form_urlencoded::parse(segment.as_bytes())
+        .next()
+        .ok_or(ApiError::NotFound)?;
+    let path = format!("/opds/tags/{}", encode(&tag));
+    let filter = ItemQuery::default().tag(&tag);
+    acquisition(&state, &path, &tag, filter, query.page.unwrap_or(1)).await
+}
+
+fn entry(item: &Item) -> CatalogEntry {
+    let updated = item
+        .time_updated
+        .unwrap_or(item.time_added)
+        .max(item.time_added);
+    let updated = DateTime::from_timestamp(updated as i64, 0).unwrap_or_default();
+    let mut entry = CatalogEntry::new(
+        &format!("urn:readlater:item:{}", item.id),
+        &item.title,
+        updated,
+    );
+    entry.content = item.excerpt.clone();
+    entry.language = item.lang.clone();
+    entry.authors = item.authors.iter().map(|a| a.name.clone()).collect();
+    entry.categories = item.tags.iter().map(|tag| tag.tag.clone()).collect();
+    entry.categories.sort();
+    entry.links.push(Link::new(
+        ACQUISITION_REL,
+        &format!("/opds/items/{}.epub", item.id),
+        EPUB_TYPE,
+    ));
+    entry.links.push(
+        Link::new("alternate", &format!("/read/{}", item.id), "text/html").title("Read online"),
+    );
+    if let Some(image) = &item.top_image_url {
+        entry.links.push(Link::new(
+            "http://opds-spec.org/image/thumbnail",
+            image,
+            "image/*",
+        ));
+    }
+    entry
+}
+
+async fn acquisition(
+    state: &AppState,
+    path: &str,
+    title: &str,
+    filter: ItemQuery,
+    page: usize,
+) -> ApiResult<Response> {
+    let db = LocalDb::new(state.pool.clone());
+    let items: Vec<Item> = db
+        .query_items(&filter)
+        .await?
+        .into_iter()
+        .filter(|item| item.status != ItemStatus::Deleted)
+        .collect();
+    let pages = items.len().div_ceil(PAGE_SIZE).max(1);
+    let page = page.clamp(1, pages);
+
+    let mut catalog = Catalog::new(&format!("urn:readlater:opds:{path}"), title)
+        .link(Link::new(
+            "self",
+            &format!("{path}?page={page}"),
+            ACQUISITION_TYPE,
+        ))
+        .link(Link::new("start", "/opds", NAVIGATION_TYPE))
+        .link(Link::new("up", "/opds", NAVIGATION_TYPE));
+    if page > 1 {
+        let previous = format!("{path}?page={}", page - 1);
+        catalog = catalog.link(Link::new("previous", &previous, ACQUISITION_TYPE));
+    }
+    if page < pages {
+        let next = format!("{path}?page={}", page + 1);
+        catalog = catalog.link(Link::new("next", &next, ACQUISITION_TYPE));
+    }
+    for item in items.iter().skip((page - 1) * PAGE_SIZE).take(PAGE_SIZE) {
+        catalog.add_entry(entry(item));
+    }
+    Ok(xml(catalog, ACQUISITION_TYPE))
+}
+
+async fn epub(State(state): State<AppState>, Path(file): Path<String>) -> ApiResult<Response> {
+    let id: i64 = file
+        .strip_suffix(".epub")
+        .and_then(|id| id.parse().ok())
+        .ok_or(ApiError::NotFound)?;
+    let mut db = LocalDb::new(state.pool.clone());
+    let item = db.get_item(id).await?.ok_or(ApiError::NotFound)?;
+    let assets = super::asset_store(&state.config);
+    let book = export::article_epub(&mut db, &assets, &item).await?;
+    let disposition = format!(
+        "attachment; filename=\"{}\"",
+        export::file_name(&item).replace('"', "")
+    );
+    Ok((
+        [
+            (header::CONTENT_TYPE, EPUB_TYPE.to_string()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        book,
+    )
+        .into_response())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_basic_auth() {
+        let mut headers = HeaderMap::new();
+        let credentials = base64::engine::general_purpose::STANDARD.encode("kobo:secret");
+        headers.insert(
+            header::AUTHORIZATION,
+            format!("Basic {credentials}").parse().unwrap(),
+        );
+        assert!(basic_auth(&headers, "secret"));
+        assert!(!basic_auth(&headers, "other"));
+    }
+}
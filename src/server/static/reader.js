@@ -0,0 +1,24 @@
+// Shows the highlight form while text of the article is selected
+document.addEventListener("DOMContentLoaded", () => {
+  const form = document.getElementById("highlight");
+  const body = document.querySelector("#content .body");
+  if (!form || !body) {
+    return;
+  }
+  const text = form.querySelector("input[name=text]");
+
+  document.addEventListener("selectionchange", () => {
+    const selection = document.getSelection();
+    const inside =
+      selection &&
+      !selection.isCollapsed &&
+      body.contains(selection.anchorNode) &&
+      body.contains(selection.focusNode);
+    if (inside) {
+      text.value = selection.toString().trim();
+      form.hidden = false;
+    } else if (!form.contains(document.activeElement)) {
+      form.hidden = true;
+    }
+  });
+});
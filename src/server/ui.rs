@@ -0,0 +1,842 @@
+//! Server rendered pages for reading the library from a browser
+
+use super::{ApiError, ApiResult, AppState, TOKEN_COOKIE};
+use crate::actions;
+use crate::feeds::{self, Images};
+use axum::extract::{Path, Query, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{Html, IntoResponse, Redirect, Response};
+use axum::routing::{get, post};
+use axum::{Form, Router};
+use epub::xhtml::escape;
+use localdb::{Highlight, Item, ItemQuery, ItemStatus, LocalDb, SortBy, Source};
+use serde::Deserialize;
+use std::fmt::Write;
+use std::ops::Range;
+
+const STYLE: &str = include_str!("static/style.css");
+const SCRIPT: &str = include_str!("static/reader.js");
+const PREFS_COOKIE: &str = "readlater_reader";
+const PAGE_SIZE: usize = 100;
+/// Most items the library shows at once
+const MAX_LIMIT: usize = 5000;
+/// Stored content may come from any page, so none of its scripts may run
+const CSP: &str = "default-src 'self'; img-src * data:; style-src 'self' 'unsafe-inline'";
+
+/// Pages that need the token
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/", get(library))
+        .route("/read/{id}", get(reader))
+        .route("/read/{id}/status", post(set_status))
+        .route("/read/{id}/favorite", post(set_favorite))
+        .route("/read/{id}/tags", post(set_tags))
+        .route("/read/{id}/highlights", post(add_highlight))
+        .route(
+            "/read/{id}/highlights/{highlight}/delete",
+            post(delete_highlight),
+        )
+        .route("/settings", post(save_settings))
+}
+
+/// Pages reachable without the token
+pub fn public_routes() -> Router<AppState> {
+    Router::new()
+        .route("/login", get(login_page).post(login))
+        .route("/static/style.css", get(style))
+        .route("/static/reader.js", get(script))
+}
+
+async fn style() -> impl IntoResponse {
+    ([(header::CONTENT_TYPE, "text/css")], STYLE)
+}
+
+async fn script() -> impl IntoResponse {
+    ([(header::CONTENT_TYPE, "text/javascript")], SCRIPT)
+}
+
+fn db(state: &AppState) -> LocalDb {
+    LocalDb::new(state.pool.clone()).with_source(Source::Server)
+}
+
+async fn find_item(db: &LocalDb, id: i64) -> ApiResult<Item> {
+    db.get_item(id).await?.ok_or(ApiError::NotFound)
+}
+
+fn page(title: &str, prefs: &ReaderPrefs, body: &str) -> Response {
+    let html = format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>{title}</title>
+<link rel="stylesheet" href="/static/style.css">
+<script src="/static/reader.js" defer></script>
+</head>
+<body class="theme-{theme} font-{font}" style="--font-size: {size}px; --measure: {width}em">
+{body}
+</body>
+</html>"#,
+        title = escape(title),
+        theme = prefs.theme.as_str(),
+        font = prefs.font.as_str(),
+        size = prefs.font_size,
+        width = prefs.width,
+    );
+    ([(header::CONTENT_SECURITY_POLICY, CSP)], Html(html)).into_response()
+}
+
+/// Only paths of this server are followed after a form is sent
+fn back(to: Option<&str>) -> Redirect {
+    match to {
+        Some(to) if is_local_path(to) => Redirect::to(to),
+        _ => Redirect::to("/"),
+    }
+}
+
+/// Whether `to` is a path on this server. Browsers read `\` as `/` and
+/// drop control characters, so `/\host` or `/\t/host` lead elsewhere.
+fn is_local_path(to: &str) -> bool {
+    let origin = url::Url::parse("http://localhost/").expect("valid url");
+    to.starts_with('/')
+        && !to.starts_with("//")
+        && !to.contains('\\')
+        && !to.chars().any(char::is_control)
+        && origin
+            .join(to)
+            .is_ok_and(|url| url.origin() == origin.origin())
+}
+
+fn domain(url: &str) -> String {
+    url::Url::parse(url)
+        .ok()
+        .and_then(|url| url.host_str().map(String::from))
+        .unwrap_or_default()
+}
+
+fn date(time: i32) -> String {
+    chrono::DateTime::from_timestamp(time as i64, 0)
+        .map(|date| date.format("%Y-%m-%d").to_string())
+        .unwrap_or_default()
+}
+
+fn status_name(status: ItemStatus) -> &'static str {
+    match status {
+        ItemStatus::Unread => "Unread",
+        ItemStatus::Archived => "Archived",
+        ItemStatus::Deleted => "Deleted",
+    }
+}
+
+fn tags(item: &Item) -> Vec<&str> {
+    let mut tags: Vec<&str> = item.tags.iter().map(|tag| tag.tag.as_str()).collect();
+    tags.sort();
+    tags
+}
+
+/// Archive and favorite toggles of an item
+fn item_buttons(item: &Item, back: &str) -> String {
+    let (status, label) = match item.status {
+        ItemStatus::Unread => ("Archived", "Archive"),
+        _ => ("Unread", "Move to unread"),
+    };
+    let (favorite, star) = match item.favorite {
+        true => (false, "Unfavorite"),
+        false => (true, "Favorite"),
+    };
+    format!(
+        r#"<form method="post" action="/read/{id}/status"><input type="hidden" name="status" value="{status}"><input type="hidden" name="back" value="{back}"><button>{label}</button></form>
+<form method="post" action="/read/{id}/favorite"><input type="hidden" name="favorite" value="{favorite}"><input type="hidden" name="back" value="{back}"><button>{star}</button></form>"#,
+        id = item.id,
+        back = escape(back),
+    )
+}
+
+#[derive(Deserialize, Default)]
+struct LibraryQuery {
+    status: Option<String>,
+    tag: Option<String>,
+    search: Option<String>,
+    #[serde(default)]
+    favorite: bool,
+    #[serde(default)]
+    sort: SortBy,
+    limit: Option<usize>,
+}
+
+impl LibraryQuery {
+    fn status(&self) -> Option<ItemStatus> {
+        match self.status.as_deref() {
+            Some("Archived") => Some(ItemStatus::Archived),
+            Some("Deleted") => Some(ItemStatus::Deleted),
+            Some("all") => None,
+            _ => Some(ItemStatus::Unread),
+        }
+    }
+}
+
+async fn library(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(query): Query<LibraryQuery>,
+    uri: axum::http::Uri,
+) -> ApiResult<Response> {
+    let mut db = db(&state);
+    let limit = query.limit.unwrap_or(PAGE_SIZE).clamp(1, MAX_LIMIT);
+    let filter = ItemQuery {
+        status: query.status(),
+        tag: query.tag.clone().filter(|tag| !tag.is_empty()),
+        favorite: query.favorite.then_some(true),
+        search: query.search.clone().filter(|search| !search.is_empty()),
+        sort: query.sort,
+        limit: Some(limit + 1),
+    };
+    let mut items = db.query_items(&filter).await?;
+    let more = items.len() > limit;
+    items.truncate(limit);
+    let mut all_tags = db.get_tags().await?;
+    all_tags.sort_by(|a, b| a.tag.cmp(&b.tag));
+
+    let back = uri
+        .path_and_query()
+        .map(|path| path.as_str())
+        .unwrap_or("/");
+    let selected = |value: bool| if value { " selected" } else { "" };
+    let status = query.status();
+
+    let mut body = String::from(r#"<header><h1><a href="/">readlater</a></h1></header>"#);
+    write!(
+        body,
+        r#"<form class="filters" method="get" action="/">
+<input type="search" name="search" placeholder="Search" value="{search}">
+<select name="status">
+<option value="Unread"{unread}>Unread</option>
+<option value="Archived"{archived}>Archived</option>
+<option value="Deleted"{deleted}>Deleted</option>
+<option value="all"{all}>All</option>
+</select>
+<select name="tag"><option value="">Any tag</option>"#,
+        search = escape(query.search.as_deref().unwrap_or_default()),
+        unread = selected(status == Some(ItemStatus::Unread)),
+        archived = selected(status == Some(ItemStatus::Archived)),
+        deleted = selected(status == Some(ItemStatus::Deleted)),
+        all = selected(status.is_none()),
+    )
+    .unwrap();
+    for tag in &all_tags {
+        write!(
+            body,
+            r#"<option{}>{}</option>"#,
+            selected(query.tag.as_deref() == Some(tag.tag.as_str())),
+            escape(&tag.tag)
+        )
+        .unwrap();
+    }
+    write!(
+        body,
+        r#"</select>
+<select name="sort"><option value="newest">Newest</option><option value="oldest"{oldest}>Oldest</option></select>
+<label><input type="checkbox" name="favorite" value="true"{favorite}> Favorites</label>
+<button>Filter</button>
+</form>
+<ul class="items">"#,
+        oldest = selected(query.sort == SortBy::Oldest),
+        favorite = if query.favorite { " checked" } else { "" },
+    )
+    .unwrap();
+
+    for item in &items {
+        let tags: String = tags(item)
+            .into_iter()
+            .map(|tag| format!(r#"<span class="tag">{}</span>"#, escape(tag)))
+            .collect();
+        write!(
+            body,
+            r#"<li class="item"><a class="title" href="/read/{id}">{title}</a>
+<div class="meta"><span>{domain}</span> <span>{added}</span>{star} {tags}</div>
+<div class="actions">{buttons}</div></li>"#,
+            id = item.id,
+            title = escape(&item.title),
+            domain = escape(&domain(&item.url)),
+            added = date(item.time_added),
+            star = if item.favorite { " ★" } else { "" },
+            buttons = item_buttons(item, back),
+        )
+        .unwrap();
+    }
+    body.push_str("</ul>");
+    if items.is_empty() {
+        body.push_str(r#"<p class="empty">Nothing here.</p>"#);
+    }
+    if more && limit < MAX_LIMIT {
+        let mut next = url::form_urlencoded::Serializer::new(String::new());
+        for (key, value) in [
+            ("status", query.status.as_deref()),
+            ("tag", query.tag.as_deref()),
+            ("search", query.search.as_deref()),
+        ] {
+            if let Some(value) = value {
+                next.append_pair(key, value);
+            }
+        }
+        if query.favorite {
+            next.append_pair("favorite", "true");
+        }
+        if query.sort == SortBy::Oldest {
+            next.append_pair("sort", "oldest");
+        }
+        next.append_pair("limit", &(limit + PAGE_SIZE).to_string());
+        write!(
+            body,
+            r#"<p><a class="more" href="/?{}">Show more</a></p>"#,
+            escape(&next.finish())
+        )
+        .unwrap();
+    }
+
+    Ok(page(
+        "readlater",
+        &ReaderPrefs::from_headers(&headers),
+        &body,
+    ))
+}
+
+/// Wraps the first occurrence of each highlight in the content. The text is
+/// looked for in the text of the page, ignoring its tags and how whitespace
+/// is laid out, so a highlight spanning several elements gets a mark in each.
+fn mark_highlights(mut html: String, highlights: &[Highlight]) -> String {
+    for highlight in highlights {
+        let (text, ranges) = text_ranges(&html);
+        let needle: Vec<char> = collapse(&highlight.text).chars().collect();
+        let Some(start) = (!needle.is_empty())
+            .then(|| {
+                text.windows(needle.len())
+                    .position(|window| window == needle)
+            })
+            .flatten()
+        else {
+            continue;
+        };
+
+        // the matched text in runs that are next to each other in the markup
+        let mut runs: Vec<Range<usize>> = vec![];
+        for range in &ranges[start..start + needle.len()] {
+            match runs.last_mut() {
+                Some(run) if run.end == range.start => run.end = range.end,
+                _ => runs.push(range.clone()),
+            }
+        }
+        for (i, run) in runs.iter().enumerate().rev() {
+            let open = match i {
+                0 => format!(r#"<mark id="h{}">"#, highlight.id),
+                _ => "<mark>".to_string(),
+            };
+            html.insert_str(run.end, "</mark>");
+            html.insert_str(run.start, &open);
+        }
+    }
+    html
+}
+
+/// Text of some XHTML with whitespace collapsed, and for each of its chars
+/// the bytes of the markup it comes from
+fn text_ranges(html: &str) -> (Vec<char>, Vec<Range<usize>>) {
+    const ENTITIES: &[(&str, char)] = &[
+        ("&amp;", '&'),
+        ("&lt;", '<'),
+        ("&gt;", '>'),
+        ("&quot;", '"'),
+        ("&#39;", '\''),
+    ];
+    let (mut text, mut ranges): (Vec<char>, Vec<Range<usize>>) = (vec![], vec![]);
+    let mut position = 0;
+    while let Some(c) = html[position..].chars().next() {
+        if c == '<' {
+            position = html[position..]
+                .find('>')
+                .map_or(html.len(), |end| position + end + 1);
+            continue;
+        }
+        let (c, len) = match ENTITIES
+            .iter()
+            .find(|(entity, _)| html[position..].starts_with(entity))
+        {
+            Some((entity, c)) => (*c, entity.len()),
+            None => (c, c.len_utf8()),
+        };
+        let range = position..position + len;
+        position += len;
+        if c.is_whitespace() {
+            match (text.last(), ranges.last_mut()) {
+                (Some(' '), Some(last)) if last.end == range.start => {
+                    last.end = range.end;
+                    continue;
+                }
+                (Some(' '), _) => continue,
+                _ => {}
+            }
+            text.push(' ');
+        } else {
+            text.push(c);
+        }
+        ranges.push(range);
+    }
+    (text, ranges)
+}
+
+fn collapse(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+async fn reader(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<i64>,
+) -> ApiResult<Response> {
+    let db = db(&state);
+    let item = find_item(&db, id).await?;
+    let highlights = db.get_highlights(Some(id)).await?;
+    let prefs = ReaderPrefs::from_headers(&headers);
+    let back = format!("/read/{id}");
+
+    let content = match db.get_content(id).await? {
+        Some(content) => {
+            let store = super::asset_store(&state.config);
+            let images = Images {
+                store: &store,
+                link: &super::images::path,
+            };
+            let links = feeds::image_links(&db, &item, &content.html, Some(&images)).await?;
+            let html = epub::xhtml::from_html(&content.html, |src| links.get(src).cloned());
+            mark_highlights(html, &highlights)
+        }
+        None => format!(
+            r#"<p class="excerpt">{}</p><p class="empty">No content was extracted for this page.</p>"#,
+            escape(item.excerpt.as_deref().unwrap_or_default())
+        ),
+    };
+
+    let authors: Vec<String> = item
+        .authors
+        .iter()
+        .map(|author| escape(&author.name))
+        .collect();
+    let mut body = format!(
+        r#"<header><a href="/">← Library</a>
+<details class="settings"><summary>Aa</summary>{settings}</details></header>
+<article id="content">
+<h1>{title}</h1>
+<p class="meta"><a href="{url}">{domain}</a> {authors} <span>{status}</span></p>
+<div class="actions">{buttons}</div>
+<form class="tags" method="post" action="/read/{id}/tags">
+<input name="tags" value="{tags}" placeholder="tags, separated, by commas">
+<button>Save tags</button>
+</form>
+<div class="body">{content}</div>
+</article>
+<form id="highlight" class="highlight" method="post" action="/read/{id}/highlights" hidden>
+<input type="hidden" name="text">
+<input name="note" placeholder="Note">
+<button>Highlight</button>
+</form>"#,
+        settings = prefs.form(&back),
+        title = escape(&item.title),
+        url = escape(&item.url),
+        domain = escape(&domain(&item.url)),
+        authors = authors.join(", "),
+        status = status_name(item.status),
+        buttons = item_buttons(&item, &back),
+        tags = escape(&tags(&item).join(", ")),
+    );
+
+    if !highlights.is_empty() {
+        body.push_str(r#"<section class="highlights"><h2>Highlights</h2><ul>"#);
+        for highlight in &highlights {
+            write!(
+                body,
+                r##"<li><a href="#h{hid}">{text}</a>{note}
+<form method="post" action="/read/{id}/highlights/{hid}/delete"><button>Remove</button></form></li>"##,
+                hid = highlight.id,
+                text = escape(&highlight.text),
+                note = highlight
+                    .note
+                    .as_deref()
+                    .map(|note| format!(r#"<p class="note">{}</p>"#, escape(note)))
+                    .unwrap_or_default(),
+            )
+            .unwrap();
+        }
+        body.push_str("</ul></section>");
+    }
+
+    Ok(page(&item.title, &prefs, &body))
+}
+
+#[derive(Deserialize)]
+struct StatusForm {
+    status: ItemStatus,
+    back: Option<String>,
+}
+
+async fn set_status(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    Form(form): Form<StatusForm>,
+) -> ApiResult<Redirect> {
+    let mut db = db(&state);
+    let item = find_item(&db, id).await?;
+    actions::set_status(&mut db, &item, form.status).await?;
+    Ok(back(form.back.as_deref()))
+}
+
+#[derive(Deserialize)]
+struct FavoriteForm {
+    favorite: bool,
+    back: Option<String>,
+}
+
+async fn set_favorite(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    Form(form): Form<FavoriteForm>,
+) -> ApiResult<Redirect> {
+    let mut db = db(&state);
+    let item = find_item(&db, id).await?;
+    actions::set_favorite(&mut db, &item, form.favorite).await?;
+    Ok(back(form.back.as_deref()))
+}
+
+#[derive(Deserialize)]
+struct TagsForm {
+    tags: String,
+}
+
+/// Replaces the tags of an item with the ones in the form
+async fn set_tags(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    Form(form): Form<TagsForm>,
+) -> ApiResult<Redirect> {
+    let mut db = db(&state);
+    let item = find_item(&db, id).await?;
+    let wanted: Vec<String> = form
+        .tags
+        .split(',')
+        .map(|tag| tag.trim().to_lowercase())
+        .filter(|tag| !tag.is_empty())
+        .collect();
+    let current = tags(&item);
+    let added = wanted
+        .iter()
+        .filter(|tag| !current.contains(&tag.as_str()))
+        .cloned()
+        .collect();
+    let removed = current
+        .iter()
+        .filter(|tag| !wanted.iter().any(|wanted| wanted == *tag))
+        .map(|tag| tag.to_string())
+        .collect();
+    actions::add_tags(&mut db, &item, added).await?;
+    actions::remove_tags(&mut db, &item, removed).await?;
+    Ok(Redirect::to(&format!("/read/{id}")))
+}
+
+#[derive(Deserialize)]
+struct HighlightForm {
+    text: String,
+    note: Option<String>,
+}
+
+async fn add_highlight(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    Form(form): Form<HighlightForm>,
+) -> ApiResult<Redirect> {
+    let mut db = db(&state);
+    find_item(&db, id).await?;
+    let text = form.text.trim();
+    if text.is_empty() {
+        return Err(ApiError::BadRequest("nothing selected".to_string()));
+    }
+    let highlight = Highlight {
+        item_id: id,
+        text: text.to_string(),
+        note: form.note.filter(|note| !note.trim().is_empty()),
+        ..Default::default()
+    };
+    let highlight = db.add_highlight(&highlight).await?;
+    Ok(Redirect::to(&format!("/read/{id}#h{highlight}")))
+}
+
+async fn delete_highlight(
+    State(state): State<AppState>,
+    Path((id, highlight)): Path<(i64, i64)>,
+) -> ApiResult<Redirect> {
+    let mut db = db(&state);
+    let found = db.get_highlight(highlight).await?;
+    if found.is_none_or(|found| found.item_id != id) {
+        return Err(ApiError::NotFound);
+    }
+    db.delete_highlight(highlight).await?;
+    Ok(Redirect::to(&format!("/read/{id}")))
+}
+
+#[derive(Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum Font {
+    #[default]
+    Serif,
+    Sans,
+    Mono,
+}
+
+impl Font {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Font::Serif => "serif",
+            Font::Sans => "sans",
+            Font::Mono => "mono",
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum Theme {
+    #[default]
+    Light,
+    Sepia,
+    Dark,
+}
+
+impl Theme {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Theme::Light => "light",
+            Theme::Sepia => "sepia",
+            Theme::Dark => "dark",
+        }
+    }
+}
+
+/// Typography of the reader, kept in a cookie so each device has its own
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct ReaderPrefs {
+    font_size: u8,
+    font: Font,
+    width: u8,
+    theme: Theme,
+}
+
+impl Default for ReaderPrefs {
+    fn default() -> Self {
+        ReaderPrefs {
+            font_size: 18,
+            font: Font::default(),
+            width: 36,
+            theme: Theme::default(),
+        }
+    }
+}
+
+impl ReaderPrefs {
+    fn clamp(self) -> Self {
+        ReaderPrefs {
+            font_size: self.font_size.clamp(12, 32),
+            width: self.width.clamp(20, 60),
+            ..self
+        }
+    }
+
+    fn from_headers(headers: &HeaderMap) -> Self {
+        super::cookie(headers, PREFS_COOKIE)
+            .and_then(|value| {
+                let mut prefs = ReaderPrefs::default();
+                for (key, value) in url::form_urlencoded::parse(value.as_bytes()) {
+                    match key.as_ref() {
+                        "font_size" => prefs.font_size = value.parse().ok()?,
+                        "width" => prefs.width = value.parse().ok()?,
+                        "font" => {
+                            prefs.font = serde_json::from_value(value.into()).ok()?;
+                        }
+                        "theme" => {
+                            prefs.theme = serde_json::from_value(value.into()).ok()?;
+                        }
+                        _ => {}
+                    }
+                }
+                Some(prefs.clamp())
+            })
+            .unwrap_or_default()
+    }
+
+    fn cookie(&self) -> String {
+        let value = url::form_urlencoded::Serializer::new(String::new())
+            .append_pair("font_size", &self.font_size.to_string())
+            .append_pair("width", &self.width.to_string())
+            .append_pair("font", self.font.as_str())
+            .append_pair("theme", self.theme.as_str())
+            .finish();
+        format!("{PREFS_COOKIE}={value}; Path=/; Max-Age=31536000; SameSite=Strict")
+    }
+
+    fn form(&self, back: &str) -> String {
+        let option = |value: &str, label: &str, current: &str| {
+            let selected = if value == current { " selected" } else { "" };
+            format!(r#"<option value="{value}"{selected}>{label}</option>"#)
+        };
+        format!(
+            r#"<form method="post" action="/settings">
+<input type="hidden" name="back" value="{back}">
+<label>Size <input type="number" name="font_size" min="12" max="32" value="{size}"></label>
+<label>Width <input type="number" name="width" min="20" max="60" value="{width}"></label>
+<select name="font">{serif}{sans}{mono}</select>
+<select name="theme">{light}{sepia}{dark}</select>
+<button>Apply</button>
+</form>"#,
+            back = escape(back),
+            size = self.font_size,
+            width = self.width,
+            serif = option("serif", "Serif", self.font.as_str()),
+            sans = option("sans", "Sans serif", self.font.as_str()),
+            mono = option("mono", "Monospace", self.font.as_str()),
+            light = option("light", "Light", self.theme.as_str()),
+            sepia = option("sepia", "Sepia", self.theme.as_str()),
+            dark = option("dark", "Dark", self.theme.as_str()),
+        )
+    }
+}
+
+#[derive(Deserialize)]
+struct SettingsForm {
+    font_size: u8,
+    font: Font,
+    width: u8,
+    theme: Theme,
+    back: Option<String>,
+}
+
+async fn save_settings(Form(form): Form<SettingsForm>) -> impl IntoResponse {
+    let prefs = ReaderPrefs {
+        font_size: form.font_size,
+        font: form.font,
+        width: form.width,
+        theme: form.theme,
+    };
+    let cookie = prefs.clamp().cookie();
+    ([(header::SET_COOKIE, cookie)], back(form.back.as_deref()))
+}
+
+fn login_form(error: &str) -> Response {
+    let body = format!(
+        r#"<form class="login" method="post" action="/login">
+<h1>readlater</h1>
+{error}
+<input type="password" name="token" placeholder="API token" autofocus>
+<button>Log in</button>
+</form>"#
+    );
+    page("Log in", &ReaderPrefs::default(), &body)
+}
+
+async fn login_page() -> Response {
+    login_form("")
+}
+
+#[derive(Deserialize)]
+struct LoginForm {
+    token: String,
+}
+
+async fn login(State(state): State<AppState>, Form(form): Form<LoginForm>) -> Response {
+    if form.token.trim() != state.token {
+        let form = login_form(r#"<p class="error">Wrong token</p>"#);
+        return (StatusCode::UNAUTHORIZED, form).into_response();
+    }
+    let cookie = format!(
+        "{TOKEN_COOKIE}={}; Path=/; Max-Age=31536000; HttpOnly; SameSite=Strict",
+        state.token
+    );
+    ([(header::SET_COOKIE, cookie)], Redirect::to("/")).into_response()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_marks_first_occurrence() {
+        let highlight = Highlight {
+            id: 7,
+            text: "fish & chips".to_string(),
+            ..Default::default()
+        };
+        let html = "<p>fish &amp; chips, then fish &amp; chips</p>".to_string();
+        assert_eq!(
+            mark_highlights(html, &[highlight]),
+            r#"<p><mark id="h7">fish &amp; chips</mark>, then fish &amp; chips</p>"#
+        );
+    }
+
+    #[test]
+    fn test_marks_text_only() {
+        let highlights = [
+            Highlight {
+                id: 1,
+                text: "p".to_string(),
+                ..Default::default()
+            },
+            Highlight {
+                id: 2,
+                text: "class".to_string(),
+                ..Default::default()
+            },
+            Highlight {
+                id: 3,
+                text: "the  quick\nfox".to_string(),
+                ..Default::default()
+            },
+        ];
+        let html = r#"<p class="x">a class of <em>the quick</em>  fox, up</p>"#.to_string();
+        assert_eq!(
+            mark_highlights(html, &highlights),
+            concat!(
+                r#"<p class="x">a <mark id="h2">class</mark> of "#,
+                r#"<em><mark id="h3">the quick</mark></em><mark>  fox</mark>, u"#,
+                r#"<mark id="h1">p</mark></p>"#
+            )
+        );
+    }
+
+    #[test]
+    fn test_prefs_round_trip_through_cookie() {
+        let prefs = ReaderPrefs {
+            font_size: 22,
+            font: Font::Sans,
+            width: 40,
+            theme: Theme::Dark,
+        };
+        let cookie = prefs.cookie();
+        let value = cookie.split(';').next().unwrap();
+        let mut headers = HeaderMap::new();
+        headers.insert(header::COOKIE, value.parse().unwrap());
+        assert!(ReaderPrefs::from_headers(&headers) == prefs);
+    }
+
+    #[test]
+    fn test_only_follows_local_paths() {
+        let location = |to| {
+            back(Some(to)).into_response().headers()[header::LOCATION]
+                .to_str()
+                .unwrap()
+                .to_string()
+        };
+        assert_eq!(location("/read/1"), "/read/1");
+        assert_eq!(location("//evil.example.com"), "/");
+        assert_eq!(location("https://evil.example.com"), "/");
+        assert_eq!(location("/\\evil.example.com"), "/");
+        assert_eq!(location("/\t/evil.example.com"), "/");
+        assert_eq!(location("/read/1?back=%2F"), "/read/1?back=%2F");
+    }
+}
@@ -0,0 +1,581 @@
+use super::text;
+use crate::actions;
+use localdb::{Item, ItemQuery, ItemStatus, LocalDb, Progress};
+use ratatui::crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+/// Device name reading progress of the terminal reader is stored under
+pub const DEVICE: &str = "readlater-tui";
+
+/// Key of the reading progress of an item read in the terminal, kept apart
+/// from the hashes KOReader reports
+pub fn progress_document(item: i64) -> String {
+    format!("readlater:{}", item)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Focus {
+    Items,
+    Tags,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Mode {
+    Normal,
+    /// Typing in the search bar
+    Search,
+    /// Typing tags to add, or to remove when prefixed with `-`
+    Tagging(String),
+    ConfirmDelete,
+    Reading,
+}
+
+/// Items shown in the list, by status
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shelf {
+    Unread,
+    Archived,
+    Favorites,
+    All,
+}
+
+impl Shelf {
+    pub fn title(&self) -> &'static str {
+        match self {
+            Shelf::Unread => "Unread",
+            Shelf::Archived => "Archived",
+            Shelf::Favorites => "Favorites",
+            Shelf::All => "All",
+        }
+    }
+
+    fn next(&self) -> Shelf {
+        match self {
+            Shelf::Unread => Shelf::Archived,
+            Shelf::Archived => Shelf::Favorites,
+            Shelf::Favorites => Shelf::All,
+            Shelf::All => Shelf::Unread,
+        }
+    }
+}
+
+pub struct Reader {
+    pub item: Item,
+    pub paragraphs: Vec<String>,
+    /// Paragraphs wrapped to the width of the pane
+    pub lines: Vec<String>,
+    pub width: usize,
+    pub height: usize,
+    pub scroll: usize,
+    /// Position to restore once the pane size is known
+    restore: Option<f64>,
+}
+
+impl Reader {
+    /// Wraps the text to the size of the pane, keeping the relative position
+    pub fn layout(&mut self, width: usize, height: usize) {
+        if width == self.width && height == self.height && !self.lines.is_empty() {
+            return;
+        }
+        let position = self.restore.take().unwrap_or_else(|| self.percentage());
+        self.width = width;
+        self.height = height;
+        self.lines = vec![];
+        for paragraph in &self.paragraphs {
+            let wrapped = textwrap::wrap(paragraph, width.max(10));
+            self.lines.extend(wrapped.into_iter().map(String::from));
+            self.lines.push(String::new());
+        }
+        self.lines.pop();
+        self.scroll = ((self.max_scroll() as f64) * position).round() as usize;
+    }
+
+    fn max_scroll(&self) -> usize {
+        self.lines.len().saturating_sub(self.height)
+    }
+
+    fn scroll_by(&mut self, lines: isize) {
+        self.scroll = self
+            .scroll
+            .saturating_add_signed(lines)
+            .min(self.max_scroll());
+    }
+
+    /// Share of the article scrolled through, 1 once the end is on screen
+    pub fn percentage(&self) -> f64 {
+        match self.max_scroll() {
+            0 if self.lines.is_empty() => 0.0,
+            0 => 1.0,
+            max => self.scroll as f64 / max as f64,
+        }
+    }
+}
+
+pub struct App {
+    pub items: Vec<Item>,
+    /// Indices into `items` matching the filters
+    pub visible: Vec<usize>,
+    pub selected: usize,
+    pub tags: Vec<String>,
+    /// Selected entry of the tag sidebar, 0 shows every tag
+    pub tag: usize,
+    pub shelf: Shelf,
+    pub search: String,
+    pub focus: Focus,
+    pub mode: Mode,
+    pub reader: Option<Reader>,
+    /// Last message, such as the result of an action
+    pub message: Option<String>,
+    pub quit: bool,
+}
+
+impl App {
+    pub async fn new(db: &mut LocalDb) -> anyhow::Result<App> {
+        let mut app = App {
+            items: vec![],
+            visible: vec![],
+            selected: 0,
+            tags: vec![],
+            tag: 0,
+            shelf: Shelf::Unread,
+            search: String::new(),
+            focus: Focus::Items,
+            mode: Mode::Normal,
+            reader: None,
+            message: None,
+            quit: false,
+        };
+        app.reload(db).await?;
+        Ok(app)
+    }
+
+    async fn reload(&mut self, db: &mut LocalDb) -> anyhow::Result<()> {
+        let mut items = db.get_items().await?;
+        items.retain(|item| item.status != ItemStatus::Deleted);
+        items.sort_by(|a, b| b.time_added.cmp(&a.time_added).then(b.id.cmp(&a.id)));
+        self.items = items;
+
+        let mut tags: Vec<String> = db.get_tags().await?.into_iter().map(|t| t.tag).collect();
+        tags.sort();
+        let selected = self.selected_tag().map(String::from);
+        self.tags = tags;
+        self.tag = selected
+            .and_then(|tag| self.tags.iter().position(|t| *t == tag))
+            .map_or(0, |i| i + 1);
+        self.refilter();
+        Ok(())
+    }
+
+    pub fn selected_tag(&self) -> Option<&str> {
+        self.tag
+            .checked_sub(1)
+            .and_then(|i| self.tags.get(i))
+            .map(String::as_str)
+    }
+
+    fn query(&self) -> ItemQuery {
+        let mut query = match self.shelf {
+            Shelf::Unread => ItemQuery::default().status(ItemStatus::Unread),
+            Shelf::Archived => ItemQuery::default().status(ItemStatus::Archived),
+            Shelf::Favorites => ItemQuery::default().favorite(true),
+            Shelf::All => ItemQuery::default(),
+        };
+        if let Some(tag) = self.selected_tag() {
+            query = query.tag(tag);
+        }
+        if !self.search.is_empty() {
+            query = query.search(&self.search);
+        }
+        query
+    }
+
+    fn refilter(&mut self) {
+        let current = self.current().map(|item| item.id);
+        let query = self.query();
+        self.visible = (0..self.items.len())
+            .filter(|i| query.matches(&self.items[*i]))
+            .collect();
+        self.selected = current
+            .and_then(|id| self.visible.iter().position(|i| self.items[*i].id == id))
+            .unwrap_or(self.selected)
+            .min(self.visible.len().saturating_sub(1));
+    }
+
+    pub fn current(&self) -> Option<&Item> {
+        self.visible.get(self.selected).map(|i| &self.items[*i])
+    }
+
+    fn current_item(&self) -> Option<Item> {
+        match &self.reader {
+            Some(reader) => Some(reader.item.clone()),
+            _ => self.current().cloned(),
+        }
+    }
+
+    /// Replaces an item after it changed in the database
+    async fn refresh(&mut self, db: &mut LocalDb, id: i64) -> anyhow::Result<()> {
+        let item = db.get_item(id).await?;
+        if let (Some(reader), Some(item)) = (&mut self.reader, &item) {
+            if reader.item.id == id {
+                reader.item = item.clone();
+            }
+        }
+        match item.filter(|item| item.status != ItemStatus::Deleted) {
+            Some(item) => {
+                if let Some(existing) = self.items.iter_mut().find(|i| i.id == id) {
+                    *existing = item;
+                }
+            }
+            None => self.items.retain(|item| item.id != id),
+        }
+        self.refilter();
+        Ok(())
+    }
+
+    pub async fn handle_key(&mut self, db: &mut LocalDb, key: KeyEvent) -> anyhow::Result<()> {
+        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+            self.close_reader(db).await?;
+            self.quit = true;
+            return Ok(());
+        }
+        match self.mode.clone() {
+            Mode::Search => self.search_key(key),
+            Mode::Tagging(input) => self.tagging_key(db, key, input).await?,
+            Mode::ConfirmDelete => {
+                self.mode = Mode::Normal;
+                if key.code == KeyCode::Char('y') {
+                    if let Some(item) = self.current().cloned() {
+                        actions::set_status(db, &item, ItemStatus::Deleted).await?;
+                        self.message = Some(format!("Deleted {}", item.title));
+                        self.refresh(db, item.id).await?;
+                    }
+                }
+            }
+            Mode::Reading => self.reader_key(db, key).await?,
+            Mode::Normal => match self.focus {
+                Focus::Items => self.items_key(db, key).await?,
+                Focus::Tags => self.tags_key(key),
+            },
+        }
+        Ok(())
+    }
+
+    fn search_key(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Enter => self.mode = Mode::Normal,
+            KeyCode::Esc => {
+                self.search.clear();
+                self.mode = Mode::Normal;
+            }
+            KeyCode::Backspace => {
+                self.search.pop();
+            }
+            KeyCode::Char(c) => self.search.push(c),
+            _ => return,
+        }
+        self.selected = 0;
+        self.refilter();
+    }
+
+    async fn tagging_key(
+        &mut self,
+        db: &mut LocalDb,
+        key: KeyEvent,
+        mut input: String,
+    ) -> anyhow::Result<()> {
+        match key.code {
+            KeyCode::Esc => self.mode = self.previous_mode(),
+            KeyCode::Backspace => {
+                input.pop();
+                self.mode = Mode::Tagging(input);
+            }
+            KeyCode::Char(c) => {
+                input.push(c);
+                self.mode = Mode::Tagging(input);
+            }
+            KeyCode::Enter => {
+                self.mode = self.previous_mode();
+                let Some(item) = self.current_item() else {
+                    return Ok(());
+                };
+                let (mut added, mut removed) = (vec![], vec![]);
+                for tag in input.split(',').map(str::trim).filter(|t| !t.is_empty()) {
+                    match tag.strip_prefix('-') {
+                        Some(tag) => removed.push(tag.to_string()),
+                        None => added.push(tag.to_string()),
+                    }
+                }
+                actions::add_tags(db, &item, added).await?;
+                actions::remove_tags(db, &item, removed).await?;
+                self.reload(db).await?;
+                self.refresh(db, item.id).await?;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Mode to go back to after typing tags
+    fn previous_mode(&self) -> Mode {
+        match self.reader {
+            Some(_) => Mode::Reading,
+            None => Mode::Normal,
+        }
+    }
+
+    fn tags_key(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Char('j') | KeyCode::Down => {
+                self.tag = (self.tag + 1).min(self.tags.len());
+            }
+            KeyCode::Char('k') | KeyCode::Up => self.tag = self.tag.saturating_sub(1),
+            KeyCode::Tab | KeyCode::Enter | KeyCode::Esc | KeyCode::Char('h') => {
+                self.focus = Focus::Items;
+            }
+            KeyCode::Char('q') => self.quit = true,
+            _ => return,
+        }
+        self.selected = 0;
+        self.refilter();
+    }
+
+    async fn items_key(&mut self, db: &mut LocalDb, key: KeyEvent) -> anyhow::Result<()> {
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => self.quit = true,
+            KeyCode::Char('j') | KeyCode::Down => {
+                self.selected = (self.selected + 1).min(self.visible.len().saturating_sub(1));
+            }
+            KeyCode::Char('k') | KeyCode::Up => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Char('g') | KeyCode::Home => self.selected = 0,
+            KeyCode::Char('G') | KeyCode::End => {
+                self.selected = self.visible.len().saturating_sub(1);
+            }
+            KeyCode::Char('/') => self.mode = Mode::Search,
+            KeyCode::Tab | KeyCode::Char('l') => self.focus = Focus::Tags,
+            KeyCode::Char('s') => {
+                self.shelf = self.shelf.next();
+                self.selected = 0;
+                self.refilter();
+            }
+            KeyCode::Char('o') => {
+                if let Some(item) = self.current() {
+                    if let Err(e) = open::that(&item.url) {
+                        self.message = Some(format!("error opening {}: {}", item.url, e));
+                    }
+                }
+            }
+            KeyCode::Char('d') if self.current().is_some() => self.mode = Mode::ConfirmDelete,
+            KeyCode::Enter => self.open_reader(db).await?,
+            _ => self.item_action(db, key).await?,
+        }
+        Ok(())
+    }
+
+    /// Actions on the selected or the open item
+    async fn item_action(&mut self, db: &mut LocalDb, key: KeyEvent) -> anyhow::Result<()> {
+        let Some(item) = self.current_item() else {
+            return Ok(());
+        };
+        match key.code {
+            KeyCode::Char('a') => {
+                let status = match item.status {
+                    ItemStatus::Unread => ItemStatus::Archived,
+                    _ => ItemStatus::Unread,
+                };
+                actions::set_status(db, &item, status).await?;
+                self.message = Some(format!("{:?}: {}", status, item.title));
+            }
+            KeyCode::Char('f') => {
+                let favorite = !item.favorite;
+                actions::set_favorite(db, &item, favorite).await?;
+            }
+            KeyCode::Char('t') => {
+                self.mode = Mode::Tagging(String::new());
+                return Ok(());
+            }
+            _ => return Ok(()),
+        }
+        self.refresh(db, item.id).await
+    }
+
+    async fn open_reader(&mut self, db: &mut LocalDb) -> anyhow::Result<()> {
+        let Some(item) = self.current().cloned() else {
+            return Ok(());
+        };
+        let paragraphs = match db.get_content(item.id).await? {
+            Some(content) => text::paragraphs(&content.html),
+            None => vec![
+                item.excerpt.clone().unwrap_or_default(),
+                "No content was extracted for this page.".to_string(),
+            ],
+        };
+        let restore = db
+            .get_progress(&progress_document(item.id))
+            .await?
+            .map(|progress| progress.percentage);
+        self.reader = Some(Reader {
+            item,
+            paragraphs,
+            lines: vec![],
+            width: 0,
+            height: 0,
+            scroll: 0,
+            restore,
+        });
+        self.mode = Mode::Reading;
+        Ok(())
+    }
+
+    /// Leaves the reader, storing how far the item was read
+    async fn close_reader(&mut self, db: &mut LocalDb) -> anyhow::Result<()> {
+        let Some(reader) = self.reader.take() else {
+            return Ok(());
+        };
+        self.mode = Mode::Normal;
+        if reader.lines.is_empty() {
+            return Ok(());
+        }
+        db.set_progress(&Progress {
+            document: progress_document(reader.item.id),
+            progress: reader.scroll.to_string(),
+            percentage: reader.percentage(),
+            device: DEVICE.to_string(),
+            device_id: DEVICE.to_string(),
+            timestamp: chrono::Utc::now().timestamp(),
+        })
+        .await?;
+        Ok(())
+    }
+
+    async fn reader_key(&mut self, db: &mut LocalDb, key: KeyEvent) -> anyhow::Result<()> {
+        let Some(reader) = &mut self.reader else {
+            self.mode = Mode::Normal;
+            return Ok(());
+        };
+        let page = reader.height.saturating_sub(2).max(1) as isize;
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc | KeyCode::Backspace => self.close_reader(db).await?,
+            KeyCode::Char('j') | KeyCode::Down => reader.scroll_by(1),
+            KeyCode::Char('k') | KeyCode::Up => reader.scroll_by(-1),
+            KeyCode::Char(' ') | KeyCode::PageDown => reader.scroll_by(page),
+            KeyCode::Char('b') | KeyCode::PageUp => reader.scroll_by(-page),
+            KeyCode::Char('g') | KeyCode::Home => reader.scroll = 0,
+            KeyCode::Char('G') | KeyCode::End => reader.scroll = reader.max_scroll(),
+            KeyCode::Char('o') => {
+                if let Err(e) = open::that(&reader.item.url) {
+                    self.message = Some(format!("error opening {}: {}", reader.item.url, e));
+                }
+            }
+            _ => self.item_action(db, key).await?,
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use localdb::{Content, Tag};
+    use std::collections::HashSet;
+
+    fn key(c: char) -> KeyEvent {
+        KeyEvent::from(KeyCode::Char(c))
+    }
+
+    async fn db() -> LocalDb {
+        let pool = localdb::open_database(":memory:").await.unwrap();
+        let mut db = LocalDb::new(pool);
+        for (i, title) in ["Rust futures", "Gardening"].iter().enumerate() {
+            let id = db
+                .add(&Item {
+                    title: title.to_string(),
+                    url: format!("https://example.com/{}", i),
+                    time_added: i as i32,
+                    tags: HashSet::from([Tag {
+                        id: 0,
+                        tag: if i == 0 { "rust" } else { "garden" }.to_string(),
+                        name: None,
+                    }]),
+                    ..Default::default()
+                })
+                .await
+                .unwrap();
+            let html = "<p>word </p>".repeat(200);
+            db.set_content(&Content {
+                item_id: id as i64,
+                html,
+                time_fetched: None,
+            })
+            .await
+            .unwrap();
+        }
+        db
+    }
+
+    #[tokio::test]
+    async fn test_filters_and_actions() {
+        let mut db = db().await;
+        let mut app = App::new(&mut db).await.unwrap();
+        // newest first
+        assert_eq!(app.current().unwrap().title, "Gardening");
+
+        for c in "/rust".chars() {
+            app.handle_key(&mut db, key(c)).await.unwrap();
+        }
+        app.handle_key(&mut db, KeyEvent::from(KeyCode::Enter))
+            .await
+            .unwrap();
+        assert_eq!(app.visible.len(), 1);
+        assert_eq!(app.current().unwrap().title, "Rust futures");
+
+        app.handle_key(&mut db, key('a')).await.unwrap();
+        assert!(app.visible.is_empty());
+        app.handle_key(&mut db, key('s')).await.unwrap();
+        assert_eq!(app.shelf, Shelf::Archived);
+        assert_eq!(app.current().unwrap().status, ItemStatus::Archived);
+
+        for c in "tlater,-rust".chars() {
+            app.handle_key(&mut db, key(c)).await.unwrap();
+        }
+        app.handle_key(&mut db, KeyEvent::from(KeyCode::Enter))
+            .await
+            .unwrap();
+        let tags: Vec<&str> = app
+            .current()
+            .unwrap()
+            .tags
+            .iter()
+            .map(|t| t.tag.as_str())
+            .collect();
+        assert_eq!(tags, vec!["later"]);
+        assert!(app.tags.contains(&"later".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_reader_saves_progress() {
+        let mut db = db().await;
+        let mut app = App::new(&mut db).await.unwrap();
+        let id = app.current().unwrap().id;
+        app.handle_key(&mut db, KeyEvent::from(KeyCode::Enter))
+            .await
+            .unwrap();
+        app.reader.as_mut().unwrap().layout(40, 20);
+        app.handle_key(&mut db, key('G')).await.unwrap();
+        app.handle_key(&mut db, key('q')).await.unwrap();
+        assert!(app.reader.is_none());
+
+        let progress = db
+            .get_progress(&progress_document(id))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(progress.percentage, 1.0);
+
+        // reopening restores the position
+        app.handle_key(&mut db, KeyEvent::from(KeyCode::Enter))
+            .await
+            .unwrap();
+        let reader = app.reader.as_mut().unwrap();
+        reader.layout(40, 20);
+        assert_eq!(reader.scroll, reader.max_scroll());
+    }
+}
@@ -0,0 +1,43 @@
+//! Terminal interface to browse the library and read the extracted articles
+
+mod app;
+mod text;
+mod view;
+
+pub use app::{progress_document, App};
+pub use text::paragraphs;
+
+use localdb::LocalDb;
+use ratatui::crossterm::event::{self, Event, KeyEventKind};
+use std::time::Duration;
+
+pub async fn run(db: &mut LocalDb) -> anyhow::Result<()> {
+    let mut app = App::new(db).await?;
+    let mut terminal = ratatui::init();
+    let result = async {
+        while !app.quit {
+            let area = terminal.get_frame().area();
+            if let Some(reader) = &mut app.reader {
+                let (width, height) = view::reader_size(area);
+                reader.layout(width, height);
+            }
+            terminal.draw(|frame| view::draw(frame, &app))?;
+
+            if !event::poll(Duration::from_millis(250))? {
+                continue;
+            }
+            if let Event::Key(key) = event::read()? {
+                if key.kind == KeyEventKind::Press {
+                    app.message = None;
+                    if let Err(e) = app.handle_key(db, key).await {
+                        app.message = Some(e.to_string());
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+    .await;
+    ratatui::restore();
+    result
+}
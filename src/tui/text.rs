@@ -0,0 +1,147 @@
+use scraper::{ElementRef, Html, Node};
+
+/// Elements that start a new paragraph
+const BLOCKS: &[&str] = &[
+    "address",
+    "article",
+    "aside",
+    "blockquote",
+    "dd",
+    "div",
+    "dl",
+    "dt",
+    "figcaption",
+    "figure",
+    "footer",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "header",
+    "hr",
+    "li",
+    "main",
+    "ol",
+    "p",
+    "pre",
+    "section",
+    "table",
+    "tr",
+    "ul",
+];
+
+const SKIPPED: &[&str] = &["script", "style", "noscript", "template", "head"];
+
+#[derive(Default)]
+struct Writer {
+    paragraphs: Vec<String>,
+    current: String,
+}
+
+impl Writer {
+    fn push_text(&mut self, text: &str, preformatted: bool) {
+        if preformatted {
+            let mut lines = text.split('\n');
+            if let Some(first) = lines.next() {
+                self.current.push_str(first);
+            }
+            for line in lines {
+                self.finish();
+                self.current.push_str(line);
+            }
+            return;
+        }
+        for word in text.split_whitespace() {
+            if !self.current.is_empty() && !self.current.ends_with(' ') {
+                self.current.push(' ');
+            }
+            self.current.push_str(word);
+        }
+        if text.ends_with(char::is_whitespace) && !self.current.is_empty() {
+            self.current.push(' ');
+        }
+    }
+
+    fn finish(&mut self) {
+        let paragraph = self.current.trim_end().to_string();
+        if !paragraph.is_empty() {
+            self.paragraphs.push(paragraph);
+        }
+        self.current.clear();
+    }
+
+    fn walk(&mut self, element: ElementRef, preformatted: bool) {
+        let name = element.value().name();
+        if SKIPPED.contains(&name) {
+            return;
+        }
+        let block = BLOCKS.contains(&name);
+        let preformatted = preformatted || name == "pre";
+        if block {
+            self.finish();
+        }
+        match name {
+            "li" => self.current.push_str("• "),
+            "blockquote" => self.current.push_str("> "),
+            "br" => self.finish(),
+            "hr" => self.current.push_str("───"),
+            "img" => {
+                let alt = element.value().attr("alt").unwrap_or_default().trim();
+                if !alt.is_empty() {
+                    self.push_text(&format!("[image: {}]", alt), false);
+                }
+            }
+            _ => {}
+        }
+        for child in element.children() {
+            match child.value() {
+                Node::Text(text) => self.push_text(text, preformatted),
+                Node::Element(_) => {
+                    if let Some(child) = ElementRef::wrap(child) {
+                        self.walk(child, preformatted);
+                    }
+                }
+                _ => {}
+            }
+        }
+        if block {
+            self.finish();
+        }
+    }
+}
+
+/// Plain text paragraphs of an article, for showing it in the terminal
+pub fn paragraphs(html: &str) -> Vec<String> {
+    let fragment = Html::parse_fragment(html);
+    let mut writer = Writer::default();
+    writer.walk(fragment.root_element(), false);
+    writer.finish();
+    writer.paragraphs
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_paragraphs() {
+        let html = r#"<h1>Title</h1><p>Some <b>bold</b>
+            text.<script>x()</script></p><ul><li>one</li><li>two</li></ul>
+            <img alt="A chart"><pre>a
+  b</pre>"#;
+        assert_eq!(
+            paragraphs(html),
+            vec![
+                "Title",
+                "Some bold text.",
+                "• one",
+                "• two",
+                "[image: A chart]",
+                "a",
+                "  b"
+            ]
+        );
+    }
+}
@@ -0,0 +1,136 @@
+use super::app::{App, Focus, Mode, Reader};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Modifier, Style, Stylize};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, List, ListItem, ListState, Paragraph};
+use ratatui::Frame;
+
+const HELP: &str = "enter read  o open  a archive  f favorite  t tag  d delete  / search  s shelf  tab tags  q quit";
+const READER_HELP: &str = "space/b page  g/G top/end  a archive  f favorite  t tag  o open  q back";
+
+/// Size of the text area of the reader for a terminal of `area`
+pub fn reader_size(area: Rect) -> (usize, usize) {
+    let width = area.width.saturating_sub(2).min(100);
+    // status bar, borders, url and the blank line after it
+    let height = area.height.saturating_sub(5);
+    (width as usize, height as usize)
+}
+
+pub fn draw(frame: &mut Frame, app: &App) {
+    let [main, status] =
+        Layout::vertical([Constraint::Min(1), Constraint::Length(1)]).areas(frame.area());
+    match &app.reader {
+        Some(reader) => draw_reader(frame, main, reader),
+        None => draw_library(frame, main, app),
+    }
+    draw_status(frame, status, app);
+}
+
+fn draw_library(frame: &mut Frame, area: Rect, app: &App) {
+    let [search, body] = Layout::vertical([Constraint::Length(3), Constraint::Min(1)]).areas(area);
+    let [tags, items] =
+        Layout::horizontal([Constraint::Length(20), Constraint::Min(1)]).areas(body);
+
+    let searching = app.mode == Mode::Search;
+    let cursor = if searching { "▏" } else { "" };
+    let search_block = Block::default()
+        .borders(Borders::ALL)
+        .title(" Search ")
+        .border_style(focused(searching));
+    frame.render_widget(
+        Paragraph::new(format!("{}{}", app.search, cursor)).block(search_block),
+        search,
+    );
+
+    let mut entries = vec![ListItem::new("All tags")];
+    entries.extend(app.tags.iter().map(|tag| ListItem::new(tag.as_str())));
+    let tag_list = List::new(entries)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(" Tags ")
+                .border_style(focused(app.focus == Focus::Tags)),
+        )
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    let mut state = ListState::default().with_selected(Some(app.tag));
+    frame.render_stateful_widget(tag_list, tags, &mut state);
+
+    let rows: Vec<ListItem> = app
+        .visible
+        .iter()
+        .map(|i| {
+            let item = &app.items[*i];
+            let star = if item.favorite { "★ " } else { "  " };
+            let mut tags: Vec<&str> = item.tags.iter().map(|t| t.tag.as_str()).collect();
+            tags.sort();
+            let host = url::Url::parse(&item.url)
+                .ok()
+                .and_then(|url| url.host_str().map(String::from))
+                .unwrap_or_default();
+            ListItem::new(Line::from(vec![
+                Span::raw(star),
+                Span::raw(item.title.clone()).bold(),
+                Span::raw(format!("  {}", host)).dim(),
+                Span::raw(format!("  {}", tags.join(", "))).italic(),
+            ]))
+        })
+        .collect();
+    let title = format!(" {} ({}) ", app.shelf.title(), app.visible.len());
+    let item_list = List::new(rows)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(title)
+                .border_style(focused(app.focus == Focus::Items && !searching)),
+        )
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    let selected = (!app.visible.is_empty()).then_some(app.selected);
+    let mut state = ListState::default().with_selected(selected);
+    frame.render_stateful_widget(item_list, items, &mut state);
+}
+
+fn draw_reader(frame: &mut Frame, area: Rect, reader: &Reader) {
+    let width = (reader.width as u16 + 2).min(area.width);
+    let area = Rect {
+        x: area.x + (area.width - width) / 2,
+        width,
+        ..area
+    };
+    let title = format!(" {} ", reader.item.title);
+    let percent = format!(" {:.0}% ", reader.percentage() * 100.0);
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(title)
+        .title_bottom(Line::from(percent).right_aligned());
+    let lines: Vec<Line> = std::iter::once(Line::from(reader.item.url.clone()).dim())
+        .chain(std::iter::once(Line::default()))
+        .chain(
+            reader
+                .lines
+                .iter()
+                .skip(reader.scroll)
+                .take(reader.height)
+                .map(|line| Line::from(line.as_str())),
+        )
+        .collect();
+    frame.render_widget(Paragraph::new(lines).block(block), area);
+}
+
+fn draw_status(frame: &mut Frame, area: Rect, app: &App) {
+    let text = match &app.mode {
+        Mode::Search => "type to filter, enter to keep, esc to clear".to_string(),
+        Mode::Tagging(input) => format!("tags (a, b, -removed): {}▏", input),
+        Mode::ConfirmDelete => "delete this item? y/n".to_string(),
+        Mode::Reading => app.message.clone().unwrap_or(READER_HELP.to_string()),
+        Mode::Normal => app.message.clone().unwrap_or(HELP.to_string()),
+    };
+    frame.render_widget(Paragraph::new(text).reversed(), area);
+}
+
+fn focused(focused: bool) -> Style {
+    if focused {
+        Style::default().bold()
+    } else {
+        Style::default().dim()
+    }
+}
@@ -0,0 +1,264 @@
+//! Secrets of the remotes, such as access tokens, kept in the configured
+//! credential store instead of the settings column of the database
+
+use crate::config::{Config, CredentialBackend};
+use anyhow::Context;
+use credentials::{CredentialStore, EncryptedFile, Keyring};
+use localdb::{LocalDb, Remote};
+use remote::RemoteResult;
+use serde_json::{Map, Value};
+use std::io::IsTerminal;
+use std::sync::OnceLock;
+
+/// Settings of the backends that grant access to an account
+pub const SECRET_FIELDS: &[&str] = &[
+    "access_token",
+    "api_key",
+    "client_secret",
+    "refresh_token",
+    "token",
+];
+pub const PASSPHRASE_ENV: &str = "READLATER_PASSPHRASE";
+/// Name the secrets are filed under in the Secret Service
+const APPLICATION: &str = "readlater";
+
+/// Passphrase asked on the terminal, kept so it is asked once per run
+static PASSPHRASE: OnceLock<String> = OnceLock::new();
+
+pub struct Vault {
+    store: Box<dyn CredentialStore>,
+    /// Keeps the remotes of each profile apart in a shared store
+    namespace: String,
+}
+
+impl Vault {
+    pub fn new(store: Box<dyn CredentialStore>, namespace: &str) -> Vault {
+        Vault {
+            store,
+            namespace: namespace.to_string(),
+        }
+    }
+
+    /// Opens the configured store, then moves in the secrets still found in
+    /// the database
+    pub async fn open(config: &Config, db: &mut LocalDb) -> anyhow::Result<Vault> {
+        let vault = Vault::connect(config).await?;
+        let moved = vault.migrate(db).await?;
+        if moved > 0 {
+            tracing::info!(
+                "moved the secrets of {} remotes to the credential store",
+                moved
+            );
+        }
+        Ok(vault)
+    }
+
+    /// Opens the configured store. The passphrase of the file store comes
+    /// from `READLATER_PASSPHRASE`, or is asked on the terminal. The native
+    /// host and the daemon have no terminal, so they need the variable.
+    pub async fn connect(config: &Config) -> anyhow::Result<Vault> {
+        let store: Box<dyn CredentialStore> = match config.credentials.store {
+            CredentialBackend::SecretService => Box::new(
+                Keyring::connect(APPLICATION)
+                    .await
+                    .context("set `store = \"file\"` under [credentials] to use a file instead")?,
+            ),
+            CredentialBackend::File => {
+                let passphrase = match (std::env::var(PASSPHRASE_ENV), PASSPHRASE.get()) {
+                    (Ok(passphrase), _) => passphrase,
+                    (_, Some(passphrase)) => passphrase.clone(),
+                    _ if std::io::stdin().is_terminal() => {
+                        let passphrase = rpassword::prompt_password(format!(
+                            "Passphrase of {}: ",
+                            config.credentials.file.display()
+                        ))?;
+                        PASSPHRASE.get_or_init(|| passphrase).clone()
+                    }
+                    _ => anyhow::bail!(
+                        "{} is not set and there is no terminal to ask the passphrase of {} on. \
+                        Set it where the browser or the daemon is started, \
+                        or use `store = \"secret-service\"` under [credentials]",
+                        PASSPHRASE_ENV,
+                        config.credentials.file.display()
+                    ),
+                };
+                Box::new(EncryptedFile::open(&config.credentials.file, &passphrase)?)
+            }
+        };
+        Ok(Vault::new(
+            store,
+            config.profile.as_deref().unwrap_or("default"),
+        ))
+    }
+
+    /// Short name of the store, for display
+    pub fn backend(&self) -> &'static str {
+        self.store.backend()
+    }
+
+    fn key(&self, remote: &str) -> String {
+        format!("{}/{}", self.namespace, remote)
+    }
+
+    /// Secrets stored for the remote named `remote`
+    pub async fn secrets(&self, remote: &str) -> RemoteResult<Map<String, Value>> {
+        match self.store.get(&self.key(remote)).await? {
+            Some(json) => Ok(serde_json::from_str(&json)?),
+            None => Ok(Map::new()),
+        }
+    }
+
+    /// Settings of a remote with its secrets put back
+    pub async fn settings(&self, remote: &Remote) -> RemoteResult<Value> {
+        let mut settings = parse(&remote.settings)?;
+        let secrets = self.secrets(&remote.name).await?;
+        if let Value::Object(settings) = &mut settings {
+            settings.extend(secrets);
+        }
+        Ok(settings)
+    }
+
+    /// Stores the settings of a remote, its secrets in the store and the
+    /// rest in the database
+    pub async fn store_settings(
+        &self,
+        db: &mut LocalDb,
+        remote: &Remote,
+        mut settings: Value,
+    ) -> RemoteResult<()> {
+        let secrets = split(&mut settings);
+        if !secrets.is_empty() {
+            let json = Value::Object(secrets).to_string();
+            self.store.set(&self.key(&remote.name), &json).await?;
+        }
+        db.set_remote_settings(remote.id, &settings.to_string())
+            .await?;
+        Ok(())
+    }
+
+    /// Deletes the secrets of a remote, returns whether there were any
+    pub async fn forget(&self, remote: &str) -> RemoteResult<bool> {
+        Ok(self.store.delete(&self.key(remote)).await?)
+    }
+
+    /// Key of the encrypted database
+    pub async fn database_key(&self) -> RemoteResult<Option<String>> {
+        Ok(self.store.get(&self.database()).await?)
+    }
+
+    pub async fn set_database_key(&self, key: &str) -> RemoteResult<()> {
+        Ok(self.store.set(&self.database(), key).await?)
+    }
+
+    pub async fn forget_database_key(&self) -> RemoteResult<bool> {
+        Ok(self.store.delete(&self.database()).await?)
+    }
+
+    /// Entry of the database key, apart from the names of remotes
+    fn database(&self) -> String {
+        format!("{}:database", self.namespace)
+    }
+
+    /// Moves the secrets left in the settings of the remotes to the store.
+    /// Returns the number of remotes that had some.
+    pub async fn migrate(&self, db: &mut LocalDb) -> RemoteResult<usize> {
+        let mut moved = 0;
+        for remote in db.get_remotes().await? {
+            let mut settings = parse(&remote.settings)?;
+            let found = split(&mut settings);
+            if found.is_empty() {
+                continue;
+            }
+            // the database holds the latest secrets, rotated ones included
+            let mut secrets = self.secrets(&remote.name).await?;
+            secrets.extend(found);
+            if let Value::Object(settings) = &mut settings {
+                settings.extend(secrets);
+            }
+            self.store_settings(db, &remote, settings).await?;
+            moved += 1;
+        }
+        Ok(moved)
+    }
+}
+
+fn parse(settings: &str) -> RemoteResult<Value> {
+    if settings.trim().is_empty() {
+        return Ok(Value::Object(Map::new()));
+    }
+    Ok(serde_json::from_str(settings)?)
+}
+
+/// Takes the secrets out of `settings`
+fn split(settings: &mut Value) -> Map<String, Value> {
+    let mut secrets = Map::new();
+    if let Value::Object(settings) = settings {
+        for field in SECRET_FIELDS {
+            if let Some(value) = settings.remove(*field) {
+                if !value.is_null() {
+                    secrets.insert(field.to_string(), value);
+                }
+            }
+        }
+    }
+    secrets
+}
+
+/// Vault keeping its secrets in an encrypted file under `dir`
+#[cfg(test)]
+pub fn test_vault(dir: &std::path::Path) -> Vault {
+    let store = EncryptedFile::open(dir.join("credentials.json"), "test").unwrap();
+    Vault::new(Box::new(store), "test")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_migrate_and_split() {
+        let dir = tempfile::tempdir().unwrap();
+        let vault = test_vault(dir.path());
+        let pool = localdb::open_database(":memory:").await.unwrap();
+        let mut db = LocalDb::new(pool);
+        let settings = r#"{"url":"https://example.com","client_id":"id","client_secret":"s","refresh_token":"r"}"#;
+        db.add_remote("work", "wallabag", settings).await.unwrap();
+        db.add_remote("home", "pocket", r#"{"consumer_key":"key"}"#)
+            .await
+            .unwrap();
+        // left over from an earlier login
+        let work = db.get_remote("work").await.unwrap().unwrap();
+        let stale = serde_json::json!({ "refresh_token": "stale", "access_token": "a" });
+        vault.store_settings(&mut db, &work, stale).await.unwrap();
+        db.set_remote_settings(work.id, settings).await.unwrap();
+
+        assert_eq!(vault.migrate(&mut db).await.unwrap(), 1);
+        assert_eq!(vault.migrate(&mut db).await.unwrap(), 0);
+        let work = db.get_remote("work").await.unwrap().unwrap();
+        assert_eq!(
+            work.settings,
+            r#"{"client_id":"id","url":"https://example.com"}"#
+        );
+        let settings = vault.settings(&work).await.unwrap();
+        assert_eq!(settings["refresh_token"], "r");
+        assert_eq!(settings["client_secret"], "s");
+        assert_eq!(settings["access_token"], "a");
+
+        // rotated secrets replace the stored ones
+        let mut settings = settings;
+        settings["refresh_token"] = "rotated".into();
+        vault
+            .store_settings(&mut db, &work, settings)
+            .await
+            .unwrap();
+        let work = db.get_remote("work").await.unwrap().unwrap();
+        assert!(!work.settings.contains("rotated"));
+        assert_eq!(
+            vault.settings(&work).await.unwrap()["refresh_token"],
+            "rotated"
+        );
+
+        assert!(vault.forget("work").await.unwrap());
+        assert!(vault.secrets("work").await.unwrap().is_empty());
+    }
+}